@@ -50,7 +50,7 @@ impl Args {
     }
     /// Returns the types of this Args.
     pub fn types(&self, userfunc: &UserFunction) -> ArgTypes {
-        ArgTypes(self.iter(userfunc).map(Expr::return_type).collect())
+        ArgTypes(self.iter(userfunc).map(Expr::result_type).collect())
     }
 }
 
@@ -71,6 +71,10 @@ impl ArgTypes {
             format!("{:?}", &self.0)
         }
     }
+    /// Returns the number of arguments.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
     /// Returns an iterator over the argument types.
     pub fn iter(&self) -> impl Iterator<Item = &Type> {
         self.0.iter()
@@ -97,6 +101,10 @@ impl<'a> ArgValues<'a> {
     pub fn len(&self) -> usize {
         self.arg_asts.len()
     }
+    /// Returns the user function that these arguments are part of.
+    pub fn userfunc(&self) -> &'a UserFunction {
+        self.userfunc
+    }
     /// Compiles the argument at the given index and returns the resulting
     /// Value.
     pub fn compile(&self, compiler: &mut Compiler, idx: usize) -> LangResult<Value> {
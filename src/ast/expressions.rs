@@ -1,11 +1,15 @@
 //! High-level representations of expressions in the AST.
 
+use std::cell::RefCell;
+
 use super::super::compiler::*;
 use super::super::errors::*;
+use super::super::functions;
 use super::super::parser;
+use super::super::types::{int_fits_in_bits, LangInt};
 use super::super::{ConstValue, Span, Type};
-use super::{ArgTypes, ArgValues, Args, UserFunction};
-use LangErrorMsg::{CannotEvalAsConst, InternalError, InvalidArguments};
+use super::{ArgTypes, ArgValues, Args, StatementBlock, UserFunction};
+use LangErrorMsg::{CannotEvalAsConst, IntegerOverflow, InternalError, InvalidArguments};
 
 /// Expression node in the AST.
 #[derive(Debug)]
@@ -17,7 +21,14 @@ pub struct Expr {
     /// Arguments (other expressions) passed to the function.
     args: Args,
     /// Type that this expression evaluates to.
-    return_type: Type,
+    result_type: Type,
+    /// Whether evaluating this expression may produce a runtime error.
+    may_error: bool,
+    /// Whether this expression never produces a value (see is_never()).
+    is_never: bool,
+    /// Result of evaluating this expression as a constant, if it has already
+    /// been evaluated (see const_eval()).
+    const_result: RefCell<Option<LangResult<ConstValue>>>,
 }
 impl Expr {
     /// Returns the span of this expression in the original source code.
@@ -25,8 +36,51 @@ impl Expr {
         self.span
     }
     /// Returns the type that this expression evaluates to.
-    pub fn return_type(&self) -> Type {
-        self.return_type
+    ///
+    /// This is determined from the function signature when the expression is
+    /// constructed, so it is always available without compiling or evaluating
+    /// the expression.
+    pub fn result_type(&self) -> Type {
+        self.result_type
+    }
+    /// Returns the function used to compile or evaluate this expression.
+    pub fn func(&self) -> &dyn Function {
+        &*self.func
+    }
+    /// Returns the arguments passed to the function of this expression.
+    pub fn args(&self) -> &Args {
+        &self.args
+    }
+    /// Returns whether evaluating this expression may produce a runtime error,
+    /// either in its own function or in any of its arguments.
+    pub fn may_error(&self) -> bool {
+        self.may_error
+    }
+    /// Marks this expression as possibly producing a runtime error.
+    pub fn set_may_error(&mut self) {
+        self.may_error = true;
+    }
+    /// Returns whether this expression never produces a value, because it
+    /// always transfers control elsewhere first (such as `{ become #1 }`).
+    ///
+    /// Such an expression can be used wherever any type is expected, so its
+    /// result type is only a placeholder, which may be changed using
+    /// set_never_type().
+    pub fn is_never(&self) -> bool {
+        self.is_never
+    }
+    /// Marks this expression as never producing a value (see is_never()).
+    pub fn set_never(&mut self) {
+        self.is_never = true;
+    }
+    /// Changes the placeholder result type of an expression that never
+    /// produces a value (see is_never()).
+    pub fn set_never_type(&mut self, ty: Type) {
+        debug_assert!(
+            self.is_never,
+            "Only the type of a never expression can change"
+        );
+        self.result_type = ty;
     }
     /// Constructs a new expression by applying the given Args to the given
     /// Function.
@@ -48,18 +102,41 @@ impl Expr {
                 expected: func.signatures().iter().map(|s| s.args.clone()).collect(),
                 got: arg_types,
             })?;
+        let may_error = args.iter(userfunc).any(Expr::may_error);
         Ok(Self {
             span,
             func,
             args,
-            return_type: signature.ret,
+            result_type: signature.ret,
+            may_error,
+            is_never: false,
+            const_result: RefCell::new(None),
         })
     }
+    /// Constructs a new expression that always returns the given value.
+    pub fn new_constant(span: Span, value: ConstValue) -> Self {
+        Self {
+            span,
+            result_type: value.ty(),
+            func: Box::new(functions::literals::Constant(value)),
+            args: Args::none(),
+            may_error: false,
+            is_never: false,
+            const_result: RefCell::new(None),
+        }
+    }
     /// Compiles this expression and returns the resulting Value.
     pub fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<Value> {
-        let ret_val = self.func.compile(compiler, self.args.values(userfunc))?;
+        let ret_val = compiler.build_with_debug_location(self.span(), |c| {
+            self.func.compile(c, self.args.values(userfunc))
+        })?;
+        if self.is_never() {
+            // Control never reaches the code that uses this value, and its
+            // type may have been changed (see set_never_type()).
+            return Ok(compiler.build_never_value(self.result_type()));
+        }
         // Check return type.
-        if ret_val.ty() == self.return_type() {
+        if ret_val.ty() == self.result_type() {
             Ok(ret_val)
         } else {
             Err(InternalError("Expression returned wrong type".into()).with_span(self.span()))
@@ -69,18 +146,54 @@ impl Expr {
     /// ConstValue.
     ///
     /// Returns Err(CannotEvalAsConst) if this expression cannot be evaluated at
-    /// compile time.
+    /// compile time, or Err(IntegerOverflow) if the result does not fit in the
+    /// rule's integer width.
+    ///
+    /// The result is memoized, so evaluating the same expression again (e.g.
+    /// once while checking the arguments of another function and again while
+    /// folding constants) does not evaluate its arguments again.
     pub fn const_eval(&self, userfunc: &UserFunction) -> LangResult<ConstValue> {
+        if let Some(result) = &*self.const_result.borrow() {
+            return result.clone();
+        }
+        let result = self.const_eval_uncached(userfunc);
+        // Internal errors (such as a missing argument) indicate a bug rather
+        // than a property of the expression, so don't remember them.
+        let is_internal_error = matches!(&result, Err(e) if matches!(e.msg, InternalError(_)));
+        if !is_internal_error {
+            *self.const_result.borrow_mut() = Some(result.clone());
+        }
+        result
+    }
+    /// Evaluates this expression as a constant without memoization.
+    fn const_eval_uncached(&self, userfunc: &UserFunction) -> LangResult<ConstValue> {
         let ret_val = self
             .func
             .const_eval(self.args.values(userfunc))
             .transpose() // Convert Result<Option<_>, _> to Option<Result<_, _>>
             .unwrap_or_else(|| Err(CannotEvalAsConst.with_span(self.span())))?;
         // Check return type.
-        if ret_val.ty() == self.return_type() {
+        if ret_val.ty() != self.result_type() {
+            Err(InternalError("Expression returned wrong type".into()).with_span(self.span()))?;
+        }
+        // Constants are always evaluated using LangInt, which may be wider
+        // than the integers in this rule.
+        let int_bits = userfunc.rule_meta().int_bits;
+        let fits = |&i: &LangInt| int_fits_in_bits(i, int_bits);
+        let in_range = match &ret_val {
+            ConstValue::Int(i) => fits(i),
+            ConstValue::Vector(values) | ConstValue::Array(values) => values.iter().all(fits),
+            ConstValue::Tuple(values) => values.iter().all(|value| match value {
+                ConstValue::Int(i) => fits(i),
+                _ => true,
+            }),
+            ConstValue::Range(start, end) => fits(start) && fits(end),
+            ConstValue::Bool(_) | ConstValue::CellState(_) => true,
+        };
+        if in_range {
             Ok(ret_val)
         } else {
-            Err(InternalError("Expression returned wrong type".into()).with_span(self.span()))
+            Err(IntegerOverflow.with_span(self.span()))
         }
     }
 }
@@ -141,6 +254,12 @@ pub trait Function: std::fmt::Debug {
     fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
         Ok(None)
     }
+
+    /// Returns the blocks of statements executed by this function (not
+    /// including those inside its arguments).
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![]
+    }
 }
 
 /// An enumeration of the kinds of "functions," most of which would not
@@ -196,19 +315,27 @@ impl FnSignature {
     pub fn matches(&self, args: &ArgTypes) -> bool {
         &self.args == args
     }
-    /// Returns the LLVM function type that is equivalent to this function signature.
+    /// Returns the LLVM function type used to compile a helper function with
+    /// this signature.
+    ///
+    /// The first parameter is a pointer to hold the return value, and the rest
+    /// are the arguments. Like a function called from Rust code, the actual
+    /// LLVM return value just signals whether there was an error.
     pub fn llvm_fn_type(
         &self,
         compiler: &Compiler,
     ) -> LangResult<inkwell::types::FunctionType<'static>> {
         use inkwell::types::BasicType;
-        // TODO: validate user function args and return value at some point.
-        let llvm_param_types = self
-            .args
-            .iter()
-            .map(|&t| compiler.get_llvm_type(t))
-            .collect::<LangResult<Vec<_>>>()?;
-        let llvm_ret_type = compiler.get_llvm_type(self.ret)?;
-        Ok(llvm_ret_type.fn_type(&llvm_param_types, false))
+        use inkwell::AddressSpace;
+        let mut llvm_param_types = vec![compiler
+            .get_llvm_storage_type(self.ret)?
+            .ptr_type(AddressSpace::Generic)
+            .as_basic_type_enum()];
+        for &ty in self.args.iter() {
+            llvm_param_types.push(compiler.get_llvm_param_type(ty)?);
+        }
+        Ok(compiler
+            .get_llvm_return_type()
+            .fn_type(&llvm_param_types, false))
     }
 }
@@ -0,0 +1,57 @@
+//! Registry of functions provided by the host, which rules can call using
+//! `extern::name(...)`.
+
+use std::collections::HashMap;
+
+use super::super::Type;
+use super::FnSignature;
+
+/// Function provided by the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternFunction {
+    /// Signature that calls from rules are checked against.
+    pub signature: FnSignature,
+    /// Address of the function, which must be an `extern "C" fn` that takes
+    /// one LangInt per argument and returns a LangInt (see
+    /// ExternFunctions::register()).
+    pub fn_ptr: usize,
+}
+
+/// Registry of functions provided by the host, indexed by name.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExternFunctions(HashMap<String, ExternFunction>);
+impl ExternFunctions {
+    /// Constructs an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers a function that rules can call as `extern::name(...)`,
+    /// replacing any existing function with the same name.
+    ///
+    /// Integers, booleans, and cell states are the only types that can be
+    /// passed to or returned from an extern function; each one is passed as a
+    /// LangInt. Rules that call a function with any other type in its
+    /// signature fail to compile. Integers returned from the function that do
+    /// not fit in the rule's integer width are truncated, and so are cell
+    /// states, which the function must keep in range.
+    ///
+    /// # Safety
+    ///
+    /// `fn_ptr` must be the address of an `extern "C" fn` that takes one
+    /// LangInt for each argument in `signature` and returns a LangInt, and it
+    /// must remain valid for as long as any compiled function calls it.
+    pub unsafe fn register(&mut self, name: &str, signature: FnSignature, fn_ptr: usize) {
+        self.0
+            .insert(name.to_owned(), ExternFunction { signature, fn_ptr });
+    }
+    /// Returns the function with the given name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&ExternFunction> {
+        self.0.get(name)
+    }
+}
+
+/// Returns whether values of the given type can be passed to or returned from
+/// an extern function.
+pub fn is_valid_extern_type(ty: Type) -> bool {
+    matches!(ty, Type::Int | Type::Bool | Type::CellState)
+}
@@ -15,12 +15,15 @@ use std::rc::Rc;
 
 mod args;
 mod expressions;
+mod externs;
+mod prelude;
 mod rule;
 pub mod statements;
 mod userfunc;
 
 pub use args::*;
 pub use expressions::*;
+pub use externs::*;
 pub use rule::*;
 pub use statements::{Statement, StatementBlock};
 pub use userfunc::*;
@@ -32,3 +35,34 @@ pub fn make_rule(source_code: Rc<String>) -> LangResult<Rule> {
     let parse_tree = super::parser::parse(source_code.clone(), &tokens)?;
     Rule::try_from(parse_tree)
 }
+
+/// Constructs a rule from source code that can call the given functions
+/// provided by the host (see ExternFunctions::register()).
+pub fn make_rule_with_externs(
+    source_code: Rc<String>,
+    extern_functions: ExternFunctions,
+) -> LangResult<Rule> {
+    let tokens = super::lexer::tokenize(&source_code)?;
+    let parse_tree = super::parser::parse(source_code.clone(), &tokens)?;
+    Rule::try_build(parse_tree, extern_functions)
+}
+
+/// Constructs a rule from source code, reporting errors to the given
+/// DiagnosticSink instead of stopping at the first one. Returns None if any
+/// errors were reported.
+///
+/// Lexing and parsing still stop at the first error.
+pub fn make_rule_with_diagnostics(
+    source_code: Rc<String>,
+    diagnostics: &mut dyn DiagnosticSink,
+) -> Option<Rule> {
+    let parse_tree = super::lexer::tokenize(&source_code)
+        .and_then(|tokens| super::parser::parse(source_code.clone(), &tokens));
+    match parse_tree {
+        Ok(parse_tree) => Rule::build(parse_tree, diagnostics),
+        Err(e) => {
+            diagnostics.report(e);
+            None
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Standard prelude of helper functions, which every rule can call unless it
+//! opts out using `@no_prelude`.
+//!
+//! The prelude provides exactly these functions:
+//!
+//! - `int sign(int x)` returns 1 if `x` is positive, -1 if `x` is negative, or
+//!   0 if `x` is zero.
+//! - `bool is_even(int x)` returns whether `x` is even.
+//! - `bool is_odd(int x)` returns whether `x` is odd.
+//!
+//! Common operations such as `min()`, `max()`, `wrap()`, and `count()` are
+//! built-in functions rather than part of the prelude, so they are always
+//! available.
+//!
+//! Prelude functions are ordinary helper functions in the rule's namespace. A
+//! helper function defined by the rule with the same name as a prelude
+//! function replaces it, with a warning (see Rule::warnings()).
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::super::errors::*;
+use super::super::lexer;
+use super::super::parser::{self, Directive, DirectiveContents, ParseTree};
+use super::super::Span;
+use LangErrorMsg::PreludeFunctionShadowed;
+
+/// Source code of the prelude. Arguments have names starting with two
+/// underscores so that they do not conflict with the rule's constants and cell
+/// state names.
+const PRELUDE: &str = "
+@function int sign(int __x) {
+    if __x > 0 { return 1 }
+    if __x < 0 { return -1 }
+    return 0
+}
+@function bool is_even(int __x) {
+    return __x % 2 == 0
+}
+@function bool is_odd(int __x) {
+    return __x % 2 != 0
+}
+";
+
+/// Adds the helper functions of the prelude to a parse tree, unless it has an
+/// `@no_prelude` directive, and returns a warning for each prelude function
+/// that is replaced by one of the rule's own helper functions.
+///
+/// The source code of the prelude is appended to the source code of the parse
+/// tree, so that the spans of prelude functions are still valid without
+/// changing the spans of the rule.
+pub fn add_prelude(parse_tree: &mut ParseTree) -> LangResult<Vec<LangError>> {
+    if parse_tree
+        .take_single_directive(Directive::NoPrelude)?
+        .is_some()
+    {
+        return Ok(vec![]);
+    }
+
+    let offset = parse_tree.source_code.len() + 1;
+    let source_code = Rc::new(format!("{}\n{}", parse_tree.source_code, PRELUDE));
+    let tokens: Vec<lexer::Token> = lexer::tokenize(&source_code[offset..])?
+        .into_iter()
+        .map(|token| lexer::Token {
+            span: Span {
+                start: token.span.start + offset,
+                end: token.span.end + offset,
+            },
+            ..token
+        })
+        .collect();
+    let mut prelude = parser::parse(source_code.clone(), &tokens)?;
+
+    let rule_functions = parse_tree
+        .directives
+        .entry(Directive::Function)
+        .or_default();
+    // Get the name of each of the rule's helper functions, along with its
+    // span.
+    let rule_function_names: HashMap<String, Span> = rule_functions
+        .iter()
+        .filter_map(|contents| match &contents.inner {
+            DirectiveContents::Func(f) => Some((f.name.inner.clone(), f.name.span)),
+            _ => None,
+        })
+        .collect();
+    let mut warnings = vec![];
+    for contents in prelude
+        .directives
+        .remove(&Directive::Function)
+        .unwrap_or_default()
+    {
+        if let DirectiveContents::Func(f) = &contents.inner {
+            if let Some(&span) = rule_function_names.get(&f.name.inner) {
+                warnings.push(PreludeFunctionShadowed(f.name.inner.clone()).with_span(span));
+                continue;
+            }
+        }
+        rule_functions.push(contents);
+    }
+    parse_tree.source_code = source_code;
+    Ok(warnings)
+}
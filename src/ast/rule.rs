@@ -1,22 +1,31 @@
 //! Root node of the AST.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::rc::Rc;
 
 use super::super::errors::*;
-use super::super::parser::{Directive, DirectiveContents, HelperFunc, ParseTree};
-use super::super::{ConstValue, Type, MAX_NDIM, MAX_STATES};
-use super::{FnSignature, UserFunction};
+use super::super::parser::{self, Directive, DirectiveContents, HelperFunc, ParseTree};
+use super::super::types::{
+    max_int_for_bits, min_int_for_bits, LangCellState, LangInt, INT_BITS, MIN_INT_BITS,
+};
+use super::super::{ConstValue, Span, Spanned, Type, MAX_NDIM, MAX_RADIUS, MAX_STATES};
+use super::{prelude, ExternFunctions, FnSignature, UserFunction};
 use LangErrorMsg::{
-    Expected, FunctionNameConflict, InternalError, InvalidDimensionCount, InvalidStateCount,
-    TypeError,
+    AssertionFailed, CellStateAlreadyNamed, CellStateOutOfRange, CellStateOutsideStates,
+    ConflictingDirectives, ConstantNameConflict, Expected, FunctionNameConflict, InternalError,
+    InvalidDimensionCount, InvalidIntBits, InvalidNbhdShape, InvalidOverflowMode, InvalidRadius,
+    InvalidStateCount, RecursiveFunctionCall, RepeatDirective, TypeError,
 };
 
 /// Number of dimensions to use when the user doesn't specify.
 const DEFAULT_NDIM: u8 = 2;
+/// Neighborhood radius to use when the user doesn't specify.
+const DEFAULT_RADIUS: u8 = 1;
 /// Number of states to use when the user doesn't specify.
 const DEFAULT_STATE_COUNT: usize = 2;
+/// Random seed to use when the user doesn't specify.
+const DEFAULT_SEED: LangInt = 0;
 
 /// Returns a list of cell states if the user does not specify, given an
 /// optional number of states (defaults to DEFAULT_STATE_COUNT).
@@ -34,120 +43,251 @@ pub struct Rule {
     helper_functions: HashMap<String, UserFunction>,
     /// Transition function used to simulate this rule.
     transition_function: UserFunction,
+    /// Warnings about things that are allowed but probably unintended.
+    warnings: Vec<LangError>,
 }
 impl TryFrom<ParseTree> for Rule {
     type Error = LangError;
-    fn try_from(mut parse_tree: ParseTree) -> LangResult<Self> {
-        let mut temp_func = UserFunction::default();
+    fn try_from(parse_tree: ParseTree) -> LangResult<Self> {
+        Self::try_build(parse_tree, ExternFunctions::new())
+    }
+}
+impl Rule {
+    /// Constructs a rule from a parse tree that can call the given functions
+    /// provided by the host, returning only the first error (if any).
+    pub fn try_build(parse_tree: ParseTree, extern_functions: ExternFunctions) -> LangResult<Self> {
+        let mut errors = vec![];
+        Self::build_with_externs(parse_tree, extern_functions, &mut errors).ok_or_else(|| {
+            errors.into_iter().next().unwrap_or_else(|| {
+                InternalError("Failed to build rule, but no error was reported".into())
+                    .without_span()
+            })
+        })
+    }
+    /// Constructs a rule from a parse tree, reporting errors to the given
+    /// DiagnosticSink instead of stopping at the first one. Returns None if any
+    /// errors were reported.
+    ///
+    /// Directives, helper functions, and the transition function are checked
+    /// independently, so each may report an error; within a single function,
+    /// every statement is checked, although errors that are only caused by an
+    /// earlier error are not reported.
+    pub fn build(parse_tree: ParseTree, diagnostics: &mut dyn DiagnosticSink) -> Option<Self> {
+        Self::build_with_externs(parse_tree, ExternFunctions::new(), diagnostics)
+    }
+    /// Constructs a rule from a parse tree, like build(), that can call the
+    /// given functions provided by the host.
+    pub fn build_with_externs(
+        mut parse_tree: ParseTree,
+        extern_functions: ExternFunctions,
+        diagnostics: &mut dyn DiagnosticSink,
+    ) -> Option<Self> {
+        let mut error_count = 0;
+        let mut report = |error: LangError| {
+            error_count += 1;
+            diagnostics.report(error);
+        };
 
-        // Get number of dimensions.
-        let ndim = match parse_tree.take_single_directive(Directive::Dimensions)? {
-            // There is no `@dimensions` directive; use the default.
-            None => DEFAULT_NDIM,
-            // There is an `@dimensions` directive.
-            Some((_span, DirectiveContents::Expr(expr))) => {
-                let ndim_expr = temp_func.build_expression_ast(&expr)?;
-                let ndim_value = temp_func.const_eval_expr(ndim_expr)?;
-                match ndim_value {
-                    // The user specified a valid dimension count.
-                    ConstValue::Int(i @ 1..=MAX_NDIM) => i as u8,
-                    // The user specified a number, but it's not a valid
-                    // dimension count.
-                    ConstValue::Int(_) => Err(InvalidDimensionCount.with_span(expr))?,
-                    // The user specified some other value.
-                    _ => Err(TypeError {
-                        expected: Type::Int,
-                        got: ndim_value.ty(),
-                    }
-                    .with_span(expr.span))?,
-                }
+        // Add helper functions from the prelude.
+        let warnings = prelude::add_prelude(&mut parse_tree).unwrap_or_else(|e| {
+            report(e);
+            vec![]
+        });
+
+        // Remember where each directive is, so that errors caused by a
+        // conflict with another directive can point to both of them.
+        let directive_spans: HashMap<Directive, Span> = parse_tree
+            .directives
+            .iter()
+            .filter_map(|(dir, instances)| Some((dir.clone(), instances.first()?.span)))
+            .collect();
+
+        // Get number of bits in an integer, which affects everything else
+        // that is evaluated at compile time.
+        let int_bits = take_int_bits(&mut parse_tree).unwrap_or_else(|e| {
+            report(e);
+            INT_BITS
+        });
+        // Get overflow behavior, which also affects constants.
+        let overflow_mode = take_overflow_mode(&mut parse_tree).unwrap_or_else(|e| {
+            report(e);
+            OverflowMode::default()
+        });
+
+        // Evaluate constants in order, so that each one can refer to the ones
+        // before it and everything else can refer to all of them. The cell
+        // states aren't known yet (and `@states` may refer to a constant), so
+        // allow every cell state for now, and skip constants that can't be
+        // evaluated yet; all of them are evaluated again once the cell states
+        // are known, and any errors are reported then.
+        let const_directives = parse_tree
+            .directives
+            .remove(&Directive::Const)
+            .unwrap_or_default();
+        let mut constants = HashMap::new();
+        let every_state = make_default_states(Some(MAX_STATES as usize));
+        for contents in &const_directives {
+            if let Ok((name, value)) = eval_constant(
+                &constants,
+                &every_state,
+                int_bits,
+                overflow_mode,
+                contents.clone(),
+            ) {
+                constants.insert(name, value);
             }
-            // The user gave something else instead of an expression.
-            Some((span, _contents)) => Err(Expected("expression").with_span(span))?,
-        };
+        }
+        let mut temp_func = make_temp_func(
+            &constants,
+            &make_default_states(None),
+            int_bits,
+            overflow_mode,
+        );
+
+        // Get number of dimensions.
+        let ndim = take_ndim(&mut parse_tree, &mut temp_func).unwrap_or_else(|e| {
+            report(e);
+            DEFAULT_NDIM
+        });
+
+        // Get neighborhood radius.
+        let radius = take_radius(&mut parse_tree, &mut temp_func).unwrap_or_else(|e| {
+            report(e);
+            DEFAULT_RADIUS
+        });
+
+        // Get neighborhood shape.
+        let nbhd_shape = take_nbhd_shape(&mut parse_tree).unwrap_or_else(|e| {
+            report(e);
+            NbhdShape::default()
+        });
+
+        // Get random seed.
+        let seed = take_seed(&mut parse_tree, &mut temp_func).unwrap_or_else(|e| {
+            report(e);
+            DEFAULT_SEED
+        });
 
         // Get states.
-        let states = match parse_tree.take_single_directive(Directive::States)? {
-            // There is no `@states` directive; use the default states.
-            None => make_default_states(None),
-            // There is an `@states` directive.
-            Some((_span, DirectiveContents::Expr(expr))) => {
-                let states_expr = temp_func.build_expression_ast(&expr)?;
-                let states_value = temp_func.const_eval_expr(states_expr)?;
-                match states_value {
-                    // The user specified a valid state count.
-                    ConstValue::Int(i @ 1..=MAX_STATES) => make_default_states(Some(i as usize)),
-                    // The user specified a number, but it's not a valid state
-                    // count.
-                    ConstValue::Int(_) => Err(InvalidStateCount.with_span(expr))?,
-                    // The user specified some other value.
-                    _ => Err(TypeError {
-                        expected: Type::Int,
-                        got: states_value.ty(),
-                    }
-                    .with_span(expr.span))?,
+        let mut states = take_states(&mut parse_tree, &mut temp_func).unwrap_or_else(|e| {
+            report(e);
+            make_default_states(None)
+        });
+
+        // Name cell states.
+        for contents in parse_tree
+            .directives
+            .remove(&Directive::State)
+            .unwrap_or_default()
+        {
+            if let Err(e) = name_cell_state(
+                &mut states,
+                directive_spans.get(&Directive::States).copied(),
+                &constants,
+                int_bits,
+                overflow_mode,
+                contents,
+            ) {
+                report(e);
+            }
+        }
+
+        // Evaluate constants again using the actual cell states, so that they
+        // can refer to cell states and their names.
+        let mut final_constants = HashMap::new();
+        for contents in const_directives {
+            match eval_constant(&final_constants, &states, int_bits, overflow_mode, contents) {
+                Ok((name, value)) => {
+                    final_constants.insert(name, value);
                 }
+                Err(e) => report(e),
             }
-            // The user gave something else instead of an expression.
-            Some((span, _contents)) => Err(Expected("expression").with_span(span))?,
-        };
+        }
+        let constants = final_constants;
+
+        // Check compile-time assertions, which may refer to constants and cell
+        // states.
+        let mut temp_func = make_temp_func(&constants, &states, int_bits, overflow_mode);
+        for contents in parse_tree
+            .directives
+            .remove(&Directive::Assert)
+            .unwrap_or_default()
+        {
+            if let Err(e) = check_assertion(&mut temp_func, contents) {
+                report(e);
+            }
+        }
 
         // Gather a list of helper functions.
-        let helper_function_parse_trees: Vec<HelperFunc> = parse_tree
+        let mut helper_function_parse_trees: Vec<HelperFunc> = vec![];
+        for contents in parse_tree
             .directives
             .remove(&Directive::Function)
             .unwrap_or_default()
-            .into_iter()
-            .map(|contents| match contents.inner {
-                DirectiveContents::Func(f) => Ok(f),
-                _ => Err(
+        {
+            match contents.inner {
+                DirectiveContents::Func(f) => helper_function_parse_trees.push(f),
+                _ => report(
                     InternalError("Invalid parse tree on helper function".into()).without_span(),
                 ),
-            })
-            .collect::<LangResult<Vec<_>>>()?;
+            }
+        }
+        // Skip any helper function with the same name as an earlier one.
         let mut helper_function_signatures = HashMap::new();
-        for helper_func in &helper_function_parse_trees {
+        helper_function_parse_trees.retain(|helper_func| {
             if helper_function_signatures.contains_key(&helper_func.name.inner) {
-                Err(FunctionNameConflict.with_span(helper_func.name.span))?;
+                report(FunctionNameConflict.with_span(helper_func.name.span));
+                false
             } else {
                 helper_function_signatures.insert(
                     helper_func.name.inner.clone(),
                     FnSignature::from_helper_function_parse_tree(helper_func, ndim),
                 );
+                true
             }
-        }
+        });
 
         let meta = Rc::new(RuleMeta {
             source_code: parse_tree.source_code.clone(),
             ndim,
+            radius,
+            nbhd_shape,
             states,
+            int_bits,
+            overflow_mode,
+            seed,
             helper_function_signatures,
+            extern_functions,
+            constants,
+            directive_spans,
         });
 
         // Build helper functions.
-        let helper_functions = helper_function_parse_trees
-            .into_iter()
-            .map(|helper_func| {
-                Ok((
-                    helper_func.name.inner.clone(),
-                    UserFunction::build_helper_function(&meta, helper_func)?,
-                ))
-            })
-            .collect::<LangResult<HashMap<_, _>>>()?;
+        let mut helper_functions = HashMap::new();
+        for helper_func in helper_function_parse_trees {
+            let name = helper_func.name.inner.clone();
+            match UserFunction::build_helper_function(&meta, helper_func) {
+                Ok(userfunc) => {
+                    helper_functions.insert(name, userfunc);
+                }
+                Err(errors) => {
+                    for e in errors {
+                        report(e);
+                    }
+                }
+            }
+        }
+        // Helper functions cannot be recursive.
+        for e in check_recursion(&helper_functions) {
+            report(e);
+        }
 
         // Build transition function.
         let mut transition_function = UserFunction::new_transition_function(meta.clone());
-        match parse_tree.take_single_directive(Directive::Transition)? {
-            // The user gave a block of code.
-            Some((_span, DirectiveContents::Block(statements))) => {
-                transition_function.build_top_level_statement_block_ast(&statements.inner)?;
-            }
-            // The user gave something else instead of a code block.
-            Some((span, _contents)) => {
-                Err(Expected("code block").with_span(span))?;
+        if let Err(errors) = build_transition_function(&mut parse_tree, &mut transition_function) {
+            for e in errors {
+                report(e);
             }
-            // The user did not provide a transition function.
-            None => transition_function.build_top_level_statement_block_ast(&vec![])?,
         }
 
         // No directive left behind!
@@ -157,26 +297,467 @@ impl TryFrom<ParseTree> for Rule {
             .filter(|(_, v)| !v.is_empty())
             .next()
         {
-            Err(InternalError(format!("Unused directive {:?}", dir).into()))?;
+            report(InternalError(format!("Unused directive {:?}", dir).into()).without_span());
+        }
+
+        if error_count > 0 {
+            return None;
         }
 
         // Construct the rule.
-        Ok(Rule {
+        Some(Rule {
             meta,
             helper_functions,
             transition_function,
+            warnings,
         })
     }
-}
-impl Rule {
+    /// Returns this rule's metadata.
+    pub fn meta(&self) -> &Rc<RuleMeta> {
+        &self.meta
+    }
     /// Returns this rule's transition function.
     pub fn transition_function(&self) -> &UserFunction {
         &self.transition_function
     }
-    /// Returns this rule's helper functions.
+    /// Returns this rule's helper functions, including those from the prelude
+    /// (unless the rule uses `@no_prelude`).
     pub fn helper_functions(&self) -> &HashMap<String, UserFunction> {
         &self.helper_functions
     }
+    /// Returns warnings about this rule, such as helper functions that replace
+    /// functions from the prelude. These do not prevent the rule from being
+    /// built.
+    pub fn warnings(&self) -> &[LangError] {
+        &self.warnings
+    }
+}
+
+/// Returns an Err(RecursiveFunctionCall) for each cycle of helper functions
+/// that call each other (including a function that calls itself), pointing to
+/// the call that completes the cycle.
+fn check_recursion(helper_functions: &HashMap<String, UserFunction>) -> Vec<LangError> {
+    let mut errors = vec![];
+    let mut finished = HashSet::new();
+    // Search in order of name, so that errors are reported in a consistent
+    // order.
+    let mut names: Vec<&str> = helper_functions.keys().map(String::as_str).collect();
+    names.sort();
+    for name in names {
+        find_recursion(
+            helper_functions,
+            name,
+            &mut vec![],
+            &mut finished,
+            &mut errors,
+        );
+    }
+    errors
+}
+/// Searches the helper functions that are reachable from the given one and
+/// not yet finished, adding an error for each call to a function on the given
+/// path of calls leading there (see check_recursion()).
+fn find_recursion<'a>(
+    helper_functions: &'a HashMap<String, UserFunction>,
+    name: &'a str,
+    path: &mut Vec<&'a str>,
+    finished: &mut HashSet<&'a str>,
+    errors: &mut Vec<LangError>,
+) {
+    if finished.contains(name) {
+        return;
+    }
+    path.push(name);
+    if let Some(userfunc) = helper_functions.get(name) {
+        for (callee, &call_span) in userfunc.called_helpers() {
+            if let Some(idx) = path.iter().position(|&f| f == callee.as_str()) {
+                let mut cycle: Vec<String> = path[idx..].iter().map(|&f| f.to_owned()).collect();
+                cycle.push(callee.clone());
+                errors.push(RecursiveFunctionCall(cycle).with_span(call_span));
+            } else {
+                find_recursion(helper_functions, callee, path, finished, errors);
+            }
+        }
+    }
+    path.pop();
+    finished.insert(name);
+}
+
+/// Returns a temporary function that can be used to evaluate expressions at
+/// compile time, with access to the given constants and cell states.
+fn make_temp_func(
+    constants: &HashMap<String, ConstValue>,
+    states: &[CellState],
+    int_bits: u32,
+    overflow_mode: OverflowMode,
+) -> UserFunction {
+    let rule_meta = RuleMeta {
+        states: states.to_vec(),
+        int_bits,
+        overflow_mode,
+        constants: constants.clone(),
+        ..RuleMeta::default()
+    };
+    UserFunction::new_helper_function(Rc::new(rule_meta), String::new(), vec![], Type::default())
+}
+
+/// Evaluates a constant definition and returns the name and value of the
+/// constant, given the constants defined before it and the cell states.
+fn eval_constant(
+    constants: &HashMap<String, ConstValue>,
+    states: &[CellState],
+    int_bits: u32,
+    overflow_mode: OverflowMode,
+    contents: Spanned<DirectiveContents>,
+) -> LangResult<(String, ConstValue)> {
+    match contents.inner {
+        DirectiveContents::Definition { name, value_expr } => {
+            if constants.contains_key(&name.inner)
+                || get_builtin_constant(&name.inner, int_bits).is_some()
+            {
+                Err(ConstantNameConflict.with_span(name.span))?;
+            }
+            let mut temp_func = make_temp_func(constants, states, int_bits, overflow_mode);
+            let expr = temp_func.build_expression_ast(&value_expr)?;
+            let value = temp_func.const_eval_expr(expr)?;
+            Ok((name.inner, value))
+        }
+        _ => Err(InternalError("Invalid parse tree on constant".into()).without_span()),
+    }
+}
+
+/// Evaluates a cell state name definition (such as `state ALIVE = #1`) and
+/// assigns the name to that cell state, given the span of the `@states`
+/// directive (if there is one) for errors about cell states that don't exist.
+fn name_cell_state(
+    states: &mut Vec<CellState>,
+    states_span: Option<Span>,
+    constants: &HashMap<String, ConstValue>,
+    int_bits: u32,
+    overflow_mode: OverflowMode,
+    contents: Spanned<DirectiveContents>,
+) -> LangResult<()> {
+    match contents.inner {
+        DirectiveContents::Definition { name, value_expr } => {
+            // Cell state names share a namespace with constants.
+            if constants.contains_key(&name.inner)
+                || states.iter().any(|s| s.name.as_ref() == Some(&name.inner))
+            {
+                Err(ConstantNameConflict.with_span(name.span))?;
+            }
+            let out_of_range = CellStateOutsideStates {
+                count: states.len(),
+                states_span,
+            };
+            let mut temp_func = make_temp_func(constants, states, int_bits, overflow_mode);
+            // Say which directive the cell state conflicts with.
+            let explain_error = |e: LangError| match e.msg {
+                CellStateOutOfRange => LangError {
+                    msg: out_of_range.clone(),
+                    ..e
+                },
+                _ => e,
+            };
+            let expr = temp_func
+                .build_expression_ast(&value_expr)
+                .map_err(explain_error)?;
+            let id = match temp_func.const_eval_expr(expr).map_err(explain_error)? {
+                ConstValue::CellState(id) => id as usize,
+                ConstValue::Int(i) => usize::try_from(i)
+                    .ok()
+                    .filter(|&i| i < states.len())
+                    .ok_or_else(|| out_of_range.with_span(value_expr.span))?,
+                other => Err(TypeError {
+                    expected: Type::CellState,
+                    got: other.ty(),
+                }
+                .with_span(value_expr.span))?,
+            };
+            let state = &mut states[id];
+            if let Some(existing_name) = &state.name {
+                Err(CellStateAlreadyNamed(existing_name.clone()).with_span(name.span))?;
+            }
+            state.name = Some(name.inner);
+            Ok(())
+        }
+        _ => Err(InternalError("Invalid parse tree on cell state name".into()).without_span()),
+    }
+}
+
+/// Removes the `@dimensions` directive from a parse tree and returns the number
+/// of dimensions it specifies, or DEFAULT_NDIM if there is none.
+fn take_ndim(parse_tree: &mut ParseTree, temp_func: &mut UserFunction) -> LangResult<u8> {
+    match parse_tree.take_single_directive(Directive::Dimensions)? {
+        // There is no `@dimensions` directive; use the default.
+        None => Ok(DEFAULT_NDIM),
+        // There is an `@dimensions` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => {
+            let ndim_expr = temp_func.build_expression_ast(&expr)?;
+            let ndim_value = temp_func.const_eval_expr(ndim_expr)?;
+            match ndim_value {
+                // The user specified a valid dimension count.
+                ConstValue::Int(i @ 1..=MAX_NDIM) => Ok(i as u8),
+                // The user specified a number, but it's not a valid dimension
+                // count.
+                ConstValue::Int(_) => Err(InvalidDimensionCount.with_span(expr)),
+                // The user specified some other value.
+                _ => Err(TypeError {
+                    expected: Type::Int,
+                    got: ndim_value.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("expression").with_span(span)),
+    }
+}
+
+/// Removes the `@radius` directive from a parse tree and returns the
+/// neighborhood radius it specifies, or DEFAULT_RADIUS if there is none.
+fn take_radius(parse_tree: &mut ParseTree, temp_func: &mut UserFunction) -> LangResult<u8> {
+    match parse_tree.take_single_directive(Directive::Radius)? {
+        // There is no `@radius` directive; use the default.
+        None => Ok(DEFAULT_RADIUS),
+        // There is a `@radius` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => {
+            let radius_expr = temp_func.build_expression_ast(&expr)?;
+            let radius_value = temp_func.const_eval_expr(radius_expr)?;
+            match radius_value {
+                // The user specified a valid radius.
+                ConstValue::Int(i @ 1..=MAX_RADIUS) => Ok(i as u8),
+                // The user specified a number, but it's not a valid radius.
+                ConstValue::Int(_) => Err(InvalidRadius.with_span(expr)),
+                // The user specified some other value.
+                _ => Err(TypeError {
+                    expected: Type::Int,
+                    got: radius_value.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("expression").with_span(span)),
+    }
+}
+
+/// Removes the `@seed` directive from a parse tree and returns the random seed
+/// it specifies, or DEFAULT_SEED if there is none.
+fn take_seed(parse_tree: &mut ParseTree, temp_func: &mut UserFunction) -> LangResult<LangInt> {
+    match parse_tree.take_single_directive(Directive::Seed)? {
+        // There is no `@seed` directive; use the default.
+        None => Ok(DEFAULT_SEED),
+        // There is a `@seed` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => {
+            let seed_expr = temp_func.build_expression_ast(&expr)?;
+            match temp_func.const_eval_expr(seed_expr)? {
+                // Any integer is a valid seed.
+                ConstValue::Int(i) => Ok(i),
+                // The user specified some other value.
+                other => Err(TypeError {
+                    expected: Type::Int,
+                    got: other.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("expression").with_span(span)),
+    }
+}
+
+/// Removes the `@neighborhood` directive from a parse tree and returns the
+/// neighborhood shape it specifies, or the default shape if there is none.
+fn take_nbhd_shape(parse_tree: &mut ParseTree) -> LangResult<NbhdShape> {
+    match parse_tree.take_single_directive(Directive::Neighborhood)? {
+        // There is no `@neighborhood` directive; use the default.
+        None => Ok(NbhdShape::default()),
+        // There is a `@neighborhood` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => match &expr.inner {
+            parser::Expr::Ident(name) => match name.to_ascii_lowercase().as_ref() {
+                "moore" => Ok(NbhdShape::Moore),
+                "vonneumann" | "von_neumann" => Ok(NbhdShape::VonNeumann),
+                _ => Err(InvalidNbhdShape.with_span(expr.span)),
+            },
+            _ => Err(InvalidNbhdShape.with_span(expr.span)),
+        },
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("neighborhood shape").with_span(span)),
+    }
+}
+
+/// Removes the `@bits` directive from a parse tree and returns the number of
+/// bits in an integer that it specifies, or INT_BITS if there is none.
+///
+/// This is evaluated before any constants, so it cannot refer to them.
+fn take_int_bits(parse_tree: &mut ParseTree) -> LangResult<u32> {
+    match parse_tree.take_single_directive(Directive::IntBits)? {
+        // There is no `@bits` directive; use the default.
+        None => Ok(INT_BITS),
+        // There is a `@bits` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => {
+            let mut temp_func = make_temp_func(
+                &HashMap::new(),
+                &make_default_states(None),
+                INT_BITS,
+                OverflowMode::default(),
+            );
+            let int_bits_expr = temp_func.build_expression_ast(&expr)?;
+            let int_bits_value = temp_func.const_eval_expr(int_bits_expr)?;
+            match int_bits_value {
+                // The user specified a valid number of bits.
+                ConstValue::Int(i)
+                    if (MIN_INT_BITS as LangInt..=INT_BITS as LangInt).contains(&i) =>
+                {
+                    Ok(i as u32)
+                }
+                // The user specified a number, but it's not a valid number of
+                // bits.
+                ConstValue::Int(_) => Err(InvalidIntBits.with_span(expr)),
+                // The user specified some other value.
+                _ => Err(TypeError {
+                    expected: Type::Int,
+                    got: int_bits_value.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("expression").with_span(span)),
+    }
+}
+
+/// Removes the `@overflow` directive from a parse tree and returns the overflow
+/// mode it specifies, or the default mode if there is none.
+///
+/// Unlike other directives, a second `@overflow` directive that specifies a
+/// different mode is reported as a conflict with the first one.
+fn take_overflow_mode(parse_tree: &mut ParseTree) -> LangResult<OverflowMode> {
+    let instances = parse_tree
+        .directives
+        .remove(&Directive::Overflow)
+        .unwrap_or_default();
+    let mut iter = instances.into_iter();
+    match iter.next() {
+        // There is no `@overflow` directive; use the default.
+        None => Ok(OverflowMode::default()),
+        // There is an `@overflow` directive.
+        Some(first) => {
+            let first_span = first.span;
+            let mode = eval_overflow_mode(first)?;
+            if let Some(second) = iter.next() {
+                let second_span = second.span;
+                if eval_overflow_mode(second)? == mode {
+                    Err(RepeatDirective(Directive::Overflow.name()).with_span(second_span))?;
+                } else {
+                    Err(ConflictingDirectives {
+                        name: Directive::Overflow.name(),
+                        first_span,
+                    }
+                    .with_span(second_span))?;
+                }
+            }
+            Ok(mode)
+        }
+    }
+}
+/// Returns the overflow mode specified by the contents of an `@overflow`
+/// directive.
+fn eval_overflow_mode(contents: Spanned<DirectiveContents>) -> LangResult<OverflowMode> {
+    match contents.inner {
+        DirectiveContents::Expr(expr) => match &expr.inner {
+            parser::Expr::Ident(name) => match name.to_ascii_lowercase().as_ref() {
+                "checked" => Ok(OverflowMode::Checked),
+                "wrapping" => Ok(OverflowMode::Wrapping),
+                _ => Err(InvalidOverflowMode.with_span(expr.span)),
+            },
+            _ => Err(InvalidOverflowMode.with_span(expr.span)),
+        },
+        // The user gave something else instead of an expression.
+        _ => Err(Expected("overflow mode").with_span(contents.span)),
+    }
+}
+
+/// Removes the `@states` directive from a parse tree and returns the list of
+/// cell states it specifies, or the default states if there is none.
+fn take_states(
+    parse_tree: &mut ParseTree,
+    temp_func: &mut UserFunction,
+) -> LangResult<Vec<CellState>> {
+    match parse_tree.take_single_directive(Directive::States)? {
+        // There is no `@states` directive; use the default states.
+        None => Ok(make_default_states(None)),
+        // There is an `@states` directive.
+        Some((_span, DirectiveContents::Expr(expr))) => {
+            let states_expr = temp_func.build_expression_ast(&expr)?;
+            let states_value = temp_func.const_eval_expr(states_expr)?;
+            match states_value {
+                // The user specified a valid state count.
+                ConstValue::Int(i @ 1..=MAX_STATES) => Ok(make_default_states(Some(i as usize))),
+                // The user specified a number, but it's not a valid state
+                // count.
+                ConstValue::Int(_) => Err(InvalidStateCount.with_span(expr)),
+                // The user specified some other value.
+                _ => Err(TypeError {
+                    expected: Type::Int,
+                    got: states_value.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        Some((span, _contents)) => Err(Expected("expression").with_span(span)),
+    }
+}
+
+/// Checks the contents of an `@assert` directive.
+fn check_assertion(
+    temp_func: &mut UserFunction,
+    contents: Spanned<DirectiveContents>,
+) -> LangResult<()> {
+    match contents.inner {
+        // There is an `@assert` directive.
+        DirectiveContents::Expr(expr) => {
+            let assert_expr = temp_func.build_expression_ast(&expr)?;
+            match temp_func.const_eval_expr(assert_expr)? {
+                // The assertion failed.
+                ConstValue::Bool(false) | ConstValue::Int(0) => {
+                    Err(AssertionFailed(None).with_span(expr.span))
+                }
+                // The assertion passed.
+                ConstValue::Bool(true) | ConstValue::Int(_) => Ok(()),
+                // The user specified some other value.
+                other => Err(TypeError {
+                    expected: Type::Bool,
+                    got: other.ty(),
+                }
+                .with_span(expr.span)),
+            }
+        }
+        // The user gave something else instead of an expression.
+        _ => Err(Expected("expression").with_span(contents.span)),
+    }
+}
+
+/// Removes the `@transition` directive from a parse tree and builds the
+/// transition function from it, returning every error encountered.
+fn build_transition_function(
+    parse_tree: &mut ParseTree,
+    transition_function: &mut UserFunction,
+) -> Result<(), Vec<LangError>> {
+    match parse_tree
+        .take_single_directive(Directive::Transition)
+        .map_err(|e| vec![e])?
+    {
+        // The user gave a block of code.
+        Some((_span, DirectiveContents::Block(statements))) => {
+            transition_function.build_top_level_statement_block_ast(&statements.inner)
+        }
+        // The user gave something else instead of a code block.
+        Some((span, _contents)) => Err(vec![Expected("code block").with_span(span)]),
+        // The user did not provide a transition function.
+        None => transition_function.build_top_level_statement_block_ast(&vec![]),
+    }
 }
 
 /// Metadata about a rule, such as the number of dimensions and a list of
@@ -187,10 +768,26 @@ pub struct RuleMeta {
     pub source_code: Rc<String>,
     /// Number of dimensions (from 1 to 6).
     pub ndim: u8,
+    /// Neighborhood radius (from 1 to MAX_RADIUS).
+    pub radius: u8,
+    /// Neighborhood shape.
+    pub nbhd_shape: NbhdShape,
     /// List of cell states.
     pub states: Vec<CellState>,
+    /// Number of bits in an integer (from MIN_INT_BITS to INT_BITS).
+    pub int_bits: u32,
+    /// Behavior of integer arithmetic on overflow.
+    pub overflow_mode: OverflowMode,
+    /// Seed for random numbers (see functions::random::Rand).
+    pub seed: LangInt,
     /// Map of names and signatures of helper functions.
     pub helper_function_signatures: HashMap<String, FnSignature>,
+    /// Functions provided by the host.
+    pub extern_functions: ExternFunctions,
+    /// Map of names and values of constants.
+    pub constants: HashMap<String, ConstValue>,
+    /// Span of the first instance of each directive in the source code.
+    pub directive_spans: HashMap<Directive, Span>,
     // /// Cell state tags.
     // tags: HashMap<String, Tag>,
 }
@@ -199,8 +796,16 @@ impl Default for RuleMeta {
         Self {
             source_code: Rc::new(String::new()),
             ndim: DEFAULT_NDIM,
+            radius: DEFAULT_RADIUS,
+            nbhd_shape: NbhdShape::default(),
             states: make_default_states(None),
+            int_bits: INT_BITS,
+            overflow_mode: OverflowMode::default(),
+            seed: DEFAULT_SEED,
             helper_function_signatures: HashMap::new(),
+            extern_functions: ExternFunctions::new(),
+            constants: HashMap::new(),
+            directive_spans: HashMap::new(),
         }
     }
 }
@@ -209,8 +814,157 @@ impl RuleMeta {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Returns the number of cell states.
+    pub fn state_count(&self) -> usize {
+        self.states.len()
+    }
+    /// Returns true if the cell at the given offset from the center of the
+    /// neighborhood is part of the neighborhood.
+    pub fn nbhd_contains(&self, offset: &[LangInt]) -> bool {
+        let radius = self.radius as LangInt;
+        // Check the bounding box first, so that the sum below cannot overflow.
+        let in_bounding_box = offset.iter().all(|&i| -radius <= i && i <= radius);
+        match self.nbhd_shape {
+            NbhdShape::Moore => in_bounding_box,
+            NbhdShape::VonNeumann => {
+                in_bounding_box && offset.iter().map(|i| i.abs()).sum::<LangInt>() <= radius
+            }
+        }
+    }
+    /// Returns the length of the bounding box of the neighborhood along each
+    /// axis.
+    pub fn nbhd_diameter(&self) -> usize {
+        2 * self.radius as usize + 1
+    }
+    /// Returns the number of cells in the bounding box of the neighborhood,
+    /// including the center cell. This is the number of cell states passed to
+    /// the transition function, even if some of them are not part of the
+    /// neighborhood.
+    pub fn nbhd_len(&self) -> usize {
+        self.nbhd_diameter().pow(self.ndim as u32)
+    }
+    /// Returns the index of the center cell among the cell states passed to
+    /// the transition function.
+    pub fn nbhd_center_index(&self) -> usize {
+        self.nbhd_len() / 2
+    }
+    /// Returns the number of cells in the neighborhood, excluding the center
+    /// cell. Unlike nbhd_len(), this only counts cells that are part of the
+    /// neighborhood shape (see nbhd_contains()).
+    pub fn neighbor_count(&self) -> usize {
+        let radius = self.radius as LangInt;
+        let diameter = self.nbhd_diameter();
+        let mut offset = vec![0; self.ndim as usize];
+        (0..self.nbhd_len())
+            .filter(|&index| {
+                // Convert the index back into an offset from the center (see
+                // CompiledFunction::set_nbhd()).
+                let mut rest = index;
+                for component in &mut offset {
+                    *component = (rest % diameter) as LangInt - radius;
+                    rest /= diameter;
+                }
+                index != self.nbhd_center_index() && self.nbhd_contains(&offset)
+            })
+            .count()
+    }
+    /// Returns the value of the constant (user-defined or builtin) or named
+    /// cell state with the given name, if there is one.
+    pub fn get_constant(&self, name: &str) -> Option<ConstValue> {
+        if let Some(value) = self.constants.get(name) {
+            return Some(value.clone());
+        }
+        if let Some(value) = get_builtin_constant(name, self.int_bits) {
+            return Some(value);
+        }
+        self.states
+            .iter()
+            .position(|s| s.name.as_ref().map(String::as_str) == Some(name))
+            .map(|id| ConstValue::CellState(id as LangCellState))
+    }
+    /// Returns the cell state name most similar to the given name, if there is
+    /// one that is similar enough to be a likely typo.
+    pub fn closest_state_name(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.states
+            .iter()
+            .filter_map(|s| s.name.as_ref())
+            .map(|s| (edit_distance(&name, &s.to_ascii_lowercase()), s))
+            .filter(|&(distance, s)| distance <= std::cmp::max(1, s.len() / 3))
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, s)| s.as_str())
+    }
+}
+
+/// Returns the value of the builtin constant with the given name, if there is
+/// one, given the number of bits in an integer.
+fn get_builtin_constant(name: &str, int_bits: u32) -> Option<ConstValue> {
+    match name {
+        "INT_MIN" => Some(ConstValue::Int(min_int_for_bits(int_bits))),
+        "INT_MAX" => Some(ConstValue::Int(max_int_for_bits(int_bits))),
+        _ => None,
+    }
+}
+
+/// Returns the Levenshtein distance between two strings (the minimum number of
+/// single-character insertions, deletions, and substitutions needed to turn one
+/// into the other).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances from the prefix of `a` seen so far to each prefix of `b`.
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut prev_diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = prev_diagonal + (a_char != b_char) as usize;
+            prev_diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
 }
 
 /// A cell state.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
-pub struct CellState;
+pub struct CellState {
+    /// Name of the cell state, if it has one (e.g. `state ALIVE = #1`).
+    pub name: Option<String>,
+}
+
+/// Shape of a neighborhood, given its radius.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NbhdShape {
+    /// Every cell whose offset from the center has no component larger than
+    /// the radius in magnitude (e.g. a 3x3 square in 2D with radius 1).
+    Moore,
+    /// Every cell whose offset from the center has components whose absolute
+    /// values add up to at most the radius (e.g. a plus sign in 2D with radius
+    /// 1).
+    VonNeumann,
+}
+impl Default for NbhdShape {
+    fn default() -> Self {
+        Self::Moore
+    }
+}
+
+/// Behavior of the `+`, `-`, and `*` operators when the result does not fit in
+/// an integer.
+///
+/// Checking for overflow costs a branch for every operation, but catches
+/// mistakes that would otherwise silently produce wrong results. Other
+/// operations (such as division, exponentiation, and `abs()`) are always
+/// checked, and division by zero is always an error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Overflow is a runtime error (`@overflow checked`).
+    Checked,
+    /// Overflow wraps around using two's complement (`@overflow wrapping`).
+    Wrapping,
+}
+impl Default for OverflowMode {
+    fn default() -> Self {
+        Self::Checked
+    }
+}
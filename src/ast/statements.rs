@@ -1,30 +1,78 @@
+use inkwell::IntPredicate;
+
 use super::super::compiler::*;
 use super::super::errors::*;
-use super::super::{Span, Type};
-use super::{ExprRef, StatementRef, UserFunction};
-use LangErrorMsg::{CannotAssignTypeToVariable, InternalError, TypeError};
+use super::super::functions::array::{build_index_check, check_const_index};
+use super::super::functions::logic::const_truthiness;
+use super::super::types::LangInt;
+use super::super::{ConstValue, Span, Spanned, Type};
+use super::{ErrorPointRef, ExprRef, StatementRef, UserFunction};
+use LangErrorMsg::{
+    AssertionFailed, BreakOutsideLoop, CannotAssignToElement, CannotAssignTypeToVariable,
+    CannotUnpack, ContinueOutsideLoop, DuplicateMatchCase, IndexOutOfBounds, IntegerOverflow,
+    InternalError, RemainInHelperFunction, TypeError,
+};
 
 /// List of statements, executed one after another.
 pub type StatementBlock = Vec<StatementRef>;
 
 /// Statement node in the AST.
 pub trait Statement: std::fmt::Debug {
+    /// Returns a short end-user-friendly description of this statement.
+    fn name(&self) -> String;
     /// Returns the span of this statement in the original source code.
     fn span(&self) -> Span;
+    /// Returns the expressions directly used by this statement (not including
+    /// those inside nested blocks).
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![]
+    }
+    /// Returns the blocks of statements nested directly inside this
+    /// statement.
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![]
+    }
+    /// Returns the names of the variables assigned directly by this statement
+    /// (not including those inside nested blocks).
+    fn assigned_vars(&self) -> Vec<&str> {
+        vec![]
+    }
+    /// Returns whether this statement always transfers control elsewhere
+    /// (e.g. `become`, `return`, or `break`), so that any statements after it
+    /// in the same block can never execute.
+    fn is_terminating(&self, _userfunc: &UserFunction) -> bool {
+        false
+    }
     /// Compiles this statement.
     fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()>;
 }
 
-/// Variable assignment statement, such as `set x = 3`.
+/// Variable assignment statement, such as `set x = 3` or `set v[i] = 3`.
 #[derive(Debug)]
 pub struct SetVar {
     /// Span of this statement in the original source code.
     span: Span,
     /// Name of the variable to assign to.
     var_name: String,
+    /// Element to assign to, if this assigns to a single component of a
+    /// vector or element of an array.
+    element: Option<Element>,
     /// Expression to assign.
     value_expr: ExprRef,
 }
+/// Component of a vector variable or element of an array variable that is
+/// assigned to.
+#[derive(Debug)]
+struct Element {
+    /// Whether the variable is an array (as opposed to a vector).
+    is_array: bool,
+    /// Length of the vector or array.
+    len: usize,
+    /// Index of the element.
+    index_expr: ExprRef,
+    /// Error returned if the index is out of range.
+    out_of_bounds_error: ErrorPointRef,
+}
 impl SetVar {
     /// Constructs a new variable assignment statement that assigns the result
     /// of the given expression to the variable with the given name.
@@ -37,9 +85,15 @@ impl SetVar {
         var_name: String,
         value_expr: ExprRef,
     ) -> LangResult<Self> {
+        // If the variable already exists, convert the value to the type of the
+        // variable if possible.
+        let value_expr = match userfunc.try_get_var(span, &var_name) {
+            Ok(var_type) => userfunc.coerce_for_assignment(value_expr, var_type)?,
+            Err(_) => value_expr,
+        };
         let value_expr_span = userfunc[value_expr].span();
         // Check that the result of the expression can be stored in a variable.
-        let expr_type = userfunc[value_expr].return_type();
+        let expr_type = userfunc[value_expr].result_type();
         if !expr_type.has_runtime_representation() {
             Err(CannotAssignTypeToVariable(expr_type).with_span(value_expr_span))?;
         }
@@ -53,28 +107,213 @@ impl SetVar {
         Ok(Self {
             span,
             var_name,
+            element: None,
+            value_expr,
+        })
+    }
+    /// Constructs a new assignment statement that assigns the result of the
+    /// given expression to a single component of an existing vector variable
+    /// or element of an existing array variable, such as `set v[i] = 3`. The
+    /// target span is the span of the variable name and index (e.g. `v[i]`).
+    ///
+    /// This method checks the types of the variable, index, and expression,
+    /// and returns an error if the index is a constant that is out of range.
+    pub fn try_new_indexed(
+        span: Span,
+        userfunc: &mut UserFunction,
+        var_name: Spanned<String>,
+        target_span: Span,
+        index_expr: ExprRef,
+        value_expr: ExprRef,
+    ) -> LangResult<Self> {
+        let (is_array, len) = match userfunc.try_read_var(var_name.span, &var_name.inner)? {
+            Type::Vector(len) => (false, len),
+            Type::Array(len) => (true, len),
+            other => Err(CannotAssignToElement(other).with_span(var_name.span))?,
+        };
+        let got = userfunc[index_expr].result_type();
+        if got != Type::Int {
+            Err(TypeError {
+                expected: Type::Int,
+                got,
+            }
+            .with_span(userfunc[index_expr].span()))?;
+        }
+        check_const_index(userfunc, index_expr, len)?;
+        // Convert the value to an integer if possible.
+        let value_expr = userfunc.coerce_for_assignment(value_expr, Type::Int)?;
+        let got = userfunc[value_expr].result_type();
+        if got != Type::Int {
+            Err(TypeError {
+                expected: Type::Int,
+                got,
+            }
+            .with_span(userfunc[value_expr].span()))?;
+        }
+        let out_of_bounds_error = userfunc.add_error_point(IndexOutOfBounds.with_span(target_span));
+        Ok(Self {
+            span,
+            var_name: var_name.inner,
+            element: Some(Element {
+                is_array,
+                len,
+                index_expr,
+                out_of_bounds_error,
+            }),
             value_expr,
         })
     }
 }
 impl Statement for SetVar {
+    fn name(&self) -> String {
+        format!("set variable {:?}", self.var_name)
+    }
     fn span(&self) -> Span {
         self.span
     }
+    fn exprs(&self) -> Vec<ExprRef> {
+        match &self.element {
+            Some(element) => vec![element.index_expr, self.value_expr],
+            None => vec![self.value_expr],
+        }
+    }
+    fn assigned_vars(&self) -> Vec<&str> {
+        vec![&self.var_name]
+    }
     fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
         let var_ptr = compiler
             .vars()
             .get(&self.var_name)
             .ok_or_else(|| InternalError("Invalid variable index".into()))?
             .ptr;
-        let value = userfunc
-            .compile_expr(compiler, self.value_expr)?
-            .into_basic_value()?;
-        compiler.builder().build_store(var_ptr, value);
+        match &self.element {
+            Some(element) => {
+                let index = userfunc
+                    .compile_expr(compiler, element.index_expr)?
+                    .as_int()?;
+                let value = userfunc.compile_expr(compiler, self.value_expr)?.as_int()?;
+                build_index_check(compiler, index, element.len, &element.out_of_bounds_error)?;
+                if element.is_array {
+                    compiler.build_array_store(var_ptr, index, value);
+                } else {
+                    let b = compiler.builder();
+                    let vector = b.build_load(var_ptr, "tmp_vector").into_vector_value();
+                    let vector = b.build_insert_element(vector, value, index, "tmp_vector");
+                    b.build_store(var_ptr, vector);
+                }
+                Ok(())
+            }
+            None => {
+                let value = userfunc.compile_expr(compiler, self.value_expr)?;
+                compiler.build_store_value(var_ptr, value)
+            }
+        }
+    }
+}
+
+/// Tuple unpacking statement, such as `set (a, b) = f()`.
+#[derive(Debug)]
+pub struct SetTuple {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Names of the variables to assign to, one for each element of the tuple.
+    var_names: Vec<String>,
+    /// Tuple to unpack.
+    value_expr: ExprRef,
+}
+impl SetTuple {
+    /// Constructs a new tuple unpacking statement that assigns each element of
+    /// the result of the given expression to the corresponding variable.
+    ///
+    /// This method creates new variables if they do not already exist, and
+    /// checks the types of the variables and tuple elements.
+    pub fn try_new(
+        span: Span,
+        userfunc: &mut UserFunction,
+        var_names: Vec<Spanned<String>>,
+        value_expr: ExprRef,
+    ) -> LangResult<Self> {
+        let ty = userfunc[value_expr].result_type();
+        let element_types = match ty.tuple_elements() {
+            Some(elements) if elements.len() == var_names.len() => elements,
+            _ => Err(CannotUnpack {
+                ty,
+                count: var_names.len(),
+            }
+            .with_span(userfunc[value_expr].span()))?,
+        };
+        for (var_name, &got) in var_names.iter().zip(element_types) {
+            let expected = userfunc.get_or_create_var(&var_name.inner, got);
+            if expected != got {
+                Err(TypeError { expected, got }.with_span(var_name.span))?;
+            }
+        }
+        Ok(Self {
+            span,
+            var_names: var_names
+                .into_iter()
+                .map(|var_name| var_name.inner)
+                .collect(),
+            value_expr,
+        })
+    }
+}
+impl Statement for SetTuple {
+    fn name(&self) -> String {
+        format!("unpack tuple into {:?}", self.var_names)
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.value_expr]
+    }
+    fn assigned_vars(&self) -> Vec<&str> {
+        self.var_names.iter().map(String::as_str).collect()
+    }
+    fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        let (element_types, tuple) = match userfunc.compile_expr(compiler, self.value_expr)? {
+            Value::Tuple(element_types, tuple) => (element_types, tuple),
+            _ => Err(UNCAUGHT_TYPE_ERROR)?,
+        };
+        for (i, (var_name, &ty)) in self.var_names.iter().zip(element_types).enumerate() {
+            let var_ptr = compiler
+                .vars()
+                .get(var_name)
+                .ok_or_else(|| InternalError("Invalid variable index".into()))?
+                .ptr;
+            let element = compiler
+                .builder()
+                .build_extract_value(tuple, i as u32, "tmp_element")
+                .unwrap();
+            compiler.build_store_value(var_ptr, Value::from_basic_value(ty, element))?;
+        }
         Ok(())
     }
 }
 
+/// Returns a TypeError if the given expression cannot be used as a condition.
+/// Booleans can be used as conditions, and so can integers (where any nonzero
+/// value is truthy).
+fn check_condition_type(userfunc: &UserFunction, cond_expr: ExprRef) -> LangResult<()> {
+    match userfunc[cond_expr].result_type() {
+        Type::Bool | Type::Int => Ok(()),
+        got => Err(TypeError {
+            expected: Type::Bool,
+            got,
+        }
+        .with_span(userfunc[cond_expr].span())),
+    }
+}
+/// Returns whether the given condition expression is truthy, or None if it
+/// cannot be evaluated at compile time.
+pub fn const_condition(userfunc: &UserFunction, cond_expr: ExprRef) -> Option<bool> {
+    userfunc
+        .const_eval_expr(cond_expr)
+        .and_then(const_truthiness)
+        .ok()
+}
+
 /// A conditional statement, such as `if x == 3 { ... } else { ... }`.
 #[derive(Debug)]
 pub struct If {
@@ -90,7 +329,7 @@ pub struct If {
 impl If {
     /// Constructs a new conditional statement branches to either of the given
     /// blocks depending on whether the result of the given expression is truthy
-    /// (nonzero) or falsey (zero).
+    /// (true or nonzero) or falsey (false or zero).
     ///
     /// This method checks the type of the condition expression.
     pub fn try_new(
@@ -100,12 +339,7 @@ impl If {
         if_true: StatementBlock,
         if_false: StatementBlock,
     ) -> LangResult<Self> {
-        let expected = Type::Int;
-        let got = userfunc[cond_expr].return_type();
-        if expected != got {
-            let cond_expr_span = userfunc[cond_expr].span();
-            Err(TypeError { expected, got }.with_span(cond_expr_span))?;
-        }
+        check_condition_type(userfunc, cond_expr)?;
         Ok(Self {
             span,
             cond_expr,
@@ -115,13 +349,33 @@ impl If {
     }
 }
 impl Statement for If {
+    fn name(&self) -> String {
+        "if".to_owned()
+    }
     fn span(&self) -> Span {
         self.span
     }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.cond_expr]
+    }
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![&self.if_true, &self.if_false]
+    }
+    fn is_terminating(&self, userfunc: &UserFunction) -> bool {
+        userfunc.block_is_terminating(&self.if_true)
+            && userfunc.block_is_terminating(&self.if_false)
+    }
     fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        // If the condition is constant, then only compile the branch that is
+        // taken.
+        match const_condition(userfunc, self.cond_expr) {
+            Some(true) => return userfunc.compile_statement_block(compiler, &self.if_true),
+            Some(false) => return userfunc.compile_statement_block(compiler, &self.if_false),
+            None => (),
+        }
         let condition_value = userfunc[self.cond_expr]
             .compile(compiler, userfunc)?
-            .as_int()?;
+            .as_condition()?;
         compiler.build_conditional(
             condition_value,
             |c| userfunc.compile_statement_block(c, &self.if_true),
@@ -131,6 +385,576 @@ impl Statement for If {
     }
 }
 
+/// A statement that branches based on the value of an integer or cell state,
+/// such as `match s { #0 => { ... } #1 => { ... } else => { ... } }`.
+#[derive(Debug)]
+pub struct Match {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Expression to branch based on.
+    expr: ExprRef,
+    /// Cases, each consisting of a constant value and the block of statements
+    /// to execute if the expression has that value.
+    cases: Vec<(ConstValue, StatementBlock)>,
+    /// Block of statements to execute if no case matches.
+    else_block: StatementBlock,
+}
+impl Match {
+    /// Constructs a new match statement that branches to the block of the case
+    /// whose value equals the result of the given expression, or to the `else`
+    /// block if there is none.
+    ///
+    /// This method checks the type of the expression and evaluates the value of
+    /// each case at compile time, returning an error if any case does not have
+    /// the same type as the expression or has the same value as an earlier
+    /// case.
+    pub fn try_new(
+        span: Span,
+        userfunc: &mut UserFunction,
+        expr: ExprRef,
+        cases: Vec<(ExprRef, StatementBlock)>,
+        else_block: StatementBlock,
+    ) -> LangResult<Self> {
+        let expected = userfunc[expr].result_type();
+        if expected != Type::CellState && expected != Type::Int {
+            Err(TypeError {
+                expected: Type::CellState,
+                got: expected,
+            }
+            .with_span(userfunc[expr].span()))?;
+        }
+        let mut const_cases: Vec<(ConstValue, StatementBlock)> = vec![];
+        for (case_expr, block) in cases {
+            let case_span = userfunc[case_expr].span();
+            let got = userfunc[case_expr].result_type();
+            if expected != got {
+                Err(TypeError { expected, got }.with_span(case_span))?;
+            }
+            let value = userfunc.const_eval_expr(case_expr)?;
+            if const_cases.iter().any(|(v, _)| *v == value) {
+                Err(DuplicateMatchCase.with_span(case_span))?;
+            }
+            const_cases.push((value, block));
+        }
+        Ok(Self {
+            span,
+            expr,
+            cases: const_cases,
+            else_block,
+        })
+    }
+}
+impl Statement for Match {
+    fn name(&self) -> String {
+        "match".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.expr]
+    }
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        let mut ret: Vec<_> = self.cases.iter().map(|(_, block)| block).collect();
+        ret.push(&self.else_block);
+        ret
+    }
+    fn is_terminating(&self, userfunc: &UserFunction) -> bool {
+        self.blocks()
+            .into_iter()
+            .all(|block| userfunc.block_is_terminating(block))
+    }
+    fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        let value = userfunc
+            .compile_expr(compiler, self.expr)?
+            .into_basic_value()?
+            .into_int_value();
+
+        // Build the destination blocks.
+        let case_bbs: Vec<_> = self
+            .cases
+            .iter()
+            .map(|_| compiler.append_basic_block("matchCase"))
+            .collect();
+        let else_bb = compiler.append_basic_block("matchElse");
+        let merge_bb = compiler.append_basic_block("endMatch");
+
+        // Build the switch instruction, using the `else` block as the default.
+        let mut switch_cases = vec![];
+        for ((case_value, _), &bb) in self.cases.iter().zip(&case_bbs) {
+            let case_value = compiler
+                .value_from_const(case_value.clone())
+                .into_basic_value()?
+                .into_int_value();
+            switch_cases.push((case_value, bb));
+        }
+        compiler
+            .builder()
+            .build_switch(value, else_bb, &switch_cases);
+
+        // Build the instructions to execute for each case.
+        for ((_, block), bb) in self.cases.iter().zip(case_bbs) {
+            compiler.builder().position_at_end(bb);
+            userfunc.compile_statement_block(compiler, block)?;
+            if compiler.needs_terminator() {
+                compiler.builder().build_unconditional_branch(merge_bb);
+            }
+        }
+
+        // Build the instructions to execute if no case matches (which may be
+        // none, if there is no `else` case).
+        compiler.builder().position_at_end(else_bb);
+        userfunc.compile_statement_block(compiler, &self.else_block)?;
+        if compiler.needs_terminator() {
+            compiler.builder().build_unconditional_branch(merge_bb);
+        }
+
+        compiler.builder().position_at_end(merge_bb);
+        Ok(())
+    }
+}
+
+/// A loop over a range of integers, such as `for x in -1..1 { ... }`.
+#[derive(Debug)]
+pub struct ForLoop {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Name of the loop variable.
+    var_name: String,
+    /// Range of values to iterate over (inclusive on both ends).
+    iter_expr: ExprRef,
+    /// Block of statements to execute on each iteration.
+    block: StatementBlock,
+    /// Error returned if the loop variable overflows.
+    overflow_error: ErrorPointRef,
+}
+impl ForLoop {
+    /// Constructs a new loop statement that executes the given block once for
+    /// each integer in the range resulting from the given expression, storing
+    /// that integer in the loop variable with the given name.
+    ///
+    /// The loop variable must already exist with type Type::Int, because it
+    /// must be created before building the AST nodes for the block. Like other
+    /// variables, it is still accessible after the loop. This method checks the
+    /// type of the range expression.
+    pub fn try_new(
+        span: Span,
+        userfunc: &mut UserFunction,
+        var_name: String,
+        iter_expr: ExprRef,
+        block: StatementBlock,
+    ) -> LangResult<Self> {
+        let iter_expr_span = userfunc[iter_expr].span();
+        let expected = Type::Range;
+        let got = userfunc[iter_expr].result_type();
+        if expected != got {
+            Err(TypeError { expected, got }.with_span(iter_expr_span))?;
+        }
+        Ok(Self {
+            span,
+            var_name,
+            iter_expr,
+            block,
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(iter_expr_span)),
+        })
+    }
+
+    /// Returns the start and end of the range to iterate over, if it is
+    /// constant and short enough to unroll the loop.
+    ///
+    /// Loops that assign to the loop variable in their body cannot be
+    /// unrolled, because that assignment affects the following iterations.
+    fn unrollable_range(
+        &self,
+        compiler: &Compiler,
+        userfunc: &UserFunction,
+    ) -> Option<(LangInt, LangInt)> {
+        let (start, end) = match userfunc.const_eval_expr(self.iter_expr) {
+            Ok(ConstValue::Range(start, end)) => (start, end),
+            _ => return None,
+        };
+        // Compute the iteration count using 128-bit integers so that it cannot
+        // overflow.
+        let iterations = (end as i128 - start as i128 + 1).max(0);
+        if iterations > compiler.unroll_threshold() as i128
+            || userfunc.block_assigns_var(&self.block, &self.var_name)
+        {
+            return None;
+        }
+        Some((start, end))
+    }
+    /// Compiles this loop by building the body once for each value in the
+    /// (constant) range, storing each value in the loop variable beforehand.
+    fn compile_unrolled(
+        &self,
+        compiler: &mut Compiler,
+        userfunc: &UserFunction,
+        start: LangInt,
+        end: LangInt,
+    ) -> LangResult<()> {
+        let var_ptr = compiler
+            .vars()
+            .get(&self.var_name)
+            .ok_or_else(|| InternalError("Invalid variable index".into()))?
+            .ptr;
+        // The loop variable is assigned even if the range is empty.
+        let start_value = compiler
+            .value_from_const(ConstValue::Int(start))
+            .into_basic_value()?;
+        compiler.builder().build_store(var_ptr, start_value);
+        if start > end {
+            return Ok(());
+        }
+
+        let exit_bb = compiler.append_basic_block("endFor");
+        for i in start..=end {
+            // `continue` jumps to the start of the next iteration.
+            let next_bb = if i == end {
+                exit_bb
+            } else {
+                compiler.append_basic_block("forUnrolled")
+            };
+            let value = compiler
+                .value_from_const(ConstValue::Int(i))
+                .into_basic_value()?;
+            compiler.builder().build_store(var_ptr, value);
+            compiler.build_loop_body(next_bb, exit_bb, |c| {
+                userfunc.compile_statement_block(c, &self.block)
+            })?;
+            if compiler.needs_terminator() {
+                compiler.builder().build_unconditional_branch(next_bb);
+            }
+            compiler.builder().position_at_end(next_bb);
+        }
+        Ok(())
+    }
+}
+impl Statement for ForLoop {
+    fn name(&self) -> String {
+        format!("for loop over variable {:?}", self.var_name)
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.iter_expr]
+    }
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![&self.block]
+    }
+    fn assigned_vars(&self) -> Vec<&str> {
+        vec![&self.var_name]
+    }
+    fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        if let Some((start, end)) = self.unrollable_range(compiler, userfunc) {
+            return self.compile_unrolled(compiler, userfunc, start, end);
+        }
+
+        let (start, end) = userfunc
+            .compile_expr(compiler, self.iter_expr)?
+            .as_range()?;
+        let var_ptr = compiler
+            .vars()
+            .get(&self.var_name)
+            .ok_or_else(|| InternalError("Invalid variable index".into()))?
+            .ptr;
+        compiler.builder().build_store(var_ptr, start);
+
+        // Build the destination blocks.
+        let body_bb = compiler.append_basic_block("forBody");
+        let latch_bb = compiler.append_basic_block("forLatch");
+        let increment_bb = compiler.append_basic_block("forIncrement");
+        let exit_bb = compiler.append_basic_block("endFor");
+
+        // Skip the loop entirely if the range is empty.
+        let is_empty =
+            compiler
+                .builder()
+                .build_int_compare(IntPredicate::SGT, start, end, "isEmptyRange");
+        compiler
+            .builder()
+            .build_conditional_branch(is_empty, exit_bb, body_bb);
+
+        // Build the instructions to execute on each iteration.
+        compiler.builder().position_at_end(body_bb);
+        compiler.build_loop_body(latch_bb, exit_bb, |c| {
+            userfunc.compile_statement_block(c, &self.block)
+        })?;
+        if compiler.needs_terminator() {
+            compiler.builder().build_unconditional_branch(latch_bb);
+        }
+
+        // Exit the loop if the loop variable has reached the end of the range
+        // (checking before incrementing so that a range ending at the maximum
+        // integer value does not overflow).
+        compiler.builder().position_at_end(latch_bb);
+        let value = compiler
+            .builder()
+            .build_load(var_ptr, &self.var_name)
+            .into_int_value();
+        let is_done =
+            compiler
+                .builder()
+                .build_int_compare(IntPredicate::SGE, value, end, "isLoopDone");
+        compiler
+            .builder()
+            .build_conditional_branch(is_done, exit_bb, increment_bb);
+
+        // Otherwise increment the loop variable and start the next iteration.
+        compiler.builder().position_at_end(increment_bb);
+        let one = compiler.int_type().const_int(1, false);
+        let next_value = compiler.build_checked_int_arithmetic(value, one, "sadd", |c| {
+            Ok(self.overflow_error.compile(c))
+        })?;
+        compiler.builder().build_store(var_ptr, next_value);
+        compiler.builder().build_unconditional_branch(body_bb);
+
+        compiler.builder().position_at_end(exit_bb);
+        Ok(())
+    }
+}
+
+/// A loop that executes while a condition is truthy, such as `while x < 3 {
+/// ... }`.
+#[derive(Debug)]
+pub struct WhileLoop {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Expression to check before each iteration.
+    cond_expr: ExprRef,
+    /// Block of statements to execute on each iteration.
+    block: StatementBlock,
+}
+impl WhileLoop {
+    /// Constructs a new loop statement that repeatedly executes the given block
+    /// as long as the result of the given expression is truthy (true or
+    /// nonzero).
+    ///
+    /// This method checks the type of the condition expression.
+    pub fn try_new(
+        span: Span,
+        userfunc: &mut UserFunction,
+        cond_expr: ExprRef,
+        block: StatementBlock,
+    ) -> LangResult<Self> {
+        check_condition_type(userfunc, cond_expr)?;
+        Ok(Self {
+            span,
+            cond_expr,
+            block,
+        })
+    }
+}
+impl Statement for WhileLoop {
+    fn name(&self) -> String {
+        "while loop".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.cond_expr]
+    }
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![&self.block]
+    }
+    fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        let const_condition = const_condition(userfunc, self.cond_expr);
+        if const_condition == Some(false) {
+            // The loop body never executes.
+            return Ok(());
+        }
+
+        // Build the destination blocks. If the condition is always true, then
+        // there is no need to check it.
+        let cond_bb = match const_condition {
+            Some(true) => None,
+            _ => Some(compiler.append_basic_block("whileCond")),
+        };
+        let body_bb = compiler.append_basic_block("whileBody");
+        let exit_bb = compiler.append_basic_block("endWhile");
+
+        let cond_bb = if let Some(cond_bb) = cond_bb {
+            // Check the condition before each iteration, using a switch
+            // instruction (because the condition might not be 1-bit).
+            compiler.builder().build_unconditional_branch(cond_bb);
+            compiler.builder().position_at_end(cond_bb);
+            let condition_value = userfunc[self.cond_expr]
+                .compile(compiler, userfunc)?
+                .as_condition()?;
+            compiler.builder().build_switch(
+                condition_value,
+                body_bb,
+                &[(condition_value.get_type().const_zero(), exit_bb)],
+            );
+            cond_bb
+        } else {
+            // Only `break` (or `become`/`return`) exits the loop.
+            compiler.builder().build_unconditional_branch(body_bb);
+            body_bb
+        };
+
+        // Build the instructions to execute on each iteration.
+        compiler.builder().position_at_end(body_bb);
+        compiler.build_loop_body(cond_bb, exit_bb, |c| {
+            userfunc.compile_statement_block(c, &self.block)
+        })?;
+        if compiler.needs_terminator() {
+            compiler.builder().build_unconditional_branch(cond_bb);
+        }
+
+        compiler.builder().position_at_end(exit_bb);
+        Ok(())
+    }
+}
+
+/// A statement that exits the innermost loop.
+#[derive(Debug)]
+pub struct Break {
+    /// Span of this statement in the original source code.
+    span: Span,
+}
+impl Break {
+    /// Constructs a new break statement, or returns an error if it is not
+    /// inside a loop.
+    pub fn try_new(span: Span, userfunc: &UserFunction) -> LangResult<Self> {
+        if !userfunc.is_in_loop() {
+            return Err(BreakOutsideLoop.with_span(span));
+        }
+        Ok(Self { span })
+    }
+}
+impl Statement for Break {
+    fn name(&self) -> String {
+        "break statement".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn is_terminating(&self, _userfunc: &UserFunction) -> bool {
+        true
+    }
+    fn compile(&self, compiler: &mut Compiler, _userfunc: &UserFunction) -> LangResult<()> {
+        let loop_blocks = compiler
+            .current_loop()
+            .ok_or_else(|| BreakOutsideLoop.with_span(self.span))?;
+        // Any statements after this one in the same block are unreachable, so
+        // compile_statement_block() will skip them.
+        compiler
+            .builder()
+            .build_unconditional_branch(loop_blocks.break_bb);
+        Ok(())
+    }
+}
+
+/// A statement that skips to the next iteration of the innermost loop.
+#[derive(Debug)]
+pub struct Continue {
+    /// Span of this statement in the original source code.
+    span: Span,
+}
+impl Continue {
+    /// Constructs a new continue statement, or returns an error if it is not
+    /// inside a loop.
+    pub fn try_new(span: Span, userfunc: &UserFunction) -> LangResult<Self> {
+        if !userfunc.is_in_loop() {
+            return Err(ContinueOutsideLoop.with_span(span));
+        }
+        Ok(Self { span })
+    }
+}
+impl Statement for Continue {
+    fn name(&self) -> String {
+        "continue statement".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn is_terminating(&self, _userfunc: &UserFunction) -> bool {
+        true
+    }
+    fn compile(&self, compiler: &mut Compiler, _userfunc: &UserFunction) -> LangResult<()> {
+        let loop_blocks = compiler
+            .current_loop()
+            .ok_or_else(|| ContinueOutsideLoop.with_span(self.span))?;
+        // Any statements after this one in the same block are unreachable, so
+        // compile_statement_block() will skip them.
+        compiler
+            .builder()
+            .build_unconditional_branch(loop_blocks.continue_bb);
+        Ok(())
+    }
+}
+
+/// An assertion statement, such as `assert x > 0, "x must be positive"`.
+#[derive(Debug)]
+pub struct Assert {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Expression to check.
+    cond_expr: ExprRef,
+    /// Error returned if the condition is falsey, or None if the condition is
+    /// always truthy.
+    failed_error: Option<ErrorPointRef>,
+}
+impl Assert {
+    /// Constructs a new assertion statement that returns an error (with the
+    /// given custom message, if any) if the result of the given expression is
+    /// falsey (false or zero).
+    ///
+    /// This method checks the type of the condition expression, and returns
+    /// an error if the condition is a constant that is falsey.
+    pub fn try_new(
+        span: Span,
+        userfunc: &mut UserFunction,
+        cond_expr: ExprRef,
+        msg: Option<String>,
+    ) -> LangResult<Self> {
+        check_condition_type(userfunc, cond_expr)?;
+        let error = AssertionFailed(msg).with_span(userfunc[cond_expr].span());
+        let failed_error = match const_condition(userfunc, cond_expr) {
+            // The assertion always passes, so it compiles to nothing.
+            Some(true) => None,
+            // The assertion always fails.
+            Some(false) => Err(error)?,
+            None => Some(userfunc.add_error_point(error)),
+        };
+        Ok(Self {
+            span,
+            cond_expr,
+            failed_error,
+        })
+    }
+}
+impl Statement for Assert {
+    fn name(&self) -> String {
+        "assert".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.cond_expr]
+    }
+    fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
+        let failed_error = match &self.failed_error {
+            Some(failed_error) => failed_error,
+            None => return Ok(()),
+        };
+        let condition_value = userfunc[self.cond_expr]
+            .compile(compiler, userfunc)?
+            .as_condition()?;
+        compiler.build_conditional(
+            condition_value,
+            |_| Ok(()),
+            |c| {
+                failed_error.compile(c);
+                Ok(())
+            },
+        )
+    }
+}
+
 /// A return statement, such as `return 3` or `become #live`.
 #[derive(Debug)]
 pub struct Return {
@@ -145,9 +969,11 @@ impl Return {
     ///
     /// This method checks the type of the expression to return.
     pub fn try_new(span: Span, userfunc: &mut UserFunction, ret_expr: ExprRef) -> LangResult<Self> {
-        // Check that the expression matches the expected return type.
+        // Convert the value to the return type if possible.
         let expected = userfunc.return_type();
-        let got = userfunc[ret_expr].return_type();
+        let ret_expr = userfunc.coerce_for_assignment(ret_expr, expected)?;
+        // Check that the expression matches the expected return type.
+        let got = userfunc[ret_expr].result_type();
         if expected != got {
             Err(TypeError { expected, got }.with_span(span))?;
         }
@@ -155,12 +981,70 @@ impl Return {
     }
 }
 impl Statement for Return {
+    fn name(&self) -> String {
+        "return".to_owned()
+    }
     fn span(&self) -> Span {
         self.span
     }
+    fn exprs(&self) -> Vec<ExprRef> {
+        vec![self.ret_expr]
+    }
+    fn is_terminating(&self, _userfunc: &UserFunction) -> bool {
+        true
+    }
     fn compile(&self, compiler: &mut Compiler, userfunc: &UserFunction) -> LangResult<()> {
         let return_value = userfunc.compile_expr(compiler, self.ret_expr)?;
         compiler.build_return_ok(return_value)?;
         Ok(())
     }
 }
+
+/// A remain statement, which returns the current state of the center cell from
+/// the transition function.
+#[derive(Debug)]
+pub struct Remain {
+    /// Span of this statement in the original source code.
+    span: Span,
+    /// Index of the center cell in the neighborhood.
+    center_index: usize,
+}
+impl Remain {
+    /// Constructs a new remain statement.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function.
+    pub fn try_new(span: Span, userfunc: &UserFunction) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(RemainInHelperFunction.with_span(span))?;
+        }
+        Ok(Self {
+            span,
+            center_index: userfunc.rule_meta().nbhd_center_index(),
+        })
+    }
+}
+impl Statement for Remain {
+    fn name(&self) -> String {
+        "remain".to_owned()
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+    fn is_terminating(&self, _userfunc: &UserFunction) -> bool {
+        true
+    }
+    fn compile(&self, compiler: &mut Compiler, _userfunc: &UserFunction) -> LangResult<()> {
+        build_remain(compiler, self.center_index)
+    }
+}
+
+/// Builds instructions to return the state of the center cell, given its
+/// index in the neighborhood.
+pub fn build_remain(compiler: &mut Compiler, center_index: usize) -> LangResult<()> {
+    let index = compiler
+        .nbhd_index_type()
+        .const_int(center_index as u64, false);
+    let center = compiler.build_nbhd_load(index)?;
+    compiler.build_return_ok(Value::CellState(center))
+}
@@ -1,17 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
 use std::ops::Index;
 use std::rc::Rc;
 
 use super::super::compiler::{CompiledFunction, Compiler, Value};
 use super::super::errors::*;
 use super::super::functions;
-use super::super::lexer::{OperatorToken, PunctuationToken};
+use super::super::lexer::{KeywordToken, OperatorToken, PunctuationToken};
 use super::super::parser;
+use super::super::types::{int_fits_in_bits, LangInt, MAX_VECTOR_LEN};
 use super::super::{ConstValue, Span, Spanned, Type};
 use super::statements;
-use super::{Args, Expr, Function, RuleMeta, Statement, StatementBlock};
+use super::{
+    is_valid_extern_type, Args, Expr, FnSignature, Function, RuleMeta, Statement, StatementBlock,
+};
 use LangErrorMsg::{
-    BecomeInHelperFunction, ExpectedGot, InternalError, ReturnInTransitionFunction,
+    AssignToConstant, BecomeInHelperFunction, Expected, ExpectedGot, IntegerOverflow,
+    InternalError, InvalidExternType, InvalidTupleElement, InvalidTupleLength, InvalidVectorLength,
+    MissingBlockValue, MissingReturn, PoisonedExpression, ReturnInTransitionFunction, TypeError,
+    UnknownExternFunction, UnknownFunction, UnknownMethod, UnknownProperty, UnreachableStatement,
     UseOfUninitializedVariable,
 };
 
@@ -34,19 +41,48 @@ pub struct UserFunction {
     expressions: Vec<Expr>,
     /// List of every possible runtime error.
     error_points: Vec<LangError>,
+    /// Number of possible runtime errors that are not in error_points because
+    /// they are inside a dead block (see dead_block_depth).
+    dead_error_point_count: usize,
+    /// List of compile errors encountered so far while building the AST.
+    errors: Vec<LangError>,
 
     /// HashMap of variable types, indexed by name.
     variables: HashMap<String, Type>,
+    /// Set of variables that are definitely assigned at the current point
+    /// while building the AST (i.e. assigned on every path leading there).
+    assigned_vars: HashSet<String>,
+    /// Set of variables whose assignment had an error, so that errors from
+    /// reading them are not reported.
+    poisoned_vars: HashSet<String>,
+    /// Set of variables that are only used inside dead blocks (see
+    /// dead_block_depth), which are left out of the compiled function.
+    dead_vars: HashSet<String>,
     /// List of variable names for arguments.
     arg_names: Vec<String>,
     /// Return ttype of this function.
     return_type: Type,
+    /// Names of helper functions called directly by this function, along with
+    /// the span of the first call to each one.
+    called_helpers: BTreeMap<String, Span>,
+    /// Largest distance along each axis from the center of the neighborhood
+    /// of any neighbor that this function may read (see halo_radius()).
+    halo_radius: Vec<usize>,
+
+    /// Number of loops enclosing the statement currently being built.
+    loop_depth: usize,
+    /// Number of dead blocks enclosing the statement currently being built. A
+    /// dead block is never compiled because its condition is constant (such as
+    /// the body of `if false { ... }`), so the error points and variables
+    /// created inside it are not registered.
+    dead_block_depth: usize,
 }
 impl UserFunction {
     /// Constructs a new transition function.
     pub fn new_transition_function(rule_meta: Rc<RuleMeta>) -> Self {
         Self {
             is_transition_function: true,
+            halo_radius: vec![0; rule_meta.ndim as usize],
             // TODO: take arguments in the transition function
             // TODO: reserved word for transition function?
             ..Self::new_helper_function(rule_meta, "transition".to_owned(), vec![], Type::CellState)
@@ -74,16 +110,37 @@ impl UserFunction {
             statements: vec![],
             expressions: vec![],
             error_points: vec![],
+            dead_error_point_count: 0,
+            errors: vec![],
 
+            // Arguments are always assigned.
+            assigned_vars: arg_names.iter().cloned().collect(),
+            poisoned_vars: HashSet::new(),
+            dead_vars: HashSet::new(),
             arg_names,
             variables,
             return_type,
+            called_helpers: BTreeMap::new(),
+            // Helper functions cannot read neighbors.
+            halo_radius: vec![],
+
+            loop_depth: 0,
+            dead_block_depth: 0,
         }
     }
+    /// Constructs a helper function from a parse tree, returning every error
+    /// encountered while building it.
     pub fn build_helper_function(
         rule_meta: &Rc<RuleMeta>,
         helper_func: parser::HelperFunc,
-    ) -> LangResult<Self> {
+    ) -> Result<Self, Vec<LangError>> {
+        // Arguments cannot have the same name as a constant or cell state.
+        for arg in &helper_func.args {
+            if rule_meta.get_constant(&arg.inner.1.inner).is_some() {
+                return Err(vec![AssignToConstant.with_span(arg.inner.1.span)]);
+            }
+        }
+        let name_span = helper_func.name.span;
         let mut ret = Self::new_helper_function(
             rule_meta.clone(),
             helper_func.name.inner,
@@ -95,6 +152,11 @@ impl UserFunction {
             helper_func.return_type.inner.resolve(rule_meta.ndim),
         );
         ret.build_top_level_statement_block_ast(&helper_func.body.inner)?;
+        // Every path through a helper function must return a value. (The
+        // transition function implicitly uses `remain` instead.)
+        if !ret.block_is_terminating(&ret.top_level_statements) {
+            return Err(vec![MissingReturn.with_span(name_span)]);
+        }
         Ok(ret)
     }
 
@@ -107,6 +169,11 @@ impl UserFunction {
     pub fn name(&self) -> &str {
         &self.name
     }
+    /// Returns whether this is the transition function, as opposed to a
+    /// helper function.
+    pub fn is_transition_function(&self) -> bool {
+        self.is_transition_function
+    }
 
     /// Returns the names of the arguments to this function.
     pub fn arg_names(&self) -> &[String] {
@@ -116,6 +183,21 @@ impl UserFunction {
     pub fn return_type(&self) -> Type {
         self.return_type
     }
+    /// Returns the names of the helper functions that this function calls
+    /// directly, along with the span of the first call to each one.
+    pub fn called_helpers(&self) -> &BTreeMap<String, Span> {
+        &self.called_helpers
+    }
+    /// Returns the signature of this function.
+    pub fn signature(&self) -> FnSignature {
+        FnSignature::new(
+            self.arg_names
+                .iter()
+                .map(|name| self.variables[name])
+                .collect::<Vec<_>>(),
+            self.return_type,
+        )
+    }
 
     /// Returns the type of an existing variable with the given name, or an
     /// Err(UseOfUninitializedVariable) if it does not exist.
@@ -125,99 +207,612 @@ impl UserFunction {
             .copied()
             .ok_or_else(|| UseOfUninitializedVariable.with_span(span))
     }
+    /// Returns the type of an existing variable with the given name, or an
+    /// Err(UseOfUninitializedVariable) if it does not exist or might not have
+    /// been assigned at the current point while building the AST.
+    ///
+    /// If an earlier assignment to the variable had an error, then this
+    /// returns Err(PoisonedExpression) instead.
+    pub fn try_read_var(&self, span: Span, var_name: &str) -> LangResult<Type> {
+        match self.variables.get(var_name) {
+            Some(&ty) if self.assigned_vars.contains(var_name) => Ok(ty),
+            _ if self.poisoned_vars.contains(var_name) => Err(PoisonedExpression.with_span(span)),
+            _ => Err(UseOfUninitializedVariable.with_span(span)),
+        }
+    }
+    /// Returns an Err(AssignToConstant) if there is a constant or named cell
+    /// state with the given name, since those cannot be assigned to.
+    pub fn check_assignable(&self, var_name: &Spanned<String>) -> LangResult<()> {
+        if self.rule_meta.get_constant(&var_name.inner).is_some() {
+            Err(AssignToConstant.with_span(var_name.span))
+        } else {
+            Ok(())
+        }
+    }
     /// Returns the type of the variable with the given name, creating it with
     /// the given type if it does not already exist.
+    ///
+    /// Variables can only be read after they are assigned, so every variable
+    /// used outside of a dead block is passed to this method outside of one.
     pub fn get_or_create_var(&mut self, var_name: &str, new_ty: Type) -> Type {
-        if let Some(existing_type) = self.variables.get(var_name) {
-            *existing_type
+        let is_dead = self.dead_block_depth > 0;
+        if let Some(&existing_type) = self.variables.get(var_name) {
+            if !is_dead {
+                self.dead_vars.remove(var_name);
+            }
+            existing_type
         } else {
             self.variables.insert(var_name.to_owned(), new_ty);
+            if is_dead {
+                self.dead_vars.insert(var_name.to_owned());
+            }
             new_ty
         }
     }
+    /// Returns the types of the variables that are used outside of dead blocks
+    /// (see dead_block_depth), indexed by name.
+    fn live_variables(&self) -> HashMap<String, Type> {
+        self.variables
+            .iter()
+            .filter(|(name, _)| !self.dead_vars.contains(*name))
+            .map(|(name, &ty)| (name.clone(), ty))
+            .collect()
+    }
+    /// Returns whether the statement currently being built is inside a loop.
+    pub fn is_in_loop(&self) -> bool {
+        self.loop_depth > 0
+    }
+    /// Records that this function reads the neighbor at the given offset from
+    /// the center of the neighborhood, or possibly any neighbor if the offset
+    /// is None (because it is not constant), so that halo_radius() covers it.
+    ///
+    /// Reads inside a dead block (see dead_block_depth) are not recorded, since
+    /// they are never compiled.
+    pub fn record_neighbor_read(&mut self, offset: Option<&[LangInt]>) {
+        if self.dead_block_depth > 0 {
+            return;
+        }
+        let radius = self.rule_meta.radius as usize;
+        for (axis, max_distance) in self.halo_radius.iter_mut().enumerate() {
+            let distance = match offset {
+                Some(offset) => offset[axis].unsigned_abs() as usize,
+                None => radius,
+            };
+            *max_distance = (*max_distance).max(distance);
+        }
+    }
+    /// Returns the largest distance along each axis from the center of the
+    /// neighborhood of any neighbor that this function may read, which is
+    /// never more than the neighborhood radius. This is empty for helper
+    /// functions, which cannot read neighbors.
+    pub fn halo_radius(&self) -> &[usize] {
+        &self.halo_radius
+    }
+
+    /// Returns an expression that converts the result of the given expression
+    /// to the given type, if the types are different and the conversion is
+    /// allowed when assigning to a variable or returning from a function;
+    /// otherwise returns the original expression.
+    ///
+    /// The conversions are from a boolean to an integer (1 or 0), and from an
+    /// integer to a vector, by copying the integer into every component. The
+    /// latter is intentionally not used in other contexts (such as
+    /// comparisons), where it would be ambiguous.
+    ///
+    /// An expression that never produces a value (see Expr::is_never()) is
+    /// given the type directly instead.
+    pub fn coerce_for_assignment(&mut self, expr: ExprRef, ty: Type) -> LangResult<ExprRef> {
+        if self[expr].is_never() {
+            self.expressions[expr.0].set_never_type(ty);
+            return Ok(expr);
+        }
+        let function: Box<dyn Function> = match (self[expr].result_type(), ty) {
+            (Type::Bool, Type::Int) => Box::new(functions::convert::BoolToInt),
+            (Type::Int, Type::Vector(len)) => Box::new(functions::convert::IntToVector { len }),
+            _ => return Ok(expr),
+        };
+        self.convert_expr(expr, function)
+    }
+    /// Returns an expression that applies the given conversion function to the
+    /// result of the given expression, with the same span as the original.
+    pub fn convert_expr(
+        &mut self,
+        expr: ExprRef,
+        function: Box<dyn Function>,
+    ) -> LangResult<ExprRef> {
+        let span = self[expr].span();
+        let new_expr = Expr::try_new(span, self, function, Args::from(vec![expr]))?;
+        Ok(self.add_expr(new_expr))
+    }
 
     /// Constructs AST nodes for statements in a block from a parse tree and
     /// adds those AST nodes to a list of top-level statements (i.e. statements
     /// that are not inside a loop or conditional block).
+    ///
+    /// Returns every error encountered while building the AST, not just the
+    /// first one.
     pub fn build_top_level_statement_block_ast(
         &mut self,
         parser_statements: &parser::StatementBlock,
-    ) -> LangResult<()> {
-        self.top_level_statements = self.build_statement_block_ast(parser_statements)?;
-        Ok(())
+    ) -> Result<(), Vec<LangError>> {
+        self.top_level_statements = self.build_statement_block_ast(parser_statements);
+        if self.errors.is_empty() {
+            self.fold_constants();
+            Ok(())
+        } else {
+            // Errors are not necessarily recorded in the order they appear in
+            // the source code, so sort them.
+            let mut errors = std::mem::take(&mut self.errors);
+            errors.sort_by_key(|e| e.span.map(|span| span.start));
+            Err(errors)
+        }
     }
     /// Constructs AST nodes for statements in a block from a parse tree.
+    ///
+    /// A statement that cannot be built is left out of the block and its error
+    /// is recorded (see report_error()), so that later statements can still be
+    /// checked.
     pub fn build_statement_block_ast(
         &mut self,
         parser_statements: &parser::StatementBlock,
-    ) -> LangResult<StatementBlock> {
-        let mut block = vec![];
+    ) -> StatementBlock {
+        let mut block: StatementBlock = vec![];
+        let mut reported_unreachable = false;
         for parser_statement in parser_statements {
-            let span = parser_statement.span;
-
-            let new_statement: Box<dyn Statement> = match &parser_statement.inner {
-                // Variable assignment statement
-                parser::Statement::SetVar {
-                    var_name,
-                    assign_op,
-                    value_expr,
-                } => {
-                    // Handle assignments with operators (e.g. `x += 3`).
-                    let value_expr = match assign_op.op() {
-                        Some(op) => self.build_expression_ast(&Spanned {
-                            span,
-                            inner: parser::Expr::BinaryOp {
-                                lhs: Box::new(var_name.clone().map(parser::Expr::Ident)),
-                                op,
-                                rhs: Box::new(value_expr.clone()),
-                            },
-                        })?,
-                        None => self.build_expression_ast(&value_expr)?,
-                    };
-                    Box::new(statements::SetVar::try_new(
+            // Statements after one that always terminates (such as `become`)
+            // can never execute, which is almost certainly a mistake.
+            if let Some(&last_statement) = block.last() {
+                if !reported_unreachable && self.statement_is_terminating(last_statement) {
+                    self.report_error(UnreachableStatement.with_span(parser_statement.span));
+                    reported_unreachable = true;
+                }
+            }
+
+            match self.build_statement_ast(parser_statement) {
+                Ok(new_statement) => block.push(self.add_statement(new_statement)),
+                Err(e) => {
+                    // If a variable assignment fails, then later errors from
+                    // reading that variable are not worth reporting. (This
+                    // does not apply to assigning a single array element,
+                    // since the variable was already assigned.)
+                    match &parser_statement.inner {
+                        parser::Statement::SetVar {
+                            var_name,
+                            index_expr: None,
+                            ..
+                        } => {
+                            self.poisoned_vars.insert(var_name.inner.clone());
+                        }
+                        parser::Statement::SetTuple { var_names, .. } => {
+                            for var_name in var_names {
+                                self.poisoned_vars.insert(var_name.inner.clone());
+                            }
+                        }
+                        _ => (),
+                    }
+                    self.report_error(e);
+                }
+            }
+        }
+        block
+    }
+    /// Constructs an AST node for a single statement from a parse tree.
+    ///
+    /// Errors inside nested blocks are recorded rather than returned (see
+    /// build_statement_block_ast()), so the nested blocks are built even if
+    /// the statement itself has an error.
+    fn build_statement_ast(
+        &mut self,
+        parser_statement: &Spanned<parser::Statement>,
+    ) -> LangResult<Box<dyn Statement>> {
+        let span = parser_statement.span;
+        Ok(match &parser_statement.inner {
+            // Variable assignment statement
+            parser::Statement::SetVar {
+                var_name,
+                index_expr,
+                assign_op,
+                value_expr,
+            } => {
+                self.check_assignable(var_name)?;
+                // Get the expression being assigned to (e.g. `x` or `a[i]`).
+                let var_expr = var_name.clone().map(parser::Expr::Ident);
+                let target_expr = match index_expr {
+                    Some(index_expr) => Spanned {
+                        span: Span::merge(var_name.span, index_expr.span),
+                        inner: parser::Expr::Index {
+                            operand: Box::new(var_expr),
+                            index: Box::new(index_expr.inner.clone()),
+                        },
+                    },
+                    None => var_expr,
+                };
+                // Handle assignments with operators (e.g. `x += 3`).
+                let value_expr = match assign_op.op() {
+                    Some(op) => self.build_expression_ast(&Spanned {
                         span,
-                        self,
-                        var_name.inner.clone(),
-                        value_expr,
-                    )?)
+                        inner: parser::Expr::BinaryOp {
+                            lhs: Box::new(target_expr),
+                            op,
+                            rhs: Box::new(value_expr.clone()),
+                        },
+                    })?,
+                    None => self.build_expression_ast(&value_expr)?,
+                };
+                match index_expr {
+                    // Assigning to a single array element
+                    Some(index_expr) => {
+                        let target_span = Span::merge(var_name.span, index_expr.span);
+                        let index_expr = self.build_int_operand_ast(&index_expr.inner)?;
+                        Box::new(statements::SetVar::try_new_indexed(
+                            span,
+                            self,
+                            var_name.clone(),
+                            target_span,
+                            index_expr,
+                            value_expr,
+                        )?)
+                    }
+                    // Assigning to a whole variable
+                    None => {
+                        let statement = statements::SetVar::try_new(
+                            span,
+                            self,
+                            var_name.inner.clone(),
+                            value_expr,
+                        )?;
+                        self.assigned_vars.insert(var_name.inner.clone());
+                        Box::new(statement)
+                    }
                 }
-                // If statement
-                parser::Statement::If {
-                    cond_expr,
-                    if_true,
-                    if_false,
-                } => {
-                    let cond_expr = self.build_expression_ast(cond_expr)?;
-                    let if_true = self.build_statement_block_ast(if_true)?;
-                    let if_false = self.build_statement_block_ast(if_false)?;
-                    Box::new(statements::If::try_new(
-                        span, self, cond_expr, if_true, if_false,
-                    )?)
+            }
+            // Tuple unpacking statement
+            parser::Statement::SetTuple {
+                var_names,
+                value_expr,
+            } => {
+                for var_name in var_names {
+                    self.check_assignable(var_name)?;
                 }
-                // Become statement (In a transition function, `become` should be used, not `return`.)
-                parser::Statement::Become(ret_expr) => {
-                    if self.is_transition_function {
-                        let ret_expr = self.build_expression_ast(ret_expr)?;
-                        Box::new(statements::Return::try_new(span, self, ret_expr)?)
-                    } else {
-                        Err(BecomeInHelperFunction.with_span(span))?
+                let value_expr = self.build_expression_ast(value_expr)?;
+                let statement =
+                    statements::SetTuple::try_new(span, self, var_names.clone(), value_expr)?;
+                for var_name in var_names {
+                    self.assigned_vars.insert(var_name.inner.clone());
+                }
+                Box::new(statement)
+            }
+            // If statement
+            parser::Statement::If {
+                cond_expr,
+                if_true,
+                if_false,
+            } => {
+                let cond_expr = self.build_expression_ast(cond_expr);
+                // If the condition is constant, then only one branch is ever
+                // compiled.
+                let live_branch = match cond_expr
+                    .as_ref()
+                    .ok()
+                    .and_then(|&e| statements::const_condition(self, e))
+                {
+                    Some(true) => Some(0),
+                    Some(false) => Some(1),
+                    None => None,
+                };
+                let mut branches = self.build_branch_block_asts(&[if_true, if_false], live_branch);
+                let if_false = branches.pop().unwrap();
+                let if_true = branches.pop().unwrap();
+                Box::new(statements::If::try_new(
+                    span, self, cond_expr?, if_true, if_false,
+                )?)
+            }
+            // Match statement
+            parser::Statement::Match {
+                expr,
+                cases,
+                else_block,
+            } => {
+                let mut exprs = vec![self.build_expression_ast(expr)];
+                for (case_expr, _block) in cases {
+                    exprs.push(self.build_expression_ast(case_expr));
+                }
+                // The `else` block is a branch too, even if it is empty.
+                let parser_blocks: Vec<_> = cases
+                    .iter()
+                    .map(|(_case_expr, block)| block)
+                    .chain(Some(else_block))
+                    .collect();
+                let mut blocks = self.build_branch_block_asts(&parser_blocks, None);
+                let else_block = blocks.pop().unwrap();
+                let mut exprs = self.collect_results(exprs)?;
+                let expr = exprs.remove(0);
+                let case_asts = exprs.into_iter().zip(blocks).collect();
+                Box::new(statements::Match::try_new(
+                    span, self, expr, case_asts, else_block,
+                )?)
+            }
+            // For loop
+            parser::Statement::For {
+                var_name,
+                iter_expr,
+                block,
+            } => {
+                self.check_assignable(var_name)?;
+                let iter_expr = self.build_expression_ast(iter_expr);
+                // Create the loop variable before building the loop body, so
+                // that it can be used there.
+                let var_type = self.get_or_create_var(&var_name.inner, Type::Int);
+                // The loop variable is assigned even if the range is empty.
+                self.assigned_vars.insert(var_name.inner.clone());
+                let block = self.build_loop_body_ast(block, false);
+                let iter_expr = iter_expr?;
+                if var_type != Type::Int {
+                    Err(TypeError {
+                        expected: Type::Int,
+                        got: var_type,
                     }
+                    .with_span(var_name.span))?;
+                }
+                Box::new(statements::ForLoop::try_new(
+                    span,
+                    self,
+                    var_name.inner.clone(),
+                    iter_expr,
+                    block,
+                )?)
+            }
+            // While loop
+            parser::Statement::While { cond_expr, block } => {
+                let cond_expr = self.build_expression_ast(cond_expr);
+                // If the condition is constantly false, then the loop body is
+                // never compiled.
+                let is_dead = match &cond_expr {
+                    Ok(e) => statements::const_condition(self, *e) == Some(false),
+                    Err(_) => false,
+                };
+                let block = self.build_loop_body_ast(block, is_dead);
+                Box::new(statements::WhileLoop::try_new(
+                    span, self, cond_expr?, block,
+                )?)
+            }
+            // Break statement
+            parser::Statement::Break => Box::new(statements::Break::try_new(span, self)?),
+            // Continue statement
+            parser::Statement::Continue => Box::new(statements::Continue::try_new(span, self)?),
+            // Assert statement
+            parser::Statement::Assert { cond_expr, msg } => {
+                let cond_expr = self.build_expression_ast(cond_expr)?;
+                Box::new(statements::Assert::try_new(
+                    span,
+                    self,
+                    cond_expr,
+                    msg.clone(),
+                )?)
+            }
+            // Become statement (In a transition function, `become` should be used, not `return`.)
+            parser::Statement::Become(ret_expr) => {
+                if self.is_transition_function {
+                    let ret_expr = self.build_expression_ast(ret_expr)?;
+                    Box::new(statements::Return::try_new(span, self, ret_expr)?)
+                } else {
+                    Err(BecomeInHelperFunction.with_span(span))?
                 }
+            }
+            // Remain statement
+            parser::Statement::Remain => Box::new(statements::Remain::try_new(span, self)?),
 
-                // Retrurn statement (In a helper function, `return` should be used, not `become`.)
-                parser::Statement::Return(ret_expr) => {
-                    if self.is_transition_function {
-                        Err(ReturnInTransitionFunction.with_span(span))?
-                    } else {
-                        let ret_expr = self.build_expression_ast(ret_expr)?;
-                        Box::new(statements::Return::try_new(span, self, ret_expr)?)
-                    }
+            // Retrurn statement (In a helper function, `return` should be used, not `become`.)
+            parser::Statement::Return(ret_expr) => {
+                if self.is_transition_function {
+                    Err(ReturnInTransitionFunction.with_span(span))?
+                } else {
+                    let ret_expr = self.build_expression_ast(ret_expr)?;
+                    Box::new(statements::Return::try_new(span, self, ret_expr)?)
                 }
+            }
+        })
+    }
+    /// Constructs AST nodes for several blocks of statements from a parse tree,
+    /// exactly one of which executes (such as the branches of an `if`
+    /// statement).
+    ///
+    /// If only one of the blocks is ever compiled (because the condition is
+    /// constant), then its index is given and the others are built as dead
+    /// blocks (see dead_block_depth).
+    ///
+    /// Afterward, a variable is considered definitely assigned only if it is
+    /// assigned at the end of every branch that does not terminate (see
+    /// Statement::is_terminating()), ignoring dead blocks.
+    fn build_branch_block_asts(
+        &mut self,
+        parser_blocks: &[&parser::StatementBlock],
+        live_block: Option<usize>,
+    ) -> Vec<StatementBlock> {
+        let assigned_before = self.assigned_vars.clone();
+        let mut assigned_after: Option<HashSet<String>> = None;
+        let mut blocks = vec![];
+        for (i, parser_block) in parser_blocks.iter().enumerate() {
+            self.assigned_vars = assigned_before.clone();
+            let is_dead = live_block.map_or(false, |live_block| live_block != i);
+            let block = if is_dead {
+                self.build_dead_statement_block_ast(parser_block)
+            } else {
+                self.build_statement_block_ast(parser_block)
             };
-
-            block.push(self.add_statement(new_statement));
+            // A dead block never executes, so it does not affect which
+            // variables are assigned afterward.
+            if !is_dead && !self.block_is_terminating(&block) {
+                assigned_after = Some(match assigned_after {
+                    Some(assigned) => assigned
+                        .intersection(&self.assigned_vars)
+                        .cloned()
+                        .collect(),
+                    None => self.assigned_vars.clone(),
+                });
+            }
+            blocks.push(block);
+        }
+        // If every branch terminates, then nothing after this is reachable
+        // anyway.
+        self.assigned_vars = assigned_after.unwrap_or(assigned_before);
+        blocks
+    }
+    /// Constructs AST nodes for a dead block of statements from a parse tree
+    /// (see dead_block_depth). The statements are still checked, but they are
+    /// never compiled.
+    fn build_dead_statement_block_ast(
+        &mut self,
+        parser_statements: &parser::StatementBlock,
+    ) -> StatementBlock {
+        self.dead_block_depth += 1;
+        let block = self.build_statement_block_ast(parser_statements);
+        self.dead_block_depth -= 1;
+        block
+    }
+    /// Constructs AST nodes for the body of a loop from a parse tree, which is
+    /// a dead block if `is_dead` is true (see dead_block_depth).
+    ///
+    /// Nothing assigned inside the loop body is definitely assigned afterward.
+    fn build_loop_body_ast(
+        &mut self,
+        parser_statements: &parser::StatementBlock,
+        is_dead: bool,
+    ) -> StatementBlock {
+        let assigned_before = self.assigned_vars.clone();
+        self.loop_depth += 1;
+        let block = if is_dead {
+            self.build_dead_statement_block_ast(parser_statements)
+        } else {
+            self.build_statement_block_ast(parser_statements)
+        };
+        self.loop_depth -= 1;
+        self.assigned_vars = assigned_before;
+        block
+    }
+    /// Constructs AST nodes for several expressions from a parse tree, only
+    /// one of which is evaluated (such as the values of a conditional
+    /// expression).
+    ///
+    /// Afterward, a variable is considered definitely assigned only if it is
+    /// assigned by every expression that produces a value (see
+    /// Expr::is_never()), similar to build_branch_block_asts().
+    fn build_branch_expression_asts(
+        &mut self,
+        parser_exprs: &[&Spanned<parser::Expr>],
+    ) -> Vec<LangResult<ExprRef>> {
+        let assigned_before = self.assigned_vars.clone();
+        let mut assigned_after: Option<HashSet<String>> = None;
+        let mut results = vec![];
+        for parser_expr in parser_exprs {
+            self.assigned_vars = assigned_before.clone();
+            let result = self.build_expression_ast(parser_expr);
+            if !matches!(&result, Ok(expr) if self[*expr].is_never()) {
+                assigned_after = Some(match assigned_after {
+                    Some(assigned) => assigned
+                        .intersection(&self.assigned_vars)
+                        .cloned()
+                        .collect(),
+                    None => self.assigned_vars.clone(),
+                });
+            }
+            results.push(result);
+        }
+        self.assigned_vars = assigned_after.unwrap_or(assigned_before);
+        results
+    }
+    /// Constructs an AST node for an expression from a parse tree that is not
+    /// always evaluated (such as the right-hand side of `and`), so nothing
+    /// assigned inside it is definitely assigned afterward.
+    fn build_optional_expression_ast(
+        &mut self,
+        parser_expr: &Spanned<parser::Expr>,
+    ) -> LangResult<ExprRef> {
+        let assigned_before = self.assigned_vars.clone();
+        let result = self.build_expression_ast(parser_expr);
+        self.assigned_vars = assigned_before;
+        result
+    }
+    /// Records an error to be returned from
+    /// build_top_level_statement_block_ast() along with any others, unless it
+    /// is a PoisonedExpression (which is caused by an error that has already
+    /// been recorded).
+    fn report_error(&mut self, error: LangError) {
+        if error.msg != PoisonedExpression {
+            self.errors.push(error);
+        }
+    }
+    /// Returns the values from a list of independent results, or returns the
+    /// first error and records the rest (see report_error()).
+    fn collect_results<T>(&mut self, results: Vec<LangResult<T>>) -> LangResult<Vec<T>> {
+        let mut values = vec![];
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(value) => values.push(value),
+                Err(e) if first_error.is_none() => first_error = Some(e),
+                Err(e) => self.report_error(e),
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(values),
+        }
+    }
+    /// Checks the number and types of the arguments to a call to a helper
+    /// function (or extern function) with the given signature, converting each argument as if it
+    /// were assigned to the corresponding variable (see
+    /// coerce_for_assignment()).
+    ///
+    /// Returns an Err(ExpectedGot) pointing to the extra arguments if there
+    /// are too many, to the whole call if there are too few, or to the first
+    /// argument with the wrong type.
+    fn check_helper_args(
+        &mut self,
+        span: Span,
+        signature: &FnSignature,
+        arg_refs: Vec<ExprRef>,
+    ) -> LangResult<Args> {
+        let arg_count = |n: usize| format!("{} argument{}", n, if n == 1 { "" } else { "s" });
+        let expected_count = signature.args.len();
+        if arg_refs.len() != expected_count {
+            let error_span = match arg_refs.get(expected_count) {
+                Some(&first_extra) => Span::merge(
+                    self[first_extra].span(),
+                    self[*arg_refs.last().unwrap()].span(),
+                ),
+                None => span,
+            };
+            return Err(ExpectedGot {
+                expected: arg_count(expected_count).into(),
+                got: arg_count(arg_refs.len()).into(),
+            }
+            .with_span(error_span));
+        }
+        let mut ret = vec![];
+        for (arg, &expected) in arg_refs.into_iter().zip(signature.args.iter()) {
+            let arg = self.coerce_for_assignment(arg, expected)?;
+            let got = self[arg].result_type();
+            if got != expected {
+                return Err(ExpectedGot {
+                    expected: expected.to_string().into(),
+                    got: got.to_string().into(),
+                }
+                .with_span(self[arg].span()));
+            }
+            ret.push(arg);
         }
-        Ok(block)
+        Ok(Args::from(ret))
+    }
+    /// Constructs an AST node for an expression from a parse tree, converting
+    /// the result to an integer if it is a boolean. This is used for operands
+    /// that must be integers, such as those of arithmetic operators.
+    fn build_int_operand_ast(
+        &mut self,
+        parser_expr: &Spanned<parser::Expr>,
+    ) -> LangResult<ExprRef> {
+        let expr = self.build_expression_ast(parser_expr)?;
+        self.coerce_for_assignment(expr, Type::Int)
     }
     /// Constructs an AST node for an expression from a parse tree.
     pub fn build_expression_ast(
@@ -227,12 +822,21 @@ impl UserFunction {
         let span = parser_expr.span;
         let args: Args;
         let function: Box<dyn Function>;
+        // Any error points added while building this expression belong to it
+        // or to its arguments.
+        let error_point_count = self.error_point_count();
+        let mut calls_helper = false;
+        let mut is_never = false;
 
         match &parser_expr.inner {
             // Integer literal
             parser::Expr::Int(i) => {
+                let value = LangInt::try_from(*i)
+                    .ok()
+                    .filter(|&i| int_fits_in_bits(i, self.rule_meta.int_bits))
+                    .ok_or_else(|| IntegerOverflow.with_span(span))?;
                 args = Args::none();
-                function = Box::new(functions::literals::Int(*i));
+                function = Box::new(functions::literals::Int(value));
             }
             // Identifier (variable)
             parser::Expr::Ident(s) => {
@@ -243,36 +847,187 @@ impl UserFunction {
             parser::Expr::Group { start_token, inner } => {
                 use PunctuationToken::*;
                 match start_token {
-                    LParen => return self.build_expression_ast(inner),
-                    LBracket => todo!("Construct vector"),
+                    LParen => match &inner.inner {
+                        parser::Expr::List(elements) => {
+                            if elements.len() < 2 {
+                                return Err(InvalidTupleLength.with_span(span));
+                            }
+                            let element_results = elements
+                                .iter()
+                                .map(|element| self.build_expression_ast(element))
+                                .collect();
+                            let element_exprs = self.collect_results(element_results)?;
+                            let mut element_types = vec![];
+                            for (element, &element_expr) in elements.iter().zip(&element_exprs) {
+                                // Check that each element can be stored in a
+                                // tuple.
+                                let ty = self[element_expr].result_type();
+                                if !ty.can_be_tuple_element() {
+                                    return Err(InvalidTupleElement.with_span(element.span));
+                                }
+                                element_types.push(ty);
+                            }
+                            args = Args::from(element_exprs);
+                            function = Box::new(functions::literals::TupleLiteral {
+                                elements: Type::tuple(&element_types).tuple_elements().unwrap(),
+                            });
+                        }
+                        _ => return self.build_expression_ast(inner),
+                    },
+                    LBracket => match &inner.inner {
+                        parser::Expr::List(components) => {
+                            if components.is_empty() || components.len() > MAX_VECTOR_LEN {
+                                return Err(InvalidVectorLength.with_span(span));
+                            }
+                            let component_results = components
+                                .iter()
+                                .map(|component| self.build_int_operand_ast(component))
+                                .collect();
+                            let component_exprs = self.collect_results(component_results)?;
+                            for (component, &component_expr) in
+                                components.iter().zip(&component_exprs)
+                            {
+                                // Check that each component is an integer.
+                                let got = self[component_expr].result_type();
+                                if got != Type::Int {
+                                    return Err(TypeError {
+                                        expected: Type::Int,
+                                        got,
+                                    }
+                                    .with_span(component.span));
+                                }
+                            }
+                            args = Args::from(component_exprs);
+                            function = Box::new(functions::literals::VectorLiteral {
+                                len: components.len(),
+                            });
+                        }
+                        _ => {
+                            return Err(
+                                InternalError("Invalid vector literal".into()).with_span(span)
+                            )
+                        }
+                    },
                     _ => return Err(InternalError("Invalid group".into()).with_span(span)),
                 }
             }
             // Comma-separated list
             parser::Expr::List(_) => {
                 return Err(ExpectedGot {
-                    expected: "expression",
-                    got: "comma-separated list",
+                    expected: "expression".into(),
+                    got: "comma-separated list".into(),
                 }
                 .with_span(span))
             }
             // Unary operator
             parser::Expr::UnaryOp { op, operand } => match op {
+                // Unary plus (which does nothing except convert a boolean to
+                // an integer)
+                OperatorToken::Plus => {
+                    let operand = self.build_int_operand_ast(operand)?;
+                    let got = self[operand].result_type();
+                    if got != Type::Int {
+                        Err(TypeError {
+                            expected: Type::Int,
+                            got,
+                        }
+                        .with_span(self[operand].span()))?;
+                    }
+                    return Ok(operand);
+                }
                 // Negation
                 OperatorToken::Minus => {
-                    args = Args::from(vec![self.build_expression_ast(operand)?]);
-                    function = Box::new(functions::math::NegInt::try_new(self, span)?);
+                    let negated_literal = match operand.inner {
+                        parser::Expr::Int(i) => LangInt::try_from(-(i as i128))
+                            .ok()
+                            .filter(|&i| int_fits_in_bits(i, self.rule_meta.int_bits)),
+                        _ => None,
+                    };
+                    if let Some(value) = negated_literal {
+                        // Fold negation of an integer literal into the literal
+                        // itself, so that the minimum integer value (whose
+                        // magnitude is larger than the maximum integer value)
+                        // can be written directly.
+                        args = Args::none();
+                        function = Box::new(functions::literals::Int(value));
+                    } else {
+                        args = Args::from(vec![self.build_int_operand_ast(operand)?]);
+                        function = Box::new(functions::math::NegInt::try_new(self, span)?);
+                    }
                 }
                 // Get cell state from integer ID
                 OperatorToken::Tag => {
-                    args = Args::from(vec![self.build_expression_ast(operand)?]);
-                    function = Box::new(functions::convert::IntToCellState::try_new(self, span)?);
+                    let operand = self.build_int_operand_ast(operand)?;
+                    // A cell state (e.g. a named one like `#ALIVE`) is left
+                    // as-is.
+                    if self[operand].result_type() == Type::CellState {
+                        return Ok(operand);
+                    }
+                    args = Args::from(vec![operand]);
+                    function = Box::new(functions::convert::IntToCellState::try_new(
+                        self, span, &args,
+                    )?);
+                }
+                // Bitwise NOT
+                OperatorToken::Tilde => {
+                    args = Args::from(vec![self.build_int_operand_ast(operand)?]);
+                    function = Box::new(functions::math::BitNot::try_new(self, &args)?);
                 }
                 _ => return Err(InternalError("Invalid unary operator".into()).with_span(span)),
             },
             // Binary operator
             parser::Expr::BinaryOp { lhs, op, rhs } => match op {
-                // Math
+                // Multiply-add, such as `a * b + c` or `c + a * b`
+                OperatorToken::Plus if is_mul_expr(lhs) || is_mul_expr(rhs) => {
+                    let addend_first = !is_mul_expr(lhs);
+                    let (product, addend) = if addend_first { (rhs, lhs) } else { (lhs, rhs) };
+                    let factors = match &product.inner {
+                        parser::Expr::BinaryOp { lhs, rhs, .. } => [lhs, rhs],
+                        _ => unreachable!(),
+                    };
+                    // Build the operands in the order they appear in the
+                    // source code.
+                    let mut operands = vec![];
+                    if addend_first {
+                        operands.push(self.build_expression_ast(addend));
+                    }
+                    operands.push(self.build_expression_ast(factors[0]));
+                    operands.push(self.build_expression_ast(factors[1]));
+                    if !addend_first {
+                        operands.push(self.build_expression_ast(addend));
+                    }
+                    let mut operands = self.collect_results(operands)?;
+                    let addend = if addend_first {
+                        operands.remove(0)
+                    } else {
+                        operands.pop().unwrap()
+                    };
+                    let factors = [operands[0], operands[1]];
+                    if functions::math::MulAdd::can_fuse(self, factors, addend) {
+                        args = Args::from(if addend_first {
+                            vec![addend, factors[0], factors[1]]
+                        } else {
+                            vec![factors[0], factors[1], addend]
+                        });
+                        function = Box::new(functions::math::MulAdd::new(self, span, addend_first));
+                    } else {
+                        let product = self.build_binary_int_op_ast(
+                            product.span,
+                            operands,
+                            OperatorToken::Asterisk,
+                        )?;
+                        let operands = if addend_first {
+                            vec![addend, product]
+                        } else {
+                            vec![product, addend]
+                        };
+                        args = functions::math::coerce_arithmetic_operands(self, operands)?;
+                        function = Box::new(functions::math::BinaryIntOp::try_new(
+                            self, span, &args, *op,
+                        )?);
+                    }
+                }
+                // Math (on integers or component-wise on vectors)
                 OperatorToken::Plus
                 | OperatorToken::Minus
                 | OperatorToken::Asterisk
@@ -283,35 +1038,331 @@ impl UserFunction {
                 | OperatorToken::DoubleGreaterThan
                 | OperatorToken::TripleGreaterThan
                 | OperatorToken::Ampersand
-                | OperatorToken::Pipe => {
-                    args = Args::from(vec![
-                        self.build_expression_ast(lhs)?,
-                        self.build_expression_ast(rhs)?,
-                    ]);
-                    function = Box::new(functions::math::BinaryIntOp::try_new(self, span, *op)?);
+                | OperatorToken::Pipe
+                | OperatorToken::Caret => {
+                    let operands = vec![
+                        self.build_expression_ast(lhs),
+                        self.build_expression_ast(rhs),
+                    ];
+                    let operands = self.collect_results(operands)?;
+                    args = functions::math::coerce_arithmetic_operands(self, operands)?;
+                    function = Box::new(functions::math::BinaryIntOp::try_new(
+                        self, span, &args, *op,
+                    )?);
                 }
-                // Method call
-                OperatorToken::Dot => todo!("Method call"),
+                // Method call or property access
+                OperatorToken::Dot => match &rhs.inner {
+                    parser::Expr::FnCall {
+                        func,
+                        args: arg_exprs,
+                    } => {
+                        // The receiver is the first argument.
+                        let mut arg_results = vec![self.build_expression_ast(lhs)];
+                        for arg_expr in arg_exprs {
+                            arg_results.push(self.build_expression_ast(arg_expr));
+                        }
+                        let arg_refs = self.collect_results(arg_results)?;
+                        let receiver_type = self[arg_refs[0]].result_type();
+                        args = Args::from(arg_refs);
+                        function = functions::try_new_method(
+                            self,
+                            span,
+                            receiver_type,
+                            &func.inner,
+                            &args,
+                        )?
+                        .ok_or_else(|| {
+                            UnknownMethod {
+                                ty: receiver_type,
+                                name: func.inner.clone(),
+                            }
+                            .with_span(span)
+                        })?;
+                    }
+                    parser::Expr::Ident(name) => {
+                        // The receiver is the only argument.
+                        let receiver = self.build_expression_ast(lhs)?;
+                        let receiver_type = self[receiver].result_type();
+                        args = Args::from(vec![receiver]);
+                        function = functions::try_new_property(rhs.span, receiver_type, name)?
+                            .ok_or_else(|| {
+                                UnknownProperty {
+                                    ty: receiver_type,
+                                    name: name.clone(),
+                                }
+                                .with_span(rhs.span)
+                            })?;
+                    }
+                    parser::Expr::Int(index) => {
+                        // Tuple element access, such as `t.0`
+                        let receiver = self.build_expression_ast(lhs)?;
+                        let receiver_type = self[receiver].result_type();
+                        args = Args::from(vec![receiver]);
+                        function = Box::new(functions::misc::TupleElement::try_new(
+                            rhs.span,
+                            receiver_type,
+                            *index,
+                        )?);
+                    }
+                    _ => return Err(Expected("method call or property").with_span(rhs.span)),
+                },
                 // Range
-                OperatorToken::DotDot => todo!("Range"),
+                OperatorToken::DotDot => {
+                    let operands = vec![
+                        self.build_int_operand_ast(lhs),
+                        self.build_int_operand_ast(rhs),
+                    ];
+                    args = Args::from(self.collect_results(operands)?);
+                    function = Box::new(functions::misc::Range);
+                }
                 _ => return Err(InternalError("Invalid binary operator".into()).with_span(span)),
             },
+            // Logical binary operator
+            parser::Expr::LogicalOp { lhs, op, rhs } => {
+                let lhs = self.build_expression_ast(lhs);
+                // `and` and `or` skip the right-hand side if the left-hand side
+                // determines the result.
+                let rhs = match op {
+                    KeywordToken::Xor => self.build_expression_ast(rhs),
+                    _ => self.build_optional_expression_ast(rhs),
+                };
+                let operands = vec![lhs, rhs];
+                args = Args::from(self.collect_results(operands)?);
+                function = Box::new(functions::logic::LogicalBinaryOp::try_new(
+                    self, &args, *op,
+                )?);
+            }
+            // Logical negation
+            parser::Expr::LogicalNot(operand) => {
+                args = Args::from(vec![self.build_expression_ast(operand)?]);
+                function = Box::new(functions::logic::LogicalNot::try_new(self, &args)?);
+            }
+            // Range membership test
+            parser::Expr::In { lhs, rhs } => {
+                let operands = vec![
+                    self.build_int_operand_ast(lhs),
+                    self.build_expression_ast(rhs),
+                ];
+                args = Args::from(self.collect_results(operands)?);
+                function = Box::new(functions::misc::RangeContains::In);
+            }
+            // Indexing operation
+            parser::Expr::Index { operand, index } => {
+                let operands = vec![
+                    self.build_expression_ast(operand),
+                    self.build_int_operand_ast(index),
+                ];
+                args = Args::from(self.collect_results(operands)?);
+                function = match self[args[0]].result_type() {
+                    Type::Array(_) => {
+                        Box::new(functions::array::IndexArray::try_new(self, span, &args)?)
+                    }
+                    _ => Box::new(functions::misc::IndexVector::try_new(self, span, &args)?),
+                };
+            }
+            // Conditional expression
+            parser::Expr::Conditional {
+                cond_expr,
+                if_true,
+                if_false,
+            } => {
+                let mut operands = vec![self.build_expression_ast(cond_expr)];
+                operands.extend(self.build_branch_expression_asts(&[if_true, if_false]));
+                args = Args::from(self.collect_results(operands)?);
+                // A value that is never produced takes the type of the other
+                // one.
+                let (if_true, if_false) = (args[1], args[2]);
+                match (self[if_true].is_never(), self[if_false].is_never()) {
+                    (true, true) => {
+                        let ty = self[if_true].result_type();
+                        self.expressions[if_false.0].set_never_type(ty);
+                        is_never = true;
+                    }
+                    (true, false) => {
+                        let ty = self[if_false].result_type();
+                        self.expressions[if_true.0].set_never_type(ty);
+                    }
+                    (false, true) => {
+                        let ty = self[if_true].result_type();
+                        self.expressions[if_false.0].set_never_type(ty);
+                    }
+                    (false, false) => (),
+                }
+                function = Box::new(functions::misc::Conditional::try_new(self, &args)?);
+            }
+            // Block expression
+            parser::Expr::Block { statements, value } => {
+                let error_count = self.errors.len();
+                let block = self.build_statement_block_ast(statements);
+                let is_terminating = self.block_is_terminating(&block);
+                let ty;
+                match value {
+                    Some(value) => {
+                        if is_terminating {
+                            return Err(UnreachableStatement.with_span(value.span));
+                        }
+                        let value = self.build_expression_ast(value)?;
+                        ty = self[value].result_type();
+                        is_never = self[value].is_never();
+                        args = Args::from(vec![value]);
+                    }
+                    None => {
+                        if self.errors.len() > error_count {
+                            // A statement that was left out of the block
+                            // because of an error may have terminated it.
+                            return Err(PoisonedExpression.with_span(span));
+                        } else if !is_terminating {
+                            return Err(MissingBlockValue.with_span(span));
+                        }
+                        // The type is a placeholder (see Expr::is_never()).
+                        ty = Type::Int;
+                        is_never = true;
+                        args = Args::none();
+                    }
+                }
+                function = Box::new(functions::misc::Block {
+                    statements: block,
+                    ty,
+                    has_value: value.is_some(),
+                });
+            }
+            // Function call
+            parser::Expr::FnCall {
+                func,
+                args: arg_exprs,
+            } => {
+                let arg_results = arg_exprs
+                    .iter()
+                    .map(|e| self.build_expression_ast(e))
+                    .collect();
+                let arg_refs = self.collect_results(arg_results)?;
+                // Helper functions take precedence over built-in functions
+                // with the same name.
+                let helper_signature = self
+                    .rule_meta
+                    .helper_function_signatures
+                    .get(&func.inner)
+                    .cloned();
+                if let Some(signature) = helper_signature {
+                    args = self.check_helper_args(span, &signature, arg_refs)?;
+                    self.called_helpers
+                        .entry(func.inner.clone())
+                        .or_insert(span);
+                    calls_helper = true;
+                    function = Box::new(functions::helper::CallHelper::new(
+                        func.inner.clone(),
+                        signature,
+                    ));
+                } else {
+                    args = Args::from(arg_refs);
+                    function = functions::try_new_builtin(self, span, &func.inner, &args)?
+                        .ok_or_else(|| UnknownFunction(func.inner.clone()).with_span(func.span))?;
+                }
+            }
+            // Call to a function provided by the host
+            parser::Expr::ExternCall {
+                func,
+                args: arg_exprs,
+            } => {
+                let arg_results = arg_exprs
+                    .iter()
+                    .map(|e| self.build_expression_ast(e))
+                    .collect();
+                let arg_refs = self.collect_results(arg_results)?;
+                let extern_function = self
+                    .rule_meta
+                    .extern_functions
+                    .get(&func.inner)
+                    .cloned()
+                    .ok_or_else(|| {
+                        UnknownExternFunction(func.inner.clone()).with_span(func.span)
+                    })?;
+                let signature = &extern_function.signature;
+                if let Some(&ty) = signature
+                    .args
+                    .iter()
+                    .chain(Some(&signature.ret))
+                    .find(|&&ty| !is_valid_extern_type(ty))
+                {
+                    return Err(InvalidExternType(ty).with_span(span));
+                }
+                args = self.check_helper_args(span, signature, arg_refs)?;
+                function = Box::new(functions::externs::CallExtern::new(
+                    func.inner.clone(),
+                    extern_function,
+                ));
+            }
             // Comparison
             parser::Expr::Cmp { exprs, cmps } => {
-                args = Args::from(
-                    exprs
-                        .iter()
-                        .map(|e| self.build_expression_ast(e))
-                        .collect::<LangResult<Vec<_>>>()?,
-                );
+                // Chained comparisons stop at the first one that is false, so
+                // only the first two operands are always evaluated.
+                let operands = exprs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| match i {
+                        0 | 1 => self.build_expression_ast(e),
+                        _ => self.build_optional_expression_ast(e),
+                    })
+                    .collect();
+                let operands = self.collect_results(operands)?;
+                args = functions::math::coerce_cmp_operands(self, operands)?;
                 function = Box::new(functions::cmp::Cmp::try_new(self, &args, cmps.clone())?);
             }
         };
 
-        let expr = Expr::try_new(span, self, function, args)?;
+        let mut expr = Expr::try_new(span, self, function, args)?;
+        // A helper function may return an error, even though the error point
+        // for it is in the helper function.
+        if self.error_point_count() > error_point_count || calls_helper {
+            expr.set_may_error();
+        }
+        if is_never {
+            expr.set_never();
+        }
         Ok(self.add_expr(expr))
     }
 
+    /// Constructs an AST node for a binary math operator whose operands have
+    /// already been built, and returns an ExprRef representing it.
+    fn build_binary_int_op_ast(
+        &mut self,
+        span: Span,
+        operands: Vec<ExprRef>,
+        op: OperatorToken,
+    ) -> LangResult<ExprRef> {
+        let error_point_count = self.error_point_count();
+        let args = functions::math::coerce_arithmetic_operands(self, operands)?;
+        let function = Box::new(functions::math::BinaryIntOp::try_new(
+            self, span, &args, op,
+        )?);
+        let mut expr = Expr::try_new(span, self, function, args)?;
+        if self.error_point_count() > error_point_count {
+            expr.set_may_error();
+        }
+        Ok(self.add_expr(expr))
+    }
+
+    /// Replaces every expression that can be evaluated at compile time with a
+    /// constant, so that no code is generated to compute it.
+    ///
+    /// Expressions that produce an error when evaluated (such as division by
+    /// zero) are left as-is, so that the error is still reported at runtime.
+    pub fn fold_constants(&mut self) {
+        // Arguments are always added before the expressions that use them, so
+        // they are folded first.
+        for idx in 0..self.expressions.len() {
+            let expr = &self.expressions[idx];
+            if expr.args().len() == 0 {
+                // This is already an atom, so folding it would not help.
+                continue;
+            }
+            let span = expr.span();
+            if let Ok(value) = expr.const_eval(self) {
+                self.expressions[idx] = Expr::new_constant(span, value);
+            }
+        }
+    }
+
     /// Adds a statement AST node to this user function, and returns a
     /// StatementRef representing it.
     fn add_statement(&mut self, statement: Box<dyn Statement>) -> StatementRef {
@@ -326,39 +1377,139 @@ impl UserFunction {
         self.expressions.push(expr);
         ExprRef(idx)
     }
+    /// Returns the number of error points added to this user function,
+    /// including those inside dead blocks.
+    fn error_point_count(&self) -> usize {
+        self.error_points.len() + self.dead_error_point_count
+    }
     /// Adds an error point to this user function, and returns an ErrorPointRef
     /// representing it.
+    ///
+    /// Error points inside a dead block (see dead_block_depth) are not
+    /// registered, since they are never compiled.
     pub fn add_error_point(&mut self, error: LangError) -> ErrorPointRef {
+        if self.dead_block_depth > 0 {
+            self.dead_error_point_count += 1;
+            return ErrorPointRef {
+                idx: DEAD_ERROR_POINT_INDEX,
+                error,
+            };
+        }
         let idx = self.error_points.len();
         self.error_points.push(error.clone());
         ErrorPointRef { idx, error }
     }
 
-    /// JIT compiles this function and returns an executable function.
-    pub fn compile(&self, compiler: &mut Compiler) -> LangResult<CompiledFunction> {
+    /// JIT compiles this function and returns an executable function, given
+    /// the helper functions of the rule (which are compiled along with it if
+    /// it calls them).
+    ///
+    /// The compiler must use the same integer width as the rule.
+    pub fn compile(
+        &self,
+        compiler: &mut Compiler,
+        helper_functions: &HashMap<String, UserFunction>,
+    ) -> LangResult<CompiledFunction> {
+        if compiler.int_bits() != self.rule_meta.int_bits {
+            Err(InternalError(
+                format!(
+                    "Compiler uses {}-bit integers, but rule uses {}-bit integers",
+                    compiler.int_bits(),
+                    self.rule_meta.int_bits,
+                )
+                .into(),
+            )
+            .without_span())?;
+        }
+
+        compiler.begin_debug_info(self.rule_meta.source_code.clone());
+
+        // Compile each helper function that this function calls (directly or
+        // indirectly) into its own LLVM function. Every function returns an
+        // index into the same list of error points, so each one's error points
+        // are numbered after those of the functions before it.
+        let callees = self.get_called_helpers(helper_functions)?;
+        for callee in &callees {
+            compiler.declare_helper_function(&callee.name, &callee.signature())?;
+        }
+        let mut error_points = self.error_points.clone();
+        for callee in &callees {
+            compiler.begin_intern_function(
+                &callee.name,
+                callee.return_type(),
+                &callee.arg_names,
+                &callee.live_variables(),
+                error_points.len(),
+            )?;
+            callee.compile_body(compiler)?;
+            error_points.extend(callee.error_points.iter().cloned());
+        }
+
+        // Compile this function last, so that it is the one that gets
+        // JIT-compiled.
         compiler.begin_extern_function(
             &self.name,
             self.return_type(),
             &self.arg_names,
-            &self.variables,
+            &self.live_variables(),
         )?;
+        self.compile_body(compiler)?;
 
-        // Compile the statements.
-        self.compile_statement_block(compiler, &self.top_level_statements)?;
-
-        if compiler.needs_terminator() {
-            // If necessary, add an implicit `return #0` at the end of the
-            // transition function. TODO: change this to `remain` once that's
-            // implemented, and handle other types as well.
-            let default_return_value = compiler.get_default_var_value(self.return_type()).unwrap();
-            compiler.build_return_ok(default_return_value)?;
-        }
+        // Only the transition function has a neighborhood and a position.
+        let (nbhd_len, pos_len) = if self.is_transition_function {
+            (self.rule_meta.nbhd_len(), self.rule_meta.ndim as usize)
+        } else {
+            (0, 0)
+        };
         CompiledFunction::try_new(
             self.rule_meta.source_code.clone(),
-            self.error_points.clone(),
+            error_points,
+            nbhd_len,
+            pos_len,
+            self.halo_radius.clone(),
             compiler,
         )
     }
+    /// Returns every helper function that this function calls, directly or
+    /// indirectly, sorted by name.
+    fn get_called_helpers<'a>(
+        &self,
+        helper_functions: &'a HashMap<String, UserFunction>,
+    ) -> LangResult<Vec<&'a UserFunction>> {
+        let mut names = BTreeSet::new();
+        let mut queue: Vec<&String> = self.called_helpers.keys().collect();
+        while let Some(name) = queue.pop() {
+            if names.insert(name.clone()) {
+                let callee = helper_functions.get(name).ok_or_else(|| {
+                    InternalError(format!("Missing helper function {:?}", name).into())
+                        .without_span()
+                })?;
+                queue.extend(callee.called_helpers.keys());
+            }
+        }
+        Ok(names.iter().map(|name| &helper_functions[name]).collect())
+    }
+    /// Compiles the statements of this function into the LLVM function
+    /// currently being built.
+    fn compile_body(&self, compiler: &mut Compiler) -> LangResult<()> {
+        self.compile_statement_block(compiler, &self.top_level_statements)?;
+
+        if compiler.needs_terminator() {
+            if self.is_transition_function {
+                // If necessary, add an implicit `remain` at the end of the
+                // transition function.
+                statements::build_remain(compiler, self.rule_meta.nbhd_center_index())?;
+            } else {
+                // Every path through a helper function returns a value, but
+                // the end of the function may still need a terminator (e.g.
+                // after an infinite loop), so return the default value.
+                let default_return_value =
+                    compiler.get_default_var_value(self.return_type()).unwrap();
+                compiler.build_return_ok(default_return_value)?;
+            }
+        }
+        Ok(())
+    }
 
     /// Compiles a block of statements into LLVM IR, stopping if a terminator
     /// (e.g. `return`) is reached.
@@ -376,13 +1527,60 @@ impl UserFunction {
         }
         Ok(())
     }
+    /// Returns whether the given block of statements always transfers control
+    /// elsewhere (see Statement::is_terminating()).
+    pub fn block_is_terminating(&self, block: &StatementBlock) -> bool {
+        block
+            .iter()
+            .any(|&statement| self.statement_is_terminating(statement))
+    }
+    /// Returns whether the given statement always transfers control elsewhere,
+    /// either by itself (see Statement::is_terminating()) or because one of
+    /// its expressions never produces a value (see Expr::is_never()).
+    pub fn statement_is_terminating(&self, statement: StatementRef) -> bool {
+        self[statement].is_terminating(self)
+            || self[statement]
+                .exprs()
+                .into_iter()
+                .any(|expr| self[expr].is_never())
+    }
+    /// Returns whether any statement in the given block of statements
+    /// (including those in nested blocks and block expressions) assigns to the
+    /// variable with the given name (see Statement::assigned_vars()).
+    pub fn block_assigns_var(&self, block: &StatementBlock, var_name: &str) -> bool {
+        block.iter().any(|&statement| {
+            self[statement].assigned_vars().contains(&var_name)
+                || self[statement]
+                    .blocks()
+                    .into_iter()
+                    .any(|inner_block| self.block_assigns_var(inner_block, var_name))
+                || self[statement]
+                    .exprs()
+                    .into_iter()
+                    .any(|expr| self.expr_assigns_var(expr, var_name))
+        })
+    }
+    /// Returns whether any block expression inside the given expression
+    /// (including its arguments) assigns to the variable with the given name.
+    fn expr_assigns_var(&self, expr: ExprRef, var_name: &str) -> bool {
+        self[expr]
+            .func()
+            .blocks()
+            .into_iter()
+            .any(|block| self.block_assigns_var(block, var_name))
+            || self[expr]
+                .args()
+                .iter_refs()
+                .any(|&arg| self.expr_assigns_var(arg, var_name))
+    }
     /// Compiles a statement into LLVM IR by calling Statement::compile().
     pub fn compile_statement(
         &self,
         compiler: &mut Compiler,
         statement: StatementRef,
     ) -> LangResult<()> {
-        self[statement].compile(compiler, self)
+        compiler
+            .build_with_debug_location(self[statement].span(), |c| self[statement].compile(c, self))
     }
     /// Compiles an expression into LLVM IR by calling Expr::compile().
     pub fn compile_expr(&self, compiler: &mut Compiler, expr: ExprRef) -> LangResult<Value> {
@@ -394,6 +1592,64 @@ impl UserFunction {
     pub fn const_eval_expr(&self, expr: ExprRef) -> LangResult<ConstValue> {
         self[expr].const_eval(self)
     }
+
+    /// Returns a human-readable dump of the AST of this function, including
+    /// the span and kind of every node and the type of every expression.
+    ///
+    /// This is intended for debugging the AST builder, since it shows exactly
+    /// how the source code was desugared.
+    pub fn dump_ast(&self) -> String {
+        let mut ret = format!("function {:?} -> {}\n", self.name, self.return_type);
+        self.dump_statement_block(&mut ret, &self.top_level_statements, 1);
+        ret
+    }
+    /// Appends a dump of the given block of statements to a string, indented
+    /// to the given depth.
+    fn dump_statement_block(&self, out: &mut String, block: &StatementBlock, depth: usize) {
+        let indent = "  ".repeat(depth);
+        for &statement in block {
+            let statement = &self[statement];
+            let span = statement.span();
+            out.push_str(&format!(
+                "{}{} @ {}..{}\n",
+                indent,
+                statement.name(),
+                span.start,
+                span.end,
+            ));
+            for expr in statement.exprs() {
+                self.dump_expr(out, expr, depth + 1);
+            }
+            for inner_block in statement.blocks() {
+                out.push_str(&format!("{}  {{\n", indent));
+                self.dump_statement_block(out, inner_block, depth + 2);
+                out.push_str(&format!("{}  }}\n", indent));
+            }
+        }
+    }
+    /// Appends a dump of the given expression and its arguments to a string,
+    /// indented to the given depth.
+    fn dump_expr(&self, out: &mut String, expr: ExprRef, depth: usize) {
+        let expr = &self[expr];
+        let span = expr.span();
+        out.push_str(&format!(
+            "{}{} ({:?}): {} @ {}..{}\n",
+            "  ".repeat(depth),
+            expr.func().name(),
+            expr.func().kind(),
+            expr.result_type(),
+            span.start,
+            span.end,
+        ));
+        for inner_block in expr.func().blocks() {
+            out.push_str(&format!("{}  {{\n", "  ".repeat(depth)));
+            self.dump_statement_block(out, inner_block, depth + 2);
+            out.push_str(&format!("{}  }}\n", "  ".repeat(depth)));
+        }
+        for &arg in expr.args().iter_refs() {
+            self.dump_expr(out, arg, depth + 1);
+        }
+    }
 }
 
 /// A newtype of usize that refers to an expression AST node of a user function.
@@ -416,6 +1672,20 @@ impl Index<StatementRef> for UserFunction {
     }
 }
 
+/// Returns whether the given parse tree is a `*` operator.
+fn is_mul_expr(parser_expr: &Spanned<parser::Expr>) -> bool {
+    matches!(
+        parser_expr.inner,
+        parser::Expr::BinaryOp {
+            op: OperatorToken::Asterisk,
+            ..
+        }
+    )
+}
+
+/// Index of every error point inside a dead block, which is never compiled.
+const DEAD_ERROR_POINT_INDEX: usize = usize::MAX;
+
 /// A reference to an error point of a user function (a possible runtime error).
 #[derive(Debug, Clone)]
 pub struct ErrorPointRef {
@@ -0,0 +1,84 @@
+//! Arena of compiled rules that can be selected by name.
+//!
+//! All the rules in an arena (like all the rules compiled on the same thread)
+//! share that thread's LLVM context, but each rule is still compiled into its
+//! own LLVM module with its own JIT execution engine. MCJIT finalizes a module
+//! the first time a function is JIT-compiled from it, so a module can't be
+//! reused for another rule afterwards, and helper functions of different rules
+//! may have the same name. Keeping each rule separate also means that removing
+//! a rule from the arena can actually free its memory.
+//!
+//! Rules returned from an arena share their machine code with the rule in the
+//! arena, so removing a rule (or clearing the arena) never invalidates rules
+//! that have already been handed out. The LLVM module and machine code of a
+//! rule are freed once it has been removed from the arena and the last clone
+//! of it has been dropped.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::super::errors::*;
+use super::CompiledRule;
+
+/// Collection of compiled rules, indexed by name.
+///
+/// LLVM contexts are per-thread, so each thread needs its own arena.
+#[derive(Debug, Default)]
+pub struct RuleArena {
+    /// Compiled rules, indexed by name.
+    rules: HashMap<String, CompiledRule>,
+}
+impl RuleArena {
+    /// Constructs an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of rules in the arena.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+    /// Returns true if the arena is empty.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+    /// Returns true if the arena has a rule with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.rules.contains_key(name)
+    }
+    /// Returns the names of all the rules in the arena, in arbitrary order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str)
+    }
+
+    /// Builds and compiles a rule from the given source code and adds it to
+    /// the arena with the given name, replacing any existing rule with that
+    /// name.
+    ///
+    /// If the source code has any errors, the first one is returned and the
+    /// arena is left unchanged.
+    pub fn add_rule(&mut self, name: &str, source_code: Rc<String>) -> LangResult<()> {
+        let rule = CompiledRule::try_new(source_code)?;
+        self.rules.insert(name.to_owned(), rule);
+        Ok(())
+    }
+    /// Returns the rule with the given name, if there is one.
+    ///
+    /// This returns a clone of the rule in the arena, which does not compile
+    /// anything again; it only allocates space for the arguments and
+    /// neighborhood of the transition function, so that the clone can be
+    /// called independently.
+    pub fn get(&self, name: &str) -> Option<CompiledRule> {
+        self.rules.get(name).cloned()
+    }
+
+    /// Removes the rule with the given name from the arena and returns it, if
+    /// there is one.
+    pub fn remove(&mut self, name: &str) -> Option<CompiledRule> {
+        self.rules.remove(name)
+    }
+    /// Removes every rule from the arena.
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+}
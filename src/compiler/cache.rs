@@ -0,0 +1,170 @@
+//! Cache of compiled rules.
+//!
+//! Compiling a rule through LLVM is by far the slowest part of building it, so
+//! when the same rule is compiled over and over (e.g. in an editor that
+//! recompiles on every keystroke), the compiled transition function is reused
+//! instead.
+//!
+//! Each cached CompiledFunction keeps its own JIT execution engine alive, so
+//! evicting an entry (or clearing the cache) never invalidates functions that
+//! have already been handed out; the LLVM module and machine code are freed
+//! once the last clone of the function is dropped.
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use super::super::ast::{self, NbhdShape, RuleMeta};
+use super::super::errors::*;
+use super::super::types::CELL_STATE_BITS;
+use super::{CompiledFunction, Compiler};
+
+/// Default maximum number of rules kept in a CompilerCache.
+pub const DEFAULT_CACHE_CAPACITY: usize = 16;
+
+/// Cache of compiled transition functions, keyed by a hash of the source code
+/// and the parameters the rule was compiled with. When the cache is full, the
+/// least recently used rule is evicted.
+///
+/// LLVM contexts are per-thread, so each thread needs its own cache.
+#[derive(Debug)]
+pub struct CompilerCache {
+    /// Maximum number of entries.
+    capacity: usize,
+    /// Entries, from least recently used to most recently used.
+    entries: RefCell<VecDeque<CacheEntry>>,
+}
+impl Default for CompilerCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+}
+impl CompilerCache {
+    /// Constructs an empty cache with the default capacity.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Constructs an empty cache that holds at most the given number of rules
+    /// (at least one).
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns the number of rules in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+    /// Returns true if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+    /// Removes every rule from the cache.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    /// Returns the cached transition function for the given source code and
+    /// key, if there is one, and marks it as most recently used.
+    fn get(&self, key: &CacheKey, source_code: &str) -> Option<CompiledFunction> {
+        let mut entries = self.entries.borrow_mut();
+        // Compare the source code too, in case of a hash collision.
+        let idx = entries
+            .iter()
+            .position(|entry| entry.key == *key && *entry.source_code == source_code)?;
+        let entry = entries.remove(idx)?;
+        let ret = entry.transition_function.clone();
+        entries.push_back(entry);
+        Some(ret)
+    }
+    /// Adds a transition function to the cache, evicting the least recently
+    /// used rule if the cache is full.
+    fn insert(&self, key: CacheKey, source_code: Rc<String>, function: &CompiledFunction) {
+        let mut entries = self.entries.borrow_mut();
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(CacheEntry {
+            key,
+            source_code,
+            transition_function: function.clone(),
+        });
+    }
+}
+
+/// Rule in a CompilerCache.
+#[derive(Debug)]
+struct CacheEntry {
+    /// Key that the entry was stored with.
+    key: CacheKey,
+    /// Source code of the rule.
+    source_code: Rc<String>,
+    /// Compiled transition function of the rule.
+    transition_function: CompiledFunction,
+}
+
+/// Everything that affects the output of the compiler for a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    /// Hash of the source code.
+    source_hash: u64,
+    /// Number of dimensions.
+    ndim: u8,
+    /// Neighborhood radius.
+    radius: u8,
+    /// Neighborhood shape.
+    nbhd_shape: NbhdShape,
+    /// Number of cell states.
+    state_count: usize,
+    /// Number of bits in an integer.
+    int_bits: u32,
+    /// Number of bits in a cell state.
+    cell_state_bits: u32,
+}
+impl CacheKey {
+    /// Constructs the key for a rule with the given source code and metadata.
+    fn new(source_code: &str, meta: &RuleMeta) -> Self {
+        let mut hasher = DefaultHasher::new();
+        source_code.hash(&mut hasher);
+        Self {
+            source_hash: hasher.finish(),
+            ndim: meta.ndim,
+            radius: meta.radius,
+            nbhd_shape: meta.nbhd_shape,
+            state_count: meta.state_count(),
+            int_bits: meta.int_bits,
+            cell_state_bits: CELL_STATE_BITS,
+        }
+    }
+}
+
+impl Compiler {
+    /// Builds the rule from the given source code and returns its compiled
+    /// transition function, reusing a previous compilation from the cache if
+    /// possible.
+    ///
+    /// Functions returned from this method are independent of the cache, and
+    /// remain valid even if they are evicted from it.
+    pub fn from_cache_or_build(
+        source_code: Rc<String>,
+        cache: &CompilerCache,
+    ) -> LangResult<CompiledFunction> {
+        // Building the AST is cheap compared to compiling it, and it's
+        // necessary to know the rule's parameters anyway.
+        let rule = ast::make_rule(source_code.clone())?;
+        let key = CacheKey::new(&source_code, rule.meta());
+        if let Some(function) = cache.get(&key, &source_code) {
+            return Ok(function);
+        }
+        let mut compiler = Self::with_int_bits(rule.meta().int_bits)?;
+        let function = rule
+            .transition_function()
+            .compile(&mut compiler, rule.helper_functions())?;
+        cache.insert(key, source_code, &function);
+        Ok(function)
+    }
+}
@@ -0,0 +1,163 @@
+//! LLVM debug info that maps instructions back to the source code.
+//!
+//! When debug info is enabled (see Compiler::set_debug_info()), each LLVM
+//! function gets a subprogram, and each instruction gets the location of the
+//! innermost statement or expression that it was built for. This lets LLVM
+//! tools (e.g. profilers) relate machine code back to the rule's source code,
+//! even after optimization.
+
+use std::rc::Rc;
+
+use inkwell::debug_info::{
+    AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DISubprogram, DWARFEmissionKind,
+    DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::FlagBehavior;
+
+use super::super::Span;
+use super::{get_ctx, Compiler};
+
+/// Name of the source file given to LLVM.
+const SOURCE_FILE_NAME: &'static str = "rule.ndca";
+/// Version of the debug info format, which LLVM requires as a module flag.
+const DEBUG_INFO_VERSION: u64 = 3;
+
+/// State of debug info generation.
+#[derive(Debug)]
+pub(super) struct DebugInfo {
+    /// LLVM debug info builder.
+    builder: DebugInfoBuilder<'static>,
+    /// LLVM compile unit that contains all functions.
+    compile_unit: DICompileUnit<'static>,
+    /// Source code of the rule.
+    source_code: Rc<String>,
+    /// Subprogram of the function currently being built.
+    subprogram: Option<DISubprogram<'static>>,
+    /// Span of the innermost statement or expression currently being built.
+    span: Option<Span>,
+}
+
+impl Compiler {
+    /// Returns whether debug info is emitted (see set_debug_info()).
+    pub fn debug_info(&self) -> bool {
+        self.debug_info_enabled
+    }
+    /// Sets whether to emit LLVM debug info that maps instructions back to the
+    /// source code. This is disabled by default, and must be set before
+    /// compiling a function.
+    pub fn set_debug_info(&mut self, debug_info: bool) {
+        self.debug_info_enabled = debug_info;
+    }
+
+    /// Begins emitting debug info for the rule with the given source code, if
+    /// debug info is enabled. This must be called before beginning any
+    /// function.
+    pub fn begin_debug_info(&mut self, source_code: Rc<String>) {
+        if !self.debug_info_enabled || self.debug_info.is_some() {
+            return;
+        }
+        self.module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            get_ctx().i32_type().const_int(DEBUG_INFO_VERSION, false),
+        );
+        let (builder, compile_unit) = self.module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            SOURCE_FILE_NAME,
+            ".",
+            "ndca",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+        );
+        self.debug_info = Some(DebugInfo {
+            builder,
+            compile_unit,
+            source_code,
+            subprogram: None,
+            span: None,
+        });
+    }
+    /// Finishes emitting debug info. This must be called after all functions
+    /// are built, before the module is verified.
+    pub(super) fn finish_debug_info(&mut self) {
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.builder.finalize();
+        }
+    }
+
+    /// Adds a subprogram to the function currently being built, if debug info
+    /// is enabled.
+    pub(super) fn begin_debug_info_function(&mut self, name: &str) {
+        let llvm_fn = self.llvm_fn();
+        if let Some(debug_info) = &mut self.debug_info {
+            let file = debug_info.compile_unit.get_file();
+            let subroutine_type =
+                debug_info
+                    .builder
+                    .create_subroutine_type(file, None, &[], DIFlags::PUBLIC);
+            let subprogram = debug_info.builder.create_function(
+                debug_info.compile_unit.as_debug_info_scope(),
+                name,
+                None,
+                file,
+                0,
+                subroutine_type,
+                true,
+                true,
+                0,
+                DIFlags::PUBLIC,
+                false,
+            );
+            llvm_fn.set_subprogram(subprogram);
+            debug_info.subprogram = Some(subprogram);
+            debug_info.span = None;
+        }
+    }
+
+    /// Builds instructions using the given function, attaching the location of
+    /// the given span to them (except those built for a nested span) if debug
+    /// info is enabled.
+    pub fn build_with_debug_location<T>(
+        &mut self,
+        span: Span,
+        build: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        if self.debug_info.is_none() {
+            return build(self);
+        }
+        let outer_span = self.set_debug_location(Some(span));
+        let ret = build(self);
+        self.set_debug_location(outer_span);
+        ret
+    }
+    /// Sets the location attached to instructions built after this, and
+    /// returns the span of the previous location. If the span is None, then
+    /// the location is left unchanged.
+    fn set_debug_location(&mut self, span: Option<Span>) -> Option<Span> {
+        let debug_info = match &mut self.debug_info {
+            Some(debug_info) => debug_info,
+            None => return None,
+        };
+        let outer_span = std::mem::replace(&mut debug_info.span, span);
+        if let (Some(span), Some(subprogram)) = (span, debug_info.subprogram) {
+            let (start, _) = span.textpoints(&debug_info.source_code);
+            let location = debug_info.builder.create_debug_location(
+                get_ctx(),
+                start.0 as u32,
+                start.1 as u32,
+                subprogram.as_debug_info_scope(),
+                None,
+            );
+            self.builder()
+                .set_current_debug_location(get_ctx(), location);
+        }
+        outer_span
+    }
+}
@@ -0,0 +1,95 @@
+//! Calls from compiled code to functions provided by the host (see
+//! ast::ExternFunctions).
+//!
+//! Each extern function is declared as an external LLVM function that takes
+//! and returns INT_BITS-bit integers, and it is mapped to the address given by
+//! the host in the execution engine the first time it is called.
+
+use inkwell::module::Linkage;
+use inkwell::IntPredicate;
+
+use super::super::ast::ExternFunction;
+use super::super::errors::*;
+use super::super::types::INT_BITS;
+use super::super::Type;
+use super::{get_ctx, Compiler, Value};
+use LangErrorMsg::InternalError;
+
+/// Prefix added to the name of each extern function in the LLVM module, so
+/// that it cannot conflict with helper functions or other external functions.
+const EXTERN_FN_PREFIX: &'static str = "ndca_extern_";
+
+impl Compiler {
+    /// Builds instructions to call the given function provided by the host,
+    /// which has the given name, and returns its result.
+    pub fn build_extern_call(
+        &mut self,
+        name: &str,
+        extern_function: &ExternFunction,
+        args: Vec<Value>,
+    ) -> LangResult<Value> {
+        let storage_int_type = get_ctx().custom_width_int_type(INT_BITS);
+
+        // Declare the function and map it in the execution engine, unless
+        // that was already done.
+        let llvm_fn_name = format!("{}{}", EXTERN_FN_PREFIX, name);
+        let llvm_fn = match self.module.get_function(&llvm_fn_name) {
+            Some(llvm_fn) => llvm_fn,
+            None => {
+                let param_types = vec![storage_int_type.into(); args.len()];
+                let fn_type = storage_int_type.fn_type(&param_types, false);
+                let llvm_fn =
+                    self.module
+                        .add_function(&llvm_fn_name, fn_type, Some(Linkage::External));
+                self.execution_engine
+                    .add_global_mapping(&llvm_fn, extern_function.fn_ptr);
+                llvm_fn
+            }
+        };
+
+        // Integers are sign-extended, and booleans and cell states are
+        // zero-extended.
+        let mut llvm_args = vec![];
+        for arg in args {
+            let b = self.builder();
+            let llvm_arg = match arg {
+                Value::Int(i) => b.build_int_s_extend_or_bit_cast(i, storage_int_type, "externArg"),
+                Value::Bool(i) | Value::CellState(i) => {
+                    b.build_int_z_extend_or_bit_cast(i, storage_int_type, "externArg")
+                }
+                _ => Err(InternalError("Invalid extern function argument".into()).without_span())?,
+            };
+            llvm_args.push(llvm_arg.into());
+        }
+        let ret = self
+            .builder()
+            .build_call(llvm_fn, &llvm_args, "externCall")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let int_type = self.int_type();
+        let cell_state_type = self.cell_state_type();
+        let b = self.builder();
+        match extern_function.signature.ret {
+            Type::Int => Ok(Value::Int(b.build_int_truncate_or_bit_cast(
+                ret,
+                int_type,
+                "externReturn",
+            ))),
+            Type::Bool => Ok(Value::Bool(b.build_int_compare(
+                IntPredicate::NE,
+                ret,
+                storage_int_type.const_zero(),
+                "externReturn",
+            ))),
+            Type::CellState => Ok(Value::CellState(b.build_int_truncate_or_bit_cast(
+                ret,
+                cell_state_type,
+                "externReturn",
+            ))),
+            _ => Err(InternalError("Invalid extern function return type".into()).without_span()),
+        }
+    }
+}
@@ -2,10 +2,12 @@
 
 use inkwell::execution_engine::JitFunction;
 use std::rc::Rc;
+use std::time::Instant;
 
 use super::super::errors::*;
+use super::super::types::{LangCellState, LangInt};
 use super::super::{ConstValue, Type};
-use super::Compiler;
+use super::{trace, CompileStats, Compiler};
 use LangErrorMsg::InternalError;
 /// Compiled user function with allocated space for arguments, return value, and
 /// optionally debug values to it.
@@ -17,32 +19,61 @@ pub struct CompiledFunction {
     /// Immutable data that is the same, even if this struct is cloned.
     meta: Rc<CompiledFunctionMeta>,
     /// The JIT function to run. (This has an Rc internally.)
-    jit_fn: JitFunction<'static, unsafe extern "C" fn(*mut u8, *mut u8) -> u32>,
+    jit_fn: JitFunction<
+        'static,
+        unsafe extern "C" fn(*mut u8, *mut u8, *const u8, *const LangInt, u64) -> u32,
+    >,
     /// Bytes used to store arguments and optionally debug values.
     inout_bytes: Vec<u8>,
     /// Bytes used to store return value.
     out_bytes: Vec<u8>,
+    /// Cell states of the neighborhood.
+    nbhd: Vec<LangCellState>,
+    /// Position of the cell.
+    pos: Vec<LangInt>,
+    /// Step number.
+    step: u64,
+    /// Values traced during the most recent call.
+    trace: Vec<ConstValue>,
 }
 impl CompiledFunction {
-    /// Completes the compilation process and returns a compiled function.
+    /// Completes the compilation process and returns a compiled function with
+    /// space for a neighborhood of the given number of cells and a position
+    /// with the given number of dimensions (or zero for either if the function
+    /// does not access it). The halo radius is reported by halo_radius().
     pub fn try_new(
         source_code: Rc<String>,
         error_points: Vec<LangError>,
+        nbhd_len: usize,
+        pos_len: usize,
+        halo_radius: Vec<usize>,
         compiler: &mut Compiler,
     ) -> LangResult<Self> {
+        // Count the LLVM IR before it is handed off to the JIT.
+        let ir_counts = if compiler.collect_stats() {
+            Some(compiler.count_ir())
+        } else {
+            None
+        };
+        let llvm_start = Instant::now();
+
+        compiler.finish_debug_info();
         // Make sure that the LLVM code is valid.
-        if !compiler.llvm_fn().verify(true) {
-            eprint!(
-                "Error encountered during function compilation; dumping LLVM function to sttderr"
-            );
-            compiler.llvm_fn().print_to_stderr();
-            Err(InternalError(
-                "LLVM function is invalid! This is a big problem".into(),
-            ))?;
+        if super::VERIFY_LLVM {
+            compiler.verify()?;
         }
+        // Optimize the function (only once it is known to be valid).
+        compiler.optimize(compiler.opt_level());
         // JIT-compile the function.
         let jit_fn = unsafe { compiler.get_jit_function() }?;
 
+        let stats = ir_counts.map(|(basic_blocks, instructions)| CompileStats {
+            basic_blocks,
+            instructions,
+            error_points: error_points.len(),
+            llvm_time: llvm_start.elapsed(),
+        });
+
         // Make a list of all the inout values.
         let mut inout_values: Vec<InOutValue> = vec![];
         let mut arg_count = 0;
@@ -77,6 +108,9 @@ impl CompiledFunction {
             meta: Rc::new(CompiledFunctionMeta {
                 source_code,
                 error_points,
+                stats,
+                trace_types: compiler.trace_types().to_vec(),
+                halo_radius,
 
                 out_type,
 
@@ -86,6 +120,10 @@ impl CompiledFunction {
             jit_fn,
             inout_bytes,
             out_bytes,
+            nbhd: vec![0; nbhd_len],
+            pos: vec![0; pos_len],
+            step: 0,
+            trace: vec![],
         })
     }
 
@@ -101,11 +139,66 @@ impl CompiledFunction {
         }
     }
 
+    /// Sets the arguments of this compiled function, calls it, and returns its
+    /// return value.
+    ///
+    /// Unlike set_args(), this method returns an InternalError instead of
+    /// panicking if given the wrong number or types of arguments. Runtime
+    /// errors (such as integer overflow) are returned as the corresponding
+    /// error from the function's error points.
+    ///
+    /// Integers (including vector components and array elements) that do not
+    /// fit in the integer width of the rule (see `@bits`) are not checked;
+    /// they are truncated to that width.
+    pub fn execute(&mut self, args: &[ConstValue]) -> LangResult<ConstValue> {
+        if args.len() != self.meta.arg_count {
+            Err(InternalError(
+                format!(
+                    "Wrong number of arguments passed to JIT function; expected {} but got {}",
+                    self.meta.arg_count,
+                    args.len(),
+                )
+                .into(),
+            ))?;
+        }
+        for (expected, arg) in self.meta.arg_values().iter().zip(args) {
+            if expected.ty != arg.ty() {
+                Err(InternalError(
+                    format!(
+                        "Wrong type for argument {:?} passed to JIT function; expected {} but got {}",
+                        expected.name,
+                        expected.ty,
+                        arg.ty(),
+                    )
+                    .into(),
+                ))?;
+            }
+        }
+        self.set_args(args);
+        self.call()
+    }
+    /// Sets the arguments and step number of this compiled function, calls it,
+    /// and returns its return value. See execute() and set_step().
+    pub fn execute_at_step(&mut self, args: &[ConstValue], step: u64) -> LangResult<ConstValue> {
+        self.set_step(step);
+        self.execute(args)
+    }
+
     /// Calls this compiled function and returns its return value.
     pub fn call(&mut self) -> LangResult<ConstValue> {
-        let ret: u32 = unsafe {
-            self.jit_fn
-                .call(self.inout_bytes.as_mut_ptr(), self.out_bytes.as_mut_ptr())
+        let jit_fn = &self.jit_fn;
+        let inout_ptr = self.inout_bytes.as_mut_ptr();
+        let out_ptr = self.out_bytes.as_mut_ptr();
+        let nbhd_ptr = self.nbhd.as_ptr();
+        let pos_ptr = self.pos.as_ptr();
+        let step = self.step;
+        let call = || unsafe { jit_fn.call(inout_ptr, out_ptr, nbhd_ptr, pos_ptr, step) };
+        let ret: u32 = if self.meta.trace_types.is_empty() {
+            call()
+        } else {
+            let (ret, trace) = trace::collect_trace(&self.meta.trace_types, call);
+            self.trace = trace;
+            ret
         };
         if ret == u32::MAX {
             // No error occurred; get the return value from self.out_bytes.
@@ -133,6 +226,67 @@ impl CompiledFunction {
         &mut self.inout_bytes
     }
 
+    /// Sets the cell states of the neighborhood used by the next call to this
+    /// function.
+    ///
+    /// Cells are ordered with the first axis varying fastest, so in 2D the
+    /// cell at offset `[x, y]` is at index `(x + r) + (y + r) * (2 * r + 1)`
+    /// where `r` is the neighborhood radius.
+    ///
+    /// Panics if given the wrong number of cells.
+    pub fn set_nbhd(&mut self, cells: &[LangCellState]) {
+        assert_eq!(
+            cells.len(),
+            self.nbhd.len(),
+            "Wrong neighborhood size for JIT function"
+        );
+        self.nbhd.copy_from_slice(cells);
+    }
+    /// Sets the position of the cell used by the next call to this function,
+    /// with one coordinate per dimension. Coordinates that do not fit in the
+    /// rule's integer width wrap around.
+    ///
+    /// Panics if given the wrong number of coordinates.
+    pub fn set_pos(&mut self, pos: &[LangInt]) {
+        assert_eq!(
+            pos.len(),
+            self.pos.len(),
+            "Wrong number of dimensions for JIT function"
+        );
+        self.pos.copy_from_slice(pos);
+    }
+    /// Sets the step number used by the next call to this function. Along with
+    /// the position of the cell, this determines the values returned by
+    /// `rand()`.
+    pub fn set_step(&mut self, step: u64) {
+        self.step = step;
+    }
+
+    /// Returns statistics about the compilation of this function, or None if
+    /// the compiler did not collect them (see Compiler::set_collect_stats()).
+    pub fn stats(&self) -> Option<&CompileStats> {
+        self.meta.stats.as_ref()
+    }
+
+    /// Returns the largest distance along each axis from the center of the
+    /// neighborhood of any neighbor that this function may read, so that a
+    /// host that splits the grid into partitions knows how many cells beyond
+    /// each partition to make available. Neighbors read at offsets that are
+    /// not constant count as the neighborhood radius.
+    ///
+    /// This has one element per dimension for the transition function, and is
+    /// empty for helper functions.
+    pub fn halo_radius(&self) -> &[usize] {
+        &self.meta.halo_radius
+    }
+
+    /// Returns the values traced during the most recent call to this function,
+    /// in order. This is always empty unless the compiler had debug tracing
+    /// enabled (see Compiler::set_debug_trace()).
+    pub fn trace(&self) -> &[ConstValue] {
+        &self.trace
+    }
+
     /// Returns the number of argument that this function takes.
     pub fn arg_count(&self) -> usize {
         self.meta.arg_count
@@ -166,6 +320,13 @@ struct CompiledFunctionMeta {
     source_code: Rc<String>,
     /// List of possible runtime errors.
     error_points: Vec<LangError>,
+    /// Statistics about the compilation, if they were collected.
+    stats: Option<CompileStats>,
+    /// Types of the values that this function may trace.
+    trace_types: Vec<Type>,
+    /// Largest distance along each axis of any neighbor that this function
+    /// may read.
+    halo_radius: Vec<usize>,
 
     /// The return type of this function.
     out_type: Type,
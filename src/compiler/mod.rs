@@ -9,34 +9,59 @@
 //! system. Instead we create a struct containing all of the inputs to the
 //! function and pass a pointer to that as the first argument, then create a
 //! variable for the output of the function and pass a pointer to that as the
-//! second argument. The actual return value of the function is just an integer
-//! to indicate any error.
+//! second argument. The third argument is a pointer to the cell states of the
+//! neighborhood, the fourth is a pointer to the position of the cell, and the
+//! fifth is the step number (used for random numbers); these are only used by
+//! transition functions. The actual return value of the function is just an
+//! integer to indicate any error.
 //!
 //! The values in that first struct I've called "in/out" values, or `inouts`.
 //! Actual function arguments only matter as inputs, but when debugging a
 //! function we can pass variable values as "in/out" values, and read the value
 //! after executing part of the function.
+//!
+//! Rust code always stores integers using INT_BITS bits, but a rule may use
+//! narrower integers. In that case, integer arguments are truncated when the
+//! function starts and integer return values are sign-extended when it
+//! returns.
 
 use std::collections::HashMap;
+use std::path::Path;
 use thread_local::ThreadLocal;
 
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
 use inkwell::execution_engine::{ExecutionEngine, JitFunction, UnsafeFunctionPointer};
-use inkwell::module::Module;
+use inkwell::module::{Linkage, Module};
+use inkwell::passes::{PassManager, PassManagerBuilder};
 use inkwell::types::{BasicType, BasicTypeEnum, FunctionType, IntType, StructType, VectorType};
-use inkwell::values::{FunctionValue, IntValue, PointerValue};
+use inkwell::values::{
+    BasicValueEnum, FunctionValue, InstructionOpcode, IntValue, PhiValue, PointerValue, VectorValue,
+};
 use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
 
+mod arena;
+mod cache;
+mod debug_info;
+mod externs;
 mod function;
+mod random;
+mod rule;
+mod stats;
+mod trace;
 mod value;
 
+pub use arena::RuleArena;
+pub use cache::{CompilerCache, DEFAULT_CACHE_CAPACITY};
 pub use function::CompiledFunction;
+pub use rule::CompiledRule;
+pub use stats::CompileStats;
 pub use value::Value;
 
+use super::ast::FnSignature;
 use super::errors::*;
-use super::types::{CELL_STATE_BITS, INT_BITS};
+use super::types::{CELL_STATE_BITS, INT_BITS, MIN_INT_BITS};
 use super::{ConstValue, Type};
 use LangErrorMsg::InternalError;
 
@@ -45,6 +70,15 @@ const MODULE_NAME: &'static str = "ndca";
 
 /// Whether to enable debug mode. TODO: move this to CompilerConfig
 const DEBUG_MODE: bool = false;
+/// Whether to verify the LLVM module after compiling each function. This is
+/// always enabled in debug builds, and can be enabled in release builds using
+/// the `verify` feature.
+pub const VERIFY_LLVM: bool = cfg!(any(debug_assertions, feature = "verify"));
+/// Default maximum number of iterations of a `for` loop over a constant range
+/// that are unrolled instead of building a real loop.
+pub const DEFAULT_UNROLL_THRESHOLD: usize = 16;
+/// Default level of LLVM optimization to run on each compiled function.
+pub const DEFAULT_OPT_LEVEL: OptimizationLevel = OptimizationLevel::Default;
 
 lazy_static! {
     /// Per-thread LLVM context.
@@ -67,15 +101,47 @@ pub struct Compiler {
     module: Module<'static>,
     /// LLVM JIT execution engine.
     execution_engine: ExecutionEngine<'static>,
+    /// Number of bits in an integer.
+    int_bits: u32,
     /// Function currently being built.
     function: Option<FunctionInProgress>,
+    /// LLVM functions of helper functions that have been declared, indexed by
+    /// name.
+    helper_functions: HashMap<String, FunctionValue<'static>>,
+    /// Whether to collect statistics about each compiled function.
+    collect_stats: bool,
+    /// Maximum number of iterations of a `for` loop over a constant range to
+    /// unroll.
+    unroll_threshold: usize,
+    /// Level of LLVM optimization to run before JIT-compiling a function.
+    opt_level: OptimizationLevel,
+    /// Whether calls to `trace()` report their values.
+    debug_trace: bool,
+    /// Types of the values traced so far (see build_trace()).
+    trace_types: Vec<Type>,
+    /// Whether to emit debug info.
+    debug_info_enabled: bool,
+    /// State of debug info generation, if it has begun.
+    debug_info: Option<debug_info::DebugInfo>,
 }
 impl Compiler {
-    /// Constructs a new compiler with a blank module and "main" function.
+    /// Constructs a new compiler with a blank module and "main" function, using
+    /// the default integer width.
     ///
     /// After constructing a Compiler, call begin_function() before building any
     /// instructions.
     pub fn new() -> LangResult<Self> {
+        Self::with_int_bits(INT_BITS)
+    }
+    /// Constructs a new compiler with a blank module and "main" function, using
+    /// integers with the given number of bits (from MIN_INT_BITS to INT_BITS).
+    /// This must match the rule being compiled.
+    pub fn with_int_bits(int_bits: u32) -> LangResult<Self> {
+        if int_bits < MIN_INT_BITS || INT_BITS < int_bits {
+            Err(
+                InternalError(format!("Invalid integer width: {}", int_bits).into()).without_span(),
+            )?;
+        }
         let module = get_ctx().create_module(MODULE_NAME);
         let execution_engine = module
             .create_jit_execution_engine(OptimizationLevel::None)
@@ -86,42 +152,168 @@ impl Compiler {
         Ok(Self {
             module,
             execution_engine,
+            int_bits,
             function: None,
+            helper_functions: HashMap::new(),
+            collect_stats: false,
+            unroll_threshold: DEFAULT_UNROLL_THRESHOLD,
+            opt_level: DEFAULT_OPT_LEVEL,
+            debug_trace: false,
+            trace_types: vec![],
+            debug_info_enabled: false,
+            debug_info: None,
         })
     }
 
-    /// Begins building a new LLVM function that can be called only from LLVM,
-    /// initializing variables and positioning the instruction pointer
-    /// accordingly.
+    /// Verifies the LLVM module, returning an InternalError containing the
+    /// verifier's message if it is invalid (e.g. if a basic block has no
+    /// terminator).
+    pub fn verify(&self) -> LangResult<()> {
+        self.module.verify().map_err(|e| {
+            InternalError(format!("LLVM module is invalid: {}", e.to_string().trim()).into())
+                .without_span()
+        })
+    }
+
+    /// Runs LLVM optimization passes at the given level on the module
+    /// (including all functions compiled so far).
+    ///
+    /// This only changes how the functions are implemented, not what they
+    /// return, so runtime errors are still reported using the same error
+    /// indices. Helper functions have internal linkage, so they may be inlined
+    /// and removed; only the function to JIT-compile is kept.
+    pub fn optimize(&mut self, level: OptimizationLevel) {
+        let pass_manager_builder = PassManagerBuilder::create();
+        pass_manager_builder.set_optimization_level(level);
+        let pass_manager = PassManager::create(());
+        pass_manager_builder.populate_module_pass_manager(&pass_manager);
+        pass_manager.run_on(&self.module);
+    }
+    /// Returns the level of LLVM optimization to run before JIT-compiling a
+    /// function.
+    pub fn opt_level(&self) -> OptimizationLevel {
+        self.opt_level
+    }
+    /// Sets the level of LLVM optimization to run before JIT-compiling a
+    /// function. Higher levels take longer to compile, but produce faster
+    /// code.
+    pub fn set_opt_level(&mut self, opt_level: OptimizationLevel) {
+        self.opt_level = opt_level;
+    }
+
+    /// Returns the LLVM IR of the module (including all functions compiled so
+    /// far) as a string, for debugging.
+    pub fn print_to_string(&self) -> String {
+        self.module.print_to_string().to_string()
+    }
+    /// Writes the module to a file, for debugging. If the file extension is
+    /// `.bc`, then this writes LLVM bitcode; otherwise it writes LLVM IR as
+    /// text.
+    pub fn write_to_file(&self, path: &Path) -> LangResult<()> {
+        if path.extension().map_or(false, |ext| ext == "bc") {
+            if self.module.write_bitcode_to_path(path) {
+                Ok(())
+            } else {
+                Err(
+                    InternalError(format!("Error writing LLVM bitcode to {:?}", path).into())
+                        .without_span(),
+                )
+            }
+        } else {
+            self.module.print_to_file(path).map_err(|e| {
+                InternalError(format!("Error writing LLVM IR to {:?}: {}", path, e).into())
+                    .without_span()
+            })
+        }
+    }
+
+    /// Declares an LLVM function for the helper function with the given name
+    /// and signature, so that calls to it can be built (see
+    /// build_helper_call()) before its body is built using
+    /// begin_intern_function().
+    pub fn declare_helper_function(
+        &mut self,
+        name: &str,
+        signature: &FnSignature,
+    ) -> LangResult<()> {
+        let fn_type = signature.llvm_fn_type(self)?;
+        let llvm_fn = self.module.add_function(
+            &format!("helper_{}", name),
+            fn_type,
+            Some(Linkage::Internal),
+        );
+        self.helper_functions.insert(name.to_owned(), llvm_fn);
+        Ok(())
+    }
+    /// Returns the LLVM function for the helper function with the given name,
+    /// which must have been declared using declare_helper_function().
+    fn get_helper_function(&self, name: &str) -> LangResult<FunctionValue<'static>> {
+        self.helper_functions.get(name).copied().ok_or_else(|| {
+            InternalError(format!("Helper function {:?} was never declared", name).into())
+                .without_span()
+        })
+    }
+    /// Begins building the body of a helper function that can be called only
+    /// from LLVM, initializing variables and positioning the instruction
+    /// builder accordingly. The function must have been declared using
+    /// declare_helper_function().
+    ///
+    /// The given offset is added to the index of every error returned from
+    /// this function, so that its error points do not overlap with those of
+    /// other functions in the same module.
     pub fn begin_intern_function(
         &mut self,
         name: &str,
         return_type: Type,
         arg_names: &[String],
         var_types: &HashMap<String, Type>,
+        error_index_offset: usize,
     ) -> LangResult<()> {
-        // Determine the LLVM function type (signature).
-        let llvm_return_type = self.get_llvm_type(return_type)?;
-        let llvm_arg_types = arg_names
-            .iter()
-            .map(|name| self.get_llvm_type(var_types[name]))
-            .collect::<LangResult<Vec<_>>>()?;
-        let fn_type = llvm_return_type.fn_type(&llvm_arg_types, false);
+        let llvm_fn = self.get_helper_function(name)?;
         // Construct the FunctionInProgress.
         self.function = Some(FunctionInProgress {
-            llvm_fn: self.module.add_function(name, fn_type, None),
+            llvm_fn,
             builder: get_ctx().create_builder(),
 
             return_type,
-            return_value_ptr: None,
+            // The first parameter is a pointer to hold the return value.
+            return_value_ptr: Some(llvm_fn.get_nth_param(0).unwrap().into_pointer_value()),
+            nbhd_ptr: None,
+            pos_ptr: None,
+            step: None,
+            rand_counter_ptr: None,
+            return_bb: None,
+            error_index_offset,
 
             inout_struct_type: None,
             vars_by_name: HashMap::new(),
+            loop_stack: vec![],
         });
+        let entry_bb = self.append_basic_block("entry");
+        self.builder().position_at_end(entry_bb);
+        self.begin_debug_info_function(name);
+
         // Allocate and initialize variables and add them to the HashMap of all
         // variables.
         for (name, &ty) in var_types {
-            let var = self.alloca_and_init_var(name.clone(), ty)?;
+            let var = match arg_names.iter().position(|arg_name| arg_name == name) {
+                // The remaining parameters are the arguments. Arrays are copied
+                // here, since only a pointer is passed.
+                Some(arg_idx) => {
+                    let llvm_type = self.get_llvm_type(ty)?;
+                    let ptr = self.builder().build_alloca(llvm_type, name);
+                    let param = llvm_fn.get_nth_param(arg_idx as u32 + 1).unwrap();
+                    self.build_store_value(ptr, Value::from_basic_value(ty, param))?;
+                    Variable {
+                        name: name.clone(),
+                        ty,
+                        ptr,
+                        is_arg: true,
+                        inout_byte_offset: None,
+                    }
+                }
+                None => self.alloca_and_init_var(name.clone(), ty)?,
+            };
             self.function_mut().vars_by_name.insert(name.clone(), var);
         }
 
@@ -157,7 +349,7 @@ impl Compiler {
         let inout_var_types = inout_var_names
             .iter()
             .map(|&name| var_types[name])
-            .map(|ty| self.get_llvm_type(ty))
+            .map(|ty| self.get_llvm_storage_type(ty))
             .collect::<LangResult<Vec<_>>>()?;
         let inout_struct_type = get_ctx().struct_type(&inout_var_types, false);
         let inout_struct_ptr_type = inout_struct_type
@@ -165,13 +357,36 @@ impl Compiler {
             .as_basic_type_enum();
         // The second parameter is a pointer to hold the return value.
         let return_ptr_type = self
-            .get_llvm_type(return_type)?
+            .get_llvm_storage_type(return_type)?
             .ptr_type(AddressSpace::Generic)
             .as_basic_type_enum();
+        // The third parameter is a pointer to the cell states of the
+        // neighborhood.
+        let nbhd_ptr_type = self
+            .cell_state_type()
+            .ptr_type(AddressSpace::Generic)
+            .as_basic_type_enum();
+        // The fourth parameter is a pointer to the position of the cell, with
+        // one INT_BITS-bit integer per dimension.
+        let pos_ptr_type = get_ctx()
+            .custom_width_int_type(INT_BITS)
+            .ptr_type(AddressSpace::Generic)
+            .as_basic_type_enum();
+        // The fifth parameter is the step number.
+        let step_type = get_ctx()
+            .custom_width_int_type(INT_BITS)
+            .as_basic_type_enum();
         // The actual LLVM return value just signals whether there was an error.
-        let fn_type = self
-            .get_llvm_return_type()
-            .fn_type(&[inout_struct_ptr_type, return_ptr_type], false);
+        let fn_type = self.get_llvm_return_type().fn_type(
+            &[
+                inout_struct_ptr_type,
+                return_ptr_type,
+                nbhd_ptr_type,
+                pos_ptr_type,
+                step_type,
+            ],
+            false,
+        );
 
         // Construct the FunctionInProgress.
         self.function = Some(FunctionInProgress {
@@ -180,12 +395,20 @@ impl Compiler {
 
             return_type,
             return_value_ptr: None,
+            nbhd_ptr: None,
+            pos_ptr: None,
+            step: None,
+            rand_counter_ptr: None,
+            return_bb: None,
+            error_index_offset: 0,
 
             inout_struct_type: Some(inout_struct_type),
             vars_by_name: HashMap::new(),
+            loop_stack: vec![],
         });
         let entry_bb = self.append_basic_block("entry");
         self.builder().position_at_end(entry_bb);
+        self.begin_debug_info_function(name);
 
         // Get pointers to the arguments.
         let shared_data_ptr = self
@@ -199,8 +422,22 @@ impl Compiler {
                 .unwrap()
                 .into_pointer_value(),
         );
+        self.function_mut().nbhd_ptr = Some(
+            self.llvm_fn()
+                .get_nth_param(2)
+                .unwrap()
+                .into_pointer_value(),
+        );
+        self.function_mut().pos_ptr = Some(
+            self.llvm_fn()
+                .get_nth_param(3)
+                .unwrap()
+                .into_pointer_value(),
+        );
+        self.function_mut().step = Some(self.llvm_fn().get_nth_param(4).unwrap().into_int_value());
 
         // Add inout variables to the HashMap of all variables.
+        let mut converted_vars = vec![];
         for (element_idx, &name) in inout_var_names.iter().enumerate() {
             // Get the byte offset of this field (so that Rust code can read and
             // modify this element).
@@ -210,22 +447,51 @@ impl Compiler {
                 .offset_of_element(&inout_struct_type, element_idx as u32)
                 .unwrap() as usize;
             // Get a pointer to the corresponding field in the struct.
-            let ptr = self
+            let ty = var_types[name];
+            let mut ptr = self
                 .builder()
                 .build_struct_gep(shared_data_ptr, element_idx as u32, name)
                 .unwrap();
+            // If the value is stored using a different type, copy it into a
+            // variable of the right type.
+            let llvm_type = self.get_llvm_type(ty)?;
+            if llvm_type != self.get_llvm_storage_type(ty)? {
+                let stored_value = self.builder().build_load(ptr, "tmp_storedValue");
+                let value = self.build_storage_conversion(stored_value, ty, false)?;
+                let storage_ptr = ptr;
+                ptr = self.builder().build_alloca(llvm_type, name);
+                self.builder().build_store(ptr, value);
+                converted_vars.push((ptr, storage_ptr, ty));
+            }
             // Insert this into the main HashMap of all variables.
             self.function_mut().vars_by_name.insert(
                 name.clone(),
                 Variable {
                     name: name.clone(),
-                    ty: var_types[name],
+                    ty,
                     is_arg: arg_names.contains(name),
                     ptr,
                     inout_byte_offset: Some(byte_offset),
                 },
             );
         }
+        if !converted_vars.is_empty() {
+            // Copy the converted variables back into the memory shared with
+            // Rust code before returning, so that Rust code can read any new
+            // values assigned to them.
+            let return_bb = self.append_basic_block("return");
+            self.builder().position_at_end(return_bb);
+            let llvm_return_type = self.get_llvm_return_type();
+            let phi = self.builder().build_phi(llvm_return_type, "returnValue");
+            for (ptr, storage_ptr, ty) in converted_vars {
+                let value = self.builder().build_load(ptr, "tmp_value");
+                let value = self.build_storage_conversion(value, ty, true)?;
+                self.builder().build_store(storage_ptr, value);
+            }
+            self.builder().build_return(Some(&phi.as_basic_value()));
+            self.function_mut().return_bb = Some((return_bb, phi));
+            self.builder().position_at_end(entry_bb);
+        }
         // Allocate and initialize alloca'd variables and add them to the
         // HashMap of all variables.
         for name in alloca_var_names {
@@ -242,8 +508,8 @@ impl Compiler {
         // Allocate space.
         let ptr = self.builder().build_alloca(llvm_type, &name);
         // Initialize to a default value.
-        let default_value = self.get_default_var_value(ty).unwrap().into_basic_value()?;
-        self.builder().build_store(ptr, default_value);
+        let default_value = self.get_default_var_value(ty).unwrap();
+        self.build_store_value(ptr, default_value)?;
         Ok(Variable {
             name,
             ty,
@@ -269,14 +535,38 @@ impl Compiler {
         })
     }
 
+    /// Returns the number of bits in an integer.
+    pub fn int_bits(&self) -> u32 {
+        self.int_bits
+    }
+    /// Returns the maximum number of iterations of a `for` loop over a constant
+    /// range that are unrolled instead of building a real loop.
+    pub fn unroll_threshold(&self) -> usize {
+        self.unroll_threshold
+    }
+    /// Sets the maximum number of iterations of a `for` loop over a constant
+    /// range that are unrolled instead of building a real loop.
+    pub fn set_unroll_threshold(&mut self, unroll_threshold: usize) {
+        self.unroll_threshold = unroll_threshold;
+    }
     /// Returns the LLVM type used to represent an integer.
     pub fn int_type(&self) -> IntType<'static> {
-        get_ctx().custom_width_int_type(INT_BITS)
+        get_ctx().custom_width_int_type(self.int_bits)
+    }
+    /// Returns the LLVM type used to represent a boolean.
+    pub fn bool_type(&self) -> IntType<'static> {
+        get_ctx().bool_type()
     }
     /// Returns the LLVM type used to represent a cell state.
     pub fn cell_state_type(&self) -> IntType<'static> {
         get_ctx().custom_width_int_type(CELL_STATE_BITS)
     }
+    /// Returns the LLVM type used to index into the neighborhood, which is
+    /// independent of the integer width because the neighborhood may have more
+    /// cells than the maximum integer value.
+    pub fn nbhd_index_type(&self) -> IntType<'static> {
+        get_ctx().i32_type()
+    }
 
     /// Returns the function currently being built, panicking if there is none.
     fn function(&self) -> &FunctionInProgress {
@@ -335,6 +625,28 @@ impl Compiler {
             .is_none()
     }
 
+    /// Builds the body of a loop, using the given BasicBlocks as the
+    /// destinations of any `break` and `continue` statements inside it.
+    pub fn build_loop_body(
+        &mut self,
+        continue_bb: BasicBlock<'static>,
+        break_bb: BasicBlock<'static>,
+        build_body: impl FnOnce(&mut Self) -> LangResult<()>,
+    ) -> LangResult<()> {
+        self.function_mut().loop_stack.push(LoopBlocks {
+            continue_bb,
+            break_bb,
+        });
+        let ret = build_body(self);
+        self.function_mut().loop_stack.pop();
+        ret
+    }
+    /// Returns the destination blocks of the innermost loop enclosing the
+    /// instruction pointer, or None if there is no such loop.
+    pub fn current_loop(&self) -> Option<LoopBlocks> {
+        self.function().loop_stack.last().copied()
+    }
+
     /// Builds a conditional expression, using an IntValue of any width. Any
     /// nonzero value is truthy, and zero is falsey.
     pub fn build_conditional(
@@ -374,20 +686,384 @@ impl Compiler {
         Ok(())
     }
 
+    /// Builds instructions to construct a vector of the given length by copying
+    /// an integer into every component.
+    pub fn build_vector_splat(
+        &mut self,
+        value: IntValue<'static>,
+        len: usize,
+    ) -> VectorValue<'static> {
+        let int_type = self.int_type();
+        let mut ret = int_type.vec_type(len as u32).get_undef();
+        for i in 0..len {
+            let idx = int_type.const_int(i as u64, false);
+            ret = self
+                .builder()
+                .build_insert_element(ret, value, idx, "tmp_splat");
+        }
+        ret
+    }
+
+    /// Builds instructions to extract every component of a vector of the given
+    /// length.
+    pub fn build_extract_components(
+        &mut self,
+        vector: VectorValue<'static>,
+        len: usize,
+    ) -> Vec<IntValue<'static>> {
+        let int_type = self.int_type();
+        (0..len)
+            .map(|i| {
+                let idx = int_type.const_int(i as u64, false);
+                self.builder()
+                    .build_extract_element(vector, idx, "tmp_component")
+                    .into_int_value()
+            })
+            .collect()
+    }
+
+    /// Builds instructions to construct a new vector from the components of an
+    /// existing vector at the given indices, which must all be in bounds.
+    pub fn build_vector_shuffle(
+        &mut self,
+        vector: VectorValue<'static>,
+        indices: &[usize],
+    ) -> VectorValue<'static> {
+        let i32_type = get_ctx().i32_type();
+        let mask_values: Vec<IntValue<'static>> = indices
+            .iter()
+            .map(|&i| i32_type.const_int(i as u64, false))
+            .collect();
+        let mask = VectorType::const_vector(&mask_values);
+        // The second vector is not used, so it can be undefined.
+        let unused = vector.get_type().get_undef();
+        self.builder()
+            .build_shuffle_vector(vector, unused, mask, "tmp_shuffle")
+    }
+
+    /// Builds instructions to load the cell state at the given index in the
+    /// neighborhood, which must be in bounds.
+    pub fn build_nbhd_load(&mut self, index: IntValue<'static>) -> LangResult<IntValue<'static>> {
+        let nbhd_ptr = self.function().nbhd_ptr.ok_or_else(|| {
+            InternalError("Function being built has no neighborhood".into()).without_span()
+        })?;
+        let cell_ptr = unsafe {
+            self.builder()
+                .build_in_bounds_gep(nbhd_ptr, &[index], "neighborPtr")
+        };
+        Ok(self
+            .builder()
+            .build_load(cell_ptr, "neighbor")
+            .into_int_value())
+    }
+    /// Builds instructions to load the position of the cell, which has the
+    /// given number of dimensions.
+    pub fn build_pos_load(&mut self, ndim: usize) -> LangResult<VectorValue<'static>> {
+        let pos_ptr = self.function().pos_ptr.ok_or_else(|| {
+            InternalError("Function being built has no position".into()).without_span()
+        })?;
+        let int_type = self.int_type();
+        let mut ret = int_type.vec_type(ndim as u32).get_undef();
+        for i in 0..ndim {
+            let idx = int_type.const_int(i as u64, false);
+            let b = self.builder();
+            let component_ptr = unsafe { b.build_in_bounds_gep(pos_ptr, &[idx], "posPtr") };
+            // Positions are stored using INT_BITS-bit integers.
+            let component = b
+                .build_load(component_ptr, "tmp_storedPos")
+                .into_int_value();
+            let component = b.build_int_cast(component, int_type, "tmp_posComponent");
+            ret = b.build_insert_element(ret, component, idx, "tmp_pos");
+        }
+        Ok(ret)
+    }
+    /// Returns the step number passed to the function currently being built,
+    /// as an INT_BITS-bit integer.
+    pub fn get_step_value(&self) -> LangResult<IntValue<'static>> {
+        self.function().step.ok_or_else(|| {
+            InternalError("Function being built has no step number".into()).without_span()
+        })
+    }
+    /// Builds instructions to convert the step number to NDCA's integer type.
+    pub fn build_step_load(&mut self) -> LangResult<IntValue<'static>> {
+        let step = self.get_step_value()?;
+        let int_type = self.int_type();
+        Ok(self.builder().build_int_cast(step, int_type, "tmp_step"))
+    }
+
+    /// Builds instructions to store a value in memory, such as in a variable.
+    /// Arrays are copied, since a Value::Array is only a pointer.
+    pub fn build_store_value(
+        &mut self,
+        ptr: PointerValue<'static>,
+        value: Value,
+    ) -> LangResult<()> {
+        let value = self.unpoison(value)?;
+        match value {
+            Value::Array(array) => self.build_array_copy(ptr, array),
+            value => {
+                let llvm_value = value.into_basic_value()?;
+                self.builder().build_store(ptr, llvm_value);
+                Ok(())
+            }
+        }
+    }
+    /// Builds instructions to copy the contents of one array into another of
+    /// the same length. The arrays may be the same.
+    pub fn build_array_copy(
+        &mut self,
+        dest: PointerValue<'static>,
+        src: PointerValue<'static>,
+    ) -> LangResult<()> {
+        let i8_ptr_type = get_ctx().i8_type().ptr_type(AddressSpace::Generic);
+        let i64_type = get_ctx().i64_type();
+        let bool_type = self.bool_type();
+        let memmove_fn_type = get_ctx().void_type().fn_type(
+            &[
+                i8_ptr_type.into(),
+                i8_ptr_type.into(),
+                i64_type.into(),
+                bool_type.into(),
+            ],
+            false,
+        );
+        let memmove_fn = self.get_llvm_intrinisic("llvm.memmove.p0i8.p0i8.i64", memmove_fn_type)?;
+        let size = src
+            .get_type()
+            .get_element_type()
+            .into_array_type()
+            .size_of()
+            .unwrap();
+        let b = self.builder();
+        let dest = b.build_pointer_cast(dest, i8_ptr_type, "tmp_destPtr");
+        let src = b.build_pointer_cast(src, i8_ptr_type, "tmp_srcPtr");
+        let is_volatile = bool_type.const_zero();
+        b.build_call(
+            memmove_fn,
+            &[dest.into(), src.into(), size.into(), is_volatile.into()],
+            "",
+        );
+        Ok(())
+    }
+    /// Builds instructions to get a pointer to the element at the given index
+    /// in an array, which must be in bounds.
+    fn build_array_element_ptr(
+        &mut self,
+        array: PointerValue<'static>,
+        index: IntValue<'static>,
+    ) -> PointerValue<'static> {
+        let zero = index.get_type().const_zero();
+        unsafe {
+            self.builder()
+                .build_in_bounds_gep(array, &[zero, index], "arrayElementPtr")
+        }
+    }
+    /// Builds instructions to load the element at the given index in an array,
+    /// which must be in bounds.
+    ///
+    /// Array elements are always stored using INT_BITS-bit integers, so that
+    /// arrays can be passed to and from Rust code directly; they are
+    /// truncated when loaded and sign-extended when stored.
+    ///
+    /// Elements stored by compiled code always fit in the integer width of the
+    /// rule, but elements passed in from Rust might not. Those are truncated
+    /// to the integer width without any error (like integer arguments), so
+    /// hosts using `@bits` below INT_BITS must pass values in range.
+    pub fn build_array_load(
+        &mut self,
+        array: PointerValue<'static>,
+        index: IntValue<'static>,
+    ) -> IntValue<'static> {
+        let element_ptr = self.build_array_element_ptr(array, index);
+        let int_type = self.int_type();
+        let b = self.builder();
+        let element = b.build_load(element_ptr, "arrayElement").into_int_value();
+        if element.get_type() == int_type {
+            element
+        } else {
+            b.build_int_truncate(element, int_type, "tmp_arrayElement")
+        }
+    }
+    /// Builds instructions to store an integer at the given index in an array,
+    /// which must be in bounds (see build_array_load()).
+    pub fn build_array_store(
+        &mut self,
+        array: PointerValue<'static>,
+        index: IntValue<'static>,
+        value: IntValue<'static>,
+    ) {
+        let element_ptr = self.build_array_element_ptr(array, index);
+        let storage_int_type = get_ctx().custom_width_int_type(INT_BITS);
+        let b = self.builder();
+        let value = if value.get_type() == storage_int_type {
+            value
+        } else {
+            b.build_int_s_extend(value, storage_int_type, "tmp_storedArrayElement")
+        };
+        b.build_store(element_ptr, value);
+    }
+
     /// Builds instructions to return a value.
     pub fn build_return_ok(&mut self, value: Value) -> LangResult<()> {
         let ptr = self.function().return_value_ptr.unwrap();
-        self.builder().build_store(ptr, value.into_basic_value()?);
+        let value = self.unpoison(value)?;
+        let ty = value.ty();
+        if let Value::Array(array) = value {
+            // Arrays are already stored using INT_BITS-bit integers.
+            self.build_array_copy(ptr, array)?;
+        } else {
+            let llvm_value = value.into_basic_value()?;
+            let llvm_value = self.build_storage_conversion(llvm_value, ty, true)?;
+            self.builder().build_store(ptr, llvm_value);
+        }
         let llvm_return_value = self.get_llvm_return_type().const_int(u64::MAX, true);
-        self.builder().build_return(Some(&llvm_return_value));
+        self.build_return_llvm_value(llvm_return_value);
         Ok(())
     }
+    /// Builds instructions to convert a value between the LLVM type used in
+    /// compiled code and the LLVM type used to store it in memory shared with
+    /// Rust code (see get_llvm_storage_type()). Integers are sign-extended when
+    /// stored and truncated when loaded.
+    ///
+    /// Arrays cannot be converted this way, but they are always stored using
+    /// INT_BITS-bit integers anyway.
+    fn build_storage_conversion(
+        &mut self,
+        value: BasicValueEnum<'static>,
+        ty: Type,
+        to_storage: bool,
+    ) -> LangResult<BasicValueEnum<'static>> {
+        let target_type = if to_storage {
+            self.get_llvm_storage_type(ty)?
+        } else {
+            self.get_llvm_type(ty)?
+        };
+        if value.get_type() == target_type {
+            return Ok(value);
+        }
+        match ty {
+            Type::Tuple(elements) => {
+                // Convert each element separately.
+                let mut ret = target_type.into_struct_type().get_undef();
+                for (i, &element_type) in elements.iter().enumerate() {
+                    let element = self
+                        .builder()
+                        .build_extract_value(value.into_struct_value(), i as u32, "tmp_element")
+                        .unwrap();
+                    let element =
+                        self.build_storage_conversion(element, element_type, to_storage)?;
+                    ret = self
+                        .builder()
+                        .build_insert_value(ret, element, i as u32, "tmp_tuple")
+                        .unwrap()
+                        .into_struct_value();
+                }
+                Ok(ret.into())
+            }
+            _ => {
+                let opcode = if to_storage {
+                    InstructionOpcode::SExt
+                } else {
+                    InstructionOpcode::Trunc
+                };
+                Ok(self
+                    .builder()
+                    .build_cast(opcode, value, target_type, "tmp_converted"))
+            }
+        }
+    }
     /// Builds instructions to return an error.
     pub fn build_return_err(&mut self, error_index: usize) {
+        let error_index = self.function().error_index_offset + error_index;
         let llvm_return_value = self
             .get_llvm_return_type()
             .const_int(error_index as u64, false);
-        self.builder().build_return(Some(&llvm_return_value));
+        self.build_return_llvm_value(llvm_return_value);
+    }
+    /// Builds instructions to return the given LLVM return value (see
+    /// get_llvm_return_type()), first copying any converted in/out variables
+    /// back into the memory shared with Rust code.
+    fn build_return_llvm_value(&mut self, llvm_return_value: IntValue<'static>) {
+        match self.function().return_bb {
+            Some((return_bb, phi)) => {
+                let current_bb = self.builder().get_insert_block().unwrap();
+                phi.add_incoming(&[(&llvm_return_value, current_bb)]);
+                self.builder().build_unconditional_branch(return_bb);
+            }
+            None => {
+                self.builder().build_return(Some(&llvm_return_value));
+            }
+        }
+    }
+
+    /// Builds instructions to call a helper function that has been declared
+    /// using declare_helper_function() and returns its return value. If the
+    /// helper function returns an error, then the function being built returns
+    /// the same error.
+    pub fn build_helper_call(
+        &mut self,
+        name: &str,
+        return_type: Type,
+        args: Vec<Value>,
+    ) -> LangResult<Value> {
+        let llvm_fn = self.get_helper_function(name)?;
+        // The return value is stored the same way as the return value of a
+        // function called from Rust code, so that build_return_ok() works the
+        // same either way.
+        let return_storage_type = self.get_llvm_storage_type(return_type)?;
+        let return_value_ptr = self.build_entry_alloca(return_storage_type, "returnValuePtr");
+        let mut llvm_args = vec![return_value_ptr.into()];
+        for arg in args {
+            llvm_args.push(arg.into_basic_value()?);
+        }
+        let error_index = self
+            .builder()
+            .build_call(llvm_fn, &llvm_args, "errorIndex")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        // Pass along any error (whose index is already offset correctly).
+        let no_error = self.get_llvm_return_type().const_int(u64::MAX, true);
+        let is_error =
+            self.builder()
+                .build_int_compare(IntPredicate::NE, error_index, no_error, "isError");
+        self.build_conditional(
+            is_error,
+            |c| {
+                c.build_return_llvm_value(error_index);
+                Ok(())
+            },
+            |_| Ok(()),
+        )?;
+
+        if let Type::Array(_) = return_type {
+            // Arrays are copied when they are assigned, so the array can stay
+            // where it is.
+            Ok(Value::Array(return_value_ptr))
+        } else {
+            let stored_value = self.builder().build_load(return_value_ptr, "returnValue");
+            let value = self.build_storage_conversion(stored_value, return_type, false)?;
+            Ok(Value::from_basic_value(return_type, value))
+        }
+    }
+    /// Builds an instruction at the start of the current function that
+    /// allocates space on the stack for a value of the given LLVM type. Unlike
+    /// an alloca built in place, this only allocates once even if it is inside
+    /// a loop.
+    fn build_entry_alloca(
+        &mut self,
+        llvm_type: BasicTypeEnum<'static>,
+        name: &str,
+    ) -> PointerValue<'static> {
+        let entry_bb = self.llvm_fn().get_first_basic_block().unwrap();
+        let builder = get_ctx().create_builder();
+        match entry_bb.get_first_instruction() {
+            Some(first_instruction) => builder.position_before(&first_instruction),
+            None => builder.position_at_end(entry_bb),
+        }
+        builder.build_alloca(llvm_type, name)
     }
 
     /// Builds instructions to perform checked integer arithmetic using an LLVM
@@ -399,6 +1075,32 @@ impl Compiler {
         name: &str,
         on_overflow: impl FnOnce(&mut Self) -> LangResult<()>,
     ) -> LangResult<IntValue<'static>> {
+        let (result_value, is_overflow) =
+            self.build_int_arithmetic_with_overflow(lhs, rhs, name)?;
+
+        // Branch based on whether there is overflow.
+        self.build_conditional(
+            is_overflow,
+            // Return an error if there is overflow.
+            on_overflow,
+            // Otherwise proceed.
+            |_| Ok(()),
+        )?;
+
+        Ok(result_value)
+    }
+    /// Builds instructions to perform integer arithmetic using an LLVM
+    /// intrinsic (such as `sadd` or `smul`), and returns the result along with
+    /// a boolean value that is true if overflow occurred.
+    ///
+    /// This does not branch on overflow, so the caller can combine several
+    /// overflow checks into one (see build_checked_int_arithmetic()).
+    pub fn build_int_arithmetic_with_overflow(
+        &mut self,
+        lhs: IntValue<'static>,
+        rhs: IntValue<'static>,
+        name: &str,
+    ) -> LangResult<(IntValue<'static>, IntValue<'static>)> {
         let intrinsic_name = format!(
             "llvm.{}.with.overflow.i{}",
             name,
@@ -440,16 +1142,7 @@ impl Compiler {
             .unwrap()
             .into_int_value();
 
-        // Branch based on whether there is overflow.
-        self.build_conditional(
-            is_overflow,
-            // Return an error if there is overflow.
-            on_overflow,
-            // Otherwise proceed.
-            |_| Ok(()),
-        )?;
-
-        Ok(result_value)
+        Ok((result_value, is_overflow))
     }
     /// Builds an overflow and division-by-zero check for arguments to a
     /// division operation (but does not actually perform the division).
@@ -508,17 +1201,23 @@ impl Compiler {
 
     /// Returns the minimum value representable by signed integers of NDCA's
     /// signed integer type.
-    fn get_min_int_value(&self) -> IntValue<'static> {
+    pub fn get_min_int_value(&self) -> IntValue<'static> {
         self.int_type().const_int(1, false).const_shl(
             self.int_type()
                 .const_int(self.int_type().get_bit_width() as u64 - 1, false),
         )
     }
+    /// Returns the maximum value representable by signed integers of NDCA's
+    /// signed integer type.
+    pub fn get_max_int_value(&self) -> IntValue<'static> {
+        self.get_min_int_value().const_not()
+    }
 
     /// Constructs a Value from a ConstValue.
     pub fn value_from_const(&self, const_value: ConstValue) -> Value {
         match const_value {
             ConstValue::Int(i) => Value::Int(self.int_type().const_int(i as u64, true)),
+            ConstValue::Bool(b) => Value::Bool(self.bool_type().const_int(b as u64, false)),
             ConstValue::CellState(i) => {
                 Value::CellState(self.cell_state_type().const_int(i as u64, false))
             }
@@ -532,25 +1231,128 @@ impl Compiler {
                     })
                     .collect::<Vec<_>>(),
             )),
+            ConstValue::Array(values) => {
+                // Constant arrays are stored in global memory, which is never
+                // modified because arrays are copied when they are assigned.
+                let storage_int_type = get_ctx().custom_width_int_type(INT_BITS);
+                let elements: Vec<IntValue<'static>> = values
+                    .iter()
+                    .map(|&i| storage_int_type.const_int(i as u64, true))
+                    .collect();
+                let initializer = storage_int_type.const_array(&elements);
+                let global = self
+                    .module
+                    .add_global(initializer.get_type(), None, "constArray");
+                global.set_initializer(&initializer);
+                global.set_constant(true);
+                global.set_linkage(Linkage::Private);
+                Value::Array(global.as_pointer_value())
+            }
+            ConstValue::Tuple(values) => {
+                let ty = Type::tuple(&values.iter().map(ConstValue::ty).collect::<Vec<_>>());
+                let elements = values
+                    .into_iter()
+                    .map(|value| {
+                        self.value_from_const(value)
+                            .into_basic_value()
+                            .expect("Failed to convert ConstValue to Value")
+                    })
+                    .collect::<Vec<_>>();
+                Value::from_basic_value(ty, get_ctx().const_struct(&elements, false).into())
+            }
+            ConstValue::Range(start, end) => Value::Range(
+                self.int_type().const_int(start as u64, true),
+                self.int_type().const_int(end as u64, true),
+            ),
         }
     }
     /// Returns the default value for variables of the given type.
     pub fn get_default_var_value(&self, ty: Type) -> Option<Value> {
         Some(self.value_from_const(ConstValue::default(ty)?))
     }
+    /// Returns a placeholder value of the given type for an expression that
+    /// never produces a value (see Expr::is_never()), after positioning the
+    /// builder at the end of a new BasicBlock with no predecessors if the
+    /// current one already has a terminator. Any instructions that use the
+    /// value are built in that block, so they never execute.
+    pub fn build_never_value(&mut self, ty: Type) -> Value {
+        if !self.needs_terminator() {
+            let unreachable_bb = self.append_basic_block("unreachable");
+            self.builder().position_at_end(unreachable_bb);
+        }
+        // Ranges are the only type without a default value.
+        self.value_from_const(ConstValue::default(ty).unwrap_or(ConstValue::Range(0, 0)))
+    }
+    /// Replaces a poisoned value with the default value of its type, so that
+    /// code generation can proceed. Any other value is returned unchanged.
+    pub fn unpoison(&self, value: Value) -> LangResult<Value> {
+        match value {
+            Value::Poison(ty) => self.get_default_var_value(ty).ok_or(POISONED_VALUE_ERROR),
+            _ => Ok(value),
+        }
+    }
 
     /// Returns the LLVM type corresponding to the given type in NDCA.
+    ///
+    /// For an array, this is the type of the array in memory; a Value::Array is
+    /// a pointer to this type.
     pub fn get_llvm_type(&self, ty: Type) -> LangResult<BasicTypeEnum<'static>> {
         match ty {
             Type::Int => Ok(self.int_type().into()),
+            Type::Bool => Ok(self.bool_type().into()),
             Type::CellState => Ok(self.cell_state_type().into()),
             Type::Vector(len) => Ok(self.int_type().vec_type(len as u32).into()),
+            Type::Array(len) => Ok(get_ctx()
+                .custom_width_int_type(INT_BITS)
+                .array_type(len as u32)
+                .into()),
+            Type::Tuple(elements) => Ok(get_ctx()
+                .struct_type(
+                    &elements
+                        .iter()
+                        .map(|&ty| self.get_llvm_type(ty))
+                        .collect::<LangResult<Vec<_>>>()?,
+                    false,
+                )
+                .into()),
             _ => Err(InternalError(
                 "Attempt to get LLVM representation of type that has none".into(),
             )
             .without_span()),
         }
     }
+    /// Returns the LLVM type used to pass a value of the given type as an
+    /// argument to a helper function. This is the same as get_llvm_type(),
+    /// except that arrays are passed as pointers.
+    pub fn get_llvm_param_type(&self, ty: Type) -> LangResult<BasicTypeEnum<'static>> {
+        let llvm_type = self.get_llvm_type(ty)?;
+        match ty {
+            Type::Array(_) => Ok(llvm_type.ptr_type(AddressSpace::Generic).into()),
+            _ => Ok(llvm_type),
+        }
+    }
+    /// Returns the LLVM type used to store a value of the given type in memory
+    /// shared with Rust code, which always uses INT_BITS-bit integers.
+    ///
+    /// Tuples are stored as packed structs, so that there is no padding
+    /// between elements (see Type::size_of()).
+    pub fn get_llvm_storage_type(&self, ty: Type) -> LangResult<BasicTypeEnum<'static>> {
+        let storage_int_type = get_ctx().custom_width_int_type(INT_BITS);
+        match ty {
+            Type::Int => Ok(storage_int_type.into()),
+            Type::Vector(len) => Ok(storage_int_type.vec_type(len as u32).into()),
+            Type::Tuple(elements) => Ok(get_ctx()
+                .struct_type(
+                    &elements
+                        .iter()
+                        .map(|&ty| self.get_llvm_storage_type(ty))
+                        .collect::<LangResult<Vec<_>>>()?,
+                    true,
+                )
+                .into()),
+            _ => self.get_llvm_type(ty),
+        }
+    }
     /// Returns the LLVM type actually returned from this function (as opposed
     /// to the type semantically returned).
     pub fn get_llvm_return_type(&self) -> IntType<'static> {
@@ -574,9 +1376,37 @@ struct FunctionInProgress {
     return_type: Type,
     /// Pointer to the place to put the return value.
     return_value_ptr: Option<PointerValue<'static>>,
+    /// Pointer to the cell states of the neighborhood.
+    nbhd_ptr: Option<PointerValue<'static>>,
+    /// Pointer to the position of the cell.
+    pos_ptr: Option<PointerValue<'static>>,
+    /// Step number.
+    step: Option<IntValue<'static>>,
+    /// Pointer to the number of random numbers generated so far during this
+    /// call, if any have been generated (see Compiler::build_rand_hash()).
+    rand_counter_ptr: Option<PointerValue<'static>>,
+    /// Basic block that copies in/out variables stored using a different type
+    /// back into the memory shared with Rust code and then returns, along with
+    /// the phi node for the LLVM return value. This is None if there are no
+    /// such variables, in which case each return is built directly.
+    return_bb: Option<(BasicBlock<'static>, PhiValue<'static>)>,
+    /// Number added to the index of every error returned from this function.
+    error_index_offset: usize,
 
     /// Variables, indexed by name.
     vars_by_name: HashMap<String, Variable>,
+
+    /// Stack of loops enclosing the instruction pointer, innermost last.
+    loop_stack: Vec<LoopBlocks>,
+}
+
+/// Destination blocks for `break` and `continue` statements inside a loop.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopBlocks {
+    /// BasicBlock to branch to for `continue`.
+    pub continue_bb: BasicBlock<'static>,
+    /// BasicBlock to branch to for `break`.
+    pub break_bb: BasicBlock<'static>,
 }
 
 /// Compiled variable.
@@ -0,0 +1,96 @@
+//! Pseudorandom numbers for stochastic rules.
+//!
+//! Random numbers are not generated using any state outside the compiled
+//! function. Instead, each one is a hash of the rule's seed, the position of
+//! the cell, the step number, and the number of random numbers generated
+//! before it in the same call. This makes every result reproducible, and lets
+//! the transition function run on many cells in parallel.
+
+use inkwell::values::{BasicValue, IntValue};
+
+use super::super::errors::*;
+use super::super::types::{LangInt, INT_BITS};
+use super::{get_ctx, Compiler};
+
+/// Constant added before each round of mixing (from SplitMix64).
+const HASH_INCREMENT: u64 = 0x9E37_79B9_7F4A_7C15;
+/// Shift amounts for each round of mixing (from SplitMix64).
+const HASH_SHIFTS: [u64; 3] = [30, 27, 31];
+/// Multipliers that follow each shift except the last (from SplitMix64).
+const HASH_MULTIPLIERS: [u64; 2] = [0xBF58_476D_1CE4_E5B9, 0x94D0_49BB_1331_11EB];
+
+impl Compiler {
+    /// Builds instructions to compute a new pseudorandom INT_BITS-bit integer
+    /// for the cell, which has the given number of dimensions, using the given
+    /// seed.
+    pub fn build_rand_hash(&mut self, seed: LangInt, ndim: usize) -> LangResult<IntValue<'static>> {
+        let hash_type = get_ctx().custom_width_int_type(INT_BITS);
+        let step = self.get_step_value()?;
+        let counter = self.build_rand_counter_increment();
+
+        let mut inputs = vec![];
+        let pos = self.build_pos_load(ndim)?;
+        for component in self.build_extract_components(pos, ndim) {
+            inputs.push(self.builder().build_int_s_extend_or_bit_cast(
+                component,
+                hash_type,
+                "tmp_posComponent",
+            ));
+        }
+        inputs.push(step);
+        inputs.push(counter);
+
+        let mut hash = hash_type.const_int(seed as u64, true);
+        for input in inputs {
+            let mixed = self.builder().build_xor(hash, input, "tmp_hashInput");
+            hash = self.build_hash_mix(mixed);
+        }
+        Ok(hash)
+    }
+    /// Builds instructions to mix the bits of an INT_BITS-bit integer, using
+    /// the SplitMix64 finalizer.
+    fn build_hash_mix(&mut self, value: IntValue<'static>) -> IntValue<'static> {
+        let hash_type = value.get_type();
+        let b = self.builder();
+        let increment = hash_type.const_int(HASH_INCREMENT, false);
+        let mut value = b.build_int_add(value, increment, "tmp_hashAdd");
+        for (i, &shift) in HASH_SHIFTS.iter().enumerate() {
+            let shift = hash_type.const_int(shift, false);
+            let shifted = b.build_right_shift(value, shift, false, "tmp_hashShift");
+            value = b.build_xor(value, shifted, "tmp_hashXor");
+            if let Some(&multiplier) = HASH_MULTIPLIERS.get(i) {
+                let multiplier = hash_type.const_int(multiplier, false);
+                value = b.build_int_mul(value, multiplier, "tmp_hashMul");
+            }
+        }
+        value
+    }
+    /// Builds instructions to increment the number of random numbers generated
+    /// during this call, and returns the number before it was incremented.
+    fn build_rand_counter_increment(&mut self) -> IntValue<'static> {
+        let hash_type = get_ctx().custom_width_int_type(INT_BITS);
+        let counter_ptr = match self.function().rand_counter_ptr {
+            Some(ptr) => ptr,
+            None => {
+                // Initialize the counter at the start of the function, so that
+                // it is reset on every call.
+                let ptr = self.build_entry_alloca(hash_type.into(), "randCounterPtr");
+                let entry_builder = get_ctx().create_builder();
+                let alloca = ptr.as_instruction_value().unwrap();
+                match alloca.get_next_instruction() {
+                    Some(next_instruction) => entry_builder.position_before(&next_instruction),
+                    None => entry_builder.position_at_end(alloca.get_parent().unwrap()),
+                }
+                entry_builder.build_store(ptr, hash_type.const_zero());
+                self.function_mut().rand_counter_ptr = Some(ptr);
+                ptr
+            }
+        };
+        let b = self.builder();
+        let counter = b.build_load(counter_ptr, "randCounter").into_int_value();
+        let one = hash_type.const_int(1, false);
+        let next_counter = b.build_int_add(counter, one, "tmp_nextRandCounter");
+        b.build_store(counter_ptr, next_counter);
+        counter
+    }
+}
@@ -0,0 +1,86 @@
+//! Compiled rule.
+
+use std::rc::Rc;
+
+use super::super::ast::{self, Rule, RuleMeta};
+use super::super::errors::*;
+use super::{CompiledFunction, Compiler};
+
+/// Rule whose transition function has been compiled, along with the metadata
+/// of the rule (such as the number of dimensions and cell states).
+///
+/// Like CompiledFunction, this struct can be cloned to run the same rule
+/// multiple times simultaneously.
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    /// Metadata of the rule.
+    meta: Rc<RuleMeta>,
+    /// Compiled transition function.
+    transition_function: CompiledFunction,
+}
+impl CompiledRule {
+    /// Builds the rule from the given source code and compiles its transition
+    /// function.
+    pub fn try_new(source_code: Rc<String>) -> LangResult<Self> {
+        Self::compile(&ast::make_rule(source_code)?)
+    }
+    /// Compiles the transition function of a rule that has already been built.
+    pub fn compile(rule: &Rule) -> LangResult<Self> {
+        let mut compiler = Compiler::with_int_bits(rule.meta().int_bits)?;
+        let transition_function = rule
+            .transition_function()
+            .compile(&mut compiler, rule.helper_functions())?;
+        Ok(Self {
+            meta: rule.meta().clone(),
+            transition_function,
+        })
+    }
+
+    /// Builds and compiles a new rule from the given source code and replaces
+    /// this rule with it, e.g. when a rule is edited while it is being
+    /// simulated.
+    ///
+    /// If the new source code has any errors, the first one is returned and
+    /// this rule is left unchanged, so the simulation can keep running the old
+    /// rule.
+    ///
+    /// Only the metadata and transition function of the rule are replaced; the
+    /// simulation grid belongs to the caller and is unaffected. The new rule
+    /// may have a different number of dimensions, cell states, or halo radius
+    /// though, so check meta() and halo_radius() again after a reload.
+    ///
+    /// # Threading
+    ///
+    /// Only reload a rule between steps. This method takes `&mut self`, so it
+    /// can't run while this rule's transition function is being called, but
+    /// clones of this rule are not affected: they keep running the old
+    /// transition function, whose machine code is only freed once the last
+    /// clone of it is dropped. Any clones that are still in use for the
+    /// current step should be replaced by clones of the reloaded rule before
+    /// the next step.
+    pub fn reload(&mut self, source_code: Rc<String>) -> LangResult<()> {
+        *self = Self::try_new(source_code)?;
+        Ok(())
+    }
+
+    /// Returns the metadata of this rule.
+    pub fn meta(&self) -> &Rc<RuleMeta> {
+        &self.meta
+    }
+    /// Returns the compiled transition function of this rule.
+    pub fn transition_function(&self) -> &CompiledFunction {
+        &self.transition_function
+    }
+    /// Returns a mutable reference to the compiled transition function of this
+    /// rule, which is needed to set its neighborhood and call it.
+    pub fn transition_function_mut(&mut self) -> &mut CompiledFunction {
+        &mut self.transition_function
+    }
+
+    /// Returns the largest distance along each axis from the center of the
+    /// neighborhood of any neighbor that this rule may read, with one element
+    /// per dimension (see CompiledFunction::halo_radius()).
+    pub fn halo_radius(&self) -> &[usize] {
+        self.transition_function.halo_radius()
+    }
+}
@@ -0,0 +1,56 @@
+//! Statistics about the compilation of a function.
+
+use std::time::Duration;
+
+use super::Compiler;
+
+/// Statistics collected while compiling a function, if enabled using
+/// Compiler::set_collect_stats().
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompileStats {
+    /// Number of LLVM basic blocks in the module (before optimization),
+    /// including those of helper functions.
+    pub basic_blocks: usize,
+    /// Number of LLVM instructions in the module (before optimization),
+    /// including those of helper functions.
+    pub instructions: usize,
+    /// Number of possible runtime errors, including those of helper functions.
+    pub error_points: usize,
+    /// Wall-clock time spent verifying, optimizing, and JIT-compiling the
+    /// LLVM module.
+    pub llvm_time: Duration,
+}
+
+impl Compiler {
+    /// Returns whether this compiler collects statistics about the functions
+    /// it compiles.
+    pub fn collect_stats(&self) -> bool {
+        self.collect_stats
+    }
+    /// Sets whether this compiler collects statistics about the functions it
+    /// compiles. Statistics are disabled by default, since counting
+    /// instructions requires iterating over the entire module.
+    pub fn set_collect_stats(&mut self, collect_stats: bool) {
+        self.collect_stats = collect_stats;
+    }
+
+    /// Returns the number of basic blocks and instructions in the LLVM module
+    /// (including all functions compiled so far).
+    pub(super) fn count_ir(&self) -> (usize, usize) {
+        let mut basic_blocks = 0;
+        let mut instructions = 0;
+        let mut next_fn = self.module.get_first_function();
+        while let Some(llvm_fn) = next_fn {
+            for bb in llvm_fn.get_basic_blocks() {
+                basic_blocks += 1;
+                let mut next_instruction = bb.get_first_instruction();
+                while let Some(instruction) = next_instruction {
+                    instructions += 1;
+                    next_instruction = instruction.get_next_instruction();
+                }
+            }
+            next_fn = llvm_fn.get_next_function();
+        }
+        (basic_blocks, instructions)
+    }
+}
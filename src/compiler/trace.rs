@@ -0,0 +1,130 @@
+//! Tracing of values from JIT-compiled code, for debugging rules.
+//!
+//! When debug tracing is enabled (see Compiler::set_debug_trace()), each call
+//! to `trace()` stores its argument in memory and calls trace_callback() with
+//! a pointer to it. The type of the value is not known to the callback, so it
+//! is passed as an index into a list of types kept by the compiled function.
+//! While a compiled function with any traces runs, that list (and the values
+//! traced so far) are kept in a thread-local variable.
+
+use std::cell::RefCell;
+
+use inkwell::module::Linkage;
+use inkwell::values::FunctionValue;
+use inkwell::AddressSpace;
+
+use super::super::errors::*;
+use super::super::{ConstValue, Type};
+use super::{get_ctx, Compiler, Value};
+use LangErrorMsg::InternalError;
+
+/// Name of the external LLVM function that calls trace_callback().
+const TRACE_FN_NAME: &'static str = "ndca_trace";
+
+thread_local! {
+    /// Types of the values traced by the compiled function currently running
+    /// on this thread (indexed by the first argument to trace_callback()), and
+    /// the values traced so far.
+    static CURRENT_TRACE: RefCell<Option<(Vec<Type>, Vec<ConstValue>)>> = RefCell::new(None);
+}
+
+/// Host function called by compiled code for each value it traces, given the
+/// index of the value's type in the list of trace types and a pointer to the
+/// value (stored the same way as a return value).
+extern "C" fn trace_callback(type_index: u32, value_ptr: *const u8) {
+    CURRENT_TRACE.with(|current_trace| {
+        if let Some((types, values)) = &mut *current_trace.borrow_mut() {
+            let ty = types[type_index as usize];
+            let bytes = unsafe { std::slice::from_raw_parts(value_ptr, ty.size_of().unwrap()) };
+            let value = ConstValue::from_bytes(ty, bytes);
+            eprintln!("trace: {} ({})", value, ty);
+            values.push(value);
+        }
+    });
+}
+
+/// Calls the given function (which runs compiled code) while collecting the
+/// values that it traces, which have the given list of types. Returns the
+/// result of the function along with the traced values, in order.
+pub(super) fn collect_trace<T>(types: &[Type], f: impl FnOnce() -> T) -> (T, Vec<ConstValue>) {
+    CURRENT_TRACE.with(|current_trace| {
+        *current_trace.borrow_mut() = Some((types.to_vec(), vec![]));
+    });
+    let ret = f();
+    let values = CURRENT_TRACE.with(|current_trace| current_trace.borrow_mut().take());
+    (ret, values.map(|(_, values)| values).unwrap_or_default())
+}
+
+impl Compiler {
+    /// Returns whether calls to `trace()` in compiled code report their values
+    /// (see set_debug_trace()).
+    pub fn debug_trace(&self) -> bool {
+        self.debug_trace
+    }
+    /// Sets whether calls to `trace()` in compiled code report their values.
+    /// When enabled, each traced value is printed to stderr along with its
+    /// type, and recorded by the compiled function (see
+    /// CompiledFunction::trace()). When disabled (the default), `trace(x)`
+    /// compiles to just `x`.
+    pub fn set_debug_trace(&mut self, debug_trace: bool) {
+        self.debug_trace = debug_trace;
+    }
+    /// Returns the types of the values traced by compiled code, indexed by the
+    /// type index passed to the trace callback.
+    pub(super) fn trace_types(&self) -> &[Type] {
+        &self.trace_types
+    }
+
+    /// Builds instructions to trace a value, if debug tracing is enabled.
+    pub fn build_trace(&mut self, value: Value) -> LangResult<()> {
+        if !self.debug_trace {
+            return Ok(());
+        }
+        let ty = value.ty();
+        if ty.size_of().is_none() {
+            Err(InternalError(format!("Cannot trace value of type {}", ty).into()).without_span())?;
+        }
+        let type_index = self.trace_types.len();
+        self.trace_types.push(ty);
+
+        // Store the value in memory so that the callback can read it.
+        let value_ptr = if let Value::Array(array) = value {
+            // Arrays are already stored using INT_BITS-bit integers.
+            array
+        } else {
+            let llvm_value = value.into_basic_value()?;
+            let llvm_value = self.build_storage_conversion(llvm_value, ty, true)?;
+            let ptr = self.build_entry_alloca(llvm_value.get_type(), "traceValuePtr");
+            self.builder().build_store(ptr, llvm_value);
+            ptr
+        };
+        let byte_ptr_type = get_ctx().i8_type().ptr_type(AddressSpace::Generic);
+        let value_ptr = self
+            .builder()
+            .build_pointer_cast(value_ptr, byte_ptr_type, "traceBytePtr");
+
+        let trace_fn = self.get_trace_fn();
+        let type_index = get_ctx().i32_type().const_int(type_index as u64, false);
+        self.builder()
+            .build_call(trace_fn, &[type_index.into(), value_ptr.into()], "");
+        Ok(())
+    }
+    /// Returns the external LLVM function that calls trace_callback(),
+    /// declaring it and mapping it in the execution engine if it does not
+    /// exist yet.
+    fn get_trace_fn(&mut self) -> FunctionValue<'static> {
+        if let Some(trace_fn) = self.module.get_function(TRACE_FN_NAME) {
+            return trace_fn;
+        }
+        let byte_ptr_type = get_ctx().i8_type().ptr_type(AddressSpace::Generic);
+        let fn_type = get_ctx()
+            .void_type()
+            .fn_type(&[get_ctx().i32_type().into(), byte_ptr_type.into()], false);
+        let trace_fn = self
+            .module
+            .add_function(TRACE_FN_NAME, fn_type, Some(Linkage::External));
+        self.execution_engine
+            .add_global_mapping(&trace_fn, trace_callback as usize);
+        trace_fn
+    }
+}
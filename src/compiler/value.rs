@@ -1,39 +1,82 @@
 //! Values used by the JIT compiler.
 
-use inkwell::values::{BasicValueEnum, IntValue, VectorValue};
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue, StructValue, VectorValue};
 
 use super::super::errors::*;
 use super::super::Type;
+use LangErrorMsg::InternalError;
 
 /// A value of any type.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     /// Integer.
     Int(IntValue<'static>),
+    /// Boolean (1-bit integer).
+    Bool(IntValue<'static>),
     /// Cell state.
     CellState(IntValue<'static>),
     /// Vector of a specific length (from 1 to 6).
     Vector(VectorValue<'static>),
+    /// Pointer to an array of integers of a specific length, which is always
+    /// stored using INT_BITS-bit integers (see Compiler::build_array_load()).
+    ///
+    /// The array may be shared with a variable or constant, so it must never
+    /// be modified through this pointer; instead, it is copied when assigned
+    /// to a variable.
+    Array(PointerValue<'static>),
+    /// Tuple, as an LLVM struct with one field for each element (along with
+    /// the types of the elements).
+    Tuple(&'static [Type], StructValue<'static>),
+    /// Range of integers, inclusive on both ends (start and end).
+    Range(IntValue<'static>, IntValue<'static>),
+    /// Placeholder of the given type for a value that could not be compiled
+    /// because of an error that has already been reported.
+    ///
+    /// This has no LLVM representation, so every operation that needs one
+    /// returns an error instead. It still has a type, though, so that
+    /// expressions using it do not report more type errors. When code
+    /// generation must proceed, it is replaced with the default value of its
+    /// type (see Compiler::unpoison()).
+    Poison(Type),
 }
 impl Value {
     /// Returns the type of this value.
     pub fn ty(&self) -> Type {
         match self {
             Self::Int(_) => Type::Int,
+            Self::Bool(_) => Type::Bool,
             Self::CellState(_) => Type::CellState,
             Self::Vector(v) => Type::Vector(v.get_type().get_size() as usize),
+            Self::Array(ptr) => {
+                Type::Array(ptr.get_type().get_element_type().into_array_type().len() as usize)
+            }
+            Self::Tuple(elements, _) => Type::Tuple(elements),
+            Self::Range(_, _) => Type::Range,
+            Self::Poison(ty) => *ty,
         }
     }
+    /// Returns true if this is Value::Poison.
+    pub fn is_poison(&self) -> bool {
+        matches!(self, Self::Poison(_))
+    }
     /// Constructs a value of the given type from an LLVM basic value.
     pub fn from_basic_value(ty: Type, basic_value: BasicValueEnum<'static>) -> Self {
         match ty {
             Type::Int => Self::Int(basic_value.into_int_value()),
+            Type::Bool => Self::Bool(basic_value.into_int_value()),
             Type::CellState => Self::CellState(basic_value.into_int_value()),
             Type::Vector(len) => {
                 let ret = Self::Vector(basic_value.into_vector_value());
                 assert_eq!(Type::Vector(len), ret.ty(), "Vector length does not match");
                 ret
             }
+            Type::Array(len) => {
+                let ret = Self::Array(basic_value.into_pointer_value());
+                assert_eq!(Type::Array(len), ret.ty(), "Array length does not match");
+                ret
+            }
+            Type::Tuple(elements) => Self::Tuple(elements, basic_value.into_struct_value()),
+            Type::Range => panic!("Cannot construct {:?} from LLVM basic value", ty),
         }
     }
     /// Returns the LLVM integer value inside if this is Value::Int; otherwise a
@@ -41,6 +84,26 @@ impl Value {
     pub fn as_int(self) -> LangResult<IntValue<'static>> {
         match self {
             Value::Int(i) => Ok(i),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the LLVM integer value inside if this is Value::Bool; otherwise
+    /// a TypeError.
+    pub fn as_bool(self) -> LangResult<IntValue<'static>> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the LLVM integer value inside if this is Value::Bool or
+    /// Value::Int (either of which may be used as a condition); otherwise a
+    /// TypeError.
+    pub fn as_condition(self) -> LangResult<IntValue<'static>> {
+        match self {
+            Value::Bool(i) | Value::Int(i) => Ok(i),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
             _ => Err(UNCAUGHT_TYPE_ERROR),
         }
     }
@@ -49,6 +112,7 @@ impl Value {
     pub fn as_cell_state(self) -> LangResult<IntValue<'static>> {
         match self {
             Value::CellState(i) => Ok(i),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
             _ => Err(UNCAUGHT_TYPE_ERROR),
         }
     }
@@ -57,6 +121,34 @@ impl Value {
     pub fn as_vector(self) -> LangResult<VectorValue<'static>> {
         match self {
             Value::Vector(v) => Ok(v),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the LLVM pointer value inside if this is Value::Array; otherwise
+    /// a TypeError.
+    pub fn as_array(self) -> LangResult<PointerValue<'static>> {
+        match self {
+            Value::Array(ptr) => Ok(ptr),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the LLVM struct value inside if this is Value::Tuple; otherwise
+    /// a TypeError.
+    pub fn as_tuple(self) -> LangResult<StructValue<'static>> {
+        match self {
+            Value::Tuple(_, s) => Ok(s),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the LLVM integer values for the start and end inside if this is
+    /// Value::Range; otherwise a TypeError.
+    pub fn as_range(self) -> LangResult<(IntValue<'static>, IntValue<'static>)> {
+        match self {
+            Value::Range(start, end) => Ok((start, end)),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
             _ => Err(UNCAUGHT_TYPE_ERROR),
         }
     }
@@ -65,8 +157,16 @@ impl Value {
     pub fn into_basic_value(self) -> LangResult<BasicValueEnum<'static>> {
         match self {
             Value::Int(i) => Ok(i.into()),
+            Value::Bool(b) => Ok(b.into()),
             Value::CellState(i) => Ok(i.into()),
             Value::Vector(v) => Ok(v.into()),
+            Value::Array(ptr) => Ok(ptr.into()),
+            Value::Tuple(_, s) => Ok(s.into()),
+            Value::Range(_, _) => Err(InternalError(
+                format!("{} has no BasicValue representation", self.ty()).into(),
+            )
+            .without_span()),
+            Value::Poison(_) => Err(POISONED_VALUE_ERROR),
             // Value::Pattern => Err(InternalError(format!("{} has no BasicValue representation", self).into())),
         }
     }
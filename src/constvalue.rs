@@ -1,10 +1,13 @@
 //! Values used by the interpreter for NDCA.
 
 use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
 
+use super::ast::ErrorPointRef;
 use super::errors::*;
-use super::types::{LangCellState, LangInt, Type};
-use LangErrorMsg::InternalError;
+use super::types::{int_fits_in_bits, LangCellState, LangInt, Type, MAX_ARRAY_LEN, MAX_VECTOR_LEN};
+use LangErrorMsg::{CannotEvalAsConst, InternalError};
 
 /// InternalError reported when a variable is used improperly and it was not
 /// caught by the type checker.
@@ -18,10 +21,18 @@ const INTERNAL_VAR_USE_ERROR: LangError = InternalError(std::borrow::Cow::Borrow
 pub enum ConstValue {
     /// Integer
     Int(LangInt),
+    /// Boolean
+    Bool(bool),
     /// Cell state
     CellState(LangCellState),
     /// Vector of a specific length from 1 to 256 (extra components are zero).
     Vector(Vec<LangInt>),
+    /// Array of integers of a specific length from 1 to 4096.
+    Array(Vec<LangInt>),
+    /// Tuple of two or more integers, booleans, and/or cell states.
+    Tuple(Vec<ConstValue>),
+    /// Range of integers, inclusive on both ends (start and end).
+    Range(LangInt, LangInt),
     // Pattern(crate::automaton::ArrayView2D<u8>),
 }
 impl ConstValue {
@@ -29,8 +40,14 @@ impl ConstValue {
     pub fn ty(&self) -> Type {
         match self {
             Self::Int(_) => Type::Int,
+            Self::Bool(_) => Type::Bool,
             Self::CellState(_) => Type::CellState,
             Self::Vector(values) => Type::Vector(values.len()),
+            Self::Array(values) => Type::Array(values.len()),
+            Self::Tuple(values) => {
+                Type::tuple(&values.iter().map(ConstValue::ty).collect::<Vec<_>>())
+            }
+            Self::Range(_, _) => Type::Range,
             // Self::Pattern(_) => Type::Pattern,
         }
     }
@@ -38,8 +55,17 @@ impl ConstValue {
     pub fn default(ty: Type) -> Option<Self> {
         match ty {
             Type::Int => Some(Self::Int(0)),
+            Type::Bool => Some(Self::Bool(false)),
             Type::CellState => Some(Self::CellState(0)),
             Type::Vector(len) => Some(Self::Vector(vec![0; len as usize])),
+            Type::Array(len) => Some(Self::Array(vec![0; len as usize])),
+            Type::Tuple(elements) => Some(Self::Tuple(
+                elements
+                    .iter()
+                    .map(|&ty| Self::default(ty))
+                    .collect::<Option<_>>()?,
+            )),
+            Type::Range => None,
         }
     }
     /// Returns the integer value inside if this is a ConstValue::Int; otherwise a
@@ -50,6 +76,14 @@ impl ConstValue {
             _ => Err(UNCAUGHT_TYPE_ERROR),
         }
     }
+    /// Returns the boolean value inside if this is a ConstValue::Bool;
+    /// otherwise a TypeError.
+    pub fn as_bool(self) -> LangResult<bool> {
+        match self {
+            Self::Bool(b) => Ok(b),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
     /// Returns the integer value inside if this is a ConstValue::CellState;
     /// otherwise a TypeError.
     pub fn as_cell_state(self) -> LangResult<LangCellState> {
@@ -58,6 +92,139 @@ impl ConstValue {
             _ => Err(UNCAUGHT_TYPE_ERROR),
         }
     }
+    /// Returns the vector components inside if this is a ConstValue::Vector;
+    /// otherwise a TypeError.
+    pub fn as_vector(self) -> LangResult<Vec<LangInt>> {
+        match self {
+            Self::Vector(values) => Ok(values),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the array elements inside if this is a ConstValue::Array;
+    /// otherwise a TypeError.
+    pub fn as_array(self) -> LangResult<Vec<LangInt>> {
+        match self {
+            Self::Array(values) => Ok(values),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the start and end inside if this is a ConstValue::Range;
+    /// otherwise a TypeError.
+    pub fn as_range(self) -> LangResult<(LangInt, LangInt)> {
+        match self {
+            Self::Range(start, end) => Ok((start, end)),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    /// Returns the elements inside if this is a ConstValue::Tuple; otherwise a
+    /// TypeError.
+    pub fn as_tuple(self) -> LangResult<Vec<ConstValue>> {
+        match self {
+            Self::Tuple(values) => Ok(values),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+
+    /// Returns the sum of two integers or vectors, matching the `+` operator
+    /// on integers with the given number of bits. If one value is an integer
+    /// and the other is a vector, the integer is added to every component.
+    ///
+    /// Returns the given overflow error if any result does not fit in the
+    /// integer width, or Err(CannotEvalAsConst) if either value is not an
+    /// integer or vector.
+    pub fn checked_add(
+        self,
+        other: Self,
+        int_bits: u32,
+        overflow_error: &ErrorPointRef,
+    ) -> LangResult<Self> {
+        self.zip_ints(other, |a, b| {
+            a.checked_add(b).filter(|&i| int_fits_in_bits(i, int_bits))
+        })?
+        .ok_or_else(|| overflow_error.error())
+    }
+    /// Returns the product of two integers or vectors, matching the `*`
+    /// operator on integers with the given number of bits. If one value is an
+    /// integer and the other is a vector, every component is multiplied by the
+    /// integer.
+    ///
+    /// Returns the given overflow error if any result does not fit in the
+    /// integer width, or Err(CannotEvalAsConst) if either value is not an
+    /// integer or vector.
+    pub fn checked_mul(
+        self,
+        other: Self,
+        int_bits: u32,
+        overflow_error: &ErrorPointRef,
+    ) -> LangResult<Self> {
+        self.zip_ints(other, |a, b| {
+            a.checked_mul(b).filter(|&i| int_fits_in_bits(i, int_bits))
+        })?
+        .ok_or_else(|| overflow_error.error())
+    }
+    /// Returns the minimum of two integers, or the component-wise minimum of
+    /// two vectors.
+    ///
+    /// Returns Err(CannotEvalAsConst) if either value is not an integer or
+    /// vector.
+    pub fn min(self, other: Self) -> LangResult<Self> {
+        self.zip_ints(other, |a, b| Some(std::cmp::min(a, b)))?
+            .ok_or_else(|| InternalError("Overflow in min".into()).without_span())
+    }
+    /// Returns the maximum of two integers, or the component-wise maximum of
+    /// two vectors.
+    ///
+    /// Returns Err(CannotEvalAsConst) if either value is not an integer or
+    /// vector.
+    pub fn max(self, other: Self) -> LangResult<Self> {
+        self.zip_ints(other, |a, b| Some(std::cmp::max(a, b)))?
+            .ok_or_else(|| InternalError("Overflow in max".into()).without_span())
+    }
+    /// Returns the absolute value of an integer, or of each component of a
+    /// vector, matching the runtime behavior on integers with the given number
+    /// of bits (where the absolute value of the minimum integer overflows).
+    ///
+    /// Returns the given overflow error if any result does not fit in the
+    /// integer width, or Err(CannotEvalAsConst) if this value is not an integer
+    /// or vector.
+    pub fn abs(self, int_bits: u32, overflow_error: &ErrorPointRef) -> LangResult<Self> {
+        // Pair each integer with a dummy integer that is ignored.
+        self.zip_ints(Self::Int(0), |a, _| {
+            a.checked_abs().filter(|&i| int_fits_in_bits(i, int_bits))
+        })?
+        .ok_or_else(|| overflow_error.error())
+    }
+    /// Applies a function to corresponding integers of two integers or
+    /// vectors, splatting an integer if the other value is a vector. Returns
+    /// Ok(None) if the function returns None for any component, or
+    /// Err(CannotEvalAsConst) if either value is not an integer or vector (or
+    /// if they are vectors of different lengths).
+    fn zip_ints(
+        self,
+        other: Self,
+        f: impl Fn(LangInt, LangInt) -> Option<LangInt>,
+    ) -> LangResult<Option<Self>> {
+        Ok(match (self, other) {
+            (Self::Int(a), Self::Int(b)) => f(a, b).map(Self::Int),
+            (Self::Int(a), Self::Vector(v)) => v
+                .into_iter()
+                .map(|b| f(a, b))
+                .collect::<Option<_>>()
+                .map(Self::Vector),
+            (Self::Vector(v), Self::Int(b)) => v
+                .into_iter()
+                .map(|a| f(a, b))
+                .collect::<Option<_>>()
+                .map(Self::Vector),
+            (Self::Vector(u), Self::Vector(v)) if u.len() == v.len() => u
+                .into_iter()
+                .zip(v)
+                .map(|(a, b)| f(a, b))
+                .collect::<Option<_>>()
+                .map(Self::Vector),
+            _ => return Err(CannotEvalAsConst.without_span()),
+        })
+    }
     /// Constructs a value of the given type from raw bytes. Panics if given an
     /// invalid value or invalid type.
     pub fn from_bytes(ty: Type, bytes: &[u8]) -> Self {
@@ -77,17 +244,26 @@ impl ConstValue {
         );
         match ty {
             Type::Int => Self::Int(LangInt::from_ne_bytes(bytes.try_into().unwrap())),
+            Type::Bool => Self::Bool(bytes[0] != 0),
             Type::CellState => {
                 Self::CellState(LangCellState::from_ne_bytes(bytes.try_into().unwrap()))
             }
-            Type::Vector(_) => Self::Vector(
-                bytes
-                    .chunks(Type::Int.size_of().unwrap())
-                    .map(TryInto::try_into)
-                    .map(Result::unwrap)
-                    .map(LangInt::from_ne_bytes)
-                    .collect(),
-            ),
+            Type::Vector(_) => Self::Vector(ints_from_bytes(bytes)),
+            Type::Array(_) => Self::Array(ints_from_bytes(bytes)),
+            Type::Tuple(elements) => {
+                let mut bytes = bytes;
+                Self::Tuple(
+                    elements
+                        .iter()
+                        .map(|&ty| {
+                            let (element_bytes, rest) = bytes.split_at(ty.size_of().unwrap());
+                            bytes = rest;
+                            Self::from_bytes(ty, element_bytes)
+                        })
+                        .collect(),
+                )
+            }
+            Type::Range => panic!("Cannot construct {:?} from bytes", ty),
         }
     }
     /// Returns raw bytes representing this value. Panics if this type has no
@@ -126,16 +302,129 @@ impl ConstValue {
                 let bytes: &mut [u8; SIZE] = bytes.try_into().unwrap();
                 *bytes = i.to_ne_bytes()
             }
+            Self::Bool(b) => bytes[0] = *b as u8,
             Self::CellState(i) => {
                 const SIZE: usize = std::mem::size_of::<LangCellState>();
                 let bytes: &mut [u8; SIZE] = bytes.try_into().unwrap();
                 *bytes = i.to_ne_bytes()
             }
-            Self::Vector(values) => {
+            Self::Vector(values) | Self::Array(values) => {
                 for (chunk, &i) in bytes.chunks_mut(Type::Int.size_of().unwrap()).zip(values) {
                     Self::Int(i).set_bytes(chunk);
                 }
             }
+            Self::Tuple(values) => {
+                let mut bytes = bytes;
+                for value in values {
+                    let (element_bytes, rest) = bytes.split_at_mut(value.ty().size_of().unwrap());
+                    value.set_bytes(element_bytes);
+                    bytes = rest;
+                }
+            }
+            Self::Range(_, _) => unreachable!(),
+        }
+    }
+}
+
+/// Converts raw bytes to a list of integers.
+fn ints_from_bytes(bytes: &[u8]) -> Vec<LangInt> {
+    bytes
+        .chunks(Type::Int.size_of().unwrap())
+        .map(TryInto::try_into)
+        .map(Result::unwrap)
+        .map(LangInt::from_ne_bytes)
+        .collect()
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::CellState(i) => write!(f, "#{}", i),
+            Self::Vector(values) => write_list(f, values),
+            Self::Array(values) => {
+                write!(f, "array")?;
+                write_list(f, values)
+            }
+            Self::Tuple(values) => {
+                write!(f, "(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Self::Range(start, end) => write!(f, "{}..{}", start, end),
+        }
+    }
+}
+/// Writes a bracketed, comma-separated list of integers.
+fn write_list(f: &mut fmt::Formatter, values: &[LangInt]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    write!(f, "]")
+}
+impl FromStr for ConstValue {
+    type Err = ();
+    /// Parses a value in the same form produced by its Display
+    /// implementation, such as `42`, `true`, `#3`, `[1, 2, 3]`, `array[0, 0]`,
+    /// `(1, #2)`, or `-1..1`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim();
+        if let Ok(b) = s.parse() {
+            Ok(Self::Bool(b))
+        } else if let Some(cell_state) = s.strip_prefix('#') {
+            Ok(Self::CellState(cell_state.parse().map_err(|_| ())?))
+        } else if let Some(elements) = s.strip_prefix("array") {
+            let values = parse_list(elements.trim())?;
+            if values.len() > MAX_ARRAY_LEN {
+                return Err(());
+            }
+            Ok(Self::Array(values))
+        } else if let Some(elements) = s.strip_prefix('(') {
+            // Tuple elements cannot contain commas, so the elements can be
+            // split on commas.
+            let values = elements
+                .strip_suffix(')')
+                .ok_or(())?
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<Self>, ()>>()?;
+            if values.len() < 2 || !values.iter().all(|v| v.ty().can_be_tuple_element()) {
+                return Err(());
+            }
+            Ok(Self::Tuple(values))
+        } else if s.starts_with('[') {
+            let values = parse_list(s)?;
+            if values.len() > MAX_VECTOR_LEN {
+                return Err(());
+            }
+            Ok(Self::Vector(values))
+        } else if let Some(idx) = s.find("..") {
+            let start = s[..idx].trim().parse().map_err(|_| ())?;
+            let end = s[idx + 2..].trim().parse().map_err(|_| ())?;
+            Ok(Self::Range(start, end))
+        } else {
+            Ok(Self::Int(s.parse().map_err(|_| ())?))
         }
     }
 }
+/// Parses a bracketed, comma-separated list of integers.
+fn parse_list(s: &str) -> Result<Vec<LangInt>, ()> {
+    let components = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or(())?;
+    components
+        .split(',')
+        .map(|component| component.trim().parse().map_err(|_| ()))
+        .collect()
+}
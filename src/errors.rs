@@ -4,12 +4,16 @@ use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
 
-use super::ast::ArgTypes;
+use super::ast::{ArgTypes, NbhdShape};
 use super::lexer::ComparisonToken;
-use super::{Span, Type, MAX_NDIM, MAX_STATES};
+use super::types::{INT_BITS, MAX_ARRAY_LEN, MAX_VECTOR_LEN, MIN_INT_BITS};
+use super::{Span, Type, MAX_NDIM, MAX_RADIUS, MAX_STATES};
 
 pub const UNCAUGHT_TYPE_ERROR: LangError =
     LangErrorMsg::InternalError(Cow::Borrowed("Uncaught type error")).without_span();
+pub const POISONED_VALUE_ERROR: LangError =
+    LangErrorMsg::InternalError(Cow::Borrowed("Poisoned value used in compiled code"))
+        .without_span();
 
 /// A Result of a LangError and an accompanying line of source code.
 pub type CompleteLangResult<T> = Result<T, LangErrorWithSource>;
@@ -105,6 +109,87 @@ impl LangError {
     }
 }
 
+/// Renders an error against the source code it came from, similar to rustc:
+/// the error message, followed by each line of source code that the error
+/// spans, with carets underlining the span on each line. If the error involves
+/// another part of the source code (see LangErrorMsg::note()), that is
+/// rendered the same way after a note.
+///
+/// Unlike LangErrorWithSource, this preserves indentation and underlines every
+/// line of a multi-line span.
+pub fn format_with_source(error: &LangError, src: &str) -> String {
+    let mut ret = format!("error: {}", error.msg);
+    if let Some(span) = error.span {
+        ret += &format_snippet(span, src);
+        if let Some((note, note_span)) = error.msg.note() {
+            ret += &format!("\nnote: {}", note);
+            ret += &format_snippet(note_span, src);
+        }
+    }
+    ret
+}
+/// Renders the lines of source code that a span covers for
+/// format_with_source(), starting with a newline.
+fn format_snippet(span: Span, src: &str) -> String {
+    let mut ret = String::new();
+    let (start_tp, end_tp) = span.textpoints(src);
+    let lines: Vec<&str> = src.lines().collect();
+    // A multi-line span that ends at the beginning of a line doesn't include
+    // anything on that line.
+    let mut end_line = end_tp.line();
+    let mut end_column = end_tp.column();
+    if end_line > start_tp.line() && end_column == 1 {
+        end_line -= 1;
+        end_column = lines.get(end_line - 1).map_or(0, |line| line.len()) + 1;
+    }
+
+    let gutter_width = end_line.to_string().len();
+    ret += &format!(
+        "\n{:w$}--> line {}, column {}\n{:w$} |",
+        "",
+        start_tp.line(),
+        start_tp.column(),
+        "",
+        w = gutter_width,
+    );
+    for line_num in start_tp.line()..=end_line {
+        let line = lines.get(line_num - 1).copied().unwrap_or("");
+        let indent_len = line.len() - line.trim_start().len();
+        // Underline from the start of the span (or the start of the line) to
+        // the end of the span (or the end of the line). Column numbers are
+        // 1-indexed, and end_column is exclusive.
+        let start_column = if line_num == start_tp.line() {
+            start_tp.column()
+        } else {
+            indent_len + 1
+        };
+        let end_column = if line_num == end_line {
+            end_column
+        } else {
+            line.len() + 1
+        };
+        // Always underline at least one character.
+        let caret_count = std::cmp::max(end_column.saturating_sub(start_column), 1);
+        // Preserve tabs so that the carets line up with the source code.
+        let padding: String = line
+            .get(..start_column - 1)
+            .unwrap_or(line)
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        ret += &format!(
+            "\n{:>w$} | {}\n{:w$} | {}{}",
+            line_num,
+            line,
+            "",
+            padding,
+            "^".repeat(caret_count),
+            w = gutter_width,
+        );
+    }
+    ret
+}
+
 /// Information about the type of error that occurred.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LangErrorMsg {
@@ -117,10 +202,12 @@ pub enum LangErrorMsg {
     UnknownSymbol,
     Unterminated(&'static str),
     Unmatched(char, char),
+    InvalidIntegerLiteral(&'static str),
+    InvalidDigitSeparator,
     Expected(&'static str),
     ExpectedGot {
-        expected: &'static str,
-        got: &'static str,
+        expected: Cow<'static, str>,
+        got: Cow<'static, str>,
     },
     ReservedWord(Cow<'static, str>),
     ElseWithoutIf,
@@ -128,9 +215,38 @@ pub enum LangErrorMsg {
     TopLevelNonDirective,
     InvalidDirectiveName,
     RepeatDirective(&'static str),
+    ConflictingDirectives {
+        name: &'static str,
+        first_span: Span,
+    },
     FunctionNameConflict,
+    PreludeFunctionShadowed(String),
+    ConstantNameConflict,
+    CellStateAlreadyNamed(String),
+    UnknownCellStateName {
+        name: String,
+        suggestion: String,
+    },
+    UnknownFunction(String),
+    UnknownExternFunction(String),
+    UnknownMethod {
+        ty: Type,
+        name: String,
+    },
+    UnknownProperty {
+        ty: Type,
+        name: String,
+    },
     InvalidDimensionCount,
     InvalidStateCount,
+    InvalidRadius,
+    InvalidNbhdShape,
+    InvalidIntBits,
+    InvalidOverflowMode,
+    InvalidVectorLength,
+    InvalidArrayLength,
+    InvalidTupleLength,
+    InvalidTupleElement,
     TypeError {
         expected: Type,
         got: Type,
@@ -147,16 +263,57 @@ pub enum LangErrorMsg {
         got: ArgTypes,
     },
     CannotAssignTypeToVariable(Type),
+    CannotTraceType(Type),
+    InvalidExternType(Type),
+    CannotIndexType(Type),
+    CannotAssignToElement(Type),
+    CannotUnpack {
+        ty: Type,
+        count: usize,
+    },
     UseOfUninitializedVariable,
+    AssignToConstant,
     BecomeInHelperFunction,
+    RemainInHelperFunction,
     ReturnInTransitionFunction,
+    MissingReturn,
+    MissingBlockValue,
+    RecursiveFunctionCall(Vec<String>),
+    NeighborInHelperFunction,
+    PositionInHelperFunction,
+    StepInHelperFunction,
+    RandomInHelperFunction,
+    InvalidRandomBound,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    DuplicateMatchCase,
+    UnreachableStatement,
+    PoisonedExpression,
     CannotEvalAsConst,
+    ShiftOutOfRange(u32),
+    InvalidClampBounds,
+    FallibleSelectOperand,
+    AssertionFailed(Option<String>),
 
     // Runtime errors
     IntegerOverflow,
     DivideByZero,
     NegativeExponent,
     CellStateOutOfRange,
+    CellStateOutsideStates {
+        count: usize,
+        states_span: Option<Span>,
+    },
+    IndexOutOfBounds,
+    NeighborOutOfRange {
+        shape: NbhdShape,
+        radius: u8,
+    },
+    NeighborOffsetLength {
+        len: usize,
+        ndim: u8,
+        ndim_span: Option<Span>,
+    },
 }
 impl<T: 'static + std::error::Error> From<T> for LangErrorMsg {
     fn from(error: T) -> Self {
@@ -185,6 +342,12 @@ impl fmt::Display for LangErrorMsg {
             Self::Unmatched(char1, char2) => {
                 write!(f, "This '{}' has no matching '{}'", char1, char2)?;
             }
+            Self::InvalidIntegerLiteral(s) => {
+                write!(f, "Invalid {} integer literal", s)?;
+            }
+            Self::InvalidDigitSeparator => {
+                write!(f, "Underscores in numbers must be between two digits")?;
+            }
             Self::Expected(s) => {
                 write!(f, "Expected {}", s)?;
             }
@@ -201,7 +364,10 @@ impl fmt::Display for LangErrorMsg {
                 write!(f, "Variable assignment requires the 'set' keyword")?;
             }
             Self::TopLevelNonDirective => {
-                write!(f, "Only directives may appear at the top level of a file")?;
+                write!(
+                    f,
+                    "Only directives and constants may appear at the top level of a file"
+                )?;
             }
             Self::InvalidDirectiveName => {
                 write!(f, "Invalid directive name")?;
@@ -209,15 +375,80 @@ impl fmt::Display for LangErrorMsg {
             Self::RepeatDirective(name) => {
                 write!(f, "Multiple {:?} directives; only one is allowed", name)?;
             }
+            Self::ConflictingDirectives { name, .. } => {
+                write!(f, "This {:?} directive conflicts with an earlier one", name)?;
+            }
             Self::FunctionNameConflict => {
                 write!(f, "There is already a function with this name")?;
             }
+            Self::PreludeFunctionShadowed(name) => {
+                write!(f, "This function replaces {:?} from the prelude", name)?;
+            }
+            Self::ConstantNameConflict => {
+                write!(f, "There is already a constant with this name")?;
+            }
+            Self::CellStateAlreadyNamed(name) => {
+                write!(f, "This cell state is already named {:?}", name)?;
+            }
+            Self::UnknownCellStateName { name, suggestion } => {
+                write!(
+                    f,
+                    "There is no variable or cell state named {:?}; did you mean {:?}?",
+                    name, suggestion
+                )?;
+            }
+            Self::UnknownFunction(name) => {
+                write!(f, "There is no function named {:?}", name)?;
+            }
+            Self::UnknownExternFunction(name) => {
+                write!(f, "There is no extern function named {:?}", name)?;
+            }
+            Self::UnknownMethod { ty, name } => {
+                write!(f, "There is no method named {:?} for {}", name, ty)?;
+            }
+            Self::UnknownProperty { ty, name } => {
+                write!(f, "There is no property named {:?} for {}", name, ty)?;
+            }
             Self::InvalidDimensionCount => {
                 write!(f, "Number of dimensions must range from 1 to {}", MAX_NDIM)?;
             }
             Self::InvalidStateCount => {
                 write!(f, "Number of states must range from 1 to {}", MAX_STATES)?;
             }
+            Self::InvalidRadius => {
+                write!(f, "Neighborhood radius must range from 1 to {}", MAX_RADIUS)?;
+            }
+            Self::InvalidNbhdShape => {
+                write!(
+                    f,
+                    "Neighborhood shape must be 'moore' or 'vonneumann'"
+                )?;
+            }
+            Self::InvalidIntBits => {
+                write!(
+                    f,
+                    "Number of bits in an integer must range from {} to {}",
+                    MIN_INT_BITS, INT_BITS
+                )?;
+            }
+            Self::InvalidOverflowMode => {
+                write!(f, "Overflow mode must be 'checked' or 'wrapping'")?;
+            }
+            Self::InvalidVectorLength => {
+                write!(f, "Vector length must range from 1 to {}", MAX_VECTOR_LEN)?;
+            }
+            Self::InvalidArrayLength => {
+                write!(f, "Array length must range from 1 to {}", MAX_ARRAY_LEN)?;
+            }
+            Self::InvalidTupleLength => {
+                write!(f, "Tuples must have at least two elements")?;
+            }
+            Self::InvalidTupleElement => {
+                write!(
+                    f,
+                    "Tuples can only contain integers, booleans, and cell states"
+                )?;
+            }
 
             Self::TypeError { expected, got } => {
                 write!(f, "Type error: expected {} but got {}", expected, got)?;
@@ -234,6 +465,12 @@ impl fmt::Display for LangErrorMsg {
                 if *lhs == Type::CellState && *rhs == Type::CellState {
                     write!(f, "; convert them to integers first using the '#id' tag")?;
                 }
+                if matches!(lhs, Type::Vector(_)) && lhs == rhs && !cmp.is_eq_only() {
+                    write!(
+                        f,
+                        "; vectors have no natural order, so compare using 'sum()' or 'dot()'"
+                    )?;
+                }
             }
             Self::InvalidArguments {
                 name,
@@ -263,24 +500,129 @@ impl fmt::Display for LangErrorMsg {
             Self::CannotAssignTypeToVariable(ty) => {
                 write!(f, "Cannot assign {} to variable", ty)?;
             }
+            Self::CannotTraceType(ty) => {
+                write!(f, "Cannot trace {}", ty)?;
+            }
+            Self::InvalidExternType(ty) => {
+                write!(
+                    f,
+                    "Extern functions can only take and return integers, booleans, and cell states, not {}",
+                    ty
+                )?;
+            }
+            Self::CannotIndexType(ty) => {
+                write!(f, "Type error: cannot index {}", ty)?;
+            }
+            Self::CannotAssignToElement(ty) => {
+                write!(f, "Type error: cannot assign to an element of {}", ty)?;
+            }
+            Self::CannotUnpack { ty, count } => {
+                write!(f, "Type error: cannot unpack {} into {} variables", ty, count)?;
+            }
             Self::UseOfUninitializedVariable => {
                 write!(f, "This variable must be initialized before it is used")?;
             }
+            Self::AssignToConstant => {
+                write!(f, "Cannot assign to a constant")?;
+            }
             Self::BecomeInHelperFunction => {
                 write!(
                     f,
                     "Use 'return' instead of 'become' outside of transition functions"
                 )?;
             }
+            Self::RemainInHelperFunction => {
+                write!(f, "'remain' can only be used in the transition function")?;
+            }
             Self::ReturnInTransitionFunction => {
                 write!(
                     f,
                     "Use 'become' instead of 'return' in transition functions"
                 )?;
             }
+            Self::MissingReturn => {
+                write!(f, "This function may reach the end without returning a value")?;
+            }
+            Self::MissingBlockValue => {
+                write!(
+                    f,
+                    "This block must end with an expression, or else become or return on every path"
+                )?;
+            }
+            Self::RecursiveFunctionCall(cycle) => {
+                write!(
+                    f,
+                    "Recursive function call ({}); helper functions cannot call themselves, even indirectly",
+                    cycle.join(" -> "),
+                )?;
+            }
+            Self::NeighborInHelperFunction => {
+                write!(
+                    f,
+                    "Neighbors can only be accessed in the transition function"
+                )?;
+            }
+            Self::PositionInHelperFunction => {
+                write!(
+                    f,
+                    "The position of the cell can only be accessed in the transition function"
+                )?;
+            }
+            Self::StepInHelperFunction => {
+                write!(
+                    f,
+                    "The step number can only be accessed in the transition function; pass it to helper functions as an argument"
+                )?;
+            }
+            Self::RandomInHelperFunction => {
+                write!(
+                    f,
+                    "Random numbers can only be generated in the transition function"
+                )?;
+            }
+            Self::InvalidRandomBound => {
+                write!(f, "Upper bound for a random number must be positive")?;
+            }
+            Self::BreakOutsideLoop => {
+                write!(f, "This 'break' is not inside a loop")?;
+            }
+            Self::ContinueOutsideLoop => {
+                write!(f, "This 'continue' is not inside a loop")?;
+            }
+            Self::DuplicateMatchCase => {
+                write!(f, "This value is already matched by an earlier case")?;
+            }
+            Self::UnreachableStatement => {
+                write!(f, "This statement is unreachable")?;
+            }
+            Self::PoisonedExpression => {
+                write!(f, "This expression depends on an earlier error")?;
+            }
             Self::CannotEvalAsConst => {
                 write!(f, "Cannot evaluate this expression as a constant")?;
             }
+            Self::ShiftOutOfRange(int_bits) => {
+                write!(
+                    f,
+                    "Shift amount out of range; must be from 0 to {}",
+                    int_bits - 1
+                )?;
+            }
+            Self::InvalidClampBounds => {
+                write!(f, "Lower bound of clamp is greater than upper bound")?;
+            }
+            Self::FallibleSelectOperand => {
+                write!(
+                    f,
+                    "Operands of 'select' are always evaluated, so they cannot produce runtime errors; use 'if ... then ... else' instead"
+                )?;
+            }
+            Self::AssertionFailed(None) => {
+                write!(f, "Assertion failed")?;
+            }
+            Self::AssertionFailed(Some(msg)) => {
+                write!(f, "Assertion failed: {}", msg)?;
+            }
 
             Self::IntegerOverflow => {
                 write!(f, "Integer overflow")?;
@@ -294,6 +636,41 @@ impl fmt::Display for LangErrorMsg {
             Self::CellStateOutOfRange => {
                 write!(f, "Cell state out of range")?;
             }
+            Self::CellStateOutsideStates { count, states_span } => {
+                write!(f, "Cell state out of range; ")?;
+                match states_span {
+                    Some(_) => write!(f, "the \"states\" directive")?,
+                    None => write!(f, "without a \"states\" directive, the rule")?,
+                }
+                write!(f, " only has states #0 to #{}", count - 1)?;
+            }
+            Self::IndexOutOfBounds => {
+                write!(f, "Index out of bounds")?;
+            }
+            Self::NeighborOutOfRange { shape, radius } => match shape {
+                NbhdShape::Moore => write!(
+                    f,
+                    "Neighbor offset out of range; each component must be from -{} to {}",
+                    radius, radius
+                )?,
+                NbhdShape::VonNeumann => write!(
+                    f,
+                    "Neighbor offset out of range; the absolute values of the components must add up to at most {}",
+                    radius
+                )?,
+            },
+            Self::NeighborOffsetLength {
+                len,
+                ndim,
+                ndim_span,
+            } => {
+                write!(f, "Neighbor offset has {} components, but ", len)?;
+                match ndim_span {
+                    Some(_) => write!(f, "the \"dimensions\" directive specifies")?,
+                    None => write!(f, "without a \"dimensions\" directive, the rule has")?,
+                }
+                write!(f, " {} dimensions", ndim)?;
+            }
         }
         Ok(())
     }
@@ -313,6 +690,25 @@ impl LangErrorMsg {
             msg: self,
         }
     }
+    /// Returns a note and the span of another part of the source code that
+    /// this error involves, such as an earlier directive that conflicts with
+    /// the one that has the error, if there is one.
+    pub fn note(&self) -> Option<(&'static str, Span)> {
+        match self {
+            Self::ConflictingDirectives { first_span, .. } => {
+                Some(("first defined here", *first_span))
+            }
+            Self::CellStateOutsideStates {
+                states_span: Some(span),
+                ..
+            } => Some(("number of states declared here", *span)),
+            Self::NeighborOffsetLength {
+                ndim_span: Some(span),
+                ..
+            } => Some(("number of dimensions declared here", *span)),
+            _ => None,
+        }
+    }
 }
 
 impl<T: Into<LangErrorMsg>> From<T> for LangError {
@@ -320,3 +716,16 @@ impl<T: Into<LangErrorMsg>> From<T> for LangError {
         msg.into().without_span()
     }
 }
+
+/// Receiver for errors discovered during compilation, allowing multiple errors
+/// to be reported (possibly as they are discovered) rather than only the first
+/// one.
+pub trait DiagnosticSink {
+    /// Reports an error.
+    fn report(&mut self, error: LangError);
+}
+impl DiagnosticSink for Vec<LangError> {
+    fn report(&mut self, error: LangError) {
+        self.push(error);
+    }
+}
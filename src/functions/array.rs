@@ -0,0 +1,154 @@
+//! Array functions.
+
+use inkwell::values::IntValue;
+use inkwell::IntPredicate;
+use std::convert::TryFrom;
+
+use super::super::ast::{
+    ArgValues, Args, ErrorPointRef, ExprRef, FnSignature, Function, FunctionKind, UserFunction,
+};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+use super::super::types::{LangInt, MAX_ARRAY_LEN};
+use super::super::{ConstValue, Span, Type};
+use LangErrorMsg::{IndexOutOfBounds, InvalidArrayLength};
+
+/// Built-in function that constructs an array of the given length with every
+/// element set to zero, such as `array(16)`. The length must be a constant.
+#[derive(Debug)]
+pub struct NewArray {
+    /// Length of the array, or None if the arguments are not a single integer.
+    len: Option<usize>,
+}
+impl NewArray {
+    /// Constructs a new NewArray instance.
+    ///
+    /// This method returns an error if the length is not a constant or is out
+    /// of range. If the arguments are not a single integer, then there is no
+    /// matching signature and Expr::try_new() reports an error.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        if args.len() != 1 || userfunc[args[0]].result_type() != Type::Int {
+            return Ok(Self { len: None });
+        }
+        let len_span = userfunc[args[0]].span();
+        let len = userfunc.const_eval_expr(args[0])?.as_int()?;
+        match usize::try_from(len) {
+            Ok(len @ 1..=MAX_ARRAY_LEN) => Ok(Self { len: Some(len) }),
+            _ => Err(InvalidArrayLength.with_span(len_span)),
+        }
+    }
+}
+impl Function for NewArray {
+    fn name(&self) -> String {
+        "array".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        match self.len {
+            Some(len) => vec![FnSignature::new(vec![Type::Int], Type::Array(len))],
+            None => vec![],
+        }
+    }
+    fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        let len = self.len.ok_or(UNCAUGHT_TYPE_ERROR)?;
+        Ok(compiler.value_from_const(ConstValue::Array(vec![0; len])))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let len = self.len.ok_or(UNCAUGHT_TYPE_ERROR)?;
+        Ok(Some(ConstValue::Array(vec![0; len])))
+    }
+}
+
+/// Built-in function that returns a single element of an array.
+#[derive(Debug, Clone)]
+pub struct IndexArray {
+    /// Length of the array.
+    len: usize,
+    /// Error returned if the index is out of range.
+    out_of_bounds_error: ErrorPointRef,
+}
+impl IndexArray {
+    /// Constructs a new IndexArray instance that indexes the array given as
+    /// the first argument using the integer given as the second argument.
+    ///
+    /// This method returns an error if the index is a constant that is out of
+    /// range.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        let len = match userfunc[args[0]].result_type() {
+            Type::Array(len) => len,
+            _ => Err(UNCAUGHT_TYPE_ERROR)?,
+        };
+        check_const_index(userfunc, args[1], len)?;
+        Ok(Self {
+            len,
+            out_of_bounds_error: userfunc.add_error_point(IndexOutOfBounds.with_span(span)),
+        })
+    }
+}
+impl Function for IndexArray {
+    fn name(&self) -> String {
+        "array index".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![Type::Array(self.len), Type::Int],
+            Type::Int,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let array = args.compile(compiler, 0)?.as_array()?;
+        let index = args.compile(compiler, 1)?.as_int()?;
+        build_index_check(compiler, index, self.len, &self.out_of_bounds_error)?;
+        Ok(Value::Int(compiler.build_array_load(array, index)))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let array = args.const_eval(0)?.as_array()?;
+        let index = args.const_eval(1)?.as_int()?;
+        match usize::try_from(index).ok().and_then(|i| array.get(i)) {
+            Some(&element) => Ok(Some(ConstValue::Int(element))),
+            None => self.out_of_bounds_error.err(),
+        }
+    }
+}
+
+/// Returns an IndexOutOfBounds error if the given index expression is a
+/// constant that is out of range for a vector or array of the given length.
+pub fn check_const_index(
+    userfunc: &UserFunction,
+    index_expr: ExprRef,
+    len: usize,
+) -> LangResult<()> {
+    if let Ok(ConstValue::Int(i)) = userfunc.const_eval_expr(index_expr) {
+        if i < 0 || i >= len as LangInt {
+            Err(IndexOutOfBounds.with_span(userfunc[index_expr].span()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds instructions to return the given error if an index is out of range
+/// for a vector or array of the given length.
+pub fn build_index_check(
+    compiler: &mut Compiler,
+    index: IntValue<'static>,
+    len: usize,
+    out_of_bounds_error: &ErrorPointRef,
+) -> LangResult<()> {
+    // Compare the index as an unsigned integer, so that negative indices are
+    // also out of bounds.
+    let len = compiler.int_type().const_int(len as u64, false);
+    let is_out_of_bounds =
+        compiler
+            .builder()
+            .build_int_compare(IntPredicate::UGE, index, len, "isOutOfBounds");
+    compiler.build_conditional(
+        is_out_of_bounds,
+        |c| Ok(out_of_bounds_error.compile(c)),
+        |_| Ok(()),
+    )
+}
@@ -34,7 +34,7 @@ impl Cmp {
             .iter(userfunc)
             .map(|e| Spanned {
                 span: e.span(),
-                inner: e.return_type(),
+                inner: e.result_type(),
             })
             .collect();
         let comparisons_iter = comparisons.iter();
@@ -57,7 +57,7 @@ impl Function for Cmp {
         FunctionKind::Operator
     }
     fn signatures(&self) -> Vec<FnSignature> {
-        vec![FnSignature::new(self.types.clone(), Type::Int)]
+        vec![FnSignature::new(self.types.clone(), Type::Bool)]
     }
     fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
         let old_bb = compiler.builder().get_insert_block().unwrap();
@@ -68,8 +68,8 @@ impl Function for Cmp {
         compiler.builder().position_at_end(merge_bb);
 
         // Create a phi node for the final result.
-        let int_type = compiler.int_type();
-        let phi = compiler.builder().build_phi(int_type, "multiCompareMerge");
+        let bool_type = compiler.bool_type();
+        let phi = compiler.builder().build_phi(bool_type, "multiCompareMerge");
 
         compiler.builder().position_at_end(old_bb);
         // Compile the first argument.
@@ -81,14 +81,14 @@ impl Function for Cmp {
             // Compare the arguments.
             let compare_result = (comparator.compile)(compiler, lhs, rhs.clone())?;
             // If the condition is false, skip ahead to the merge and give the
-            // phi node a value of 0. If it is true, continue on to check the
+            // phi node a value of false. If it is true, continue on to check the
             // next condition.
             let next_bb = compiler.append_basic_block("compare");
             compiler
                 .builder()
                 .build_conditional_branch(compare_result, next_bb, merge_bb);
             phi.add_incoming(&[(
-                &int_type.const_zero(),
+                &bool_type.const_zero(),
                 compiler.builder().get_insert_block().unwrap(),
             )]);
             compiler.builder().position_at_end(next_bb);
@@ -97,11 +97,11 @@ impl Function for Cmp {
         }
 
         // After the last comparison, unconditionally jump directly to the merge
-        // block and give the phi node a value of 1 because all conditions were
-        // true.
+        // block and give the phi node a value of true because all conditions
+        // were true.
         compiler.builder().build_unconditional_branch(merge_bb);
         phi.add_incoming(&[(
-            &int_type.const_int(1, false),
+            &bool_type.const_int(1, false),
             compiler.builder().get_insert_block().unwrap(),
         )]);
 
@@ -109,9 +109,9 @@ impl Function for Cmp {
         // instructions.
         compiler.builder().position_at_end(merge_bb);
 
-        // This phi node now contains 1 if all conditions were true and 0 if all
-        // conditions were false.
-        Ok(Value::Int(phi.as_basic_value().into_int_value()))
+        // This phi node now contains true if all conditions were true and
+        // false if any condition was false.
+        Ok(Value::Bool(phi.as_basic_value().into_int_value()))
     }
     fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
         let mut lhs = args.const_eval(0)?;
@@ -121,7 +121,7 @@ impl Function for Cmp {
                 // It is possible to evaluate this comparison at compile time.
                 if !(const_eval_fn)(lhs, rhs.clone())? {
                     // Short-circuit if any comparison returns false.
-                    return Ok(Some(ConstValue::Int(0)));
+                    return Ok(Some(ConstValue::Bool(false)));
                 }
             } else {
                 // It is not possible to evaluate this comparison at compile
@@ -132,7 +132,7 @@ impl Function for Cmp {
             lhs = rhs;
         }
         // If all comparisons returned true, then return true.
-        Ok(Some(ConstValue::Int(1)))
+        Ok(Some(ConstValue::Bool(true)))
     }
 }
 
@@ -163,7 +163,9 @@ impl Comparator {
         let eq_only = cmp == ComparisonToken::Eql || cmp == ComparisonToken::Neq;
         match ty {
             Type::Int => Ok(Self::int_cmp(ty, cmp, true)),
+            Type::Bool if eq_only => Ok(Self::int_cmp(ty, cmp, false)),
             Type::CellState if eq_only => Ok(Self::int_cmp(ty, cmp, false)),
+            Type::Vector(len) if eq_only => Ok(Self::vector_cmp(len, cmp)),
             _ => Err(CmpError { lhs, cmp, rhs }.with_span(span)),
         }
     }
@@ -183,7 +185,15 @@ impl Comparator {
                 ))
             }),
             const_eval: Some(match ty {
+                // Reinterpret the bits as unsigned for unsigned comparisons,
+                // to match the LLVM predicate.
+                Type::Int if cmp.is_unsigned() => Box::new(move |lhs, rhs| {
+                    Ok(cmp.eval(lhs.as_int()? as u64, rhs.as_int()? as u64))
+                }),
                 Type::Int => Box::new(move |lhs, rhs| Ok(cmp.eval(lhs.as_int()?, rhs.as_int()?))),
+                Type::Bool => {
+                    Box::new(move |lhs, rhs| Ok(cmp.eval(lhs.as_bool()?, rhs.as_bool()?)))
+                }
                 Type::CellState => Box::new(move |lhs, rhs| {
                     Ok(cmp.eval(lhs.as_cell_state()?, rhs.as_cell_state()?))
                 }),
@@ -191,4 +201,48 @@ impl Comparator {
             }),
         }
     }
+
+    /// Constructs a new comparator that checks two vectors of the given length
+    /// for equality (`==`) or inequality (`!=`). Vectors are equal only if all
+    /// of their components are equal.
+    fn vector_cmp(len: usize, cmp: ComparisonToken) -> Self {
+        let inkwell_predicate = cmp.inkwell_predicate(true);
+        Self {
+            compile: Box::new(move |compiler, lhs, rhs| {
+                // Compare all the components at once, producing a vector of
+                // booleans.
+                let component_results = compiler.builder().build_int_compare(
+                    inkwell_predicate,
+                    lhs.as_vector()?,
+                    rhs.as_vector()?,
+                    "vecCmp",
+                );
+                // Reduce the vector of booleans to a single boolean: `==`
+                // requires every component to be equal, and `!=` requires any
+                // component to be different.
+                let mut components = compiler
+                    .build_extract_components(component_results, len)
+                    .into_iter();
+                let mut ret = components.next().ok_or(UNCAUGHT_TYPE_ERROR)?;
+                for component in components {
+                    let b = compiler.builder();
+                    ret = match cmp {
+                        ComparisonToken::Eql => b.build_and(ret, component, "vecEql"),
+                        _ => b.build_or(ret, component, "vecNeq"),
+                    };
+                }
+                Ok(ret)
+            }),
+            const_eval: Some(Box::new(move |lhs, rhs| {
+                let lhs = lhs.as_vector()?;
+                let rhs = rhs.as_vector()?;
+                // The type check in try_new() already guarantees that both
+                // vectors have the same length.
+                if lhs.len() != len || rhs.len() != len {
+                    Err(UNCAUGHT_TYPE_ERROR)?;
+                }
+                Ok(cmp.eval(lhs, rhs))
+            })),
+        }
+    }
 }
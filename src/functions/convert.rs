@@ -4,7 +4,7 @@ use inkwell::IntPredicate;
 use std::rc::Rc;
 
 use super::super::ast::{
-    ArgValues, ErrorPointRef, FnSignature, Function, FunctionKind, RuleMeta, UserFunction,
+    ArgValues, Args, ErrorPointRef, FnSignature, Function, FunctionKind, RuleMeta, UserFunction,
 };
 use super::super::compiler::{Compiler, Value};
 use super::super::errors::*;
@@ -23,7 +23,15 @@ pub struct IntToCellState {
 }
 impl IntToCellState {
     /// Constructs a new IntToCellState instance.
-    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+    ///
+    /// This method returns an error if the cell state ID is a constant that is
+    /// out of range. If it is not constant, it is checked at runtime instead.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        if let Ok(ConstValue::Int(id)) = userfunc.const_eval_expr(args[0]) {
+            if id < 0 || id >= userfunc.rule_meta().state_count() as LangInt {
+                Err(CellStateOutOfRange.with_span(span))?;
+            }
+        }
         Ok(Self {
             rule_meta: userfunc.rule_meta().clone(),
             out_of_range_error: userfunc.add_error_point(CellStateOutOfRange.with_span(span)),
@@ -49,7 +57,7 @@ impl Function for IntToCellState {
         // positive number, which will be too large.)
         let cell_state_count_value = cell_state_value
             .get_type()
-            .const_int(self.rule_meta.states.len() as u64, false);
+            .const_int(self.rule_meta.state_count() as u64, false);
         let condition = compiler.builder().build_int_compare(
             IntPredicate::ULT, // Unsigned Less-Than
             cell_state_value,
@@ -75,10 +83,125 @@ impl Function for IntToCellState {
     }
     fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
         let arg = args.const_eval(0)?.as_int()?;
-        if 0 <= arg && arg < self.rule_meta.states.len() as LangInt {
+        if 0 <= arg && arg < self.rule_meta.state_count() as LangInt {
             Ok(Some(ConstValue::CellState(arg as LangCellState)))
         } else {
             self.out_of_range_error.err()
         }
     }
 }
+
+/// Built-in function that converts an integer to a vector by copying it into
+/// every component. This struct can be constructed directly.
+///
+/// This conversion is only performed implicitly when assigning an integer to a
+/// vector variable or returning an integer from a function that returns a
+/// vector.
+#[derive(Debug, Clone)]
+pub struct IntToVector {
+    /// Length of the resulting vector.
+    pub len: usize,
+}
+impl Function for IntToVector {
+    fn name(&self) -> String {
+        format!(
+            "conversion from {} to {}",
+            Type::Int,
+            Type::Vector(self.len)
+        )
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int], Type::Vector(self.len))]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let int_value = args.compile(compiler, 0)?.as_int()?;
+        Ok(Value::Vector(
+            compiler.build_vector_splat(int_value, self.len),
+        ))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let arg = args.const_eval(0)?.as_int()?;
+        Ok(Some(ConstValue::Vector(vec![arg; self.len])))
+    }
+}
+
+/// Built-in function that converts a boolean to an integer (1 for true and 0
+/// for false). This struct can be constructed directly.
+///
+/// This conversion is only performed implicitly when a boolean is used where
+/// an integer is expected, such as in arithmetic or when assigning a boolean to
+/// an integer variable.
+#[derive(Debug, Clone)]
+pub struct BoolToInt;
+impl Function for BoolToInt {
+    fn name(&self) -> String {
+        format!("conversion from {} to {}", Type::Bool, Type::Int)
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Bool], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let bool_value = args.compile(compiler, 0)?.as_bool()?;
+        let int_type = compiler.int_type();
+        Ok(Value::Int(compiler.builder().build_int_z_extend(
+            bool_value,
+            int_type,
+            "tmp_intFromBool",
+        )))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let arg = args.const_eval(0)?.as_bool()?;
+        Ok(Some(ConstValue::Int(arg as LangInt)))
+    }
+}
+
+/// Built-in function that converts a cell state to an integer (its ID). This
+/// struct can be constructed directly.
+///
+/// This conversion is performed implicitly when a cell state is used as a
+/// number (see math::numeric_coercion()), or explicitly using `id(state)`. For
+/// any valid cell state `s`, `#(id(s))` is `s`.
+#[derive(Debug, Clone)]
+pub struct CellStateToInt {
+    /// Whether this is an explicit call to `id()` rather than an implicit
+    /// conversion.
+    pub explicit: bool,
+}
+impl Function for CellStateToInt {
+    fn name(&self) -> String {
+        if self.explicit {
+            "id".to_owned()
+        } else {
+            format!("conversion from {} to {}", Type::CellState, Type::Int)
+        }
+    }
+    fn kind(&self) -> FunctionKind {
+        if self.explicit {
+            FunctionKind::Function
+        } else {
+            FunctionKind::Operator
+        }
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::CellState], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let cell_state_value = args.compile(compiler, 0)?.as_cell_state()?;
+        let int_type = compiler.int_type();
+        Ok(Value::Int(compiler.builder().build_int_z_extend(
+            cell_state_value,
+            int_type,
+            "tmp_intFromCellState",
+        )))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let arg = args.const_eval(0)?.as_cell_state()?;
+        Ok(Some(ConstValue::Int(arg as LangInt)))
+    }
+}
@@ -0,0 +1,42 @@
+//! Calls to functions provided by the host.
+
+use super::super::ast::{ArgValues, ExternFunction, FnSignature, Function, FunctionKind};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+
+/// Function that calls a function provided by the host, such as
+/// `extern::noise(x, y)` (see ExternFunctions::register()).
+#[derive(Debug)]
+pub struct CallExtern {
+    /// Name of the extern function.
+    name: String,
+    /// Signature and address of the extern function.
+    extern_function: ExternFunction,
+}
+impl CallExtern {
+    /// Constructs a new CallExtern instance that calls the given extern
+    /// function, which has the given name.
+    pub fn new(name: String, extern_function: ExternFunction) -> Self {
+        Self {
+            name,
+            extern_function,
+        }
+    }
+}
+impl Function for CallExtern {
+    fn name(&self) -> String {
+        format!("extern::{}", self.name)
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![self.extern_function.signature.clone()]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let arg_values = (0..args.len())
+            .map(|i| args.compile(compiler, i))
+            .collect::<LangResult<Vec<_>>>()?;
+        compiler.build_extern_call(&self.name, &self.extern_function, arg_values)
+    }
+}
@@ -0,0 +1,42 @@
+//! Calls to helper functions.
+
+use super::super::ast::{ArgValues, FnSignature, Function, FunctionKind};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+
+/// Function that calls a helper function defined by the user, such as `f(x)`
+/// given `@function int f(int x) { ... }`.
+///
+/// The helper function is compiled into its own LLVM function, and any runtime
+/// error it returns is returned from the caller as well.
+#[derive(Debug)]
+pub struct CallHelper {
+    /// Name of the helper function.
+    name: String,
+    /// Signature of the helper function.
+    signature: FnSignature,
+}
+impl CallHelper {
+    /// Constructs a new CallHelper instance that calls the helper function
+    /// with the given name and signature.
+    pub fn new(name: String, signature: FnSignature) -> Self {
+        Self { name, signature }
+    }
+}
+impl Function for CallHelper {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![self.signature.clone()]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let arg_values = (0..args.len())
+            .map(|i| args.compile(compiler, i))
+            .collect::<LangResult<Vec<_>>>()?;
+        compiler.build_helper_call(&self.name, self.signature.ret, arg_values)
+    }
+}
@@ -29,3 +29,109 @@ impl Function for Int {
         Ok(Some(ConstValue::Int(self.0)))
     }
 }
+
+/// Built-in function that constructs a vector from integer components. This
+/// struct can be constructed directly.
+#[derive(Debug, Clone)]
+pub struct VectorLiteral {
+    /// Number of components in the vector.
+    pub len: usize,
+}
+impl Function for VectorLiteral {
+    fn name(&self) -> String {
+        "vector literal".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Atom
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![Type::Int; self.len],
+            Type::Vector(self.len),
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let int_type = compiler.int_type();
+        let mut ret = int_type.vec_type(self.len as u32).get_undef();
+        for i in 0..self.len {
+            let component = args.compile(compiler, i)?.as_int()?;
+            let idx = int_type.const_int(i as u64, false);
+            ret = compiler
+                .builder()
+                .build_insert_element(ret, component, idx, "tmp_vec");
+        }
+        Ok(Value::Vector(ret))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let components = (0..self.len)
+            .map(|i| args.const_eval(i)?.as_int())
+            .collect::<LangResult<Vec<LangInt>>>()?;
+        Ok(Some(ConstValue::Vector(components)))
+    }
+}
+
+/// Built-in function that constructs a tuple from its elements. This struct
+/// can be constructed directly.
+#[derive(Debug, Clone)]
+pub struct TupleLiteral {
+    /// Types of the elements of the tuple.
+    pub elements: &'static [Type],
+}
+impl Function for TupleLiteral {
+    fn name(&self) -> String {
+        "tuple literal".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Atom
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            self.elements.to_vec(),
+            Type::Tuple(self.elements),
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let mut ret = compiler
+            .get_llvm_type(Type::Tuple(self.elements))?
+            .into_struct_type()
+            .get_undef();
+        for i in 0..self.elements.len() {
+            let element = args.compile(compiler, i)?.into_basic_value()?;
+            ret = compiler
+                .builder()
+                .build_insert_value(ret, element, i as u32, "tmp_tuple")
+                .unwrap()
+                .into_struct_value();
+        }
+        Ok(Value::Tuple(self.elements, ret))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let elements = (0..self.elements.len())
+            .map(|i| args.const_eval(i))
+            .collect::<LangResult<Vec<ConstValue>>>()?;
+        Ok(Some(ConstValue::Tuple(elements)))
+    }
+}
+
+/// Built-in function that returns a fixed value of any type, such as the
+/// result of folding a constant expression. This struct can be constructed
+/// directly.
+#[derive(Debug, Clone)]
+pub struct Constant(pub ConstValue);
+impl Function for Constant {
+    fn name(&self) -> String {
+        format!("constant {}", self.0)
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Atom
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::atom(self.0.ty())]
+    }
+    fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        Ok(compiler.value_from_const(self.0.clone()))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        Ok(Some(self.0.clone()))
+    }
+}
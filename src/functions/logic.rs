@@ -0,0 +1,181 @@
+//! Logical (boolean) operators.
+
+use inkwell::values::IntValue;
+use inkwell::IntPredicate;
+
+use super::super::ast::{ArgValues, Args, FnSignature, Function, FunctionKind, UserFunction};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+use super::super::lexer::KeywordToken;
+use super::super::{ConstValue, Type};
+use LangErrorMsg::{InternalError, TypeError};
+
+/// Returns the types of the given arguments, or a TypeError if any of them
+/// cannot be used as a condition.
+fn condition_arg_types(userfunc: &UserFunction, args: &Args) -> LangResult<Vec<Type>> {
+    args.iter(userfunc)
+        .map(|e| match e.result_type() {
+            ty @ Type::Bool | ty @ Type::Int => Ok(ty),
+            got => Err(TypeError {
+                expected: Type::Bool,
+                got,
+            }
+            .with_span(e.span())),
+        })
+        .collect()
+}
+
+/// Builds instructions to convert a condition value (an integer of any width)
+/// to a boolean, where any nonzero value is true.
+fn build_truthiness(compiler: &mut Compiler, value: IntValue<'static>) -> IntValue<'static> {
+    if value.get_type().get_bit_width() == 1 {
+        // This is already a boolean.
+        value
+    } else {
+        let zero = value.get_type().const_zero();
+        compiler
+            .builder()
+            .build_int_compare(IntPredicate::NE, value, zero, "truthiness")
+    }
+}
+
+/// Returns whether a constant condition value is truthy (true or nonzero).
+pub fn const_truthiness(value: ConstValue) -> LangResult<bool> {
+    match value {
+        ConstValue::Bool(b) => Ok(b),
+        ConstValue::Int(i) => Ok(i != 0),
+        _ => Err(UNCAUGHT_TYPE_ERROR),
+    }
+}
+
+/// Built-in function that performs a logical binary operation (`and`, `or`,
+/// or `xor`) on two conditions. `and` and `or` short-circuit, so the
+/// right-hand side is only evaluated if the left-hand side does not already
+/// determine the result.
+#[derive(Debug)]
+pub struct LogicalBinaryOp {
+    /// Operator to apply.
+    op: KeywordToken,
+    /// Types of the operands (each either a boolean or an integer).
+    types: Vec<Type>,
+}
+impl LogicalBinaryOp {
+    /// Constructs a new LogicalBinaryOp instance.
+    ///
+    /// This method checks the types of the arguments and returns an error if
+    /// either of them cannot be used as a condition.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args, op: KeywordToken) -> LangResult<Self> {
+        match op {
+            KeywordToken::And | KeywordToken::Or | KeywordToken::Xor => Ok(Self {
+                op,
+                types: condition_arg_types(userfunc, args)?,
+            }),
+            _ => Err(InternalError("Invalid logical operator".into()).without_span()),
+        }
+    }
+}
+impl Function for LogicalBinaryOp {
+    fn name(&self) -> String {
+        format!("binary {:?} operator", self.op.to_string())
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(self.types.clone(), Type::Bool)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?.as_condition()?;
+        let lhs = build_truthiness(compiler, lhs);
+
+        if self.op == KeywordToken::Xor {
+            // There is no way to short-circuit `xor`.
+            let rhs = args.compile(compiler, 1)?.as_condition()?;
+            let rhs = build_truthiness(compiler, rhs);
+            return Ok(Value::Bool(compiler.builder().build_xor(lhs, rhs, "xor")));
+        }
+
+        // Build the destination blocks.
+        let lhs_bb = compiler.builder().get_insert_block().unwrap();
+        let rhs_bb = compiler.append_basic_block("logicRhs");
+        let merge_bb = compiler.append_basic_block("logicMerge");
+
+        // If the left-hand side determines the result (false for `and`, true
+        // for `or`), skip the right-hand side entirely.
+        if self.op == KeywordToken::And {
+            compiler
+                .builder()
+                .build_conditional_branch(lhs, rhs_bb, merge_bb);
+        } else {
+            compiler
+                .builder()
+                .build_conditional_branch(lhs, merge_bb, rhs_bb);
+        }
+
+        // Otherwise the right-hand side determines the result.
+        compiler.builder().position_at_end(rhs_bb);
+        let rhs = args.compile(compiler, 1)?.as_condition()?;
+        let rhs = build_truthiness(compiler, rhs);
+        // Compiling the right-hand side may have moved the instruction pointer
+        // to a different block.
+        let rhs_end_bb = compiler.builder().get_insert_block().unwrap();
+        compiler.builder().build_unconditional_branch(merge_bb);
+
+        // Merge the two possibilities using a phi node.
+        compiler.builder().position_at_end(merge_bb);
+        let bool_type = compiler.bool_type();
+        let phi = compiler.builder().build_phi(bool_type, "logicMerge");
+        phi.add_incoming(&[(&lhs, lhs_bb), (&rhs, rhs_end_bb)]);
+        Ok(Value::Bool(phi.as_basic_value().into_int_value()))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = const_truthiness(args.const_eval(0)?)?;
+        let ret = match self.op {
+            // Short-circuit if the left-hand side determines the result.
+            KeywordToken::And if !lhs => false,
+            KeywordToken::Or if lhs => true,
+            KeywordToken::Xor => lhs ^ const_truthiness(args.const_eval(1)?)?,
+            _ => const_truthiness(args.const_eval(1)?)?,
+        };
+        Ok(Some(ConstValue::Bool(ret)))
+    }
+}
+
+/// Built-in function that performs logical negation (`not`) on a condition.
+#[derive(Debug)]
+pub struct LogicalNot {
+    /// Type of the operand (either a boolean or an integer).
+    ty: Type,
+}
+impl LogicalNot {
+    /// Constructs a new LogicalNot instance.
+    ///
+    /// This method checks the type of the argument and returns an error if it
+    /// cannot be used as a condition.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        let types = condition_arg_types(userfunc, args)?;
+        Ok(Self {
+            ty: types.get(0).copied().unwrap_or_default(),
+        })
+    }
+}
+impl Function for LogicalNot {
+    fn name(&self) -> String {
+        format!("unary {:?} operator", KeywordToken::Not.to_string())
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![self.ty], Type::Bool)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let operand = args.compile(compiler, 0)?.as_condition()?;
+        let operand = build_truthiness(compiler, operand);
+        Ok(Value::Bool(compiler.builder().build_not(operand, "not")))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let operand = const_truthiness(args.const_eval(0)?)?;
+        Ok(Some(ConstValue::Bool(!operand)))
+    }
+}
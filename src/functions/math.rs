@@ -1,16 +1,23 @@
 //! Math functions.
 
+use inkwell::values::IntValue;
+use inkwell::IntPredicate;
 use std::convert::TryInto;
 
 use super::super::ast::{
-    ArgValues, ErrorPointRef, FnSignature, Function, FunctionKind, UserFunction,
+    ArgValues, Args, ErrorPointRef, ExprRef, FnSignature, Function, FunctionKind, OverflowMode,
+    UserFunction,
 };
 use super::super::compiler::{Compiler, Value};
 use super::super::errors::*;
 use super::super::lexer::OperatorToken;
-use super::super::types::LangInt;
+use super::super::types::{saturate_int_to_bits, wrap_int_to_bits, LangInt};
 use super::super::{ConstValue, Span, Type};
-use LangErrorMsg::{DivideByZero, IntegerOverflow, InternalError, NegativeExponent};
+use super::convert;
+use LangErrorMsg::{
+    DivideByZero, ExpectedGot, IntegerOverflow, InternalError, InvalidClampBounds,
+    NegativeExponent, ShiftOutOfRange, TypeError,
+};
 
 /// Built-in function that negates an integer.
 #[derive(Debug)]
@@ -54,23 +61,336 @@ impl Function for NegInt {
     }
 }
 
-/// Built-in function that performs a fixed two-input integer math operation.
+/// Built-in function that performs bitwise NOT on an integer, or on each
+/// component of a vector.
+#[derive(Debug)]
+pub struct BitNot {
+    /// Type of the operand and the result.
+    ty: Type,
+}
+impl BitNot {
+    /// Constructs a new BitNot instance.
+    ///
+    /// This method checks the type of the argument and returns an error if it
+    /// is not an integer or a vector.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        let operand = &userfunc[args[0]];
+        match operand.result_type() {
+            ty @ Type::Int | ty @ Type::Vector(_) => Ok(Self { ty }),
+            got => Err(TypeError {
+                expected: Type::Int,
+                got,
+            }
+            .with_span(operand.span())),
+        }
+    }
+}
+impl Function for BitNot {
+    fn name(&self) -> String {
+        format!("unary {:?} operator", OperatorToken::Tilde.to_string())
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![self.ty], self.ty)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        match args.compile(compiler, 0)? {
+            Value::Int(i) => Ok(Value::Int(compiler.builder().build_not(i, "tmp_not"))),
+            Value::Vector(v) => Ok(Value::Vector(compiler.builder().build_not(v, "tmp_not"))),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        match args.const_eval(0)? {
+            ConstValue::Int(i) => Ok(Some(ConstValue::Int(!i))),
+            ConstValue::Vector(v) => Ok(Some(ConstValue::Vector(
+                v.into_iter().map(|i| !i).collect(),
+            ))),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+}
+
+/// Built-in function that returns the absolute value of an integer. This can
+/// be called either as a function (`abs(x)`) or as a method (`x.abs()`).
+#[derive(Debug)]
+pub struct Abs {
+    /// Whether this is called as a method.
+    is_method: bool,
+    /// Number of bits in an integer.
+    int_bits: u32,
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+}
+impl Abs {
+    /// Returns a new Abs instance.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, is_method: bool) -> LangResult<Self> {
+        Ok(Self {
+            is_method,
+            int_bits: userfunc.rule_meta().int_bits,
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+        })
+    }
+}
+impl Function for Abs {
+    fn name(&self) -> String {
+        if self.is_method {
+            format!("method {:?} of {}", "abs", Type::Int)
+        } else {
+            "abs".to_owned()
+        }
+    }
+    fn kind(&self) -> FunctionKind {
+        if self.is_method {
+            FunctionKind::Method
+        } else {
+            FunctionKind::Function
+        }
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let arg = args.compile(compiler, 0)?.as_int()?;
+        Ok(Value::Int(build_checked_abs(
+            compiler,
+            arg,
+            &self.overflow_error,
+        )?))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let arg = args.const_eval(0)?;
+        Ok(Some(arg.abs(self.int_bits, &self.overflow_error)?))
+    }
+}
+
+/// Builds instructions to compute the absolute value of an integer, returning
+/// the given error if the integer is the minimum integer value (whose absolute
+/// value cannot be represented).
+fn build_checked_abs(
+    compiler: &mut Compiler,
+    value: IntValue<'static>,
+    overflow_error: &ErrorPointRef,
+) -> LangResult<IntValue<'static>> {
+    let min_value = compiler.get_min_int_value();
+    let is_overflow =
+        compiler
+            .builder()
+            .build_int_compare(IntPredicate::EQ, value, min_value, "isAbsOverflow");
+    compiler.build_conditional(
+        is_overflow,
+        // Overflow would occur.
+        |c| Ok(overflow_error.compile(c)),
+        // Overflow would not occur; it is safe to negate.
+        |_| Ok(()),
+    )?;
+    // Use the negated value if the original is negative.
+    let zero = compiler.int_type().const_zero();
+    let b = compiler.builder();
+    let negated = b.build_int_neg(value, "tmp_neg");
+    let is_negative = b.build_int_compare(IntPredicate::SLT, value, zero, "isNegative");
+    Ok(b.build_select(is_negative, negated, value, "tmp_abs")
+        .into_int_value())
+}
+
+/// Returns the conversion that is applied implicitly to a value of the given
+/// type when it is used as a number, or None if no conversion is needed or
+/// possible.
+///
+/// These are the rules for implicit conversion of operands:
+///
+/// - A boolean widens to an integer (1 for true and 0 for false).
+/// - A cell state widens to an integer (its ID).
+/// - Integers and vectors are left as-is.
+/// - Nothing is ever implicitly converted to a cell state, since not every
+///   integer is a valid cell state ID; that requires the `#` operator.
+///
+/// In arithmetic (see coerce_arithmetic_operands()), every operand is
+/// converted. In comparisons (see coerce_cmp_operands()), only cell states
+/// are converted, and only when compared to an integer.
+pub fn numeric_coercion(ty: Type) -> Option<Box<dyn Function>> {
+    match ty {
+        Type::Bool => Some(Box::new(convert::BoolToInt)),
+        Type::CellState => Some(Box::new(convert::CellStateToInt { explicit: false })),
+        _ => None,
+    }
+}
+
+/// Converts both operands of a binary arithmetic operator to numbers (see
+/// numeric_coercion()).
+///
+/// This function returns an Err(ExpectedGot) spanning both operands if either
+/// of them is not an integer or vector after conversion.
+pub fn coerce_arithmetic_operands(
+    userfunc: &mut UserFunction,
+    operands: Vec<ExprRef>,
+) -> LangResult<Args> {
+    let mut ret = vec![];
+    for expr in operands {
+        ret.push(match numeric_coercion(userfunc[expr].result_type()) {
+            Some(function) => userfunc.convert_expr(expr, function)?,
+            None => expr,
+        });
+    }
+    let args = Args::from(ret);
+    let is_number =
+        |expr: ExprRef| matches!(userfunc[expr].result_type(), Type::Int | Type::Vector(_));
+    if !args.iter_refs().all(|&expr| is_number(expr)) {
+        let (lhs, rhs) = (&userfunc[args[0]], &userfunc[args[1]]);
+        Err(ExpectedGot {
+            expected: "integers or vectors".into(),
+            got: format!("{} and {}", lhs.result_type(), rhs.result_type()).into(),
+        }
+        .with_span(Span::merge(lhs.span(), rhs.span())))?;
+    }
+    Ok(args)
+}
+
+/// Converts the operands of a comparison so that cell states can be compared
+/// to integers (see numeric_coercion()).
+///
+/// If any operand is an integer, then every cell state operand is compared as
+/// an integer. Otherwise all operands are left as-is, so comparing a cell
+/// state to another cell state does not convert either of them.
+pub fn coerce_cmp_operands(
+    userfunc: &mut UserFunction,
+    operands: Vec<ExprRef>,
+) -> LangResult<Args> {
+    let any_int = operands
+        .iter()
+        .any(|&expr| userfunc[expr].result_type() == Type::Int);
+    let mut ret = vec![];
+    for expr in operands {
+        let ty = userfunc[expr].result_type();
+        ret.push(match numeric_coercion(ty) {
+            Some(function) if any_int && ty == Type::CellState => {
+                userfunc.convert_expr(expr, function)?
+            }
+            _ => expr,
+        });
+    }
+    Ok(Args::from(ret))
+}
+
+/// Returns the type shared by all of the given arguments, or a TypeError if
+/// they are not all integers or all vectors of the same length.
+///
+/// If there are no arguments, this returns Type::Int and lets Expr::try_new()
+/// report the missing arguments.
+fn check_int_or_vector_args(userfunc: &UserFunction, args: &Args) -> LangResult<Type> {
+    let mut exprs = args.iter(userfunc);
+    let ty = match exprs.next() {
+        Some(first) => match first.result_type() {
+            ty @ Type::Int | ty @ Type::Vector(_) => ty,
+            got => Err(TypeError {
+                expected: Type::Int,
+                got,
+            }
+            .with_span(first.span()))?,
+        },
+        None => Type::Int,
+    };
+    for expr in exprs {
+        let got = expr.result_type();
+        if got != ty {
+            Err(TypeError { expected: ty, got }.with_span(expr.span()))?;
+        }
+    }
+    Ok(ty)
+}
+
+/// Built-in function that performs a fixed two-input integer math operation,
+/// either on two integers or component-wise on vectors. If one argument is an
+/// integer and the other is a vector, the integer is applied to every
+/// component of the vector.
 #[derive(Debug)]
 pub struct BinaryIntOp {
     /// Token signifying what operation to perform.
     op: OperatorToken,
+    /// Types of the arguments (each either an integer or a vector).
+    arg_types: Vec<Type>,
+    /// Type of the result (a vector if either argument is a vector).
+    ret_type: Type,
+    /// Number of bits in an integer.
+    int_bits: u32,
+    /// Whether addition, subtraction, and multiplication wrap on overflow
+    /// instead of returning an error.
+    wrapping: bool,
     /// Error returned if overflow occurs.
     overflow_error: Option<ErrorPointRef>,
     /// Error returned if the divisor of an operation is negative.
     div_by_zero_error: Option<ErrorPointRef>,
     /// Error returned if the exponent is negative.
     negative_exponent_error: Option<ErrorPointRef>,
+    /// Error returned if the shift amount of a bitshift is out of range.
+    shift_error: Option<ErrorPointRef>,
 }
 impl BinaryIntOp {
-    /// Constructs a new BinaryIntOp instance that performs the given operation.
-    pub fn try_new(userfunc: &mut UserFunction, span: Span, op: OperatorToken) -> LangResult<Self> {
+    /// Constructs a new BinaryIntOp instance that performs the given operation
+    /// on the given arguments.
+    ///
+    /// This method returns an error if the arguments are vectors of different
+    /// lengths, or if the operation is a bitshift and the shift amount is a
+    /// constant that is out of range. If the shift amount is not constant, it
+    /// is checked at runtime instead.
+    pub fn try_new(
+        userfunc: &mut UserFunction,
+        span: Span,
+        args: &Args,
+        op: OperatorToken,
+    ) -> LangResult<Self> {
         use OperatorToken::*;
-        let overflow_error = if matches!(op, Plus | Minus | Asterisk | Slash | Percent) {
+        // If either argument is a vector, then the result is a vector of the
+        // same length.
+        let mut vector_len = None;
+        for expr in args.iter(userfunc) {
+            if let Type::Vector(len) = expr.result_type() {
+                match vector_len {
+                    None => vector_len = Some(len),
+                    Some(expected_len) if expected_len == len => (),
+                    Some(expected_len) => Err(TypeError {
+                        expected: Type::Vector(expected_len),
+                        got: Type::Vector(len),
+                    }
+                    .with_span(expr.span()))?,
+                }
+            }
+        }
+        // Anything else that isn't an integer will be reported by
+        // Expr::try_new().
+        let arg_types = args
+            .iter(userfunc)
+            .map(|expr| match expr.result_type() {
+                ty @ Type::Vector(_) => ty,
+                _ => Type::Int,
+            })
+            .collect();
+        let ret_type = vector_len.map(Type::Vector).unwrap_or(Type::Int);
+        let int_bits = userfunc.rule_meta().int_bits;
+        let wrapping = userfunc.rule_meta().overflow_mode == OverflowMode::Wrapping
+            && matches!(op, Plus | Minus | Asterisk);
+
+        if matches!(op, DoubleLessThan | DoubleGreaterThan | TripleGreaterThan) {
+            // If the shift amount is known at compile time, make sure that it
+            // is less than the number of bits in an integer.
+            let shift_amounts = match userfunc.const_eval_expr(args[1]) {
+                Ok(ConstValue::Int(shift_amount)) => vec![shift_amount],
+                Ok(ConstValue::Vector(shift_amounts)) => shift_amounts,
+                _ => vec![],
+            };
+            for shift_amount in shift_amounts {
+                if shift_amount < 0 || shift_amount >= int_bits as LangInt {
+                    Err(ShiftOutOfRange(int_bits).with_span(userfunc[args[1]].span()))?;
+                }
+            }
+        }
+        let overflow_error = if !wrapping
+            && matches!(
+                op,
+                Plus | Minus | Asterisk | Slash | Percent | DoubleAsterisk
+            ) {
             Some(userfunc.add_error_point(IntegerOverflow.with_span(span)))
         } else {
             None
@@ -85,11 +405,22 @@ impl BinaryIntOp {
         } else {
             None
         };
+        let shift_error = if matches!(op, DoubleLessThan | DoubleGreaterThan | TripleGreaterThan) {
+            let shift_amount_span = userfunc[args[1]].span();
+            Some(userfunc.add_error_point(ShiftOutOfRange(int_bits).with_span(shift_amount_span)))
+        } else {
+            None
+        };
         Ok(Self {
             op,
+            arg_types,
+            ret_type,
+            int_bits,
+            wrapping,
             overflow_error,
             div_by_zero_error,
             negative_exponent_error,
+            shift_error,
         })
     }
     /// Returns the OverflowError error point; panics if this function cannot
@@ -107,24 +438,27 @@ impl BinaryIntOp {
     fn negative_exponent_error(&self) -> &ErrorPointRef {
         self.negative_exponent_error.as_ref().unwrap()
     }
-}
-impl Function for BinaryIntOp {
-    fn name(&self) -> String {
-        format!("binary {:?} operator", self.op.to_string())
-    }
-    fn kind(&self) -> FunctionKind {
-        FunctionKind::Operator
-    }
-    fn signatures(&self) -> Vec<FnSignature> {
-        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    /// Returns the ShiftOutOfRange error point; panics if this function cannot
+    /// return an Err(ShiftOutOfRange).
+    fn shift_error(&self) -> &ErrorPointRef {
+        self.shift_error.as_ref().unwrap()
     }
-    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
-        let lhs = args.compile(compiler, 0)?.as_int()?;
-        let rhs = args.compile(compiler, 1)?.as_int()?;
+
+    /// Builds instructions to perform the operation on two integers.
+    fn build_int_op(
+        &self,
+        compiler: &mut Compiler,
+        lhs: IntValue<'static>,
+        rhs: IntValue<'static>,
+    ) -> LangResult<IntValue<'static>> {
         let b = compiler.builder();
         use OperatorToken::*;
         // Perform the operation.
-        Ok(Value::Int(match self.op {
+        Ok(match self.op {
+            // Wrapping addition, subtraction, and multiplication
+            Plus if self.wrapping => b.build_int_add(lhs, rhs, "tmp_add"),
+            Minus if self.wrapping => b.build_int_sub(lhs, rhs, "tmp_sub"),
+            Asterisk if self.wrapping => b.build_int_mul(lhs, rhs, "tmp_mul"),
             // Addition, subtraction, and multiplication
             Plus | Minus | Asterisk => {
                 let intrinsic = match self.op {
@@ -149,19 +483,49 @@ impl Function for BinaryIntOp {
                 match self.op {
                     // Division
                     Slash => compiler.builder().build_int_signed_div(lhs, rhs, "tmp_div"),
-                    // Remainder
+                    // Remainder (truncated, so the result has the same sign
+                    // as the dividend; see Mod for floored modulo)
                     Percent => compiler.builder().build_int_signed_rem(lhs, rhs, "tmp_rem"),
                     _ => unreachable!(),
                 }
             }
             // Exponentiation
-            DoubleAsterisk => todo!("Exponentiation"),
-            // Bitshift left
-            DoubleLessThan => b.build_left_shift(lhs, rhs, "tmp_shl"),
-            // Bitshift right (arithmetic)
-            DoubleGreaterThan => b.build_right_shift(lhs, rhs, true, "tmp_ashr"),
-            // Bitshift right (logical)
-            TripleGreaterThan => b.build_right_shift(lhs, rhs, false, "tmp_lshr"),
+            DoubleAsterisk => build_checked_pow(
+                compiler,
+                lhs,
+                rhs,
+                self.overflow_error(),
+                self.negative_exponent_error(),
+            )?,
+            // Bitshifts
+            DoubleLessThan | DoubleGreaterThan | TripleGreaterThan => {
+                // Shifting by an amount that is negative or at least the
+                // number of bits in an integer is undefined behavior in LLVM,
+                // so check for that first. (An unsigned comparison catches
+                // negative shift amounts too.)
+                let int_bits = compiler.int_type().const_int(self.int_bits as u64, false);
+                let is_out_of_range = compiler.builder().build_int_compare(
+                    IntPredicate::UGE,
+                    rhs,
+                    int_bits,
+                    "isShiftOutOfRange",
+                );
+                compiler.build_conditional(
+                    is_out_of_range,
+                    |c| Ok(self.shift_error().compile(c)),
+                    |_| Ok(()),
+                )?;
+                let b = compiler.builder();
+                match self.op {
+                    // Bitshift left
+                    DoubleLessThan => b.build_left_shift(lhs, rhs, "tmp_shl"),
+                    // Bitshift right (arithmetic, i.e. sign-extending)
+                    DoubleGreaterThan => b.build_right_shift(lhs, rhs, true, "tmp_ashr"),
+                    // Bitshift right (logical, i.e. zero-filling)
+                    TripleGreaterThan => b.build_right_shift(lhs, rhs, false, "tmp_lshr"),
+                    _ => unreachable!(),
+                }
+            }
             // Bitwise AND
             Ampersand => b.build_and(lhs, rhs, "tmp_and"),
             // Bitwise OR
@@ -170,14 +534,19 @@ impl Function for BinaryIntOp {
             Caret => b.build_xor(lhs, rhs, "tmp_xor"),
             // Anything else
             _ => Err(InternalError("Uncaught invalid operator".into()).without_span())?,
-        }))
+        })
     }
-    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
-        let lhs = args.const_eval(0)?.as_int()?;
-        let rhs = args.const_eval(1)?.as_int()?;
+    /// Performs the operation on two constant integers.
+    fn eval_int_op(&self, lhs: LangInt, rhs: LangInt) -> LangResult<LangInt> {
         use OperatorToken::*;
         // Perform the operation.
         match self.op {
+            // Wrapping addition, subtraction, and multiplication
+            Plus if self.wrapping => Some(wrap_int_to_bits(lhs.wrapping_add(rhs), self.int_bits)),
+            Minus if self.wrapping => Some(wrap_int_to_bits(lhs.wrapping_sub(rhs), self.int_bits)),
+            Asterisk if self.wrapping => {
+                Some(wrap_int_to_bits(lhs.wrapping_mul(rhs), self.int_bits))
+            }
             // Addition
             Plus => lhs.checked_add(rhs),
             // Subtraction
@@ -201,15 +570,39 @@ impl Function for BinaryIntOp {
                 if rhs < 0 {
                     self.negative_exponent_error().err()?
                 } else {
-                    rhs.try_into().ok().map(|exp| lhs.pow(exp))
+                    match rhs.try_into() {
+                        Ok(exp) => lhs.checked_pow(exp),
+                        // The exponent is huge, so the result only fits in an
+                        // integer if the base is 0, 1, or -1.
+                        Err(_) => match lhs {
+                            0 | 1 => Some(lhs),
+                            -1 => Some(if rhs % 2 == 0 { 1 } else { -1 }),
+                            _ => None,
+                        },
+                    }
+                }
+            }
+            // Bitshifts
+            DoubleLessThan | DoubleGreaterThan | TripleGreaterThan => {
+                if rhs < 0 || rhs >= self.int_bits as LangInt {
+                    self.shift_error().err()?
+                } else {
+                    // Bits shifted past the width of an integer are discarded.
+                    let int_mask = u64::MAX >> (64 - self.int_bits);
+                    match self.op {
+                        // Bitshift left
+                        DoubleLessThan => Some(wrap_int_to_bits(lhs << rhs, self.int_bits)),
+                        // Bitshift right (arithmetic, i.e. sign-extending)
+                        DoubleGreaterThan => Some(lhs >> rhs),
+                        // Bitshift right (logical, i.e. zero-filling)
+                        TripleGreaterThan => Some(wrap_int_to_bits(
+                            ((lhs as u64 & int_mask) >> rhs) as LangInt,
+                            self.int_bits,
+                        )),
+                        _ => unreachable!(),
+                    }
                 }
             }
-            // Bitshift left
-            DoubleLessThan => Some(lhs << rhs),
-            // Bitshift right (arithmetic)
-            DoubleGreaterThan => Some(lhs >> rhs),
-            // Bitshift right (logical)
-            TripleGreaterThan => Some((lhs as u64 >> rhs) as LangInt),
             // Bitwise AND
             Ampersand => Some(lhs & rhs),
             // Bitwise OR
@@ -221,7 +614,1272 @@ impl Function for BinaryIntOp {
         }
         // IntegerOverflow error by default
         .ok_or_else(|| self.overflow_error().error())
-        .map(ConstValue::Int)
-        .map(Some)
+    }
+}
+impl Function for BinaryIntOp {
+    fn name(&self) -> String {
+        format!("binary {:?} operator", self.op.to_string())
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(self.arg_types.clone(), self.ret_type)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?;
+        let rhs = args.compile(compiler, 1)?;
+        match self.ret_type {
+            Type::Int => Ok(Value::Int(self.build_int_op(
+                compiler,
+                lhs.as_int()?,
+                rhs.as_int()?,
+            )?)),
+            Type::Vector(len) => {
+                // Splat any integer argument to a vector.
+                let mut to_vector = |value: Value| match value {
+                    Value::Int(i) => Ok(compiler.build_vector_splat(i, len)),
+                    Value::Vector(v) => Ok(v),
+                    _ => Err(UNCAUGHT_TYPE_ERROR),
+                };
+                let lhs = to_vector(lhs)?;
+                let rhs = to_vector(rhs)?;
+                let lhs_components = compiler.build_extract_components(lhs, len);
+                let rhs_components = compiler.build_extract_components(rhs, len);
+                // Perform the operation on each pair of components in order,
+                // so that any error comes from the first component where it
+                // occurs.
+                let int_type = compiler.int_type();
+                let mut ret = int_type.vec_type(len as u32).get_undef();
+                for (i, (l, r)) in lhs_components.into_iter().zip(rhs_components).enumerate() {
+                    let component = self.build_int_op(compiler, l, r)?;
+                    let idx = int_type.const_int(i as u64, false);
+                    ret = compiler.builder().build_insert_element(
+                        ret,
+                        component,
+                        idx,
+                        "tmp_component",
+                    );
+                }
+                Ok(Value::Vector(ret))
+            }
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?;
+        let rhs = args.const_eval(1)?;
+        match self.op {
+            OperatorToken::Plus if !self.wrapping => {
+                return Ok(Some(lhs.checked_add(
+                    rhs,
+                    self.int_bits,
+                    self.overflow_error(),
+                )?))
+            }
+            OperatorToken::Asterisk if !self.wrapping => {
+                return Ok(Some(lhs.checked_mul(
+                    rhs,
+                    self.int_bits,
+                    self.overflow_error(),
+                )?))
+            }
+            _ => (),
+        }
+        match self.ret_type {
+            Type::Int => Ok(Some(ConstValue::Int(
+                self.eval_int_op(lhs.as_int()?, rhs.as_int()?)?,
+            ))),
+            Type::Vector(len) => {
+                // Splat any integer argument to a vector.
+                let to_vector = |value: ConstValue| match value {
+                    ConstValue::Int(i) => Ok(vec![i; len]),
+                    ConstValue::Vector(v) => Ok(v),
+                    _ => Err(UNCAUGHT_TYPE_ERROR),
+                };
+                let lhs = to_vector(lhs)?;
+                let rhs = to_vector(rhs)?;
+                Ok(Some(ConstValue::Vector(
+                    lhs.into_iter()
+                        .zip(rhs)
+                        .map(|(l, r)| self.eval_int_op(l, r))
+                        .collect::<LangResult<_>>()?,
+                )))
+            }
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+}
+
+/// Built-in function that multiplies two integers and adds a third, which is
+/// used in place of a `+` operator whose operand is a `*` operator (such as
+/// `a * b + c` or `c + a * b`).
+///
+/// Both operations share an error point, so the compiled code only checks for
+/// overflow once. Overflow in either operation is reported at the span of the
+/// whole expression.
+#[derive(Debug)]
+pub struct MulAdd {
+    /// Whether the addend comes before the factors in the source code (as in
+    /// `c + a * b`), in which case the arguments are `[c, a, b]` instead of
+    /// `[a, b, c]`.
+    addend_first: bool,
+    /// Number of bits in an integer.
+    int_bits: u32,
+    /// Error returned if overflow occurs, or None if overflow wraps.
+    overflow_error: Option<ErrorPointRef>,
+}
+impl MulAdd {
+    /// Returns whether a `+` operator can be fused with the `*` operator of
+    /// the given factors and the given addend.
+    ///
+    /// This is only done if all three are integers, the product is not a
+    /// constant (in which case fold_constants() removes the multiplication
+    /// anyway), and the addend cannot produce an error (so that an overflow in
+    /// the multiplication is still reported before any error in the addend).
+    pub fn can_fuse(userfunc: &UserFunction, factors: [ExprRef; 2], addend: ExprRef) -> bool {
+        let is_int = |e: ExprRef| userfunc[e].result_type() == Type::Int;
+        let is_const = |e: ExprRef| userfunc.const_eval_expr(e).is_ok();
+        factors.iter().chain(Some(&addend)).all(|&e| is_int(e))
+            && !factors.iter().all(|&e| is_const(e))
+            && !userfunc[addend].may_error()
+    }
+    /// Constructs a new MulAdd instance.
+    pub fn new(userfunc: &mut UserFunction, span: Span, addend_first: bool) -> Self {
+        let int_bits = userfunc.rule_meta().int_bits;
+        let overflow_error = match userfunc.rule_meta().overflow_mode {
+            OverflowMode::Checked => {
+                Some(userfunc.add_error_point(IntegerOverflow.with_span(span)))
+            }
+            OverflowMode::Wrapping => None,
+        };
+        Self {
+            addend_first,
+            int_bits,
+            overflow_error,
+        }
+    }
+    /// Returns the indices of the two factors and the addend in the arguments.
+    fn arg_indices(&self) -> (usize, usize, usize) {
+        if self.addend_first {
+            (1, 2, 0)
+        } else {
+            (0, 1, 2)
+        }
+    }
+}
+impl Function for MulAdd {
+    fn name(&self) -> String {
+        format!(
+            "fused {:?} and {:?} operators",
+            OperatorToken::Asterisk.to_string(),
+            OperatorToken::Plus.to_string(),
+        )
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int; 3], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        // Compile the arguments in the order they appear in the source code.
+        let mut arg_values = vec![];
+        for i in 0..3 {
+            arg_values.push(args.compile(compiler, i)?.as_int()?);
+        }
+        let (a, b, c) = self.arg_indices();
+        let (lhs, rhs, addend) = (arg_values[a], arg_values[b], arg_values[c]);
+        Ok(Value::Int(match &self.overflow_error {
+            Some(overflow_error) => {
+                // Check both operations for overflow with a single branch.
+                let (product, mul_overflow) =
+                    compiler.build_int_arithmetic_with_overflow(lhs, rhs, "smul")?;
+                let (sum, add_overflow) =
+                    compiler.build_int_arithmetic_with_overflow(product, addend, "sadd")?;
+                let is_overflow =
+                    compiler
+                        .builder()
+                        .build_or(mul_overflow, add_overflow, "isMulAddOverflow");
+                compiler.build_conditional(
+                    is_overflow,
+                    |c| Ok(overflow_error.compile(c)),
+                    |_| Ok(()),
+                )?;
+                sum
+            }
+            None => {
+                let b = compiler.builder();
+                let product = b.build_int_mul(lhs, rhs, "tmp_mul");
+                b.build_int_add(product, addend, "tmp_add")
+            }
+        }))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let (a, b, c) = self.arg_indices();
+        let a = args.const_eval(a)?;
+        let b = args.const_eval(b)?;
+        let c = args.const_eval(c)?;
+        Ok(Some(match &self.overflow_error {
+            Some(overflow_error) => a
+                .checked_mul(b, self.int_bits, overflow_error)?
+                .checked_add(c, self.int_bits, overflow_error)?,
+            None => ConstValue::Int(wrap_int_to_bits(
+                a.as_int()?
+                    .wrapping_mul(b.as_int()?)
+                    .wrapping_add(c.as_int()?),
+                self.int_bits,
+            )),
+        }))
+    }
+}
+
+/// Builds instructions to raise an integer to a nonnegative integer power,
+/// returning an error if the exponent is negative or if overflow occurs.
+///
+/// This uses exponentiation by squaring, so the number of iterations is
+/// logarithmic in the exponent. Every multiplication is checked, and the base
+/// is only squared if a higher bit of the exponent remains, so overflow is
+/// reported exactly when the final result would overflow.
+fn build_checked_pow(
+    compiler: &mut Compiler,
+    base: IntValue<'static>,
+    exponent: IntValue<'static>,
+    overflow_error: &ErrorPointRef,
+    negative_exponent_error: &ErrorPointRef,
+) -> LangResult<IntValue<'static>> {
+    let int_type = compiler.int_type();
+    let zero = int_type.const_zero();
+    let one = int_type.const_int(1, false);
+
+    // If the exponent is negative, that's a NegativeExponent error.
+    let is_negative = compiler.builder().build_int_compare(
+        IntPredicate::SLT,
+        exponent,
+        zero,
+        "isNegativeExponent",
+    );
+    compiler.build_conditional(
+        is_negative,
+        |c| Ok(negative_exponent_error.compile(c)),
+        |_| Ok(()),
+    )?;
+
+    // Build the destination blocks.
+    let entry_bb = compiler.builder().get_insert_block().unwrap();
+    let loop_bb = compiler.append_basic_block("powLoop");
+    let body_bb = compiler.append_basic_block("powBody");
+    let exit_bb = compiler.append_basic_block("powEnd");
+    compiler.builder().build_unconditional_branch(loop_bb);
+
+    // Loop until there are no bits left in the exponent.
+    compiler.builder().position_at_end(loop_bb);
+    let result_phi = compiler.builder().build_phi(int_type, "powResult");
+    let base_phi = compiler.builder().build_phi(int_type, "powBase");
+    let exponent_phi = compiler.builder().build_phi(int_type, "powExponent");
+    let result = result_phi.as_basic_value().into_int_value();
+    let current_base = base_phi.as_basic_value().into_int_value();
+    let current_exponent = exponent_phi.as_basic_value().into_int_value();
+    let is_done =
+        compiler
+            .builder()
+            .build_int_compare(IntPredicate::EQ, current_exponent, zero, "isPowDone");
+    compiler
+        .builder()
+        .build_conditional_branch(is_done, exit_bb, body_bb);
+
+    // If the lowest bit of the exponent is set, multiply the result by the
+    // base (otherwise multiply it by one).
+    compiler.builder().position_at_end(body_bb);
+    let b = compiler.builder();
+    let low_bit = b.build_and(current_exponent, one, "tmp_powLowBit");
+    let has_low_bit = b.build_int_compare(IntPredicate::NE, low_bit, zero, "hasPowLowBit");
+    let factor = b
+        .build_select(has_low_bit, current_base, one, "tmp_powFactor")
+        .into_int_value();
+    let next_result = compiler
+        .build_checked_int_arithmetic(result, factor, "smul", |c| Ok(overflow_error.compile(c)))?;
+    // Square the base, but only if it will be used again.
+    let b = compiler.builder();
+    let next_exponent = b.build_right_shift(current_exponent, one, false, "tmp_powExponent");
+    let has_more_bits =
+        b.build_int_compare(IntPredicate::NE, next_exponent, zero, "hasMorePowBits");
+    let square_factor = b
+        .build_select(has_more_bits, current_base, one, "tmp_powSquareFactor")
+        .into_int_value();
+    let next_base =
+        compiler.build_checked_int_arithmetic(current_base, square_factor, "smul", |c| {
+            Ok(overflow_error.compile(c))
+        })?;
+    // The checked multiplications may have moved the instruction pointer to a
+    // different block.
+    let body_end_bb = compiler.builder().get_insert_block().unwrap();
+    compiler.builder().build_unconditional_branch(loop_bb);
+
+    result_phi.add_incoming(&[(&one, entry_bb), (&next_result, body_end_bb)]);
+    base_phi.add_incoming(&[(&base, entry_bb), (&next_base, body_end_bb)]);
+    exponent_phi.add_incoming(&[(&exponent, entry_bb), (&next_exponent, body_end_bb)]);
+
+    compiler.builder().position_at_end(exit_bb);
+    Ok(result)
+}
+
+/// Built-in function that performs floored integer division (i.e. division
+/// that rounds toward negative infinity instead of toward zero).
+#[derive(Debug)]
+pub struct DivFloor {
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+    /// Error returned if the divisor is zero.
+    div_by_zero_error: ErrorPointRef,
+}
+impl DivFloor {
+    /// Returns a new DivFloor instance.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        Ok(Self {
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+            div_by_zero_error: userfunc.add_error_point(DivideByZero.with_span(span)),
+        })
+    }
+}
+impl Function for DivFloor {
+    fn name(&self) -> String {
+        "div_floor".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?.as_int()?;
+        let rhs = args.compile(compiler, 1)?.as_int()?;
+        // Check for overflow and division by zero.
+        compiler.build_div_check(
+            lhs,
+            rhs,
+            |c| Ok(self.overflow_error.compile(c)),
+            |c| Ok(self.div_by_zero_error.compile(c)),
+        )?;
+        let int_type = compiler.int_type();
+        let b = compiler.builder();
+        // Perform truncating division.
+        let quotient = b.build_int_signed_div(lhs, rhs, "tmp_div");
+        let remainder = b.build_int_signed_rem(lhs, rhs, "tmp_rem");
+        // If the remainder is nonzero and its sign differs from the sign of
+        // the divisor, then truncating division rounded up instead of down, so
+        // subtract one from the quotient. (This can never overflow.)
+        let rem_is_nonzero = b.build_int_compare(
+            IntPredicate::NE,
+            remainder,
+            int_type.const_zero(),
+            "remIsNonzero",
+        );
+        let signs_differ = b.build_int_compare(
+            IntPredicate::SLT,
+            b.build_xor(remainder, rhs, "tmp_signXor"),
+            int_type.const_zero(),
+            "signsDiffer",
+        );
+        let needs_correction = b.build_and(rem_is_nonzero, signs_differ, "needsCorrection");
+        let correction = b.build_int_z_extend(needs_correction, int_type, "correction");
+        Ok(Value::Int(b.build_int_sub(
+            quotient,
+            correction,
+            "tmp_divFloor",
+        )))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?.as_int()?;
+        let rhs = args.const_eval(1)?.as_int()?;
+        if rhs == 0 {
+            return self.div_by_zero_error.err();
+        }
+        let quotient = match lhs.checked_div(rhs) {
+            Some(q) => q,
+            None => return self.overflow_error.err(),
+        };
+        let remainder = lhs % rhs;
+        if remainder != 0 && (remainder < 0) != (rhs < 0) {
+            Ok(Some(ConstValue::Int(quotient - 1)))
+        } else {
+            Ok(Some(ConstValue::Int(quotient)))
+        }
+    }
+}
+
+/// Built-in function that performs floored modulo (i.e. the remainder of
+/// floored division, which always has the same sign as the divisor).
+///
+/// Unlike the `%` operator, which performs truncated remainder and so returns a
+/// negative result for a negative dividend, `mod(-1, 3)` is `2`. This is useful
+/// for wrapping coordinates around the edges of a grid.
+#[derive(Debug)]
+pub struct Mod {
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+    /// Error returned if the divisor is zero.
+    div_by_zero_error: ErrorPointRef,
+}
+impl Mod {
+    /// Returns a new Mod instance.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        Ok(Self {
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+            div_by_zero_error: userfunc.add_error_point(DivideByZero.with_span(span)),
+        })
+    }
+}
+impl Function for Mod {
+    fn name(&self) -> String {
+        "mod".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?.as_int()?;
+        let rhs = args.compile(compiler, 1)?.as_int()?;
+        Ok(Value::Int(build_floored_mod(
+            compiler,
+            lhs,
+            rhs,
+            &self.overflow_error,
+            &self.div_by_zero_error,
+        )?))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?.as_int()?;
+        let rhs = args.const_eval(1)?.as_int()?;
+        Ok(Some(ConstValue::Int(eval_floored_mod(
+            lhs,
+            rhs,
+            &self.overflow_error,
+            &self.div_by_zero_error,
+        )?)))
+    }
+}
+
+/// Builds instructions to compute the floored modulo of two integers,
+/// returning an error if the divisor is zero or if overflow occurs.
+fn build_floored_mod(
+    compiler: &mut Compiler,
+    lhs: IntValue<'static>,
+    rhs: IntValue<'static>,
+    overflow_error: &ErrorPointRef,
+    div_by_zero_error: &ErrorPointRef,
+) -> LangResult<IntValue<'static>> {
+    // Check for overflow and division by zero.
+    compiler.build_div_check(
+        lhs,
+        rhs,
+        |c| Ok(overflow_error.compile(c)),
+        |c| Ok(div_by_zero_error.compile(c)),
+    )?;
+    let int_type = compiler.int_type();
+    let b = compiler.builder();
+    // Compute the truncated remainder.
+    let remainder = b.build_int_signed_rem(lhs, rhs, "tmp_rem");
+    // This is equivalent to `((a % b) + b) % b`, but only adds the divisor if
+    // the remainder is nonzero and its sign differs from the sign of the
+    // divisor. (This can never overflow.)
+    let rem_is_nonzero = b.build_int_compare(
+        IntPredicate::NE,
+        remainder,
+        int_type.const_zero(),
+        "remIsNonzero",
+    );
+    let signs_differ = b.build_int_compare(
+        IntPredicate::SLT,
+        b.build_xor(remainder, rhs, "tmp_signXor"),
+        int_type.const_zero(),
+        "signsDiffer",
+    );
+    let needs_correction = b.build_and(rem_is_nonzero, signs_differ, "needsCorrection");
+    let correction = b
+        .build_select(needs_correction, rhs, int_type.const_zero(), "correction")
+        .into_int_value();
+    Ok(b.build_int_add(remainder, correction, "tmp_mod"))
+}
+/// Returns the floored modulo of two integers, or an error if the divisor is
+/// zero or if overflow occurs.
+fn eval_floored_mod(
+    lhs: LangInt,
+    rhs: LangInt,
+    overflow_error: &ErrorPointRef,
+    div_by_zero_error: &ErrorPointRef,
+) -> LangResult<LangInt> {
+    if rhs == 0 {
+        return div_by_zero_error.err();
+    }
+    let remainder = match lhs.checked_rem(rhs) {
+        Some(r) => r,
+        None => return overflow_error.err(),
+    };
+    if remainder != 0 && (remainder < 0) != (rhs < 0) {
+        Ok(remainder + rhs)
+    } else {
+        Ok(remainder)
+    }
+}
+
+/// Built-in function that wraps an index into the range from zero (inclusive)
+/// to a size (exclusive) using floored modulo, or wraps each component of a
+/// vector position using the corresponding component of a vector size.
+///
+/// This is the usual way to access cells on a toroidal grid, where positions
+/// past one edge continue from the opposite edge. `wrap(-1, 5)` is `4`, and
+/// `wrap([5, -1], [5, 3])` is `[0, 2]`. If the size is negative, then the
+/// result is in the range from the size (exclusive) to zero (inclusive), the
+/// same as `mod()`.
+#[derive(Debug)]
+pub struct Wrap {
+    /// Type of the index, size, and result.
+    ty: Type,
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+    /// Error returned if the size is zero.
+    div_by_zero_error: ErrorPointRef,
+}
+impl Wrap {
+    /// Constructs a new Wrap instance.
+    ///
+    /// This method checks the types of the arguments and returns an error if
+    /// they are not both integers or both vectors of the same length.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        let ty = check_int_or_vector_args(userfunc, args)?;
+        Ok(Self {
+            ty,
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+            div_by_zero_error: userfunc.add_error_point(DivideByZero.with_span(span)),
+        })
+    }
+}
+impl Function for Wrap {
+    fn name(&self) -> String {
+        "wrap".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![self.ty, self.ty], self.ty)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let index = args.compile(compiler, 0)?;
+        let size = args.compile(compiler, 1)?;
+        match (index, size) {
+            (Value::Int(index), Value::Int(size)) => Ok(Value::Int(build_floored_mod(
+                compiler,
+                index,
+                size,
+                &self.overflow_error,
+                &self.div_by_zero_error,
+            )?)),
+            (Value::Vector(index), Value::Vector(size)) => {
+                let len = index.get_type().get_size() as usize;
+                let index_components = compiler.build_extract_components(index, len);
+                let size_components = compiler.build_extract_components(size, len);
+                // Wrap each component in order, so that any error comes from
+                // the first component where it occurs.
+                let int_type = compiler.int_type();
+                let mut ret = int_type.vec_type(len as u32).get_undef();
+                for (i, (index, size)) in index_components
+                    .into_iter()
+                    .zip(size_components)
+                    .enumerate()
+                {
+                    let component = build_floored_mod(
+                        compiler,
+                        index,
+                        size,
+                        &self.overflow_error,
+                        &self.div_by_zero_error,
+                    )?;
+                    let idx = int_type.const_int(i as u64, false);
+                    ret = compiler.builder().build_insert_element(
+                        ret,
+                        component,
+                        idx,
+                        "tmp_component",
+                    );
+                }
+                Ok(Value::Vector(ret))
+            }
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let index = args.const_eval(0)?;
+        let size = args.const_eval(1)?;
+        let wrap = |index: LangInt, size: LangInt| {
+            eval_floored_mod(index, size, &self.overflow_error, &self.div_by_zero_error)
+        };
+        match (index, size) {
+            (ConstValue::Int(index), ConstValue::Int(size)) => {
+                Ok(Some(ConstValue::Int(wrap(index, size)?)))
+            }
+            (ConstValue::Vector(index), ConstValue::Vector(size)) => Ok(Some(ConstValue::Vector(
+                index
+                    .into_iter()
+                    .zip(size)
+                    .map(|(index, size)| wrap(index, size))
+                    .collect::<LangResult<_>>()?,
+            ))),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+}
+
+/// Built-in function that returns the minimum or maximum of two or more
+/// integers, or the component-wise minimum or maximum of two or more vectors of
+/// the same length.
+#[derive(Debug)]
+pub struct MinMax {
+    /// Whether to return the maximum (true) or minimum (false).
+    is_max: bool,
+    /// Type of the arguments and the result.
+    ty: Type,
+    /// Number of arguments.
+    arg_count: usize,
+}
+impl MinMax {
+    /// Constructs a new MinMax instance that takes the minimum or maximum of
+    /// the given arguments.
+    ///
+    /// This method checks the types of the arguments and returns an error if
+    /// they are not all integers or all vectors of the same length.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args, is_max: bool) -> LangResult<Self> {
+        let ty = check_int_or_vector_args(userfunc, args)?;
+        Ok(Self {
+            is_max,
+            ty,
+            arg_count: args.len(),
+        })
+    }
+}
+impl Function for MinMax {
+    fn name(&self) -> String {
+        let name = if self.is_max { "max" } else { "min" };
+        name.to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![self.ty; std::cmp::max(self.arg_count, 2)],
+            self.ty,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let predicate = if self.is_max {
+            IntPredicate::SGT
+        } else {
+            IntPredicate::SLT
+        };
+        let mut ret = args.compile(compiler, 0)?;
+        for arg_index in 1..args.len() {
+            let arg = args.compile(compiler, arg_index)?;
+            // Take the new argument if it is less than (for min) or greater
+            // than (for max) the result so far.
+            let b = compiler.builder();
+            ret = match (ret, arg) {
+                (Value::Int(old), Value::Int(new)) => {
+                    let is_new_better = b.build_int_compare(predicate, new, old, "minMaxCmp");
+                    Value::Int(
+                        b.build_select(is_new_better, new, old, "tmp_minMax")
+                            .into_int_value(),
+                    )
+                }
+                (Value::Vector(old), Value::Vector(new)) => {
+                    // The comparison produces a vector of booleans, so this
+                    // selects each component independently.
+                    let is_new_better = b.build_int_compare(predicate, new, old, "minMaxCmp");
+                    Value::Vector(
+                        b.build_select(is_new_better, new, old, "tmp_minMax")
+                            .into_vector_value(),
+                    )
+                }
+                _ => Err(UNCAUGHT_TYPE_ERROR)?,
+            };
+        }
+        Ok(ret)
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let mut ret = args.const_eval(0)?;
+        for arg_index in 1..args.len() {
+            let arg = args.const_eval(arg_index)?;
+            ret = if self.is_max {
+                ret.max(arg)?
+            } else {
+                ret.min(arg)?
+            };
+        }
+        Ok(Some(ret))
+    }
+}
+
+/// Built-in function that clamps an integer to a range, or clamps each
+/// component of a vector to a range. If the lower bound is greater than the
+/// upper bound at runtime, the result is the lower bound.
+#[derive(Debug)]
+pub struct Clamp {
+    /// Type of the value being clamped and the result.
+    ty: Type,
+}
+impl Clamp {
+    /// Constructs a new Clamp instance.
+    ///
+    /// This method checks the types of the arguments (value, lower bound, and
+    /// upper bound) and returns an error if the value is not an integer or
+    /// vector or if the bounds are not integers. It also returns an error if
+    /// the bounds are constant and the lower bound is greater than the upper
+    /// bound.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        let exprs: Vec<_> = args.iter(userfunc).collect();
+        let ty = match exprs.get(0).map(|e| (e.result_type(), e.span())) {
+            Some((ty @ Type::Int, _)) | Some((ty @ Type::Vector(_), _)) => ty,
+            Some((got, span)) => Err(TypeError {
+                expected: Type::Int,
+                got,
+            }
+            .with_span(span))?,
+            // Let Expr::try_new() report the missing arguments.
+            None => Type::Int,
+        };
+        for bound in exprs.iter().skip(1) {
+            let got = bound.result_type();
+            if got != Type::Int {
+                Err(TypeError {
+                    expected: Type::Int,
+                    got,
+                }
+                .with_span(bound.span()))?;
+            }
+        }
+        // Check the bounds if they are constant.
+        let refs: Vec<_> = args.iter_refs().copied().collect();
+        if refs.len() == 3 {
+            let lo = userfunc
+                .const_eval_expr(refs[1])
+                .and_then(ConstValue::as_int);
+            let hi = userfunc
+                .const_eval_expr(refs[2])
+                .and_then(ConstValue::as_int);
+            if let (Ok(lo), Ok(hi)) = (lo, hi) {
+                if lo > hi {
+                    Err(InvalidClampBounds.with_span(span))?;
+                }
+            }
+        }
+        Ok(Self { ty })
+    }
+}
+impl Function for Clamp {
+    fn name(&self) -> String {
+        "clamp".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![self.ty, Type::Int, Type::Int],
+            self.ty,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let value = args.compile(compiler, 0)?;
+        let lo = args.compile(compiler, 1)?.as_int()?;
+        let hi = args.compile(compiler, 2)?.as_int()?;
+        // Compute `max(lo, min(hi, value))`.
+        match value {
+            Value::Int(value) => {
+                let b = compiler.builder();
+                let is_above = b.build_int_compare(IntPredicate::SGT, value, hi, "isAbove");
+                let value = b
+                    .build_select(is_above, hi, value, "tmp_clampHi")
+                    .into_int_value();
+                let is_below = b.build_int_compare(IntPredicate::SLT, value, lo, "isBelow");
+                Ok(Value::Int(
+                    b.build_select(is_below, lo, value, "tmp_clamp")
+                        .into_int_value(),
+                ))
+            }
+            Value::Vector(value) => {
+                // Clamp each component independently.
+                let len = value.get_type().get_size() as usize;
+                let lo = compiler.build_vector_splat(lo, len);
+                let hi = compiler.build_vector_splat(hi, len);
+                let b = compiler.builder();
+                let is_above = b.build_int_compare(IntPredicate::SGT, value, hi, "isAbove");
+                let value = b
+                    .build_select(is_above, hi, value, "tmp_clampHi")
+                    .into_vector_value();
+                let is_below = b.build_int_compare(IntPredicate::SLT, value, lo, "isBelow");
+                Ok(Value::Vector(
+                    b.build_select(is_below, lo, value, "tmp_clamp")
+                        .into_vector_value(),
+                ))
+            }
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let value = args.const_eval(0)?;
+        let lo = args.const_eval(1)?.as_int()?;
+        let hi = args.const_eval(2)?.as_int()?;
+        let clamp = |x: LangInt| std::cmp::max(lo, std::cmp::min(hi, x));
+        match value {
+            ConstValue::Int(x) => Ok(Some(ConstValue::Int(clamp(x)))),
+            ConstValue::Vector(values) => Ok(Some(ConstValue::Vector(
+                values.into_iter().map(clamp).collect(),
+            ))),
+            _ => Err(UNCAUGHT_TYPE_ERROR),
+        }
+    }
+}
+
+/// Builds instructions to compute the greatest common divisor of two
+/// nonnegative integers using the Euclidean algorithm.
+fn build_gcd(
+    compiler: &mut Compiler,
+    a: IntValue<'static>,
+    b: IntValue<'static>,
+) -> IntValue<'static> {
+    // Build the destination blocks.
+    let entry_bb = compiler.builder().get_insert_block().unwrap();
+    let loop_bb = compiler.append_basic_block("gcdLoop");
+    let body_bb = compiler.append_basic_block("gcdBody");
+    let exit_bb = compiler.append_basic_block("gcdEnd");
+    compiler.builder().build_unconditional_branch(loop_bb);
+
+    // Each iteration replaces (x, y) with (y, x % y), until y is zero.
+    compiler.builder().position_at_end(loop_bb);
+    let int_type = compiler.int_type();
+    let x_phi = compiler.builder().build_phi(int_type, "gcdX");
+    let y_phi = compiler.builder().build_phi(int_type, "gcdY");
+    let x = x_phi.as_basic_value().into_int_value();
+    let y = y_phi.as_basic_value().into_int_value();
+    let is_done = compiler.builder().build_int_compare(
+        IntPredicate::EQ,
+        y,
+        int_type.const_zero(),
+        "isGcdDone",
+    );
+    compiler
+        .builder()
+        .build_conditional_branch(is_done, exit_bb, body_bb);
+
+    // Both values are nonnegative and y is nonzero, so this remainder is
+    // well-defined.
+    compiler.builder().position_at_end(body_bb);
+    let remainder = compiler.builder().build_int_signed_rem(x, y, "tmp_gcdRem");
+    compiler.builder().build_unconditional_branch(loop_bb);
+
+    x_phi.add_incoming(&[(&a, entry_bb), (&y, body_bb)]);
+    y_phi.add_incoming(&[(&b, entry_bb), (&remainder, body_bb)]);
+
+    compiler.builder().position_at_end(exit_bb);
+    x
+}
+
+/// Returns the greatest common divisor of the absolute values of two integers,
+/// or None if either is the minimum integer value (whose absolute value cannot
+/// be represented).
+fn const_gcd(a: LangInt, b: LangInt) -> Option<LangInt> {
+    let mut x = a.checked_abs()?;
+    let mut y = b.checked_abs()?;
+    while y != 0 {
+        let remainder = x % y;
+        x = y;
+        y = remainder;
+    }
+    Some(x)
+}
+
+/// Built-in function that returns the greatest common divisor of the absolute
+/// values of two integers.
+#[derive(Debug)]
+pub struct Gcd {
+    /// Error returned if the absolute value of either argument overflows.
+    overflow_error: ErrorPointRef,
+}
+impl Gcd {
+    /// Returns a new Gcd instance.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        Ok(Self {
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+        })
+    }
+}
+impl Function for Gcd {
+    fn name(&self) -> String {
+        "gcd".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let a = args.compile(compiler, 0)?.as_int()?;
+        let b = args.compile(compiler, 1)?.as_int()?;
+        let a = build_checked_abs(compiler, a, &self.overflow_error)?;
+        let b = build_checked_abs(compiler, b, &self.overflow_error)?;
+        Ok(Value::Int(build_gcd(compiler, a, b)))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let a = args.const_eval(0)?.as_int()?;
+        let b = args.const_eval(1)?.as_int()?;
+        match const_gcd(a, b) {
+            Some(result) => Ok(Some(ConstValue::Int(result))),
+            None => self.overflow_error.err(),
+        }
+    }
+}
+
+/// Built-in function that returns the least common multiple of the absolute
+/// values of two integers.
+#[derive(Debug)]
+pub struct Lcm {
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+}
+impl Lcm {
+    /// Returns a new Lcm instance.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        Ok(Self {
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+        })
+    }
+}
+impl Function for Lcm {
+    fn name(&self) -> String {
+        "lcm".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let a = args.compile(compiler, 0)?.as_int()?;
+        let b = args.compile(compiler, 1)?.as_int()?;
+        let a = build_checked_abs(compiler, a, &self.overflow_error)?;
+        let b = build_checked_abs(compiler, b, &self.overflow_error)?;
+        let gcd = build_gcd(compiler, a, b);
+        // The GCD is only zero if both arguments are zero, in which case the
+        // LCM is also zero; divide by one instead to avoid dividing by zero.
+        let int_type = compiler.int_type();
+        let builder = compiler.builder();
+        let gcd_is_zero =
+            builder.build_int_compare(IntPredicate::EQ, gcd, int_type.const_zero(), "gcdIsZero");
+        let divisor = builder
+            .build_select(
+                gcd_is_zero,
+                int_type.const_int(1, false),
+                gcd,
+                "tmp_lcmDivisor",
+            )
+            .into_int_value();
+        // Divide before multiplying to avoid unnecessary overflow.
+        let quotient = builder.build_int_signed_div(a, divisor, "tmp_lcmQuotient");
+        Ok(Value::Int(compiler.build_checked_int_arithmetic(
+            quotient,
+            b,
+            "smul",
+            |c| Ok(self.overflow_error.compile(c)),
+        )?))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let a = args.const_eval(0)?.as_int()?;
+        let b = args.const_eval(1)?.as_int()?;
+        let result = const_gcd(a, b).and_then(|gcd| {
+            if gcd == 0 {
+                Some(0)
+            } else {
+                (a.abs() / gcd).checked_mul(b.abs())
+            }
+        });
+        match result {
+            Some(result) => Ok(Some(ConstValue::Int(result))),
+            None => self.overflow_error.err(),
+        }
+    }
+}
+
+/// Built-in function that adds or subtracts two integers, saturating at the
+/// minimum or maximum integer value instead of returning an error if overflow
+/// occurs (`sat_add(a, b)` and `sat_sub(a, b)`).
+#[derive(Debug)]
+pub struct SaturatingArithmetic {
+    /// Whether to subtract (true) or add (false).
+    is_sub: bool,
+    /// Number of bits in an integer.
+    int_bits: u32,
+}
+impl SaturatingArithmetic {
+    /// Constructs a new SaturatingArithmetic instance that performs saturating
+    /// subtraction or addition.
+    pub fn new(userfunc: &UserFunction, is_sub: bool) -> Self {
+        Self {
+            is_sub,
+            int_bits: userfunc.rule_meta().int_bits,
+        }
+    }
+}
+impl Function for SaturatingArithmetic {
+    fn name(&self) -> String {
+        if self.is_sub {
+            "sat_sub".to_owned()
+        } else {
+            "sat_add".to_owned()
+        }
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?.as_int()?;
+        let rhs = args.compile(compiler, 1)?.as_int()?;
+        let intrinsic_name = format!(
+            "llvm.{}.sat.i{}",
+            if self.is_sub { "ssub" } else { "sadd" },
+            compiler.int_type().get_bit_width(),
+        );
+        let intrinsic_fn_type = compiler
+            .int_type()
+            .fn_type(&[compiler.int_type().into(); 2], false);
+        let intrinsic_fn = compiler.get_llvm_intrinisic(&intrinsic_name, intrinsic_fn_type)?;
+        // Build a call to an LLVM intrinsic to do the operation. Unlike
+        // build_checked_int_arithmetic(), this needs no branch, since the
+        // intrinsic handles overflow itself.
+        let call_site_value = compiler.builder().build_call(
+            intrinsic_fn,
+            &[lhs.into(), rhs.into()],
+            &format!("tmp_{}", intrinsic_name),
+        );
+        Ok(Value::Int(
+            call_site_value
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value(),
+        ))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?.as_int()?;
+        let rhs = args.const_eval(1)?.as_int()?;
+        let result = if self.is_sub {
+            lhs.saturating_sub(rhs)
+        } else {
+            lhs.saturating_add(rhs)
+        };
+        Ok(Some(ConstValue::Int(saturate_int_to_bits(
+            result,
+            self.int_bits,
+        ))))
+    }
+}
+
+/// Operation performed by a wrapping arithmetic function.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WrappingOp {
+    /// Wrapping addition.
+    Add,
+    /// Wrapping subtraction.
+    Sub,
+    /// Wrapping multiplication.
+    Mul,
+}
+impl WrappingOp {
+    /// Returns the name of the built-in function that performs this operation.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Add => "wrap_add",
+            Self::Sub => "wrap_sub",
+            Self::Mul => "wrap_mul",
+        }
+    }
+}
+
+/// Built-in function that adds, subtracts, or multiplies two integers using
+/// two's complement wraparound (`wrap_add(a, b)`, `wrap_sub(a, b)`, and
+/// `wrap_mul(a, b)`).
+///
+/// Unlike the `+`, `-`, and `*` operators, these never check for overflow, so
+/// they are only useful for intentional modular arithmetic such as hashing.
+#[derive(Debug)]
+pub struct WrappingArithmetic {
+    /// Operation to perform.
+    op: WrappingOp,
+    /// Number of bits in an integer.
+    int_bits: u32,
+}
+impl WrappingArithmetic {
+    /// Constructs a new WrappingArithmetic instance that performs the given
+    /// operation.
+    pub fn new(userfunc: &UserFunction, op: WrappingOp) -> Self {
+        Self {
+            op,
+            int_bits: userfunc.rule_meta().int_bits,
+        }
+    }
+}
+impl Function for WrappingArithmetic {
+    fn name(&self) -> String {
+        self.op.name().to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let lhs = args.compile(compiler, 0)?.as_int()?;
+        let rhs = args.compile(compiler, 1)?.as_int()?;
+        // LLVM's plain integer instructions already wrap on overflow.
+        let b = compiler.builder();
+        Ok(Value::Int(match self.op {
+            WrappingOp::Add => b.build_int_add(lhs, rhs, "tmp_wrapAdd"),
+            WrappingOp::Sub => b.build_int_sub(lhs, rhs, "tmp_wrapSub"),
+            WrappingOp::Mul => b.build_int_mul(lhs, rhs, "tmp_wrapMul"),
+        }))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?.as_int()?;
+        let rhs = args.const_eval(1)?.as_int()?;
+        let result = match self.op {
+            WrappingOp::Add => lhs.wrapping_add(rhs),
+            WrappingOp::Sub => lhs.wrapping_sub(rhs),
+            WrappingOp::Mul => lhs.wrapping_mul(rhs),
+        };
+        Ok(Some(ConstValue::Int(wrap_int_to_bits(
+            result,
+            self.int_bits,
+        ))))
+    }
+}
+
+/// Bit-counting operation performed by a BitCount function.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BitCountOp {
+    /// Number of bits that are set.
+    PopCount,
+    /// Number of zero bits before the most significant set bit.
+    LeadingZeros,
+    /// Number of zero bits after the least significant set bit.
+    TrailingZeros,
+}
+impl BitCountOp {
+    /// Returns the name of the built-in function that performs this operation.
+    fn name(self) -> &'static str {
+        match self {
+            Self::PopCount => "popcount",
+            Self::LeadingZeros => "ctlz",
+            Self::TrailingZeros => "cttz",
+        }
+    }
+    /// Returns the name of the LLVM intrinsic that performs this operation,
+    /// without the type suffix.
+    fn intrinsic_name(self) -> &'static str {
+        match self {
+            Self::PopCount => "llvm.ctpop",
+            Self::LeadingZeros => "llvm.ctlz",
+            Self::TrailingZeros => "llvm.cttz",
+        }
+    }
+}
+
+/// Built-in function that counts bits of an integer (`popcount(x)`, `ctlz(x)`,
+/// and `cttz(x)`). Bits are counted over the full width of an integer, so
+/// `ctlz(0)` and `cttz(0)` are both the number of bits in an integer.
+#[derive(Debug)]
+pub struct BitCount {
+    /// Operation to perform.
+    op: BitCountOp,
+    /// Number of bits in an integer.
+    int_bits: u32,
+}
+impl BitCount {
+    /// Constructs a new BitCount instance that performs the given operation.
+    pub fn new(userfunc: &UserFunction, op: BitCountOp) -> Self {
+        Self {
+            op,
+            int_bits: userfunc.rule_meta().int_bits,
+        }
+    }
+}
+impl Function for BitCount {
+    fn name(&self) -> String {
+        self.op.name().to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let arg = args.compile(compiler, 0)?.as_int()?;
+        let int_type = compiler.int_type();
+        let intrinsic_name = format!("{}.i{}", self.op.intrinsic_name(), int_type.get_bit_width(),);
+        let call_site_value = match self.op {
+            BitCountOp::PopCount => {
+                let intrinsic_fn_type = int_type.fn_type(&[int_type.into()], false);
+                let intrinsic_fn =
+                    compiler.get_llvm_intrinisic(&intrinsic_name, intrinsic_fn_type)?;
+                compiler.builder().build_call(
+                    intrinsic_fn,
+                    &[arg.into()],
+                    &format!("tmp_{}", intrinsic_name),
+                )
+            }
+            BitCountOp::LeadingZeros | BitCountOp::TrailingZeros => {
+                // The second argument is `is_zero_poison`; it is false so that
+                // zero produces the number of bits in an integer.
+                let bool_type = compiler.bool_type();
+                let intrinsic_fn_type =
+                    int_type.fn_type(&[int_type.into(), bool_type.into()], false);
+                let intrinsic_fn =
+                    compiler.get_llvm_intrinisic(&intrinsic_name, intrinsic_fn_type)?;
+                compiler.builder().build_call(
+                    intrinsic_fn,
+                    &[arg.into(), bool_type.const_zero().into()],
+                    &format!("tmp_{}", intrinsic_name),
+                )
+            }
+        };
+        Ok(Value::Int(
+            call_site_value
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value(),
+        ))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let arg = args.const_eval(0)?.as_int()?;
+        // Only count the bits that are actually part of an integer.
+        let unused_bits = 64 - self.int_bits;
+        let bits = (arg as u64) << unused_bits;
+        let result = match self.op {
+            BitCountOp::PopCount => bits.count_ones(),
+            BitCountOp::LeadingZeros => std::cmp::min(bits.leading_zeros(), self.int_bits),
+            BitCountOp::TrailingZeros => bits.trailing_zeros() - unused_bits,
+        };
+        Ok(Some(ConstValue::Int(result as LangInt)))
     }
 }
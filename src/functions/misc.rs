@@ -1,22 +1,63 @@
 //! Miscellaneous functions.
 
-use super::super::ast::{ArgValues, FnSignature, Function, FunctionKind, UserFunction};
+use inkwell::IntPredicate;
+use std::convert::TryFrom;
+
+use super::super::ast::{
+    ArgValues, Args, ErrorPointRef, FnSignature, Function, FunctionKind, StatementBlock,
+    UserFunction,
+};
 use super::super::compiler::{Compiler, Value};
 use super::super::errors::*;
-use super::super::{Span, Type};
+use super::super::lexer::{KeywordToken, OperatorToken};
+use super::super::types::LangInt;
+use super::super::{ConstValue, Span, Type};
+use super::array;
+use super::logic::const_truthiness;
+use LangErrorMsg::{
+    CannotIndexType, CannotTraceType, FallibleSelectOperand, IndexOutOfBounds, PoisonedExpression,
+    TypeError, UnknownCellStateName, UnknownProperty,
+};
 
-/// Built-in function that returns a fixed variable.
+/// Built-in function that returns a fixed variable, constant, or named cell
+/// state.
 #[derive(Debug, Clone)]
 pub struct GetVar {
     pub var_name: String,
     pub var_type: Type,
+    /// Value of the constant or cell state with this name, if this is not a
+    /// variable.
+    pub const_value: Option<ConstValue>,
 }
 impl GetVar {
-    /// Returns a new GetVar instance that returns the value of the variable
-    /// with the given name.
+    /// Returns a new GetVar instance that returns the value of the variable,
+    /// constant, or named cell state with the given name.
+    ///
+    /// If there is no such variable but there is a cell state with a similar
+    /// name, the error suggests that name.
     pub fn try_new(userfunc: &mut UserFunction, span: Span, var_name: String) -> LangResult<Self> {
-        let var_type = userfunc.try_get_var(span, &var_name)?;
-        Ok(Self { var_name, var_type })
+        if let Some(value) = userfunc.rule_meta().get_constant(&var_name) {
+            return Ok(Self {
+                var_name,
+                var_type: value.ty(),
+                const_value: Some(value),
+            });
+        }
+        let var_type = userfunc.try_read_var(span, &var_name).map_err(|e| {
+            match userfunc.rule_meta().closest_state_name(&var_name) {
+                Some(suggestion) if e.msg != PoisonedExpression => UnknownCellStateName {
+                    name: var_name.clone(),
+                    suggestion: suggestion.to_owned(),
+                }
+                .with_span(span),
+                _ => e,
+            }
+        })?;
+        Ok(Self {
+            var_name,
+            var_type,
+            const_value: None,
+        })
     }
 }
 impl Function for GetVar {
@@ -30,8 +71,581 @@ impl Function for GetVar {
         vec![FnSignature::atom(self.var_type)]
     }
     fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        if let Some(value) = &self.const_value {
+            return Ok(compiler.value_from_const(value.clone()));
+        }
         let var_ptr = compiler.vars()[&self.var_name].ptr;
+        if let Type::Array(_) = self.var_type {
+            // Arrays are not loaded all at once; see Value::Array.
+            return Ok(Value::Array(var_ptr));
+        }
         let value = compiler.builder().build_load(var_ptr, &self.var_name);
         Ok(Value::from_basic_value(self.var_type, value))
     }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        Ok(self.const_value.clone())
+    }
+}
+
+/// Built-in function that returns a single component of a vector.
+#[derive(Debug, Clone)]
+pub struct IndexVector {
+    /// Length of the vector.
+    len: usize,
+    /// Error returned if the index is out of range.
+    out_of_bounds_error: ErrorPointRef,
+}
+impl IndexVector {
+    /// Constructs a new IndexVector instance that indexes the vector given as
+    /// the first argument using the integer given as the second argument.
+    ///
+    /// This method returns an error if the index is a constant that is out of
+    /// range.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        let len = match userfunc[args[0]].result_type() {
+            Type::Vector(len) => len,
+            other => Err(CannotIndexType(other).with_span(userfunc[args[0]].span()))?,
+        };
+        array::check_const_index(userfunc, args[1], len)?;
+        Ok(Self {
+            len,
+            out_of_bounds_error: userfunc.add_error_point(IndexOutOfBounds.with_span(span)),
+        })
+    }
+}
+impl Function for IndexVector {
+    fn name(&self) -> String {
+        "vector index".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![Type::Vector(self.len), Type::Int],
+            Type::Int,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let vector = args.compile(compiler, 0)?.as_vector()?;
+        let index = args.compile(compiler, 1)?.as_int()?;
+        array::build_index_check(compiler, index, self.len, &self.out_of_bounds_error)?;
+        Ok(Value::Int(
+            compiler
+                .builder()
+                .build_extract_element(vector, index, "tmp_component")
+                .into_int_value(),
+        ))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let vector = args.const_eval(0)?.as_vector()?;
+        let index = args.const_eval(1)?.as_int()?;
+        match usize::try_from(index).ok().and_then(|i| vector.get(i)) {
+            Some(&component) => Ok(Some(ConstValue::Int(component))),
+            None => self.out_of_bounds_error.err(),
+        }
+    }
+}
+
+/// Built-in property that returns a single element of a tuple, such as `t.0`.
+#[derive(Debug, Clone)]
+pub struct TupleElement {
+    /// Types of the elements of the tuple.
+    elements: &'static [Type],
+    /// Index of the element to return.
+    index: usize,
+}
+impl TupleElement {
+    /// Constructs a new TupleElement instance that returns the element at the
+    /// given index of a tuple of the given type.
+    ///
+    /// This method returns an error if the receiver is not a tuple or if the
+    /// index is out of range.
+    pub fn try_new(span: Span, receiver_type: Type, index: u64) -> LangResult<Self> {
+        match receiver_type {
+            Type::Tuple(elements) => match usize::try_from(index) {
+                Ok(index) if index < elements.len() => Ok(Self { elements, index }),
+                _ => Err(IndexOutOfBounds.with_span(span)),
+            },
+            other => Err(UnknownProperty {
+                ty: other,
+                name: index.to_string(),
+            }
+            .with_span(span)),
+        }
+    }
+}
+impl Function for TupleElement {
+    fn name(&self) -> String {
+        format!("tuple element {}", self.index)
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Property
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::property(
+            Type::Tuple(self.elements),
+            self.elements[self.index],
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let tuple = args.compile(compiler, 0)?.as_tuple()?;
+        let element = compiler
+            .builder()
+            .build_extract_value(tuple, self.index as u32, "tmp_element")
+            .unwrap();
+        Ok(Value::from_basic_value(self.elements[self.index], element))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let mut elements = args.const_eval(0)?.as_tuple()?;
+        Ok(Some(elements.swap_remove(self.index)))
+    }
+}
+
+/// Built-in method that returns the length of a vector. This struct can be
+/// constructed directly.
+#[derive(Debug, Clone)]
+pub struct VectorLen {
+    /// Length of the vector.
+    pub len: usize,
+}
+impl Function for VectorLen {
+    fn name(&self) -> String {
+        format!("method {:?} of {}", "len", Type::Vector(self.len))
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Method
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Vector(self.len)], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        // Compile the vector anyway, in case it produces an error.
+        args.compile(compiler, 0)?;
+        Ok(Value::Int(
+            compiler.int_type().const_int(self.len as u64, false),
+        ))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        args.const_eval(0)?;
+        Ok(Some(ConstValue::Int(self.len as LangInt)))
+    }
+}
+
+/// Built-in function that constructs a range from two integers (start and
+/// end). Ranges are inclusive on both ends.
+#[derive(Debug, Clone)]
+pub struct Range;
+impl Function for Range {
+    fn name(&self) -> String {
+        format!("binary {:?} operator", OperatorToken::DotDot.to_string())
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int, Type::Int], Type::Range)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let start = args.compile(compiler, 0)?.as_int()?;
+        let end = args.compile(compiler, 1)?.as_int()?;
+        Ok(Value::Range(start, end))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let start = args.const_eval(0)?.as_int()?;
+        let end = args.const_eval(1)?.as_int()?;
+        Ok(Some(ConstValue::Range(start, end)))
+    }
+}
+
+/// Built-in function that checks whether an integer is in a range (inclusive
+/// on both ends). This enum can be constructed directly.
+#[derive(Debug, Clone)]
+pub enum RangeContains {
+    /// `r.contains(x)`, where the range is the first argument.
+    Contains,
+    /// `x.is_in_range(r)`, where the integer is the first argument.
+    IsInRange,
+    /// `x in r`, where the integer is the first argument.
+    In,
+}
+impl RangeContains {
+    /// Returns whether the range is the first argument (as opposed to the
+    /// second).
+    fn range_first(&self) -> bool {
+        match self {
+            Self::Contains => true,
+            Self::IsInRange | Self::In => false,
+        }
+    }
+}
+impl Function for RangeContains {
+    fn name(&self) -> String {
+        match self {
+            Self::Contains => format!("method {:?} of {}", "contains", Type::Range),
+            Self::IsInRange => format!("method {:?} of {}", "is_in_range", Type::Int),
+            Self::In => format!("binary {:?} operator", KeywordToken::In.to_string()),
+        }
+    }
+    fn kind(&self) -> FunctionKind {
+        match self {
+            Self::Contains | Self::IsInRange => FunctionKind::Method,
+            Self::In => FunctionKind::Operator,
+        }
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        if self.range_first() {
+            vec![FnSignature::new(vec![Type::Range, Type::Int], Type::Bool)]
+        } else {
+            vec![FnSignature::new(vec![Type::Int, Type::Range], Type::Bool)]
+        }
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        // Compile the arguments in order, in case either produces an error.
+        let first = args.compile(compiler, 0)?;
+        let second = args.compile(compiler, 1)?;
+        let (range, x) = if self.range_first() {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let (start, end) = range.as_range()?;
+        let x = x.as_int()?;
+        let b = compiler.builder();
+        let after_start = b.build_int_compare(IntPredicate::SGE, x, start, "rangeStartCmp");
+        let before_end = b.build_int_compare(IntPredicate::SLE, x, end, "rangeEndCmp");
+        Ok(Value::Bool(b.build_and(
+            after_start,
+            before_end,
+            "rangeContains",
+        )))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let first = args.const_eval(0)?;
+        let second = args.const_eval(1)?;
+        let (range, x) = if self.range_first() {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let (start, end) = range.as_range()?;
+        let x = x.as_int()?;
+        Ok(Some(ConstValue::Bool(start <= x && x <= end)))
+    }
+}
+
+/// Built-in function that evaluates to one of two values depending on a
+/// condition, such as `if x then 1 else 2`. Only the value that is selected is
+/// evaluated.
+#[derive(Debug, Clone)]
+pub struct Conditional {
+    /// Type of the condition (either a boolean or an integer).
+    cond_type: Type,
+    /// Type of the two possible values.
+    ty: Type,
+}
+impl Conditional {
+    /// Constructs a new Conditional instance.
+    ///
+    /// This method checks the types of the arguments (condition, value if
+    /// truthy, and value if falsey) and returns an error if the condition
+    /// cannot be used as a condition or if the two values have different
+    /// types.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        let exprs: Vec<_> = args.iter(userfunc).collect();
+        let cond_type = match exprs[0].result_type() {
+            ty @ Type::Bool | ty @ Type::Int => ty,
+            got => {
+                return Err(TypeError {
+                    expected: Type::Bool,
+                    got,
+                }
+                .with_span(exprs[0].span()))
+            }
+        };
+        let expected = exprs[1].result_type();
+        let got = exprs[2].result_type();
+        if expected != got {
+            return Err(TypeError { expected, got }.with_span(exprs[2].span()));
+        }
+        Ok(Self {
+            cond_type,
+            ty: expected,
+        })
+    }
+}
+impl Function for Conditional {
+    fn name(&self) -> String {
+        "conditional expression".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![self.cond_type, self.ty, self.ty],
+            self.ty,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let condition_value = args.compile(compiler, 0)?.as_condition()?;
+        // Compile each value in its own branch, and record the block that each
+        // branch ends in (which may be different from the block it started in).
+        let mut if_true = None;
+        let mut if_false = None;
+        compiler.build_conditional(
+            condition_value,
+            |c| {
+                let value = args.compile(c, 1)?;
+                if_true = Some((value, c.builder().get_insert_block().unwrap()));
+                Ok(())
+            },
+            |c| {
+                let value = args.compile(c, 2)?;
+                if_false = Some((value, c.builder().get_insert_block().unwrap()));
+                Ok(())
+            },
+        )?;
+        let (true_value, true_bb) = if_true.unwrap();
+        let (false_value, false_bb) = if_false.unwrap();
+
+        // Merge the two values using phi nodes.
+        match (true_value, false_value) {
+            (Value::Range(true_start, true_end), Value::Range(false_start, false_end)) => {
+                // Ranges have no single LLVM representation, so merge the
+                // start and end separately.
+                let int_type = compiler.int_type();
+                let start_phi = compiler.builder().build_phi(int_type, "conditionalStart");
+                start_phi.add_incoming(&[(&true_start, true_bb), (&false_start, false_bb)]);
+                let end_phi = compiler.builder().build_phi(int_type, "conditionalEnd");
+                end_phi.add_incoming(&[(&true_end, true_bb), (&false_end, false_bb)]);
+                Ok(Value::Range(
+                    start_phi.as_basic_value().into_int_value(),
+                    end_phi.as_basic_value().into_int_value(),
+                ))
+            }
+            (true_value, false_value) => {
+                // Use the type of the LLVM value rather than the NDCA type,
+                // since an array is represented as a pointer.
+                let true_value = true_value.into_basic_value()?;
+                let false_value = false_value.into_basic_value()?;
+                let phi = compiler
+                    .builder()
+                    .build_phi(true_value.get_type(), "conditional");
+                phi.add_incoming(&[(&true_value, true_bb), (&false_value, false_bb)]);
+                Ok(Value::from_basic_value(self.ty, phi.as_basic_value()))
+            }
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // Only evaluate the value that is selected.
+        if const_truthiness(args.const_eval(0)?)? {
+            Ok(Some(args.const_eval(1)?))
+        } else {
+            Ok(Some(args.const_eval(2)?))
+        }
+    }
+}
+
+/// "Function" that executes a block of statements and then evaluates to the
+/// value at the end of the block (if there is one), such as `{ set y = x * 2
+/// y + 1 }`.
+///
+/// A block without a value never produces one, since its statements always
+/// become or return (see Expr::is_never()).
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Statements to execute before evaluating the value.
+    pub statements: StatementBlock,
+    /// Type of the value.
+    pub ty: Type,
+    /// Whether there is a value at the end of the block (which is then the
+    /// only argument).
+    pub has_value: bool,
+}
+impl Function for Block {
+    fn name(&self) -> String {
+        "block expression".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Operator
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        let args = if self.has_value {
+            vec![self.ty]
+        } else {
+            vec![]
+        };
+        vec![FnSignature::new(args, self.ty)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        args.userfunc()
+            .compile_statement_block(compiler, &self.statements)?;
+        if self.has_value {
+            args.compile(compiler, 0)
+        } else {
+            Ok(compiler.build_never_value(self.ty))
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // Statements cannot be evaluated at compile time.
+        if self.has_value && self.statements.is_empty() {
+            Ok(Some(args.const_eval(0)?))
+        } else {
+            Ok(None)
+        }
+    }
+    fn blocks(&self) -> Vec<&StatementBlock> {
+        vec![&self.statements]
+    }
+}
+
+/// Built-in function that evaluates to one of two values depending on a
+/// condition, such as `select(x, 1, 2)`, without branching.
+///
+/// Unlike a conditional expression, both values are always evaluated, so
+/// neither of them may produce a runtime error.
+#[derive(Debug, Clone)]
+pub struct Select {
+    /// Type of the condition (either a boolean or an integer).
+    cond_type: Type,
+    /// Type of the two possible values.
+    ty: Type,
+}
+impl Select {
+    /// Constructs a new Select instance.
+    ///
+    /// This method checks the types of the arguments (condition, value if
+    /// truthy, and value if falsey) and returns an error if the condition
+    /// cannot be used as a condition, if the two values have different types,
+    /// or if either value may produce a runtime error.
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        let exprs: Vec<_> = args.iter(userfunc).collect();
+        if exprs.len() != 3 {
+            // Let Expr::try_new() report the wrong number of arguments.
+            return Ok(Self {
+                cond_type: Type::Bool,
+                ty: Type::Int,
+            });
+        }
+        let cond_type = match exprs[0].result_type() {
+            ty @ Type::Bool | ty @ Type::Int => ty,
+            got => {
+                return Err(TypeError {
+                    expected: Type::Bool,
+                    got,
+                }
+                .with_span(exprs[0].span()))
+            }
+        };
+        let expected = exprs[1].result_type();
+        let got = exprs[2].result_type();
+        if expected != got {
+            return Err(TypeError { expected, got }.with_span(exprs[2].span()));
+        }
+        // The condition need not be checked, since it is evaluated either way.
+        for value in &exprs[1..] {
+            if value.may_error() {
+                return Err(FallibleSelectOperand.with_span(value.span()));
+            }
+        }
+        Ok(Self {
+            cond_type,
+            ty: expected,
+        })
+    }
+}
+impl Function for Select {
+    fn name(&self) -> String {
+        "select".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![self.cond_type, self.ty, self.ty],
+            self.ty,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let condition_value = args.compile(compiler, 0)?.as_condition()?;
+        let true_value = args.compile(compiler, 1)?;
+        let false_value = args.compile(compiler, 2)?;
+        // The condition might not be 1-bit, so compare it to zero.
+        let b = compiler.builder();
+        let condition_value = b.build_int_compare(
+            IntPredicate::NE,
+            condition_value,
+            condition_value.get_type().const_zero(),
+            "selectCondition",
+        );
+        match (true_value, false_value) {
+            (Value::Range(true_start, true_end), Value::Range(false_start, false_end)) => {
+                // Ranges have no single LLVM representation, so select the
+                // start and end separately.
+                let start = b.build_select(condition_value, true_start, false_start, "selectStart");
+                let end = b.build_select(condition_value, true_end, false_end, "selectEnd");
+                Ok(Value::Range(start.into_int_value(), end.into_int_value()))
+            }
+            (true_value, false_value) => {
+                let true_value = true_value.into_basic_value()?;
+                let false_value = false_value.into_basic_value()?;
+                let value = b.build_select(condition_value, true_value, false_value, "select");
+                Ok(Value::from_basic_value(self.ty, value))
+            }
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        if const_truthiness(args.const_eval(0)?)? {
+            Ok(Some(args.const_eval(1)?))
+        } else {
+            Ok(Some(args.const_eval(2)?))
+        }
+    }
+}
+
+/// Built-in function that reports a value for debugging and returns it
+/// unchanged, such as `trace(x + 1)`.
+///
+/// The value is only reported if the compiler has debug tracing enabled (see
+/// Compiler::set_debug_trace()); otherwise this compiles to just its argument.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// Type of the value.
+    ty: Type,
+}
+impl Trace {
+    /// Constructs a new Trace instance.
+    ///
+    /// This method checks the type of the argument and returns an error if it
+    /// has no runtime representation (e.g. a range).
+    pub fn try_new(userfunc: &mut UserFunction, args: &Args) -> LangResult<Self> {
+        let exprs: Vec<_> = args.iter(userfunc).collect();
+        if exprs.len() != 1 {
+            // Let Expr::try_new() report the wrong number of arguments.
+            return Ok(Self { ty: Type::Int });
+        }
+        let ty = exprs[0].result_type();
+        if !ty.has_runtime_representation() {
+            return Err(CannotTraceType(ty).with_span(exprs[0].span()));
+        }
+        Ok(Self { ty })
+    }
+}
+impl Function for Trace {
+    fn name(&self) -> String {
+        "trace".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![self.ty], self.ty)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let value = args.compile(compiler, 0)?;
+        compiler.build_trace(value.clone())?;
+        Ok(value)
+    }
 }
@@ -1,7 +1,157 @@
 //! Built-in functions, methods/properties, and operators.
 
+use super::ast::{Args, Function, UserFunction};
+use super::errors::*;
+use super::{Span, Type};
+
+pub mod array;
 pub mod cmp;
 pub mod convert;
+pub mod externs;
+pub mod helper;
 pub mod literals;
+pub mod logic;
 pub mod math;
 pub mod misc;
+pub mod nbhd;
+pub mod random;
+pub mod vector;
+
+/// Constructs the built-in function with the given name that will be called
+/// with the given arguments.
+///
+/// Returns Ok(None) if there is no built-in function with the given name.
+pub fn try_new_builtin(
+    userfunc: &mut UserFunction,
+    span: Span,
+    name: &str,
+    args: &Args,
+) -> LangResult<Option<Box<dyn Function>>> {
+    let function: Box<dyn Function> = match name {
+        "abs" => Box::new(math::Abs::try_new(userfunc, span, false)?),
+        "array" => Box::new(array::NewArray::try_new(userfunc, args)?),
+        "clamp" => Box::new(math::Clamp::try_new(userfunc, span, args)?),
+        "count" => Box::new(nbhd::Count::try_new(userfunc, span)?),
+        "ctlz" => Box::new(math::BitCount::new(
+            userfunc,
+            math::BitCountOp::LeadingZeros,
+        )),
+        "cttz" => Box::new(math::BitCount::new(
+            userfunc,
+            math::BitCountOp::TrailingZeros,
+        )),
+        "div_floor" => Box::new(math::DivFloor::try_new(userfunc, span)?),
+        "dot" => Box::new(vector::Dot::try_new(userfunc, span, args)?),
+        "gcd" => Box::new(math::Gcd::try_new(userfunc, span)?),
+        "id" => Box::new(convert::CellStateToInt { explicit: true }),
+        "lcm" => Box::new(math::Lcm::try_new(userfunc, span)?),
+        "max" if is_single_vector(userfunc, args) => Box::new(vector::Reduce::try_new(
+            userfunc,
+            span,
+            args,
+            vector::ReduceOp::Max,
+        )?),
+        "max" => Box::new(math::MinMax::try_new(userfunc, args, true)?),
+        "min" if is_single_vector(userfunc, args) => Box::new(vector::Reduce::try_new(
+            userfunc,
+            span,
+            args,
+            vector::ReduceOp::Min,
+        )?),
+        "min" => Box::new(math::MinMax::try_new(userfunc, args, false)?),
+        "mod" => Box::new(math::Mod::try_new(userfunc, span)?),
+        "neighbor" => Box::new(nbhd::Neighbor::try_new(userfunc, span, args)?),
+        "neighbor_count" => Box::new(nbhd::NeighborCount::try_new(userfunc, span)?),
+        "popcount" => Box::new(math::BitCount::new(userfunc, math::BitCountOp::PopCount)),
+        "pos" => Box::new(nbhd::Pos::try_new(userfunc, span)?),
+        "product" => Box::new(vector::Reduce::try_new(
+            userfunc,
+            span,
+            args,
+            vector::ReduceOp::Product,
+        )?),
+        "rand" => Box::new(random::Rand::try_new(userfunc, span, args)?),
+        "sat_add" => Box::new(math::SaturatingArithmetic::new(userfunc, false)),
+        "sat_sub" => Box::new(math::SaturatingArithmetic::new(userfunc, true)),
+        "select" => Box::new(misc::Select::try_new(userfunc, args)?),
+        "step" => Box::new(nbhd::Step::try_new(userfunc, span)?),
+        "sum" => Box::new(vector::Reduce::try_new(
+            userfunc,
+            span,
+            args,
+            vector::ReduceOp::Sum,
+        )?),
+        "trace" => Box::new(misc::Trace::try_new(userfunc, args)?),
+        "wrap" => Box::new(math::Wrap::try_new(userfunc, span, args)?),
+        "wrap_add" => Box::new(math::WrappingArithmetic::new(
+            userfunc,
+            math::WrappingOp::Add,
+        )),
+        "wrap_mul" => Box::new(math::WrappingArithmetic::new(
+            userfunc,
+            math::WrappingOp::Mul,
+        )),
+        "wrap_sub" => Box::new(math::WrappingArithmetic::new(
+            userfunc,
+            math::WrappingOp::Sub,
+        )),
+        _ => return Ok(None),
+    };
+    Ok(Some(function))
+}
+
+/// Returns true if the given arguments consist of a single vector, in which
+/// case functions like `min` and `max` reduce the components of that vector
+/// instead of comparing their arguments.
+fn is_single_vector(userfunc: &UserFunction, args: &Args) -> bool {
+    args.len() == 1
+        && matches!(
+            args.iter(userfunc).next().map(|e| e.result_type()),
+            Some(Type::Vector(_))
+        )
+}
+
+/// Constructs the built-in method with the given name that will be called on a
+/// value of the given type (the receiver) with the given arguments. The
+/// receiver is the first argument.
+///
+/// Returns Ok(None) if there is no built-in method with the given name for the
+/// given type.
+pub fn try_new_method(
+    userfunc: &mut UserFunction,
+    span: Span,
+    receiver_type: Type,
+    name: &str,
+    _args: &Args,
+) -> LangResult<Option<Box<dyn Function>>> {
+    let function: Box<dyn Function> = match (receiver_type, name) {
+        (Type::Int, "abs") => Box::new(math::Abs::try_new(userfunc, span, true)?),
+        (Type::Int, "is_in_range") => Box::new(misc::RangeContains::IsInRange),
+        (Type::Range, "contains") => Box::new(misc::RangeContains::Contains),
+        (Type::Vector(len), "len") => Box::new(misc::VectorLen { len }),
+        _ => return Ok(None),
+    };
+    Ok(Some(function))
+}
+
+/// Constructs the built-in property with the given name that will be accessed
+/// on a value of the given type (the receiver). The receiver is the only
+/// argument.
+///
+/// The span is the span of the property name.
+///
+/// Returns Ok(None) if there is no built-in property with the given name for
+/// the given type.
+pub fn try_new_property(
+    span: Span,
+    receiver_type: Type,
+    name: &str,
+) -> LangResult<Option<Box<dyn Function>>> {
+    let function: Box<dyn Function> = match receiver_type {
+        Type::Vector(len) if vector::Swizzle::is_swizzle(name) => {
+            Box::new(vector::Swizzle::try_new(span, len, name)?)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(function))
+}
@@ -0,0 +1,455 @@
+//! Neighborhood functions.
+
+use inkwell::values::IntValue;
+use inkwell::IntPredicate;
+
+use super::super::ast::{
+    ArgValues, Args, ErrorPointRef, FnSignature, Function, FunctionKind, NbhdShape, UserFunction,
+};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+use super::super::parser::Directive;
+use super::super::types::{int_fits_in_bits, LangInt};
+use super::super::{ConstValue, Span, Type};
+use LangErrorMsg::{
+    IntegerOverflow, NeighborInHelperFunction, NeighborOffsetLength, NeighborOutOfRange,
+    PositionInHelperFunction, StepInHelperFunction,
+};
+
+/// Built-in function that returns the cell state of the cell at the given
+/// offset (a vector with one component per dimension) from the center of the
+/// neighborhood, such as `neighbor([1, 0])`.
+///
+/// The offset does not have to be constant; it may be computed from loop
+/// variables, such as `neighbor([x, y])` inside `for x in -1..1`. Offsets are
+/// always relative to the neighborhood passed to the transition function, not
+/// to the grid, so no bounds need to be declared for the grid. An offset
+/// outside the declared radius and shape is an error (at compile time if it
+/// is constant, and at runtime otherwise); it never wraps around to the other
+/// side of the neighborhood.
+#[derive(Debug)]
+pub struct Neighbor {
+    /// Number of dimensions.
+    ndim: usize,
+    /// Neighborhood radius.
+    radius: u8,
+    /// Neighborhood shape.
+    shape: NbhdShape,
+    /// Error returned if the offset is outside the neighborhood.
+    out_of_range_error: ErrorPointRef,
+}
+impl Neighbor {
+    /// Constructs a new Neighbor instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function, if the offset is a vector with the wrong number of
+    /// components, or if the offset is a constant outside the neighborhood. If
+    /// the offset is not constant, it is checked at runtime instead.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(NeighborInHelperFunction.with_span(span))?;
+        }
+        let rule_meta = userfunc.rule_meta().clone();
+        let ndim = rule_meta.ndim as usize;
+        let out_of_range = NeighborOutOfRange {
+            shape: rule_meta.nbhd_shape,
+            radius: rule_meta.radius,
+        };
+        let mut const_offset = None;
+        if args.len() == 1 {
+            let offset_expr = &userfunc[args[0]];
+            let offset_span = offset_expr.span();
+            // The offset must have one component per dimension, as set by the
+            // `@dimensions` directive (if there is one).
+            if let Type::Vector(len) = offset_expr.result_type() {
+                if len != ndim {
+                    Err(NeighborOffsetLength {
+                        len,
+                        ndim: rule_meta.ndim,
+                        ndim_span: rule_meta
+                            .directive_spans
+                            .get(&Directive::Dimensions)
+                            .copied(),
+                    }
+                    .with_span(offset_span))?;
+                }
+            }
+            if offset_expr.result_type() == Type::Vector(ndim) {
+                if let Ok(ConstValue::Vector(offset)) = userfunc.const_eval_expr(args[0]) {
+                    if !rule_meta.nbhd_contains(&offset) {
+                        Err(out_of_range.with_span(offset_span))?;
+                    }
+                    const_offset = Some(offset);
+                }
+            }
+        }
+        userfunc.record_neighbor_read(const_offset.as_deref());
+        Ok(Self {
+            ndim,
+            radius: rule_meta.radius,
+            shape: rule_meta.nbhd_shape,
+            out_of_range_error: userfunc.add_error_point(out_of_range.with_span(span)),
+        })
+    }
+}
+impl Function for Neighbor {
+    fn name(&self) -> String {
+        "neighbor".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![Type::Vector(self.ndim)],
+            Type::CellState,
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let offset = args.compile(compiler, 0)?.as_vector()?;
+        let int_type = compiler.int_type();
+        let index_type = compiler.nbhd_index_type();
+        let radius = int_type.const_int(self.radius as u64, false);
+        let diameter = int_type.const_int(2 * self.radius as u64 + 1, false);
+
+        // Shift each component so that it ranges from 0 to the diameter of the
+        // neighborhood, and check that it is in that range. (A negative number
+        // will be interpreted as a very large positive number, which will be
+        // too large.)
+        let mut index = index_type.const_zero();
+        let mut stride = index_type.const_int(1, false);
+        let index_diameter = index_type.const_int(2 * self.radius as u64 + 1, false);
+        let components = compiler.build_extract_components(offset, self.ndim);
+        for &component in &components {
+            let b = compiler.builder();
+            let shifted = b.build_int_add(component, radius, "tmp_shiftedOffset");
+            let is_in_range =
+                b.build_int_compare(IntPredicate::ULT, shifted, diameter, "neighborRangeCheck");
+            compiler.build_conditional(
+                is_in_range,
+                // The component is in range.
+                |_| Ok(()),
+                // The component is out of range.
+                |c| Ok(self.out_of_range_error.compile(c)),
+            )?;
+            // Now that we know the component is in range, none of this can
+            // overflow.
+            let b = compiler.builder();
+            let shifted = b.build_int_cast(shifted, index_type, "tmp_neighborCoord");
+            let term = b.build_int_mul(shifted, stride, "tmp_neighborTerm");
+            index = b.build_int_add(index, term, "tmp_neighborIndex");
+            stride = stride.const_mul(index_diameter);
+        }
+
+        if self.shape == NbhdShape::VonNeumann {
+            // Check that the absolute values of the components add up to at
+            // most the radius. Each component is in range, so this cannot
+            // overflow.
+            let mut distance = int_type.const_zero();
+            for component in components {
+                let abs = build_small_abs(compiler, component);
+                distance = compiler
+                    .builder()
+                    .build_int_add(distance, abs, "tmp_neighborDistance");
+            }
+            let is_in_range = compiler.builder().build_int_compare(
+                IntPredicate::ULE,
+                distance,
+                radius,
+                "neighborShapeCheck",
+            );
+            compiler.build_conditional(
+                is_in_range,
+                // The offset is in the neighborhood.
+                |_| Ok(()),
+                // The offset is not in the neighborhood.
+                |c| Ok(self.out_of_range_error.compile(c)),
+            )?;
+        }
+
+        Ok(Value::CellState(compiler.build_nbhd_load(index)?))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // The neighborhood is only known at runtime.
+        Ok(None)
+    }
+}
+
+/// Built-in function that returns the position of the cell (a vector with one
+/// component per dimension), such as `pos()`.
+#[derive(Debug)]
+pub struct Pos {
+    /// Number of dimensions.
+    ndim: usize,
+}
+impl Pos {
+    /// Constructs a new Pos instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(PositionInHelperFunction.with_span(span))?;
+        }
+        Ok(Self {
+            ndim: userfunc.rule_meta().ndim as usize,
+        })
+    }
+}
+impl Function for Pos {
+    fn name(&self) -> String {
+        "pos".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![], Type::Vector(self.ndim))]
+    }
+    fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        Ok(Value::Vector(compiler.build_pos_load(self.ndim)?))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // The position is only known at runtime.
+        Ok(None)
+    }
+}
+
+/// Built-in function that returns the step number (the number of generations
+/// simulated so far), such as `step()`.
+#[derive(Debug)]
+pub struct Step;
+impl Step {
+    /// Constructs a new Step instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(StepInHelperFunction.with_span(span))?;
+        }
+        Ok(Self)
+    }
+}
+impl Function for Step {
+    fn name(&self) -> String {
+        "step".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        Ok(Value::Int(compiler.build_step_load()?))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // The step number is only known at runtime.
+        Ok(None)
+    }
+}
+
+/// Builds instructions to compute the absolute value of an integer that is
+/// known not to be the minimum integer value.
+fn build_small_abs(compiler: &mut Compiler, value: IntValue<'static>) -> IntValue<'static> {
+    let zero = value.get_type().const_zero();
+    let b = compiler.builder();
+    let is_negative = b.build_int_compare(IntPredicate::SLT, value, zero, "isNegative");
+    let negated = b.build_int_neg(value, "tmp_neg");
+    b.build_select(is_negative, negated, value, "tmp_abs")
+        .into_int_value()
+}
+
+/// Built-in function that returns the number of cells in the neighborhood
+/// (excluding the center cell) with the given cell state, such as `count(#1)`.
+#[derive(Debug)]
+pub struct Count {
+    /// Number of dimensions.
+    ndim: usize,
+    /// Neighborhood radius.
+    radius: u8,
+    /// Neighborhood shape.
+    shape: NbhdShape,
+    /// Number of cells in the bounding box of the neighborhood, including the
+    /// center cell.
+    nbhd_len: usize,
+    /// Index of the center cell.
+    center_index: usize,
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+}
+impl Count {
+    /// Constructs a new Count instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(NeighborInHelperFunction.with_span(span))?;
+        }
+        let rule_meta = userfunc.rule_meta().clone();
+        // Every neighbor is read.
+        userfunc.record_neighbor_read(None);
+        Ok(Self {
+            ndim: rule_meta.ndim as usize,
+            radius: rule_meta.radius,
+            shape: rule_meta.nbhd_shape,
+            nbhd_len: rule_meta.nbhd_len(),
+            center_index: rule_meta.nbhd_center_index(),
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+        })
+    }
+    /// Builds instructions to check whether the cell at the given index is
+    /// part of a von Neumann neighborhood, by converting the index back into
+    /// an offset from the center.
+    fn build_von_neumann_check(
+        &self,
+        compiler: &mut Compiler,
+        index: IntValue<'static>,
+    ) -> IntValue<'static> {
+        let index_type = index.get_type();
+        let radius = index_type.const_int(self.radius as u64, false);
+        let diameter = index_type.const_int(2 * self.radius as u64 + 1, false);
+        let mut distance = index_type.const_zero();
+        let mut stride = index_type.const_int(1, false);
+        for _ in 0..self.ndim {
+            let b = compiler.builder();
+            let coord = b.build_int_unsigned_div(index, stride, "tmp_countCoord");
+            let coord = b.build_int_unsigned_rem(coord, diameter, "tmp_countCoord");
+            let component = b.build_int_sub(coord, radius, "tmp_countOffset");
+            let abs = build_small_abs(compiler, component);
+            distance = compiler
+                .builder()
+                .build_int_add(distance, abs, "tmp_countDistance");
+            stride = stride.const_mul(diameter);
+        }
+        compiler
+            .builder()
+            .build_int_compare(IntPredicate::ULE, distance, radius, "isInNbhd")
+    }
+}
+impl Function for Count {
+    fn name(&self) -> String {
+        "count".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::CellState], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let state = args.compile(compiler, 0)?.as_cell_state()?;
+        let int_type = compiler.int_type();
+        let index_type = compiler.nbhd_index_type();
+        let entry_bb = compiler.builder().get_insert_block().unwrap();
+
+        // Build the destination blocks.
+        let body_bb = compiler.append_basic_block("countBody");
+        let exit_bb = compiler.append_basic_block("endCount");
+        compiler.builder().build_unconditional_branch(body_bb);
+
+        // Create phi nodes for the index of the current cell and the number of
+        // matching cells so far.
+        compiler.builder().position_at_end(body_bb);
+        let index_phi = compiler.builder().build_phi(index_type, "countIndex");
+        let count_phi = compiler.builder().build_phi(int_type, "countTotal");
+        index_phi.add_incoming(&[(&index_type.const_zero(), entry_bb)]);
+        count_phi.add_incoming(&[(&int_type.const_zero(), entry_bb)]);
+        let index = index_phi.as_basic_value().into_int_value();
+        let count = count_phi.as_basic_value().into_int_value();
+
+        // Add one to the count if the current cell matches.
+        let cell = compiler.build_nbhd_load(index)?;
+        let b = compiler.builder();
+        let mut is_match = b.build_int_compare(IntPredicate::EQ, cell, state, "isCountMatch");
+        if self.shape == NbhdShape::VonNeumann {
+            // Skip cells in the bounding box that are not in the neighborhood.
+            let is_in_nbhd = self.build_von_neumann_check(compiler, index);
+            is_match = compiler
+                .builder()
+                .build_and(is_match, is_in_nbhd, "isCountMatch");
+        }
+        let b = compiler.builder();
+        let increment = b.build_int_z_extend(is_match, int_type, "countIncrement");
+        let next_count = compiler.build_checked_int_arithmetic(count, increment, "sadd", |c| {
+            Ok(self.overflow_error.compile(c))
+        })?;
+
+        // Move on to the next cell, or exit the loop if this was the last one.
+        let b = compiler.builder();
+        let one = index_type.const_int(1, false);
+        let next_index = b.build_int_add(index, one, "nextCountIndex");
+        let len = index_type.const_int(self.nbhd_len as u64, false);
+        let is_done = b.build_int_compare(IntPredicate::EQ, next_index, len, "isCountDone");
+        b.build_conditional_branch(is_done, exit_bb, body_bb);
+        let latch_bb = compiler.builder().get_insert_block().unwrap();
+        index_phi.add_incoming(&[(&next_index, latch_bb)]);
+        count_phi.add_incoming(&[(&next_count, latch_bb)]);
+
+        // The center cell is not its own neighbor, so subtract one if it
+        // matches. (This cannot overflow, because the center cell was counted
+        // above.)
+        compiler.builder().position_at_end(exit_bb);
+        let center_index = index_type.const_int(self.center_index as u64, false);
+        let center = compiler.build_nbhd_load(center_index)?;
+        let b = compiler.builder();
+        let is_center_match = b.build_int_compare(IntPredicate::EQ, center, state, "isCountMatch");
+        let correction = b.build_int_z_extend(is_center_match, int_type, "countCorrection");
+        Ok(Value::Int(b.build_int_sub(next_count, correction, "count")))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // The neighborhood is only known at runtime.
+        Ok(None)
+    }
+}
+
+/// Built-in function that returns the number of cells in the neighborhood,
+/// such as `neighbor_count()`. Like `count()`, this excludes the center cell
+/// and any cells outside the neighborhood shape, so it is the largest value
+/// that `count()` can return (see RuleMeta::neighbor_count()). A rule without
+/// `@neighborhood` or `@radius` directives has the default neighborhood, a
+/// Moore neighborhood of radius 1.
+///
+/// The neighborhood is known at compile time, so this is a constant and can be
+/// used anywhere a constant is required, such as the length of an array.
+#[derive(Debug)]
+pub struct NeighborCount {
+    /// Number of cells in the neighborhood.
+    neighbor_count: LangInt,
+}
+impl NeighborCount {
+    /// Constructs a new NeighborCount instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function, or if the number of cells does not fit in an integer.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(NeighborInHelperFunction.with_span(span))?;
+        }
+        let rule_meta = userfunc.rule_meta();
+        let neighbor_count = rule_meta.neighbor_count() as LangInt;
+        if !int_fits_in_bits(neighbor_count, rule_meta.int_bits) {
+            Err(IntegerOverflow.with_span(span))?;
+        }
+        Ok(Self { neighbor_count })
+    }
+}
+impl Function for NeighborCount {
+    fn name(&self) -> String {
+        "neighbor_count".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, _args: ArgValues) -> LangResult<Value> {
+        Ok(compiler.value_from_const(ConstValue::Int(self.neighbor_count)))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        Ok(Some(ConstValue::Int(self.neighbor_count)))
+    }
+}
@@ -0,0 +1,100 @@
+//! Pseudorandom number functions.
+
+use inkwell::IntPredicate;
+
+use super::super::ast::{
+    ArgValues, Args, ErrorPointRef, FnSignature, Function, FunctionKind, UserFunction,
+};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+use super::super::types::LangInt;
+use super::super::{ConstValue, Span, Type};
+use LangErrorMsg::{InvalidRandomBound, RandomInHelperFunction};
+
+/// Built-in function that returns a pseudorandom integer from zero (inclusive)
+/// to the given upper bound (exclusive), such as `rand(6)`.
+///
+/// The result is a hash of the rule's seed (see the `@seed` directive), the
+/// position of the cell, the step number, and the number of earlier calls to
+/// `rand()` for the same cell, so it is the same every time the rule runs on
+/// the same cell at the same step. (See compiler::random for details.)
+#[derive(Debug)]
+pub struct Rand {
+    /// Number of dimensions.
+    ndim: usize,
+    /// Rule's random seed.
+    seed: LangInt,
+    /// Error returned if the upper bound is not positive.
+    invalid_bound_error: ErrorPointRef,
+}
+impl Rand {
+    /// Constructs a new Rand instance.
+    ///
+    /// This method returns an error if it is not used in the transition
+    /// function, or if the upper bound is a constant that is not positive. If
+    /// the upper bound is not constant, it is checked at runtime instead.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        if !userfunc.is_transition_function() {
+            Err(RandomInHelperFunction.with_span(span))?;
+        }
+        if args.len() == 1 {
+            if let Ok(ConstValue::Int(bound)) = userfunc.const_eval_expr(args[0]) {
+                if bound <= 0 {
+                    Err(InvalidRandomBound.with_span(userfunc[args[0]].span()))?;
+                }
+            }
+        }
+        let rule_meta = userfunc.rule_meta().clone();
+        Ok(Self {
+            ndim: rule_meta.ndim as usize,
+            seed: rule_meta.seed,
+            invalid_bound_error: userfunc.add_error_point(InvalidRandomBound.with_span(span)),
+        })
+    }
+}
+impl Function for Rand {
+    fn name(&self) -> String {
+        "rand".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(vec![Type::Int], Type::Int)]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let bound = args.compile(compiler, 0)?.as_int()?;
+        let int_type = compiler.int_type();
+
+        // Check that the upper bound is positive.
+        let is_valid = compiler.builder().build_int_compare(
+            IntPredicate::SGT,
+            bound,
+            int_type.const_zero(),
+            "randBoundCheck",
+        );
+        compiler.build_conditional(
+            is_valid,
+            // The upper bound is positive.
+            |_| Ok(()),
+            // The upper bound is not positive.
+            |c| Ok(self.invalid_bound_error.compile(c)),
+        )?;
+
+        // Reduce the hash to the range from zero to the upper bound. The upper
+        // bound is much smaller than the range of the hash, so any bias is
+        // negligible.
+        let hash = compiler.build_rand_hash(self.seed, self.ndim)?;
+        let b = compiler.builder();
+        let bound = b.build_int_z_extend_or_bit_cast(bound, hash.get_type(), "tmp_randBound");
+        let ret = b.build_int_unsigned_rem(hash, bound, "tmp_randRem");
+        Ok(Value::Int(
+            b.build_int_truncate_or_bit_cast(ret, int_type, "tmp_rand"),
+        ))
+    }
+    fn const_eval(&self, _args: ArgValues) -> LangResult<Option<ConstValue>> {
+        // The position of the cell and the step number are only known at
+        // runtime.
+        Ok(None)
+    }
+}
@@ -0,0 +1,314 @@
+//! Vector functions.
+
+use inkwell::IntPredicate;
+
+use super::super::ast::{
+    ArgValues, Args, ErrorPointRef, FnSignature, Function, FunctionKind, UserFunction,
+};
+use super::super::compiler::{Compiler, Value};
+use super::super::errors::*;
+use super::super::types::LangInt;
+use super::super::{ConstValue, Span, Type};
+use LangErrorMsg::{IndexOutOfBounds, IntegerOverflow};
+
+/// Built-in function that returns the dot product of two vectors of the same
+/// length (i.e. the sum of the products of corresponding components).
+#[derive(Debug)]
+pub struct Dot {
+    /// Length of the vectors, or None if neither argument is a vector.
+    len: Option<usize>,
+    /// Error returned if overflow occurs.
+    overflow_error: ErrorPointRef,
+}
+impl Dot {
+    /// Constructs a new Dot instance.
+    ///
+    /// The length of the vectors is taken from the first argument that is a
+    /// vector; if the arguments are not both vectors of that length, then
+    /// there is no matching signature and Expr::try_new() reports an error.
+    pub fn try_new(userfunc: &mut UserFunction, span: Span, args: &Args) -> LangResult<Self> {
+        let len = args.iter(userfunc).find_map(|e| match e.result_type() {
+            Type::Vector(len) => Some(len),
+            _ => None,
+        });
+        Ok(Self {
+            len,
+            overflow_error: userfunc.add_error_point(IntegerOverflow.with_span(span)),
+        })
+    }
+}
+impl Function for Dot {
+    fn name(&self) -> String {
+        "dot".to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        match self.len {
+            Some(len) => vec![FnSignature::new(
+                vec![Type::Vector(len), Type::Vector(len)],
+                Type::Int,
+            )],
+            None => vec![],
+        }
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let len = self.len.ok_or(UNCAUGHT_TYPE_ERROR)?;
+        let lhs = args.compile(compiler, 0)?.as_vector()?;
+        let rhs = args.compile(compiler, 1)?.as_vector()?;
+        let lhs_components = compiler.build_extract_components(lhs, len);
+        let rhs_components = compiler.build_extract_components(rhs, len);
+        let mut sum = compiler.int_type().const_zero();
+        for (l, r) in lhs_components.into_iter().zip(rhs_components) {
+            // Check both the product and the running sum for overflow.
+            let product = compiler.build_checked_int_arithmetic(l, r, "smul", |c| {
+                Ok(self.overflow_error.compile(c))
+            })?;
+            sum = compiler.build_checked_int_arithmetic(sum, product, "sadd", |c| {
+                Ok(self.overflow_error.compile(c))
+            })?;
+        }
+        Ok(Value::Int(sum))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let lhs = args.const_eval(0)?.as_vector()?;
+        let rhs = args.const_eval(1)?.as_vector()?;
+        let mut sum: LangInt = 0;
+        for (l, r) in lhs.into_iter().zip(rhs) {
+            match l
+                .checked_mul(r)
+                .and_then(|product| sum.checked_add(product))
+            {
+                Some(new_sum) => sum = new_sum,
+                None => return self.overflow_error.err(),
+            }
+        }
+        Ok(Some(ConstValue::Int(sum)))
+    }
+}
+
+/// Operation used to reduce the components of a vector to a single integer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReduceOp {
+    /// Sum of all components.
+    Sum,
+    /// Product of all components.
+    Product,
+    /// Minimum component.
+    Min,
+    /// Maximum component.
+    Max,
+}
+impl ReduceOp {
+    /// Returns the name of the built-in function that performs this reduction.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Product => "product",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// Built-in function that reduces the components of a vector to a single
+/// integer, such as by adding them all together.
+#[derive(Debug)]
+pub struct Reduce {
+    /// Operation to perform.
+    op: ReduceOp,
+    /// Length of the vector, or None if the argument is not a vector.
+    len: Option<usize>,
+    /// Error returned if overflow occurs (only for sum and product).
+    overflow_error: Option<ErrorPointRef>,
+}
+impl Reduce {
+    /// Constructs a new Reduce instance.
+    ///
+    /// If the argument is not a vector, then there is no matching signature
+    /// and Expr::try_new() reports an error.
+    pub fn try_new(
+        userfunc: &mut UserFunction,
+        span: Span,
+        args: &Args,
+        op: ReduceOp,
+    ) -> LangResult<Self> {
+        let len = match args.iter(userfunc).next().map(|e| e.result_type()) {
+            Some(Type::Vector(len)) => Some(len),
+            _ => None,
+        };
+        let overflow_error = match op {
+            ReduceOp::Sum | ReduceOp::Product => {
+                Some(userfunc.add_error_point(IntegerOverflow.with_span(span)))
+            }
+            ReduceOp::Min | ReduceOp::Max => None,
+        };
+        Ok(Self {
+            op,
+            len,
+            overflow_error,
+        })
+    }
+    /// Returns the IntegerOverflow error point; panics if this function cannot
+    /// return an Err(IntegerOverflow).
+    fn overflow_error(&self) -> &ErrorPointRef {
+        self.overflow_error.as_ref().unwrap()
+    }
+}
+impl Function for Reduce {
+    fn name(&self) -> String {
+        self.op.name().to_owned()
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Function
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        match self.len {
+            Some(len) => vec![FnSignature::new(vec![Type::Vector(len)], Type::Int)],
+            None => vec![],
+        }
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let len = self.len.ok_or(UNCAUGHT_TYPE_ERROR)?;
+        let vector = args.compile(compiler, 0)?.as_vector()?;
+        let mut components = compiler.build_extract_components(vector, len).into_iter();
+        // Vectors always have at least one component, so start with the first
+        // one; a vector of length 1 reduces to its only component.
+        let mut ret = components.next().ok_or(UNCAUGHT_TYPE_ERROR)?;
+        for component in components {
+            ret = match self.op {
+                ReduceOp::Sum | ReduceOp::Product => {
+                    let intrinsic = match self.op {
+                        ReduceOp::Sum => "sadd",
+                        ReduceOp::Product => "smul",
+                        _ => unreachable!(),
+                    };
+                    compiler.build_checked_int_arithmetic(ret, component, intrinsic, |c| {
+                        Ok(self.overflow_error().compile(c))
+                    })?
+                }
+                ReduceOp::Min | ReduceOp::Max => {
+                    let predicate = match self.op {
+                        ReduceOp::Min => IntPredicate::SLT,
+                        ReduceOp::Max => IntPredicate::SGT,
+                        _ => unreachable!(),
+                    };
+                    let b = compiler.builder();
+                    let is_new_better = b.build_int_compare(predicate, component, ret, "reduceCmp");
+                    b.build_select(is_new_better, component, ret, "tmp_reduce")
+                        .into_int_value()
+                }
+            };
+        }
+        Ok(Value::Int(ret))
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let mut components = args.const_eval(0)?.as_vector()?.into_iter();
+        let mut ret = components.next().ok_or(UNCAUGHT_TYPE_ERROR)?;
+        for component in components {
+            ret = match self.op {
+                ReduceOp::Sum => match ret.checked_add(component) {
+                    Some(sum) => sum,
+                    None => return self.overflow_error().err(),
+                },
+                ReduceOp::Product => match ret.checked_mul(component) {
+                    Some(product) => product,
+                    None => return self.overflow_error().err(),
+                },
+                ReduceOp::Min => std::cmp::min(ret, component),
+                ReduceOp::Max => std::cmp::max(ret, component),
+            };
+        }
+        Ok(Some(ConstValue::Int(ret)))
+    }
+}
+
+/// Built-in property that accesses one or more components of a vector by name
+/// (`x`, `y`, `z`, and `w` for the first four components), such as `v.x` or
+/// `v.yx`. Accessing a single component returns an integer; accessing multiple
+/// components returns a new vector.
+#[derive(Debug)]
+pub struct Swizzle {
+    /// Name of the property (e.g. "xy").
+    name: String,
+    /// Length of the original vector.
+    len: usize,
+    /// Indices of the components to access.
+    components: Vec<usize>,
+}
+impl Swizzle {
+    /// Component names, in order.
+    const COMPONENT_NAMES: &'static str = "xyzw";
+
+    /// Returns true if the given property name consists only of component names.
+    pub fn is_swizzle(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| Self::COMPONENT_NAMES.contains(c))
+    }
+    /// Constructs a new Swizzle instance that accesses the given components of
+    /// a vector of the given length.
+    ///
+    /// This method returns an error if any of the components is beyond the
+    /// length of the vector.
+    pub fn try_new(span: Span, len: usize, name: &str) -> LangResult<Self> {
+        let components = name
+            .chars()
+            .map(|c| Self::COMPONENT_NAMES.find(c).unwrap())
+            .collect::<Vec<_>>();
+        if components.iter().any(|&i| i >= len) {
+            Err(IndexOutOfBounds.with_span(span))?;
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            len,
+            components,
+        })
+    }
+    /// Returns the type of the result.
+    fn ret_type(&self) -> Type {
+        match self.components.len() {
+            1 => Type::Int,
+            n => Type::Vector(n),
+        }
+    }
+}
+impl Function for Swizzle {
+    fn name(&self) -> String {
+        format!("property {:?} of {}", self.name, Type::Vector(self.len))
+    }
+    fn kind(&self) -> FunctionKind {
+        FunctionKind::Property
+    }
+    fn signatures(&self) -> Vec<FnSignature> {
+        vec![FnSignature::new(
+            vec![Type::Vector(self.len)],
+            self.ret_type(),
+        )]
+    }
+    fn compile(&self, compiler: &mut Compiler, args: ArgValues) -> LangResult<Value> {
+        let vector = args.compile(compiler, 0)?.as_vector()?;
+        if let [i] = self.components[..] {
+            let idx = compiler.int_type().const_int(i as u64, false);
+            Ok(Value::Int(
+                compiler
+                    .builder()
+                    .build_extract_element(vector, idx, "tmp_component")
+                    .into_int_value(),
+            ))
+        } else {
+            Ok(Value::Vector(
+                compiler.build_vector_shuffle(vector, &self.components),
+            ))
+        }
+    }
+    fn const_eval(&self, args: ArgValues) -> LangResult<Option<ConstValue>> {
+        let vector = args.const_eval(0)?.as_vector()?;
+        if let [i] = self.components[..] {
+            Ok(Some(ConstValue::Int(vector[i])))
+        } else {
+            Ok(Some(ConstValue::Vector(
+                self.components.iter().map(|&i| vector[i]).collect(),
+            )))
+        }
+    }
+}
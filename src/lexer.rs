@@ -7,9 +7,10 @@ use std::fmt;
 use std::str::FromStr;
 
 use super::errors::*;
-use super::types::LangInt;
 use super::{Span, Type};
-use LangErrorMsg::{UnknownSymbol, Unterminated};
+use LangErrorMsg::{
+    IntegerOverflow, InvalidDigitSeparator, InvalidIntegerLiteral, UnknownSymbol, Unterminated,
+};
 
 /// A list of token patterns, arranged roughly from least to most general.
 const TOKEN_PATTERNS: &'static [&'static str] = &[
@@ -28,10 +29,23 @@ const TOKEN_PATTERNS: &'static [&'static str] = &[
     r#"\w?'[^']*'"#,
     // Unterminated string -- this should raise an error.
     r#"\w?["']"#,
-    // Number with decimal point.
-    r#"-?\d?\.\d+"#,
-    // Number without decimal point.
-    r#"-?\d+"#,
+    // Integer with a base prefix: `0x` for hexadecimal, `0b` for binary, or
+    // `0o` for octal. This also matches invalid digits and misplaced digit
+    // separators, so that they raise an error instead of becoming a separate
+    // token.
+    r#"0[xbo][\dA-Za-z_]*"#,
+    // Number with decimal point. Like all number literals, this does not
+    // include a sign; negative numbers are parsed as a unary minus applied to
+    // a positive literal. This requires a digit before the decimal point, so
+    // that tuple element access (e.g. `t.0`) is a dot followed by an integer.
+    r#"\d+\.\d+"#,
+    // Number without decimal point, optionally with underscores as digit
+    // separators (e.g. `1_000_000`).
+    r#"\d[\d_]*"#,
+    // Unsigned comparisons `u<`, `u>`, `u<=`, and `u>=`. These take priority
+    // over identifiers, so a variable named `u` must be separated from a
+    // following `<` or `>` by whitespace.
+    r#"u[<>]=?"#,
     // Identifier consisting of a letter or underscore followed by any letters,
     // digits, and/or underscores, with an optional `#` (for tags) or `@` (for
     // directives) in front.
@@ -44,6 +58,10 @@ const TOKEN_PATTERNS: &'static [&'static str] = &[
     r#"(\.\.|\*\*|<<|>>>?)"#,
     // Equality checks `==`, `!=`, `<=`, and `>=`.
     r#"[=!<>]="#,
+    // Arrow `=>` (used in `match` statements).
+    r#"=>"#,
+    // Path separator `::` (used in `extern::name(...)`).
+    r#"::"#,
     // Any other single character.
     r#"[^\s]"#,
 ];
@@ -70,6 +88,8 @@ lazy_static! {
 
     /// A regex that matches a vector type name.
     static ref VEC_TYPE_PATTERN: Regex = Regex::new(r#"vec(\d+)"#).unwrap();
+    /// A regex that matches an array type name.
+    static ref ARRAY_TYPE_PATTERN: Regex = Regex::new(r#"^array(\d+)$"#).unwrap();
 }
 
 /// Splits a string into tokens and returns them as a Vec, with all comments
@@ -140,8 +160,8 @@ pub enum TokenClass<'a> {
     Operator(OperatorToken),
     /// Miscellaneous punctuation.
     Punctuation(PunctuationToken),
-    /// Integer literal.
-    Integer(LangInt),
+    /// Integer literal (without sign).
+    Integer(u64),
     /// String literal.
     String {
         /// Optional single-character prefix (like Python's `r"..."` and
@@ -201,8 +221,8 @@ impl<'a> TryFrom<&'a str> for TokenClass<'a> {
             Ok(Self::Operator(operator))
         } else if let Ok(punctuation) = s.parse() {
             Ok(Self::Punctuation(punctuation))
-        } else if let Ok(i) = s.parse() {
-            Ok(Self::Integer(i))
+        } else if let Some(i) = parse_integer(s) {
+            Ok(Self::Integer(i?))
         } else if let Some(captures) = STRING_PATTERN.captures(s) {
             if let Some(contents_capture) = captures.get(3) {
                 let prefix = captures.get(1).unwrap().as_str().chars().next();
@@ -253,24 +273,35 @@ enum_with_str_repr! {
         Remain = "remain",
         Return = "return",
 
+        // Assertions
+        Assert = "assert",
+
         // Branching
         Case = "case",
         Else = "else",
         If = "if",
+        Match = "match",
+        Then = "then",
         Unless = "unless",
 
         // Variables
         Set = "set",
+        Const = "const",
+        State = "state",
 
         // Boolean operators
         Or = "or",
         Xor = "xor",
         And = "and",
+        Not = "not",
 
         // Boolean tests
         In = "in",
         Is = "is",
 
+        // Host functions
+        Extern = "extern",
+
         // Unused reserved words
         Bind = "bind",
         Bound = "bound",
@@ -299,6 +330,7 @@ enum_with_str_repr! {
         Ampersand = "&",
         Pipe = "|",
         Caret = "^",
+        Tilde = "~",
 
         // Boolean operators and boolean tests are in KeywordToken.
 
@@ -318,6 +350,8 @@ enum_with_str_repr! {
         RBrace = "}",
         Comma = ",",
         Semicolon = ";",
+        FatArrow = "=>",
+        DoubleColon = "::",
     }
 
     /// Comparison.
@@ -335,6 +369,14 @@ enum_with_str_repr! {
         Lte = "<=",
         /// Greater than or equal.
         Gte = ">=",
+        /// Unsigned less than.
+        ULt = "u<",
+        /// Unsigned greater than.
+        UGt = "u>",
+        /// Unsigned less than or equal.
+        ULte = "u<=",
+        /// Unsigned greater than or equal.
+        UGte = "u>=",
     }
 }
 
@@ -348,16 +390,23 @@ impl KeywordToken {
             | Self::Become
             | Self::Remain
             | Self::Return
+            | Self::Assert
             | Self::Case
             | Self::Else
             | Self::If
+            | Self::Match
             | Self::Unless
             | Self::Set => true,
-            Self::Or
+            Self::Const
+            | Self::State
+            | Self::Then
+            | Self::Or
             | Self::Xor
             | Self::And
+            | Self::Not
             | Self::In
             | Self::Is
+            | Self::Extern
             | Self::Bind
             | Self::Bound
             | Self::Static
@@ -373,8 +422,17 @@ impl ComparisonToken {
     pub fn is_eq_only(self) -> bool {
         self == Self::Eql || self == Self::Neq
     }
+    /// Returns true if this comparison always treats its operands as unsigned
+    /// (e.g. `u<`), regardless of their type.
+    pub fn is_unsigned(self) -> bool {
+        match self {
+            Self::ULt | Self::UGt | Self::ULte | Self::UGte => true,
+            _ => false,
+        }
+    }
     /// Returns the predicate used by Inkwell that performs this comparison
     /// operation, given whether the operands are signed or unsigned.
+    /// Unsigned comparisons (e.g. `u<`) ignore `signed`.
     pub fn inkwell_predicate(self, signed: bool) -> inkwell::IntPredicate {
         use inkwell::IntPredicate::*;
         if signed {
@@ -385,27 +443,34 @@ impl ComparisonToken {
                 Self::Gt => SGT,
                 Self::Lte => SLE,
                 Self::Gte => SGE,
+                Self::ULt => ULT,
+                Self::UGt => UGT,
+                Self::ULte => ULE,
+                Self::UGte => UGE,
             }
         } else {
             match self {
                 Self::Eql => EQ,
                 Self::Neq => NE,
-                Self::Lt => SLT,
-                Self::Gt => SGT,
-                Self::Lte => SLE,
-                Self::Gte => SGE,
+                Self::Lt | Self::ULt => ULT,
+                Self::Gt | Self::UGt => UGT,
+                Self::Lte | Self::ULte => ULE,
+                Self::Gte | Self::UGte => UGE,
             }
         }
     }
     /// Evaluates this comparison using the given arguments.
+    ///
+    /// Unsigned comparisons (e.g. `u<`) compare the arguments as given, so the
+    /// caller must convert them to an unsigned type first.
     pub fn eval<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
         match self {
             Self::Eql => lhs == rhs,
             Self::Neq => lhs != rhs,
-            Self::Lt => lhs < rhs,
-            Self::Gt => lhs > rhs,
-            Self::Lte => lhs <= rhs,
-            Self::Gte => lhs >= rhs,
+            Self::Lt | Self::ULt => lhs < rhs,
+            Self::Gt | Self::UGt => lhs > rhs,
+            Self::Lte | Self::ULte => lhs <= rhs,
+            Self::Gte | Self::UGte => lhs >= rhs,
         }
     }
 }
@@ -414,16 +479,20 @@ impl ComparisonToken {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TypeToken {
     Int,
+    Bool,
     CellState,
     Vector(Option<usize>),
+    Array(usize),
 }
 impl fmt::Display for TypeToken {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
             Self::CellState => write!(f, "cellstate"),
             Self::Vector(None) => write!(f, "vec"),
             Self::Vector(Some(len)) => write!(f, "vec{}", len),
+            Self::Array(len) => write!(f, "array{}", len),
         }
     }
 }
@@ -441,8 +510,19 @@ impl FromStr for TypeToken {
                 return Ok(Self::Vector(Some(len)));
             }
         }
+        if let Some(len_str) = ARRAY_TYPE_PATTERN
+            .captures(s)
+            .and_then(|captures| captures.get(1))
+            .as_ref()
+            .map(regex::Match::as_str)
+        {
+            if let Ok(len @ 1..=super::types::MAX_ARRAY_LEN) = len_str.parse() {
+                return Ok(Self::Array(len));
+            }
+        }
         match s {
             "int" => Ok(Self::Int),
+            "bool" => Ok(Self::Bool),
             "cellstate" => Ok(Self::CellState),
             "vec" => Ok(Self::Vector(None)),
             _ => Err(()),
@@ -455,9 +535,11 @@ impl TypeToken {
     pub fn resolve(self, ndim: u8) -> Type {
         match self {
             Self::Int => Type::Int,
+            Self::Bool => Type::Bool,
             Self::CellState => Type::CellState,
             Self::Vector(None) => Type::Vector(ndim as usize),
             Self::Vector(Some(len)) => Type::Vector(len),
+            Self::Array(len) => Type::Array(len),
         }
     }
 }
@@ -501,6 +583,32 @@ impl FromStr for AssignmentToken {
         }
     }
 }
+/// Parses an integer literal, which is either decimal or has a base prefix
+/// (`0x`, `0b`, or `0o`), and may contain underscores between digits. Returns
+/// None if the string is not an integer literal at all, or an error if its
+/// digits or underscores are invalid or it does not fit in a u64.
+fn parse_integer(s: &str) -> Option<Result<u64, LangErrorMsg>> {
+    let (radix, radix_name, digits) = match s.get(..2) {
+        Some("0x") => (16, "hexadecimal", &s[2..]),
+        Some("0b") => (2, "binary", &s[2..]),
+        Some("0o") => (8, "octal", &s[2..]),
+        _ if s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_ascii_digit() || c == '_') =>
+        {
+            (10, "decimal", s)
+        }
+        _ => return None,
+    };
+    if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Some(Err(InvalidDigitSeparator));
+    }
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Some(Err(InvalidIntegerLiteral(radix_name)));
+    }
+    Some(u64::from_str_radix(&digits, radix).map_err(|_| IntegerOverflow))
+}
+
 impl AssignmentToken {
     /// Returns the token of the operator, if any, that this assignmeent uses.
     pub fn op(self) -> Option<OperatorToken> {
@@ -547,4 +655,48 @@ if this is {
             tokens[7].span.textpoints(source_code)
         );
     }
+
+    #[test]
+    fn test_integer_literals() {
+        let classes = |s| {
+            tokenize(s)
+                .map(|tokens| tokens.into_iter().map(|t| t.class).collect::<Vec<_>>())
+                .map_err(|e| e.msg)
+        };
+        assert_eq!(
+            Ok(vec![
+                TokenClass::Integer(255),
+                TokenClass::Integer(10),
+                TokenClass::Integer(15),
+                TokenClass::Integer(42),
+                TokenClass::Integer(0),
+            ]),
+            classes("0xFF 0b1010 0o17 42 0"),
+        );
+        assert_eq!(
+            Ok(vec![TokenClass::Integer(u64::MAX)]),
+            classes("0xffffffffffffffff"),
+        );
+        assert_eq!(Err(IntegerOverflow), classes("0x10000000000000000"));
+        assert_eq!(Err(IntegerOverflow), classes("18446744073709551616"));
+        assert_eq!(Err(InvalidIntegerLiteral("binary")), classes("0b102"));
+        assert_eq!(Err(InvalidIntegerLiteral("octal")), classes("0o8"));
+        assert_eq!(Err(InvalidIntegerLiteral("hexadecimal")), classes("0x"));
+
+        // Digit separators
+        assert_eq!(
+            Ok(vec![
+                TokenClass::Integer(1_000_000),
+                TokenClass::Integer(0xDEAD_BEEF),
+                TokenClass::Integer(0b1010_0101),
+            ]),
+            classes("1_000_000 0xDEAD_BEEF 0b1010_0101"),
+        );
+        assert_eq!(Err(InvalidDigitSeparator), classes("1000_"));
+        assert_eq!(Err(InvalidDigitSeparator), classes("1__000"));
+        assert_eq!(Err(InvalidDigitSeparator), classes("0x_FF"));
+        assert_eq!(Err(InvalidDigitSeparator), classes("0b1_"));
+        // A leading underscore makes an identifier instead.
+        assert_eq!(Ok(vec![TokenClass::Ident("_1000")]), classes("_1000"));
+    }
 }
@@ -33,6 +33,8 @@ use errors::LangResult;
 pub const MAX_NDIM: types::LangInt = 6;
 /// Maximum number of states.
 pub const MAX_STATES: types::LangInt = 256;
+/// Maximum neighborhood radius.
+pub const MAX_RADIUS: types::LangInt = 8;
 
 fn main() -> Result<(), ()> {
     let args: Vec<String> = std::env::args().collect();
@@ -59,7 +61,7 @@ Please specify a file to run. E.g.
     match result {
         Ok(ret) => println!("JIT-compiled transition function output: {:?}", ret),
         Err(err) => {
-            eprintln!("{}", err.with_source(&source_code));
+            eprintln!("{}", errors::format_with_source(&err, &source_code));
             Err(())?
         }
     }
@@ -70,10 +72,8 @@ Please specify a file to run. E.g.
 /// Runs the given rule's transition function using the compiler and returns the
 /// result.
 fn compile_and_run(source_code: Rc<String>) -> LangResult<ConstValue> {
-    let rule = ast::make_rule(source_code.clone())?;
-    let mut compiler = compiler::Compiler::new()?;
-    let mut transition_function = rule.transition_function().compile(&mut compiler)?;
-    transition_function.call()
+    let mut rule = compiler::CompiledRule::try_new(source_code)?;
+    rule.transition_function_mut().execute(&[])
 }
 
 #[cfg(test)]
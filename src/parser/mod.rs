@@ -11,8 +11,9 @@ use super::errors::*;
 use super::lexer::*;
 use super::{Span, Spanned};
 use LangErrorMsg::{
-    ElseWithoutIf, Expected, InternalError, InvalidDirectiveName, MissingSetKeyword, ReservedWord,
-    TopLevelNonDirective, Unimplemented, Unmatched,
+    ElseWithoutIf, Expected, InternalError, InvalidDirectiveName, InvalidTupleElement,
+    InvalidTupleLength, MissingSetKeyword, ReservedWord, TopLevelNonDirective, Unimplemented,
+    Unmatched,
 };
 
 /// Parses the given tokens and returns a ParseTree.
@@ -37,7 +38,9 @@ enum OpPrecedence {
     LogicalXor,
     LogicalAnd,
     LogicalNot,
+    Membership,
     Comparison,
+    Range,
     BitwiseOr,
     BitwiseXor,
     BitwiseAnd,
@@ -67,8 +70,10 @@ impl OpPrecedence {
             Self::LogicalOr => Self::LogicalXor,
             Self::LogicalXor => Self::LogicalAnd,
             Self::LogicalAnd => Self::LogicalNot,
-            Self::LogicalNot => Self::Comparison,
-            Self::Comparison => Self::BitwiseOr,
+            Self::LogicalNot => Self::Membership,
+            Self::Membership => Self::Comparison,
+            Self::Comparison => Self::Range,
+            Self::Range => Self::BitwiseOr,
             Self::BitwiseOr => Self::BitwiseXor,
             Self::BitwiseXor => Self::BitwiseAnd,
             Self::BitwiseAnd => Self::Bitshift,
@@ -209,10 +214,21 @@ impl<'a> ParseBuilder<'a> {
                     .map_err(|_| InvalidDirectiveName.with_span(self.span()))?;
                 let contents = match directive {
                     Directive::Function => self.expect(Self::function_definition)?,
+                    // `@no_prelude` doesn't take any arguments.
+                    Directive::NoPrelude => Spanned {
+                        span: self.span(),
+                        inner: DirectiveContents::Empty,
+                    },
                     _ => self.expect(Self::simple_directive_contents)?,
                 };
                 Ok((directive, contents))
             }
+            Some(TokenClass::Keyword(KeywordToken::Const)) => {
+                Ok((Directive::Const, self.expect(Self::definition)?))
+            }
+            Some(TokenClass::Keyword(KeywordToken::State)) => {
+                Ok((Directive::State, self.expect(Self::definition)?))
+            }
             Some(_) => self.err(TopLevelNonDirective),
             None => self.err(Expected("directive")),
         }
@@ -220,7 +236,7 @@ impl<'a> ParseBuilder<'a> {
     /// Consumes a function definition.
     fn function_definition(&mut self) -> LangResult<DirectiveContents> {
         Ok(DirectiveContents::Func(HelperFunc {
-            return_type: self.expect(Self::type_name)?,
+            return_type: self.expect(Self::return_type)?,
             name: self.expect(Self::ident)?,
             args: self
                 .expect(|pb| {
@@ -238,6 +254,44 @@ impl<'a> ParseBuilder<'a> {
             body: self.expect(Self::block)?,
         }))
     }
+    /// Consumes a named definition (of a constant or cell state), consisting of
+    /// an identifier followed by `=` and an expression.
+    fn definition(&mut self) -> LangResult<DirectiveContents> {
+        let name = self.expect(Self::ident)?;
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Assignment(AssignmentToken::Assign)) => (),
+            _ => self.err(Expected("'='"))?,
+        }
+        let value_expr = self.expect(Self::expression)?;
+        Ok(DirectiveContents::Definition { name, value_expr })
+    }
+    /// Consumes the return type of a helper function, which is either a type
+    /// name or a parenthesized tuple of type names, such as `(int, cellstate)`.
+    fn return_type(&mut self) -> LangResult<ReturnType> {
+        if !self.next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::LParen)]) {
+            return Ok(ReturnType::Single(self.type_name()?));
+        }
+        let elements = self.expect(|pb| {
+            pb.paren(|pb| {
+                pb.list(
+                    &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                    &[TokenClass::Punctuation(PunctuationToken::RParen)],
+                    Self::type_name,
+                    "tuple element type",
+                )
+            })
+        })?;
+        if elements.inner.inner.len() < 2 {
+            Err(InvalidTupleLength.with_span(elements.span))?;
+        }
+        for element in &elements.inner.inner {
+            match element.inner {
+                TypeToken::Int | TypeToken::Bool | TypeToken::CellState => (),
+                _ => Err(InvalidTupleElement.with_span(element.span))?,
+            }
+        }
+        Ok(ReturnType::Tuple(elements.inner.inner))
+    }
     /// Consumes a parmeter definition, consisting of a type followed by an
     /// identifier.
     fn function_param(&mut self) -> LangResult<(Spanned<TypeToken>, Spanned<String>)> {
@@ -290,12 +344,34 @@ impl<'a> ParseBuilder<'a> {
         use KeywordToken::*;
         match self.next().map(|t| t.class) {
             Some(TokenClass::Keyword(kw)) if kw.starts_statement() => match kw {
+                Assert => Ok(Statement::Assert {
+                    cond_expr: self.expect(Self::expression)?,
+                    msg: if self
+                        .next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::Comma)])
+                    {
+                        // There's a custom error message.
+                        self.next();
+                        Some(self.expect(Self::string)?.inner)
+                    } else {
+                        None
+                    },
+                }),
                 Become => Ok(Statement::Become(self.expect(Self::expression)?)),
-                Break => self.err(Unimplemented),
+                Break => Ok(Statement::Break),
                 Case => self.err(Unimplemented),
-                Continue => self.err(Unimplemented),
+                Continue => Ok(Statement::Continue),
                 Else => self.err(ElseWithoutIf),
-                For => self.err(Unimplemented),
+                For => Ok(Statement::For {
+                    var_name: self.expect(Self::ident)?,
+                    iter_expr: {
+                        match self.next().map(|t| t.class) {
+                            Some(TokenClass::Keyword(In)) => (),
+                            _ => self.err(Expected("'in'"))?,
+                        }
+                        self.expect(Self::expression)?
+                    },
+                    block: self.expect(Self::block)?.inner,
+                }),
                 If => Ok(Statement::If {
                     cond_expr: self.expect(Self::expression)?,
                     if_true: self.expect(Self::block)?.inner,
@@ -317,11 +393,57 @@ impl<'a> ParseBuilder<'a> {
                         vec![]
                     },
                 }),
-                Remain => self.err(Unimplemented),
+                Match => Ok({
+                    let expr = self.expect(Self::expression)?;
+                    let (cases, else_block) = self.expect(Self::match_cases)?.inner;
+                    Statement::Match {
+                        expr,
+                        cases,
+                        else_block,
+                    }
+                }),
+                Remain => Ok(Statement::Remain),
                 Return => Ok(Statement::Return(self.expect(Self::expression)?)),
+                Set if self
+                    .next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::LParen)]) =>
+                {
+                    // Get the variables to unpack a tuple into.
+                    let var_names = self
+                        .expect(|pb| {
+                            pb.paren(|pb| {
+                                pb.list(
+                                    &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                                    &[TokenClass::Punctuation(PunctuationToken::RParen)],
+                                    Self::ident,
+                                    "variable name",
+                                )
+                            })
+                        })?
+                        .inner
+                        .inner;
+                    // Unpacking cannot be combined with an operator.
+                    match self.next().map(|t| t.class) {
+                        Some(TokenClass::Assignment(AssignmentToken::Assign)) => (),
+                        _ => self.err(Expected("'='"))?,
+                    }
+                    // Get the tuple to unpack.
+                    let value_expr = self.expect(Self::expression)?;
+                    Ok(Statement::SetTuple {
+                        var_names,
+                        value_expr,
+                    })
+                }
                 Set => Ok({
                     // Get the variable name.
                     let var_name = self.expect(Self::ident)?;
+                    // Get the index of the array element to set (if any).
+                    let index_expr = if self.next_token_is_one_of(&[TokenClass::Punctuation(
+                        PunctuationToken::LBracket,
+                    )]) {
+                        Some(self.expect(|pb| pb.bracket(Self::expression))?)
+                    } else {
+                        None
+                    };
                     // Get the operator to use when assigning (if any). E.g.
                     // `+=` uses the `+` operator.
                     let assign_op = self.expect(Self::assign_op)?.inner;
@@ -330,12 +452,16 @@ impl<'a> ParseBuilder<'a> {
                     // Construct the statement.
                     Statement::SetVar {
                         var_name,
+                        index_expr,
                         assign_op,
                         value_expr,
                     }
                 }),
                 Unless => self.err(Unimplemented),
-                While => self.err(Unimplemented),
+                While => Ok(Statement::While {
+                    cond_expr: self.expect(Self::expression)?,
+                    block: self.expect(Self::block)?.inner,
+                }),
                 _ => self.err(Expected("statement")),
             },
             _ => {
@@ -349,6 +475,68 @@ impl<'a> ParseBuilder<'a> {
             }
         }
     }
+    /// Consumes the braced list of cases in a `match` statement and returns the
+    /// cases along with the `else` block (which is empty if there is no `else`
+    /// case). Cases may be separated by commas, and the `else` case must be
+    /// last.
+    fn match_cases(
+        &mut self,
+    ) -> LangResult<(Vec<(Spanned<Expr>, StatementBlock)>, StatementBlock)> {
+        // Get a left brace.
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::LBrace)) => (),
+            _ => self.err(Expected("match cases beginning with '{'"))?,
+        }
+        // Record the span of the left brace.
+        let open_span = self.span();
+        let mut cases = vec![];
+        let mut else_block = vec![];
+        loop {
+            match self.peek_next().map(|t| t.class) {
+                // There's a closing brace.
+                Some(TokenClass::Punctuation(PunctuationToken::RBrace)) => {
+                    self.next();
+                    break;
+                }
+                // There's an `else` case, which must be the last one.
+                Some(TokenClass::Keyword(KeywordToken::Else)) => {
+                    self.next();
+                    self.fat_arrow()?;
+                    else_block = self.expect(Self::block)?.inner;
+                    if self
+                        .next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::Comma)])
+                    {
+                        self.next();
+                    }
+                    match self.next().map(|t| t.class) {
+                        Some(TokenClass::Punctuation(PunctuationToken::RBrace)) => break,
+                        _ => self.err(Expected("'}' after 'else' case"))?,
+                    }
+                }
+                // There's some other case.
+                Some(_) => {
+                    let case_expr = self.expect(Self::expression)?;
+                    self.fat_arrow()?;
+                    cases.push((case_expr, self.expect(Self::block)?.inner));
+                    if self
+                        .next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::Comma)])
+                    {
+                        self.next();
+                    }
+                }
+                // We've reached the end of the file without closing the block.
+                None => Err(Unmatched('{', '}').with_span(open_span))?,
+            }
+        }
+        Ok((cases, else_block))
+    }
+    /// Consumes a `=>` token.
+    fn fat_arrow(&mut self) -> LangResult<()> {
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::FatArrow)) => Ok(()),
+            _ => self.err(Expected("'=>'")),
+        }
+    }
     /// Consumes a nested expression.
     fn expression(&mut self) -> LangResult<Expr> {
         // Start at the lowest precedence level.
@@ -364,10 +552,45 @@ impl<'a> ParseBuilder<'a> {
         // Get an expression at the given precedence level, which may
         // consist of expressions with higher precedence.
         match precedence {
+            OpPrecedence::LogicalOr => {
+                self.left_binary_op(&[TokenClass::Keyword(KeywordToken::Or)], precedence)
+            }
+            OpPrecedence::LogicalXor => {
+                self.left_binary_op(&[TokenClass::Keyword(KeywordToken::Xor)], precedence)
+            }
+            OpPrecedence::LogicalAnd => {
+                self.left_binary_op(&[TokenClass::Keyword(KeywordToken::And)], precedence)
+            }
+            OpPrecedence::LogicalNot => {
+                self.unary_op(&[TokenClass::Keyword(KeywordToken::Not)], precedence)
+            }
             OpPrecedence::UnaryPrefix => self.unary_op(
                 &[
                     TokenClass::Operator(OperatorToken::Tag),
+                    TokenClass::Operator(OperatorToken::Plus),
                     TokenClass::Operator(OperatorToken::Minus),
+                    TokenClass::Operator(OperatorToken::Tilde),
+                ],
+                precedence,
+            ),
+            OpPrecedence::Range => {
+                self.left_binary_op(&[TokenClass::Operator(OperatorToken::DotDot)], precedence)
+            }
+            OpPrecedence::BitwiseOr => {
+                self.left_binary_op(&[TokenClass::Operator(OperatorToken::Pipe)], precedence)
+            }
+            OpPrecedence::BitwiseXor => {
+                self.left_binary_op(&[TokenClass::Operator(OperatorToken::Caret)], precedence)
+            }
+            OpPrecedence::BitwiseAnd => self.left_binary_op(
+                &[TokenClass::Operator(OperatorToken::Ampersand)],
+                precedence,
+            ),
+            OpPrecedence::Bitshift => self.left_binary_op(
+                &[
+                    TokenClass::Operator(OperatorToken::DoubleLessThan),
+                    TokenClass::Operator(OperatorToken::DoubleGreaterThan),
+                    TokenClass::Operator(OperatorToken::TripleGreaterThan),
                 ],
                 precedence,
             ),
@@ -386,15 +609,32 @@ impl<'a> ParseBuilder<'a> {
                 ],
                 precedence,
             ),
+            OpPrecedence::Exp => self.exp_op(precedence),
+            OpPrecedence::Membership => self.membership_op(precedence),
             OpPrecedence::Comparison => self.comparison_op(precedence),
+            OpPrecedence::Dot => {
+                self.left_binary_op(&[TokenClass::Operator(OperatorToken::Dot)], precedence)
+            }
+            OpPrecedence::ArrayIndex => self.array_index(precedence),
+            OpPrecedence::FunctionCall => self.function_call(precedence),
             // TODO add remaining precedence levels
             OpPrecedence::Atom => match self.peek_next().map(|t| t.class) {
                 Some(TokenClass::Punctuation(PunctuationToken::LParen)) => {
                     // Expressions inside parentheses always start again at the
-                    // lowest precedence level.
+                    // lowest precedence level. If there is more than one
+                    // expression, this is a tuple instead.
                     self.expect_spanned(|pb| pb.paren(Self::expression))
+                        .or_else(|e| self.expect(Self::tuple_literal).map_err(|_| e))
+                }
+                Some(TokenClass::Punctuation(PunctuationToken::LBracket)) => {
+                    self.expect(Self::vector_literal)
+                }
+                Some(TokenClass::Punctuation(PunctuationToken::LBrace)) => {
+                    self.expect(Self::block_expression)
                 }
                 Some(TokenClass::Integer(_)) => self.expect(Self::int),
+                Some(TokenClass::Keyword(KeywordToken::If)) => self.expect(Self::conditional),
+                Some(TokenClass::Keyword(KeywordToken::Extern)) => self.expect(Self::extern_call),
                 Some(TokenClass::String { .. }) => self.err(Unimplemented),
                 Some(TokenClass::Tag(_)) => self.err(Unimplemented),
                 Some(TokenClass::Ident(_)) => self
@@ -431,6 +671,7 @@ impl<'a> ParseBuilder<'a> {
                 span: Span::merge(op_token, &*operand),
                 inner: match op_token.class {
                     TokenClass::Operator(op) => Expr::UnaryOp { op, operand },
+                    TokenClass::Keyword(KeywordToken::Not) => Expr::LogicalNot(operand),
                     other => Err(InternalError(
                         format!("Invalid unary operator: {:?}", other).into(),
                     ))?,
@@ -467,14 +708,39 @@ impl<'a> ParseBuilder<'a> {
                 span: Span::merge(&*lhs, &*rhs),
                 inner: match op_token.class {
                     TokenClass::Operator(op) => Expr::BinaryOp { lhs, op, rhs },
+                    TokenClass::Keyword(op) => Expr::LogicalOp { lhs, op, rhs },
                     other => Err(InternalError(
-                        format!("Invalid unary operator: {:?}", other).into(),
+                        format!("Invalid binary operator: {:?}", other).into(),
                     ))?,
                 },
             };
         }
         Ok(ret)
     }
+    /// Consumes an expression consisting of an exponentiation operator applied
+    /// to an expression of the given precedence level or higher. Unlike other
+    /// binary operators, exponentiation is right-associative, and its exponent
+    /// may have unary prefix operators (e.g. `2 ** -1`).
+    fn exp_op(&mut self, precedence: OpPrecedence) -> LangResult<Spanned<Expr>> {
+        // Get the base.
+        let lhs = self.expression_with_precedence(precedence.next())?;
+        if !self.next_token_is_one_of(&[TokenClass::Operator(OperatorToken::DoubleAsterisk)]) {
+            return Ok(lhs);
+        }
+        self.next();
+        // Get the exponent, which may itself contain exponentiation.
+        let rhs = self.expression_with_precedence(OpPrecedence::UnaryPrefix)?;
+        let lhs = Box::new(lhs);
+        let rhs = Box::new(rhs);
+        Ok(Spanned {
+            span: Span::merge(&*lhs, &*rhs),
+            inner: Expr::BinaryOp {
+                lhs,
+                op: OperatorToken::DoubleAsterisk,
+                rhs,
+            },
+        })
+    }
     /// Consumes an expression consisting of any number of chained comparison
     /// operators. This function is similar to left_binary_op().
     fn comparison_op(&mut self, precedence: OpPrecedence) -> LangResult<Spanned<Expr>> {
@@ -505,6 +771,231 @@ impl<'a> ParseBuilder<'a> {
             },
         })
     }
+    /// Consumes an expression of the given precedence level or higher,
+    /// optionally followed by `in` and a second such expression (e.g. `x in
+    /// 1..10`). Unlike other binary operators, `in` cannot be chained.
+    fn membership_op(&mut self, precedence: OpPrecedence) -> LangResult<Spanned<Expr>> {
+        // Get the value to look for.
+        let lhs = self.expression_with_precedence(precedence.next())?;
+        if !self.next_token_is_one_of(&[TokenClass::Keyword(KeywordToken::In)]) {
+            return Ok(lhs);
+        }
+        self.next();
+        // Get the range to look in.
+        let rhs = self.expression_with_precedence(precedence.next())?;
+        Ok(Spanned {
+            span: Span::merge(&lhs, &rhs),
+            inner: Expr::In {
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+        })
+    }
+    /// Consumes an expression of the given precedence level or higher, followed
+    /// by any number of bracketed indices.
+    fn array_index(&mut self, precedence: OpPrecedence) -> LangResult<Spanned<Expr>> {
+        let mut ret = self.expression_with_precedence(precedence.next())?;
+        while self.next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::LBracket)]) {
+            let index = self.expect(|pb| pb.bracket(Self::expression))?;
+            ret = Spanned {
+                // This indexing operation spans from the start of the operand
+                // to the closing bracket.
+                span: Span::merge(&ret, &index),
+                inner: Expr::Index {
+                    operand: Box::new(ret),
+                    index: Box::new(index.inner),
+                },
+            };
+        }
+        Ok(ret)
+    }
+    /// Consumes an expression of the given precedence level or higher, followed
+    /// by a parenthesized list of arguments if it is an identifier (i.e. a
+    /// function call).
+    fn function_call(&mut self, precedence: OpPrecedence) -> LangResult<Spanned<Expr>> {
+        let func = self.expression_with_precedence(precedence.next())?;
+        if let Expr::Ident(func_name) = &func.inner {
+            if self.next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::LParen)]) {
+                let args = self.expect(|pb| {
+                    pb.paren(|pb| {
+                        pb.list(
+                            &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                            &[TokenClass::Punctuation(PunctuationToken::RParen)],
+                            Self::expression,
+                            "function argument",
+                        )
+                    })
+                })?;
+                return Ok(Spanned {
+                    // This function call spans from the name of the function
+                    // to the closing parenthesis.
+                    span: Span::merge(&func, &args),
+                    inner: Expr::FnCall {
+                        func: Spanned {
+                            span: func.span,
+                            inner: func_name.clone(),
+                        },
+                        args: args.inner.inner,
+                    },
+                });
+            }
+        }
+        Ok(func)
+    }
+    /// Consumes a bracketed, comma-separated list of expressions (i.e. a
+    /// vector literal).
+    fn vector_literal(&mut self) -> LangResult<Expr> {
+        let components = self.bracket(|pb| {
+            pb.list(
+                &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                &[TokenClass::Punctuation(PunctuationToken::RBracket)],
+                Self::expression,
+                "vector component",
+            )
+        })?;
+        Ok(Expr::Group {
+            start_token: PunctuationToken::LBracket,
+            inner: Box::new(
+                components.map(|exprs| Expr::List(exprs.into_iter().map(Box::new).collect())),
+            ),
+        })
+    }
+    /// Consumes a tuple literal, such as `(1, #2)`.
+    fn tuple_literal(&mut self) -> LangResult<Expr> {
+        let elements = self.paren(|pb| {
+            pb.list(
+                &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                &[TokenClass::Punctuation(PunctuationToken::RParen)],
+                Self::expression,
+                "tuple element",
+            )
+        })?;
+        Ok(Expr::Group {
+            start_token: PunctuationToken::LParen,
+            inner: Box::new(
+                elements.map(|exprs| Expr::List(exprs.into_iter().map(Box::new).collect())),
+            ),
+        })
+    }
+    /// Consumes a conditional expression, such as `if x then 1 else 2` or `if
+    /// x { 1 } else { 2 }`. In the second form, each value is a block
+    /// expression, and the `else` value may instead be another conditional
+    /// expression in the same form (i.e. `else if`).
+    fn conditional(&mut self) -> LangResult<Expr> {
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Keyword(KeywordToken::If)) => (),
+            _ => self.err(Expected("'if'"))?,
+        }
+        let cond_expr = self.expect(Self::expression)?;
+        let is_block_form =
+            self.next_token_is_one_of(&[TokenClass::Punctuation(PunctuationToken::LBrace)]);
+        let if_true = if is_block_form {
+            self.expect(Self::block_expression)?
+        } else {
+            match self.next().map(|t| t.class) {
+                Some(TokenClass::Keyword(KeywordToken::Then)) => (),
+                _ => self.err(Expected("'then'"))?,
+            }
+            self.expect(Self::expression)?
+        };
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Keyword(KeywordToken::Else)) => (),
+            _ => self.err(Expected("'else'"))?,
+        }
+        let if_false = if !is_block_form {
+            self.expect(Self::expression)?
+        } else if self.next_token_is_one_of(&[TokenClass::Keyword(KeywordToken::If)]) {
+            self.expect(Self::conditional)?
+        } else {
+            self.expect(Self::block_expression)?
+        };
+        Ok(Expr::Conditional {
+            cond_expr: Box::new(cond_expr),
+            if_true: Box::new(if_true),
+            if_false: Box::new(if_false),
+        })
+    }
+    /// Consumes a block expression, which consists of statements followed by
+    /// an expression that gives the value of the block, such as `{ set y = x
+    /// * 2  y + 1 }`. The expression may be omitted, in which case the
+    /// statements must always become or return (which is checked when
+    /// building the AST).
+    ///
+    /// Something that could be either a statement or the value of the block
+    /// (such as `if c { ... } else { ... }`) is parsed as a statement if
+    /// possible.
+    fn block_expression(&mut self) -> LangResult<Expr> {
+        // Get a left brace.
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::LBrace)) => (),
+            _ => self.err(Expected("block expression"))?,
+        }
+        // Record the span of the left brace.
+        let open_span = self.span();
+        // Get statements.
+        let mut statements = vec![];
+        let value = loop {
+            match self.peek_next().map(|t| t.class) {
+                // There's the beginning of a statement, or possibly the value
+                // of the block.
+                Some(TokenClass::Keyword(kw)) if kw.starts_statement() => {
+                    match self.expect(Self::statement) {
+                        Ok(statement) => statements.push(statement),
+                        Err(e) => break Some(self.expect(Self::block_value).map_err(|_| e)?),
+                    }
+                }
+                // There's a closing brace, so the block has no value.
+                Some(TokenClass::Punctuation(PunctuationToken::RBrace)) => {
+                    self.next();
+                    break None;
+                }
+                // There's the value of the block.
+                Some(_) => break Some(self.expect(Self::block_value)?),
+                // We've reached the end of the file without closing the block.
+                None => Err(Unmatched('{', '}').with_span(open_span))?,
+            }
+        };
+        Ok(Expr::Block {
+            statements,
+            value: value.map(|spanned| Box::new(spanned.inner)),
+        })
+    }
+    /// Consumes the value at the end of a block expression, followed by the
+    /// closing brace (which is not included in the span of the value).
+    fn block_value(&mut self) -> LangResult<Spanned<Expr>> {
+        let value = self.expect(Self::expression)?;
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::RBrace)) => Ok(value),
+            _ => self.err(Expected("'}'")),
+        }
+    }
+    /// Consumes a call to a function provided by the host, such as
+    /// `extern::noise(x, y)`.
+    fn extern_call(&mut self) -> LangResult<Expr> {
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Keyword(KeywordToken::Extern)) => (),
+            _ => self.err(Expected("'extern'"))?,
+        }
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::DoubleColon)) => (),
+            _ => self.err(Expected("'::'"))?,
+        }
+        let func = self.expect(Self::ident)?;
+        let args = self.expect(|pb| {
+            pb.paren(|pb| {
+                pb.list(
+                    &[TokenClass::Punctuation(PunctuationToken::Comma)],
+                    &[TokenClass::Punctuation(PunctuationToken::RParen)],
+                    Self::expression,
+                    "function argument",
+                )
+            })
+        })?;
+        Ok(Expr::ExternCall {
+            func,
+            args: args.inner.inner,
+        })
+    }
     /// Consumes an integer literal.
     fn int(&mut self) -> LangResult<Expr> {
         match self.next().map(|t| t.class) {
@@ -512,11 +1003,24 @@ impl<'a> ParseBuilder<'a> {
             _ => self.err(Expected("integer")),
         }
     }
+    /// Consumes a string literal (without a prefix) and returns its contents.
+    fn string(&mut self) -> LangResult<String> {
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::String {
+                prefix: None,
+                contents,
+                ..
+            }) => Ok(contents.to_owned()),
+            _ => self.err(Expected("string")),
+        }
+    }
     /// Consumes a type name.
     fn type_name(&mut self) -> LangResult<TypeToken> {
         match self.next().map(|t| t.class) {
             Some(TokenClass::Type(ty)) => Ok(ty),
-            _ => self.err(Expected("type name; e.g. int, cellstate, vec, vec1..256")),
+            _ => self.err(Expected(
+                "type name; e.g. int, bool, cellstate, vec, vec1..256, array1..4096",
+            )),
         }
     }
     /// Consumes an identifier.
@@ -555,6 +1059,24 @@ impl<'a> ParseBuilder<'a> {
             None => Err(Unmatched('(', ')').with_span(open_span)),
         }
     }
+    /// Consumes a pair of square brackets with the given matcher run inside.
+    fn bracket<T>(
+        &mut self,
+        inner_matcher: impl FnOnce(&mut Self) -> LangResult<T>,
+    ) -> LangResult<Spanned<T>> {
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::LBracket)) => (),
+            _ => self.err(Expected("bracketed expression beginning with '['"))?,
+        }
+        // Record the span of the left bracket.
+        let open_span = self.span();
+        let expr = self.expect(inner_matcher)?;
+        match self.next().map(|t| t.class) {
+            Some(TokenClass::Punctuation(PunctuationToken::RBracket)) => Ok(expr),
+            Some(_) => self.err(Expected("']'")),
+            None => Err(Unmatched('[', ']').with_span(open_span)),
+        }
+    }
     /// Consumes a list of things (using the given matcher) separated by a given
     /// separator token and ending with any of the given end tokens.
     ///
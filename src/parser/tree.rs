@@ -6,9 +6,9 @@ use std::rc::Rc;
 
 use super::super::errors::*;
 use super::super::lexer::{
-    AssignmentToken, ComparisonToken, OperatorToken, PunctuationToken, TypeToken,
+    AssignmentToken, ComparisonToken, KeywordToken, OperatorToken, PunctuationToken, TypeToken,
 };
-use super::super::{Span, Spanned};
+use super::super::{Span, Spanned, Type};
 use LangErrorMsg::RepeatDirective;
 
 /// Parse tree of containing tokens.
@@ -60,8 +60,26 @@ pub enum Directive {
     States,
     /// Number of dimensions.
     Dimensions,
+    /// Neighborhood radius.
+    Radius,
+    /// Neighborhood shape.
+    Neighborhood,
+    /// Number of bits in an integer.
+    IntBits,
+    /// Behavior of integer arithmetic on overflow.
+    Overflow,
+    /// Seed for random numbers.
+    Seed,
     /// Helper function.
     Function,
+    /// Compile-time assertion.
+    Assert,
+    /// Named constant (declared using `const` rather than `@`).
+    Const,
+    /// Cell state name (declared using `state` rather than `@`).
+    State,
+    /// Opt-out of the standard prelude of helper functions.
+    NoPrelude,
 }
 impl Directive {
     pub fn name(self) -> &'static str {
@@ -69,7 +87,16 @@ impl Directive {
             Self::Transition => "transition",
             Self::States => "states",
             Self::Dimensions => "dimensions",
+            Self::Radius => "radius",
+            Self::Neighborhood => "neighborhood",
+            Self::IntBits => "bits",
+            Self::Overflow => "overflow",
+            Self::Seed => "seed",
             Self::Function => "function",
+            Self::Assert => "assert",
+            Self::Const => "const",
+            Self::State => "state",
+            Self::NoPrelude => "no_prelude",
         }
     }
 }
@@ -80,7 +107,14 @@ impl TryFrom<&str> for Directive {
             "transition" => Ok(Self::Transition),
             "states" => Ok(Self::States),
             "dim" | "dimen" | "dimensions" | "ndim" => Ok(Self::Dimensions),
+            "radius" => Ok(Self::Radius),
+            "nbhd" | "neighborhood" => Ok(Self::Neighborhood),
+            "bits" | "int_bits" => Ok(Self::IntBits),
+            "overflow" => Ok(Self::Overflow),
+            "seed" => Ok(Self::Seed),
             "fn" | "function" => Ok(Self::Function),
+            "assert" => Ok(Self::Assert),
+            "no_prelude" => Ok(Self::NoPrelude),
             _ => Err(()),
         }
     }
@@ -95,6 +129,15 @@ pub enum DirectiveContents {
     Expr(Spanned<Expr>),
     /// Function definition.
     Func(HelperFunc),
+    /// Named definition of a constant or cell state.
+    Definition {
+        /// Name being defined.
+        name: Spanned<String>,
+        /// Value of the constant or cell state.
+        value_expr: Spanned<Expr>,
+    },
+    /// Nothing (for directives that take no arguments).
+    Empty,
 }
 impl From<Spanned<StatementBlock>> for DirectiveContents {
     fn from(block: Spanned<StatementBlock>) -> Self {
@@ -111,7 +154,7 @@ impl From<Spanned<Expr>> for DirectiveContents {
 #[derive(Debug, Clone)]
 pub struct HelperFunc {
     /// Type returned by the helper function.
-    pub return_type: Spanned<TypeToken>,
+    pub return_type: Spanned<ReturnType>,
     /// Name of the helper function.
     pub name: Spanned<String>,
     /// Arguments passed to the helper function (name and type).
@@ -120,6 +163,31 @@ pub struct HelperFunc {
     pub body: Spanned<StatementBlock>,
 }
 
+/// Return type of a helper function in the parse tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReturnType {
+    /// Single type, such as `int`.
+    Single(TypeToken),
+    /// Tuple of two or more types, such as `(int, cellstate)`. Each element is
+    /// an integer, boolean, or cell state.
+    Tuple(Vec<Spanned<TypeToken>>),
+}
+impl ReturnType {
+    /// Returns the Type corresponding to this return type, in an automaton
+    /// with the given number of dimensions.
+    pub fn resolve(&self, ndim: u8) -> Type {
+        match self {
+            Self::Single(ty) => ty.resolve(ndim),
+            Self::Tuple(elements) => Type::tuple(
+                &elements
+                    .iter()
+                    .map(|ty| ty.inner.resolve(ndim))
+                    .collect::<Vec<_>>(),
+            ),
+        }
+    }
+}
+
 /// Statement node in the parse tree.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Statement {
@@ -127,11 +195,22 @@ pub enum Statement {
     SetVar {
         /// Variable to set.
         var_name: Spanned<String>,
+        /// Index of the array element to set, if any. The outer span includes
+        /// the brackets.
+        index_expr: Option<Spanned<Spanned<Expr>>>,
         /// Assignment operator.
         assign_op: AssignmentToken,
         /// Value to store in the variable.
         value_expr: Spanned<Expr>,
     },
+    /// Sets several variables at once from the elements of a tuple, such as
+    /// `set (a, b) = f()`.
+    SetTuple {
+        /// Variables to set, one for each element of the tuple.
+        var_names: Vec<Spanned<String>>,
+        /// Tuple to unpack.
+        value_expr: Spanned<Expr>,
+    },
     /// Branches conditionally.
     If {
         /// Condition.
@@ -141,14 +220,48 @@ pub enum Statement {
         /// Statements to execute if condition is falsey.
         if_false: StatementBlock,
     },
-    // ForLoop(Spanned<Expr>, Spanned<Expr>, StatementBlock),
-    // WhileLoop(Spanned<Expr>, StatementBlock),
+    /// Branches based on the value of an expression.
+    Match {
+        /// Expression to branch based on.
+        expr: Spanned<Expr>,
+        /// Cases, each consisting of a value to compare against and the
+        /// statements to execute if it matches.
+        cases: Vec<(Spanned<Expr>, StatementBlock)>,
+        /// Statements to execute if no case matches.
+        else_block: StatementBlock,
+    },
+    /// Loops over a range of values.
+    For {
+        /// Loop variable.
+        var_name: Spanned<String>,
+        /// Expression to iterate over.
+        iter_expr: Spanned<Expr>,
+        /// Statements to execute on each iteration.
+        block: StatementBlock,
+    },
+    /// Loops while a condition is truthy.
+    While {
+        /// Condition.
+        cond_expr: Spanned<Expr>,
+        /// Statements to execute on each iteration.
+        block: StatementBlock,
+    },
     // DoWhileLoop(StatementBlock, Spanned<Expr>),
-    // Break,
-    // Continue,
+    /// Exits the innermost loop.
+    Break,
+    /// Skips to the next iteration of the innermost loop.
+    Continue,
+
+    /// Returns an error if a condition is falsey.
+    Assert {
+        /// Condition.
+        cond_expr: Spanned<Expr>,
+        /// Custom error message (if any).
+        msg: Option<String>,
+    },
 
-    // /// Returns the center cell state from the transition function.
-    // Remain,
+    /// Returns the center cell state from the transition function.
+    Remain,
     /// Returns a value from a transition function.
     Become(Spanned<Expr>),
     /// Returns a value from a helper function.
@@ -158,8 +271,9 @@ pub enum Statement {
 /// Expression node in the parse tree.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Expr {
-    /// Integer literal.
-    Int(i64),
+    /// Integer literal (without sign). This may be larger than the maximum
+    /// integer value, since it may be negated.
+    Int(u64),
     /// Identifier.
     Ident(String),
     /// Parethetical or bracketed group.
@@ -187,6 +301,63 @@ pub enum Expr {
         /// Right-hand-side operand.
         rhs: Box<Spanned<Expr>>,
     },
+    /// Logical operation on two values, such as `a and b`.
+    LogicalOp {
+        /// Left-hand-side operand.
+        lhs: Box<Spanned<Expr>>,
+        /// Operator (`and`, `or`, or `xor`).
+        op: KeywordToken,
+        /// Right-hand-side operand.
+        rhs: Box<Spanned<Expr>>,
+    },
+    /// Logical negation, such as `not a`.
+    LogicalNot(Box<Spanned<Expr>>),
+    /// Range membership test, such as `x in 1..10`.
+    In {
+        /// Value to look for.
+        lhs: Box<Spanned<Expr>>,
+        /// Range to look in.
+        rhs: Box<Spanned<Expr>>,
+    },
+    /// Indexing operation, such as `v[2]`.
+    Index {
+        /// Value being indexed.
+        operand: Box<Spanned<Expr>>,
+        /// Index.
+        index: Box<Spanned<Expr>>,
+    },
+    /// Conditional expression, such as `if x then 1 else 2`.
+    Conditional {
+        /// Condition.
+        cond_expr: Box<Spanned<Expr>>,
+        /// Value if the condition is truthy.
+        if_true: Box<Spanned<Expr>>,
+        /// Value if the condition is falsey.
+        if_false: Box<Spanned<Expr>>,
+    },
+    /// Block expression, such as `{ set y = x * 2  y + 1 }`, whose value is
+    /// the expression at the end of the block.
+    Block {
+        /// Statements to execute before evaluating the value.
+        statements: StatementBlock,
+        /// Value of the block, which may only be omitted if the statements
+        /// always become or return.
+        value: Option<Box<Spanned<Expr>>>,
+    },
+    /// Function call.
+    FnCall {
+        /// Name of the function.
+        func: Spanned<String>,
+        /// Arguments passed to the function.
+        args: Vec<Spanned<Expr>>,
+    },
+    /// Call to a function provided by the host, such as `extern::noise(x, y)`.
+    ExternCall {
+        /// Name of the function.
+        func: Spanned<String>,
+        /// Arguments passed to the function.
+        args: Vec<Spanned<Expr>>,
+    },
     /// Comparison between two values.
     Cmp {
         /// Expressions to compare (at least two).
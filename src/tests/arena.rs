@@ -0,0 +1,122 @@
+use std::rc::Rc;
+
+use super::super::compiler::RuleArena;
+use super::super::errors::LangErrorMsg;
+use super::ConstValue;
+
+#[test]
+fn test_rule_arena() {
+    let mut arena = RuleArena::new();
+    assert!(arena.is_empty());
+
+    // Load several rules with different parameters into the same arena.
+    arena
+        .add_rule("one", Rc::new("@transition { become #1 }".to_owned()))
+        .unwrap();
+    arena
+        .add_rule(
+            "count",
+            Rc::new(
+                "
+                @states 10
+                @transition {
+                    become #(count(#1))
+                }"
+                .to_owned(),
+            ),
+        )
+        .unwrap();
+    arena
+        .add_rule(
+            "narrow",
+            Rc::new(
+                "
+                @bits 16
+                @function int double(int x) {
+                    return x * 2
+                }
+                @transition {
+                    become #(double(1))
+                }"
+                .to_owned(),
+            ),
+        )
+        .unwrap();
+    assert_eq!(3, arena.len());
+    let mut names: Vec<&str> = arena.names().collect();
+    names.sort();
+    assert_eq!(vec!["count", "narrow", "one"], names);
+
+    // Execute each of them.
+    let mut one = arena.get("one").unwrap();
+    assert_eq!(
+        ConstValue::CellState(1),
+        one.transition_function_mut().execute(&[]).unwrap(),
+    );
+    let mut count = arena.get("count").unwrap();
+    count
+        .transition_function_mut()
+        .set_nbhd(&[1, 1, 0, 1, 0, 0, 1, 0, 1]);
+    assert_eq!(
+        ConstValue::CellState(5),
+        count.transition_function_mut().execute(&[]).unwrap(),
+    );
+    let mut narrow = arena.get("narrow").unwrap();
+    assert_eq!(
+        ConstValue::CellState(2),
+        narrow.transition_function_mut().execute(&[]).unwrap(),
+    );
+    assert!(arena.get("two").is_none());
+
+    // Rules returned from the arena are independent of each other.
+    let mut other_count = arena.get("count").unwrap();
+    other_count.transition_function_mut().set_nbhd(&[0; 9]);
+    assert_eq!(
+        ConstValue::CellState(0),
+        other_count.transition_function_mut().execute(&[]).unwrap(),
+    );
+    assert_eq!(
+        ConstValue::CellState(5),
+        count.transition_function_mut().execute(&[]).unwrap(),
+    );
+
+    // Adding a rule with an existing name replaces it, unless the new rule
+    // has an error.
+    arena
+        .add_rule("one", Rc::new("@transition { become #0 }".to_owned()))
+        .unwrap();
+    let error = arena
+        .add_rule("one", Rc::new("@transition { become #2 }".to_owned()))
+        .unwrap_err();
+    assert_eq!(LangErrorMsg::CellStateOutOfRange, error.msg);
+    assert_eq!(3, arena.len());
+    assert_eq!(
+        ConstValue::CellState(0),
+        arena
+            .get("one")
+            .unwrap()
+            .transition_function_mut()
+            .execute(&[])
+            .unwrap(),
+    );
+
+    // Rules that have already been handed out keep working after they are
+    // removed from the arena.
+    assert!(arena.remove("count").is_some());
+    assert!(arena.remove("count").is_none());
+    assert!(!arena.contains("count"));
+    assert_eq!(
+        ConstValue::CellState(5),
+        count.transition_function_mut().execute(&[]).unwrap(),
+    );
+    arena.clear();
+    assert!(arena.is_empty());
+    assert_eq!(
+        ConstValue::CellState(2),
+        narrow.transition_function_mut().execute(&[]).unwrap(),
+    );
+    assert_eq!(
+        ConstValue::CellState(1),
+        one.transition_function_mut().execute(&[]).unwrap(),
+    );
+}
@@ -0,0 +1,125 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_array_access() {
+    assert_output(
+        Ok(ConstValue::CellState(9)),
+        "
+        @states 10
+        @transition {
+            set a = array(4)
+            for i in 0..3 {
+                set a[i] = i * 2
+            }
+            set a[1] += 3
+            become #(a[1] + a[2] + a[0])
+        }",
+    );
+
+    // Arrays are passed in and returned by value.
+    let source_code = "
+        @function array3 f(array3 a) {
+            set a[2] = a[0] + a[1]
+            return a
+        }";
+    assert_func_output(
+        &[ConstValue::Array(vec![1, 2, 0])],
+        Ok(ConstValue::Array(vec![1, 2, 3])),
+        source_code,
+        Some("f"),
+    );
+
+    // Assigning an array copies it.
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(5)),
+        "
+        @function int f() {
+            set a = array(2)
+            set b = a
+            set a[0] = 5
+            return b[0] * 10 + a[0]
+        }",
+        Some("f"),
+    );
+}
+
+#[test]
+fn test_array_index_out_of_bounds() {
+    // Index out of bounds at runtime
+    let source_code = "
+        @function int f(int i) {
+            set a = array(4)
+            return a[i]
+        }";
+    for &i in &[-1, 4] {
+        assert_func_output(
+            &[ConstValue::Int(i)],
+            Err("Error at line 4; column 20
+return a[i]
+       ^^^^   Index out of bounds"),
+            source_code,
+            Some("f"),
+        );
+    }
+    let source_code = "
+        @function int f(int i) {
+            set a = array(4)
+            set a[i] = 1
+            return 0
+        }";
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Ok(ConstValue::Int(0)),
+        source_code,
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(4)],
+        Err("Error at line 4; column 17
+set a[i] = 1
+    ^^^^   Index out of bounds"),
+        source_code,
+        Some("f"),
+    );
+
+    // Constant index out of bounds
+    assert_output(
+        Err("Error at line 4; column 22
+return a[4]
+         ^   Index out of bounds"),
+        "
+        @function int f() {
+            set a = array(4)
+            return a[4]
+        }",
+    );
+}
+
+#[test]
+fn test_array_errors() {
+    // Invalid length
+    assert_output(
+        Err("Error at line 3; column 27
+set a = array(0)
+              ^   Array length must range from 1 to 4096"),
+        "
+        @function int f() {
+            set a = array(0)
+            return 0
+        }",
+    );
+
+    // Assigning to an element of an integer
+    assert_output(
+        Err("Error at line 4; column 17
+set x[0] = 1
+    ^   Type error: cannot assign to an element of integer"),
+        "
+        @function int f() {
+            set x = 3
+            set x[0] = 1
+            return 0
+        }",
+    );
+}
@@ -0,0 +1,88 @@
+use super::{assert_func_output, assert_output, eval_func, ConstValue, LangErrorMsg};
+
+#[test]
+fn test_assert() {
+    let source_code = "
+        @function int f(int x) {
+            assert x > 0, \"x must be positive\"
+            assert x - 3
+            return x
+        }";
+    assert_func_output(
+        &[ConstValue::Int(5)],
+        Ok(ConstValue::Int(5)),
+        source_code,
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(-2)],
+        Err("Error at line 3; column 20
+assert x > 0, \"x must be positive\"
+       ^^^^^   Assertion failed: x must be positive"),
+        source_code,
+        Some("f"),
+    );
+    // Integers are truthy if they are nonzero.
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Err("Error at line 4; column 20
+assert x - 3
+       ^^^^^   Assertion failed"),
+        source_code,
+        Some("f"),
+    );
+    // The custom message is part of the error.
+    assert_eq!(
+        Err(LangErrorMsg::AssertionFailed(Some(
+            "x must be positive".to_owned()
+        ))),
+        eval_func(&[ConstValue::Int(0)], source_code, "f"),
+    );
+
+    // An assertion that is always true compiles to nothing.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            assert 2 > 1, \"math is broken\"
+            become #1
+        }",
+    );
+}
+
+#[test]
+fn test_assert_errors() {
+    // An assertion that is always false is an error at compile time.
+    assert_output(
+        Err("Error at line 3; column 20
+assert 1 + 1 == 3, \"math is broken\"
+       ^^^^^^^^^^   Assertion failed: math is broken"),
+        "
+        @transition {
+            assert 1 + 1 == 3, \"math is broken\"
+            become #1
+        }",
+    );
+    // The condition must be a boolean or an integer.
+    assert_output(
+        Err("Error at line 3; column 20
+assert [1, 2]
+       ^^^^^^   Type error: expected boolean but got vector of length 2"),
+        "
+        @transition {
+            assert [1, 2]
+            become #1
+        }",
+    );
+    // The message must be a string.
+    assert_output(
+        Err("Error at line 3; column 23
+assert 1, 2
+          ^   Expected string"),
+        "
+        @transition {
+            assert 1, 2
+            become #1
+        }",
+    );
+}
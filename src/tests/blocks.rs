@@ -0,0 +1,133 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_block_expr() {
+    // The value of a block is the expression at the end of it.
+    for &(x, expected) in &[(4, 9), (-1, -1)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            "
+            @function int test(int x) {
+                set y = {
+                    set doubled = x * 2
+                    doubled + 1
+                }
+                return y
+            }",
+            Some("test"),
+        );
+    }
+
+    // Conditional expression with blocks
+    for &(x, expected) in &[(0, 2), (5, 1)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            "@function int test(int x) { set y = { if x { 1 } else { 2 } }  return y }",
+            Some("test"),
+        );
+    }
+
+    // Statements in a block are executed even if its value is constant.
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(6)),
+        "
+        @function int test() {
+            set y = 0
+            set x = { set y = 5  1 }
+            return x + y
+        }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_nested_block_expr() {
+    // Some branches return from the function, and others produce a value.
+    for &(x, expected) in &[(-5, -1), (0, 200), (3, 40)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            "
+            @function int test(int x) {
+                set y = {
+                    if x < 0 {
+                        return -1
+                    } else if x == 0 {
+                        {
+                            set z = 10
+                            z * 2
+                        }
+                    } else {
+                        x + 1
+                    }
+                }
+                return y * 10
+            }",
+            Some("test"),
+        );
+    }
+
+    // A branch that becomes a cell state can be used where a cell state is
+    // expected.
+    for &(n, expected) in &[(3, 2), (1, 1)] {
+        assert_output(
+            Ok(ConstValue::CellState(expected)),
+            &format!(
+                "
+                @transition {{
+                    set n = {}
+                    become {{ if n > 2 {{ become #2 }} else {{ #(n) }} }}
+                }}
+                @states 3",
+                n,
+            ),
+        );
+    }
+
+    // Blocks that terminate on every path never produce a value, so anything
+    // after them is unreachable.
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 78
+@function int test(int x) { set y = { if x { return 1 } else { return 2 } }  return y }
+                                                                             ^^^^^^^^   This statement is unreachable"),
+        "@function int test(int x) { set y = { if x { return 1 } else { return 2 } }  return y }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_block_expr_errors() {
+    // Missing value
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return { set y = 1 } }
+                              ^^^^^^^^^^^^^   This block must end with an expression, or else become or return on every path"),
+        "@function int test() { return { set y = 1 } }",
+        Some("test"),
+    );
+
+    // Value after a statement that always returns
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 43
+@function int test() { return { return 1  2 } }
+                                          ^   This statement is unreachable"),
+        "@function int test() { return { return 1  2 } }",
+        Some("test"),
+    );
+
+    // Variables assigned in only one branch of a conditional expression
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 82
+@function int test(int x) { set y = if x { set z = 1  z } else { 2 }  return y + z }
+                                                                                 ^   This variable must be initialized before it is used"),
+        "@function int test(int x) { set y = if x { set z = 1  z } else { 2 }  return y + z }",
+        Some("test"),
+    );
+}
@@ -0,0 +1,49 @@
+use std::rc::Rc;
+
+use super::super::compiler::{Compiler, CompilerCache};
+use super::ConstValue;
+
+#[test]
+fn test_compiler_cache() {
+    let make_source = |i: usize| {
+        Rc::new(format!(
+            "
+            @transition {{
+                become #{}
+            }}
+            @states 10",
+            i,
+        ))
+    };
+    let cache = CompilerCache::with_capacity(2);
+
+    // Compiling the same rule twice only adds it to the cache once.
+    let mut f = Compiler::from_cache_or_build(make_source(1), &cache).unwrap();
+    assert_eq!(ConstValue::CellState(1), f.execute(&[]).unwrap());
+    let mut f = Compiler::from_cache_or_build(make_source(1), &cache).unwrap();
+    assert_eq!(ConstValue::CellState(1), f.execute(&[]).unwrap());
+    assert_eq!(1, cache.len());
+
+    // Changing the rule's parameters produces a different entry.
+    let source = Rc::new(make_source(1).replace("@states 10", "@states 20"));
+    let mut g = Compiler::from_cache_or_build(source, &cache).unwrap();
+    assert_eq!(ConstValue::CellState(1), g.execute(&[]).unwrap());
+    assert_eq!(2, cache.len());
+
+    // The least recently used rule is evicted when the cache is full, but
+    // functions that were already returned still work.
+    let mut h = Compiler::from_cache_or_build(make_source(2), &cache).unwrap();
+    assert_eq!(ConstValue::CellState(2), h.execute(&[]).unwrap());
+    assert_eq!(2, cache.len());
+    assert_eq!(ConstValue::CellState(1), f.execute(&[]).unwrap());
+
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(ConstValue::CellState(1), g.execute(&[]).unwrap());
+    assert_eq!(ConstValue::CellState(2), h.execute(&[]).unwrap());
+
+    // Errors are not cached.
+    let source = Rc::new("@transition { become 3 }".to_owned());
+    assert!(Compiler::from_cache_or_build(source, &cache).is_err());
+    assert!(cache.is_empty());
+}
@@ -1,4 +1,4 @@
-use super::{assert_output, ConstValue};
+use super::{assert_func_output, assert_output, ConstValue};
 
 #[test]
 fn test_branch_nonzero() {
@@ -51,3 +51,132 @@ fn test_cmp() {
         }",
     );
 }
+
+#[test]
+fn test_unsigned_cmp() {
+    for &(lhs, op, rhs, expected) in &[
+        (-1, "u>", 0, true),
+        (-1, ">", 0, false),
+        (1, "u<", 2, true),
+        (-2, "u<=", -1, true),
+        (-1, "u>=", -1, true),
+        (0, "u>=", -1, false),
+    ] {
+        let expected = Ok(ConstValue::CellState(expected as u8));
+        // Compare at runtime.
+        assert_output(
+            expected.clone(),
+            &format!(
+                "
+                @transition {{
+                    set x = {}
+                    set y = {}
+                    become #(x {} y)
+                }}",
+                lhs, rhs, op,
+            ),
+        );
+        // Compare at compile time.
+        assert_output(
+            expected,
+            &format!(
+                "
+                @transition {{
+                    become #({} {} {})
+                }}",
+                lhs, op, rhs,
+            ),
+        );
+    }
+
+    // Signed and unsigned comparisons can be chained.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            set x = -1
+            become #(0 < 1 u< x)
+        }",
+    );
+}
+
+#[test]
+fn test_bool() {
+    // Booleans can be stored in variables and used as conditions.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            set b = 1 < 2
+            if b {
+                become #1
+            }
+            become #0
+        }",
+    );
+    // Booleans can be compared for equality.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            if (1 < 2) == (3 > 4) {
+                become #0
+            }
+            if (1 < 2) != (3 > 4) {
+                become #1
+            }
+            become #0
+        }",
+    );
+    // Booleans are converted to integers where necessary.
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @transition {
+            set x = 0
+            set x = 2 < 3
+            become #(x + (3 > 2) + (2 > 3))
+        }
+        @states 3",
+    );
+    // Booleans can be passed to and returned from functions.
+    assert_func_output(
+        &[ConstValue::Int(5)],
+        Ok(ConstValue::Bool(true)),
+        "@function bool test(int x) { return x > 3 }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Bool(false)],
+        Ok(ConstValue::Int(2)),
+        "@function int test(bool b) { if b { return 1 } return 2 }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_cell_state_cmp() {
+    // Cell states can be compared to integers.
+    assert_func_output(
+        &[ConstValue::CellState(2)],
+        Ok(ConstValue::Bool(true)),
+        "@function bool test(cellstate s) { return 1 < s == 2 }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::CellState(2)],
+        Ok(ConstValue::Bool(false)),
+        "@function bool test(cellstate s) { return s == #2 != 1 }",
+        Some("test"),
+    );
+
+    // Cell states compared to other cell states are not converted.
+    assert_func_output(
+        &[ConstValue::CellState(2)],
+        Err("Error at line 1; column 43
+@function bool test(cellstate s) { return s < #3 }
+                                          ^^^^^^   Type error: cannot compare cell state to cell state using '<'; convert them to integers first using the '#id' tag"),
+        "@function bool test(cellstate s) { return s < #3 }",
+        Some("test"),
+    );
+}
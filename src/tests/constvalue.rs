@@ -0,0 +1,74 @@
+use proptest::prelude::*;
+
+use super::super::types::{LangCellState, LangInt, MAX_ARRAY_LEN, MAX_VECTOR_LEN};
+use super::ConstValue;
+
+#[test]
+fn test_const_value_display() {
+    assert_eq!("42", ConstValue::Int(42).to_string());
+    assert_eq!("-7", ConstValue::Int(-7).to_string());
+    assert_eq!("true", ConstValue::Bool(true).to_string());
+    assert_eq!("#3", ConstValue::CellState(3).to_string());
+    assert_eq!("[1, 2, 3]", ConstValue::Vector(vec![1, 2, 3]).to_string());
+    assert_eq!("[-5]", ConstValue::Vector(vec![-5]).to_string());
+    assert_eq!("array[0, 7]", ConstValue::Array(vec![0, 7]).to_string());
+    assert_eq!(
+        "(1, #2)",
+        ConstValue::Tuple(vec![ConstValue::Int(1), ConstValue::CellState(2)]).to_string()
+    );
+    assert_eq!("-1..1", ConstValue::Range(-1, 1).to_string());
+}
+
+#[test]
+fn test_const_value_from_str() {
+    assert_eq!(Ok(ConstValue::Int(42)), " 42 ".parse());
+    assert_eq!(Ok(ConstValue::Bool(false)), "false".parse());
+    assert_eq!(Ok(ConstValue::CellState(3)), "#3".parse());
+    assert_eq!(Ok(ConstValue::Vector(vec![1, 2, 3])), "[1,2, 3]".parse());
+    assert_eq!(Ok(ConstValue::Array(vec![4, -1])), "array [4, -1]".parse());
+    assert_eq!(
+        Ok(ConstValue::Tuple(vec![
+            ConstValue::Bool(true),
+            ConstValue::Int(-2)
+        ])),
+        "( true,-2 )".parse()
+    );
+    assert_eq!(Ok(ConstValue::Range(-3, -1)), "-3 .. -1".parse());
+    assert_eq!(Err(()), "".parse::<ConstValue>());
+    assert_eq!(Err(()), "[]".parse::<ConstValue>());
+    assert_eq!(Err(()), "[1, 2,]".parse::<ConstValue>());
+    assert_eq!(Err(()), "#256".parse::<ConstValue>());
+    assert_eq!(Err(()), "#-1".parse::<ConstValue>());
+    assert_eq!(Err(()), "[1, 2".parse::<ConstValue>());
+    assert_eq!(Err(()), "1..".parse::<ConstValue>());
+    assert_eq!(Err(()), "(1)".parse::<ConstValue>());
+}
+
+proptest! {
+    #[test]
+    fn proptest_const_value_round_trip(
+        i: LangInt,
+        b: bool,
+        cell_state: LangCellState,
+        values in prop::collection::vec(any::<LangInt>(), 1..=MAX_VECTOR_LEN),
+        elements in prop::collection::vec(any::<LangInt>(), 1..=MAX_ARRAY_LEN),
+        start: LangInt,
+        end: LangInt,
+    ) {
+        for value in vec![
+            ConstValue::Int(i),
+            ConstValue::Bool(b),
+            ConstValue::CellState(cell_state),
+            ConstValue::Vector(values),
+            ConstValue::Array(elements),
+            ConstValue::Tuple(vec![
+                ConstValue::Int(i),
+                ConstValue::Bool(b),
+                ConstValue::CellState(cell_state),
+            ]),
+            ConstValue::Range(start, end),
+        ] {
+            prop_assert_eq!(Ok(value.clone()), value.to_string().parse());
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use inkwell::OptimizationLevel;
+
+use super::compiler::{Compiler, Value};
+use super::{assert_func_output, ast};
+use super::{ConstValue, Type};
+
+#[test]
+fn test_dump_ast() {
+    let source_code = "
+        @transition {
+            set x = 3
+            set x += 2
+            become #(x)
+        }
+        @states 6";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let dump = rule.transition_function().dump_ast();
+    println!("{}", dump);
+
+    assert!(dump.starts_with("function \"transition\" -> cell state\n"));
+    // `x += 2` desugars to `x = x + 2`.
+    assert!(dump.contains("set variable \"x\" @ "));
+    assert!(dump.contains("binary \"+\" operator (Operator): integer @ "));
+    assert!(dump.contains("variable \"x\" (Atom): integer @ "));
+    assert!(dump.contains("return @ "));
+    assert!(dump.contains("unary \"#\" operator (Operator): cell state @ "));
+}
+
+#[test]
+fn test_print_llvm_ir() {
+    let source_code = "
+        @transition {
+            set x = 3
+            become #(x / 2)
+        }
+        @states 6";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    // Disable optimization so that the IR is the same as was built.
+    compiler.set_opt_level(OptimizationLevel::None);
+    rule.transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    let ir = compiler.print_to_string();
+    println!("{}", ir);
+
+    assert!(ir.contains("@transition("));
+    // Division builds a check for division by zero.
+    assert!(ir.contains("isDivByZero"));
+}
+
+#[test]
+fn test_prune_constant_conditions() {
+    let source_code = "
+        @transition {
+            set x = 3
+            if false {
+                become #(x / x)
+            }
+            while 0 {
+                set x = x / x
+            }
+            while true {
+                break
+            }
+            become #(x)
+        }
+        @states 6";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    compiler.set_opt_level(OptimizationLevel::None);
+    let mut compiled = rule
+        .transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    let ir = compiler.print_to_string();
+    println!("{}", ir);
+
+    // Branches and loop bodies that never execute are not compiled at all, so
+    // neither is the division inside them.
+    assert!(!ir.contains("ifTrue"));
+    assert!(!ir.contains("isDivByZero"));
+    // A loop whose condition is always true does not check it.
+    assert!(ir.contains("whileBody"));
+    assert!(!ir.contains("whileCond"));
+
+    let result = compiled.call().expect("Failed to call transition function");
+    assert_eq!(ConstValue::CellState(3), result);
+
+    // Variables and error points that only appear in code that is never
+    // compiled are left out of the compiled function.
+    let source_code = "
+        @function int test(int x) {
+            if true {
+                set y = x
+            } else {
+                set z = x / 0
+            }
+            while false {
+                set w = x * x
+            }
+            return x + 1
+        }";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    compiler.set_collect_stats(true);
+    let mut compiled = rule.helper_functions()["test"]
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile function");
+    let mut value_names: Vec<String> = (0..compiled.value_count())
+        .map(|i| compiled.value_mut(i).name().to_owned())
+        .collect();
+    value_names.sort();
+    assert_eq!(vec!["x".to_owned(), "y".to_owned()], value_names);
+    // Only the addition can overflow.
+    let stats = compiled.stats().expect("Missing compile stats");
+    assert_eq!(1, stats.error_points);
+}
+
+#[test]
+fn test_fold_constants() {
+    let source_code = "
+        @transition {
+            set x = 2 + 3 * 4 - 13
+            become #(x + 12 / (3 - 3))
+        }
+        @states 6";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let dump = rule.transition_function().dump_ast();
+    println!("{}", dump);
+
+    // `2 + 3 * 4 - 13` is folded into a single constant.
+    assert!(dump.contains("constant 1 (Atom): integer @ "));
+    // `12 / (3 - 3)` cannot be folded because it divides by zero, but
+    // `(3 - 3)` can.
+    assert!(dump.contains("binary \"/\" operator (Operator): integer @ "));
+    assert!(dump.contains("constant 0 (Atom): integer @ "));
+
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    compiler.set_opt_level(OptimizationLevel::None);
+    let mut compiled = rule
+        .transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    let ir = compiler.print_to_string();
+    println!("{}", ir);
+
+    // The only arithmetic left is the division and the addition after it.
+    assert!(!ir.contains("llvm.ssub.with.overflow"));
+    assert!(!ir.contains("llvm.smul.with.overflow"));
+
+    // The division by zero is still reported when the function runs.
+    let err = compiled
+        .call()
+        .expect_err("Division by zero was not reported");
+    assert_eq!("Divide by zero", err.msg.to_string());
+}
+
+#[test]
+fn test_fuse_mul_add() {
+    // Compiles a convolution kernel, with the multiply-add written either
+    // directly or split across two statements (which prevents fusion), and
+    // returns its compile stats along with its result.
+    let compile = |body: &str| {
+        let source_code = format!(
+            "
+            @function int test(vec3 weights, vec3 cells) {{
+                set total = 0
+                for i in 0..2 {{
+                    {}
+                }}
+                return total
+            }}",
+            body,
+        );
+        let rule = ast::make_rule(Rc::new(source_code)).expect("Failed to build AST");
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_collect_stats(true);
+        compiler.set_opt_level(OptimizationLevel::None);
+        let mut compiled = rule.helper_functions()["test"]
+            .compile(&mut compiler, rule.helper_functions())
+            .expect("Failed to compile function");
+        let result = compiled
+            .execute(&[
+                ConstValue::Vector(vec![1, 2, 1]),
+                ConstValue::Vector(vec![3, -4, 5]),
+            ])
+            .map_err(|_| ());
+        (
+            compiled.stats().cloned().expect("Missing compile stats"),
+            result,
+        )
+    };
+
+    let (fused, fused_result) = compile("set total += weights[i] * cells[i]");
+    let (unfused, unfused_result) = compile(
+        "set product = weights[i] * cells[i]
+         set total += product",
+    );
+    println!("Fused: {:?}", fused);
+    println!("Unfused: {:?}", unfused);
+
+    assert_eq!(Ok(ConstValue::Int(0)), fused_result);
+    assert_eq!(fused_result, unfused_result);
+    // The multiplication and addition share one overflow check.
+    assert_eq!(fused.error_points + 1, unfused.error_points);
+    assert!(fused.basic_blocks < unfused.basic_blocks);
+    assert!(fused.instructions < unfused.instructions);
+}
+
+#[test]
+fn test_verify_llvm() {
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    compiler
+        .begin_extern_function("unterminated", Type::Int, &[], &HashMap::new())
+        .expect("Failed to begin function");
+    // The entry block has no terminator, so the module is invalid.
+    let err = compiler
+        .verify()
+        .expect_err("Unterminated basic block passed verification");
+    let msg = err.msg.to_string();
+    println!("{}", msg);
+    assert!(msg.contains("LLVM module is invalid"));
+    assert!(msg.contains("unterminated"));
+
+    // Terminating the block makes the module valid.
+    compiler
+        .build_return_ok(Value::Int(compiler.int_type().const_zero()))
+        .expect("Failed to build return");
+    compiler
+        .verify()
+        .expect("Terminated basic block failed verification");
+}
+
+#[test]
+fn test_trace() {
+    let source_code = "
+        @function int double(int x) {
+            return trace(x * 2)
+        }
+        @function int test(int x) {
+            set v = trace([x, 2])
+            for i in 1..2 {
+                set x += trace(i * 10)
+            }
+            if trace(x > 30) {
+                set x += 1
+            }
+            return double(x) + v[1]
+        }";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let compile = |debug_trace| {
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_debug_trace(debug_trace);
+        let compiled = rule.helper_functions()["test"]
+            .compile(&mut compiler, rule.helper_functions())
+            .expect("Failed to compile function");
+        (compiled, compiler.print_to_string())
+    };
+
+    // Each traced value is recorded in order, including those in helper
+    // functions, and `trace()` returns its argument.
+    let (mut compiled, _) = compile(true);
+    assert_eq!(
+        Ok(ConstValue::Int(74)),
+        compiled.execute(&[ConstValue::Int(5)]).map_err(|_| ()),
+    );
+    assert_eq!(
+        &[
+            ConstValue::Vector(vec![5, 2]),
+            ConstValue::Int(10),
+            ConstValue::Int(20),
+            ConstValue::Bool(true),
+            ConstValue::Int(72),
+        ],
+        compiled.trace(),
+    );
+    // Each call replaces the previous trace.
+    assert_eq!(
+        Ok(ConstValue::Int(62)),
+        compiled.execute(&[ConstValue::Int(0)]).map_err(|_| ()),
+    );
+    assert_eq!(
+        &[
+            ConstValue::Vector(vec![0, 2]),
+            ConstValue::Int(10),
+            ConstValue::Int(20),
+            ConstValue::Bool(false),
+            ConstValue::Int(60),
+        ],
+        compiled.trace(),
+    );
+
+    // Without debug tracing, `trace()` compiles to just its argument.
+    let (mut compiled, ir) = compile(false);
+    assert!(!ir.contains("ndca_trace"));
+    assert_eq!(
+        Ok(ConstValue::Int(74)),
+        compiled.execute(&[ConstValue::Int(5)]).map_err(|_| ()),
+    );
+    assert!(compiled.trace().is_empty());
+
+    // Ranges cannot be traced.
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 37
+@function int test() { return trace(1..2) }
+                                    ^^^^   Cannot trace range"),
+        "@function int test() { return trace(1..2) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_debug_info() {
+    let source_code = "
+        @function int square(int x) {
+            return x * x
+        }
+        @function int test(int x) {
+            return square(x) + 1
+        }";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build AST");
+    let compile = |debug_info| {
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_debug_info(debug_info);
+        compiler.set_opt_level(OptimizationLevel::None);
+        let compiled = rule.helper_functions()["test"]
+            .compile(&mut compiler, rule.helper_functions())
+            .expect("Failed to compile function");
+        (compiled, compiler.print_to_string())
+    };
+
+    let (mut compiled, ir) = compile(true);
+    println!("{}", ir);
+    // Each function has a subprogram, and instructions have the location of
+    // the expression they were built for.
+    assert!(ir.contains("DISubprogram(name: \"square\""));
+    assert!(ir.contains("DISubprogram(name: \"test\""));
+    assert!(ir.contains("!DILocation(line: 3, column: 20"));
+    assert!(ir.contains("!DILocation(line: 6, column: 20"));
+    // Debug info does not change the result.
+    assert_eq!(
+        Ok(ConstValue::Int(10)),
+        compiled.execute(&[ConstValue::Int(3)]).map_err(|_| ()),
+    );
+
+    // Debug info is disabled by default.
+    let (_, ir) = compile(false);
+    assert!(!ir.contains("!dbg"));
+}
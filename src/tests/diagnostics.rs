@@ -0,0 +1,108 @@
+use std::rc::Rc;
+
+use super::super::ast;
+use super::super::errors::{format_with_source, LangError, LangErrorMsg};
+use super::super::Span;
+
+#[test]
+fn test_multiple_diagnostics() {
+    let source_code = "
+        @states 0
+        @function int f() { return #1 }
+        @transition {
+            become 3
+        }";
+    let mut errors: Vec<LangError> = vec![];
+    let rule = ast::make_rule_with_diagnostics(Rc::new(source_code.to_owned()), &mut errors);
+    assert!(rule.is_none());
+
+    let errors: Vec<String> = errors
+        .into_iter()
+        .map(|e| e.with_source(source_code).to_string())
+        .collect();
+    println!("{:#?}", errors);
+    assert_eq!(3, errors.len());
+    assert!(errors[0].starts_with("Error at line 2; column 17\n"));
+    assert!(errors[0].ends_with("Number of states must range from 1 to 256"));
+    assert!(errors[1].starts_with("Error at line 3; column 29\n"));
+    assert!(errors[1].ends_with("Type error: expected integer but got cell state"));
+    assert!(errors[2].starts_with("Error at line 5; column 13\n"));
+    assert!(errors[2].ends_with("Type error: expected cell state but got integer"));
+
+    // The same rule only produces the first error when not using a
+    // DiagnosticSink.
+    assert!(ast::make_rule(Rc::new(source_code.to_owned())).is_err());
+}
+
+#[test]
+fn test_multiple_errors_per_function() {
+    let source_code = "
+        @transition {
+            set x = y
+            become #(x)
+            if z {
+                become #(w)
+            }
+            become #(a + b)
+        }";
+    let mut errors: Vec<LangError> = vec![];
+    let rule = ast::make_rule_with_diagnostics(Rc::new(source_code.to_owned()), &mut errors);
+    assert!(rule.is_none());
+
+    let errors: Vec<String> = errors
+        .into_iter()
+        .map(|e| e.with_source(source_code).to_string())
+        .collect();
+    println!("{:#?}", errors);
+    // Reading `x` is not an error by itself, since it is only uninitialized
+    // because of the error before it.
+    let expected_positions = [(3, 21), (5, 16), (6, 26), (8, 22), (8, 26)];
+    assert_eq!(expected_positions.len(), errors.len());
+    for (error, (line, col)) in errors.iter().zip(&expected_positions) {
+        let prefix = format!("Error at line {}; column {}\n", line, col);
+        assert!(error.starts_with(&prefix));
+        assert!(error.ends_with("This variable must be initialized before it is used"));
+    }
+}
+
+#[test]
+fn test_format_with_source() {
+    let source_code = "@transition {\n    set x = 10\n    become #(x)\n}";
+
+    // Single-line span
+    let start = source_code.find("10").unwrap();
+    let error = LangErrorMsg::AssertionFailed.with_span(Span {
+        start,
+        end: start + 2,
+    });
+    assert_eq!(
+        "error: Assertion failed
+ --> line 2, column 13
+  |
+2 |     set x = 10
+  |             ^^",
+        format_with_source(&error, source_code),
+    );
+
+    // Multi-line span
+    let start = source_code.find("set").unwrap();
+    let end = source_code.find("\n}").unwrap();
+    let error = LangErrorMsg::AssertionFailed.with_span(Span { start, end });
+    assert_eq!(
+        "error: Assertion failed
+ --> line 2, column 5
+  |
+2 |     set x = 10
+  |     ^^^^^^^^^^
+3 |     become #(x)
+  |     ^^^^^^^^^^^",
+        format_with_source(&error, source_code),
+    );
+
+    // No span
+    let error = LangErrorMsg::Unimplemented.without_span();
+    assert_eq!(
+        "error: This feature is unimplemented",
+        format_with_source(&error, source_code),
+    );
+}
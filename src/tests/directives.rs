@@ -0,0 +1,315 @@
+use std::rc::Rc;
+
+use super::super::errors::format_with_source;
+use super::{assert_output, ast, ConstValue};
+
+#[test]
+fn test_assert_directive() {
+    // Passing assertions
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @assert 1 < 2
+        @assert 3 * 4 == 12
+        @transition {
+            become #1
+        }",
+    );
+
+    // Failing assertion
+    assert_output(
+        Err("Error at line 3; column 17
+@assert 2 < 1
+        ^^^^^   Assertion failed"),
+        "
+        @assert 1 < 2
+        @assert 2 < 1
+        @transition {
+            become #1
+        }",
+    );
+
+    // Assertions about cell states use the rule's actual cell states.
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @assert id(DYING) == 3
+        @assert #9 != #0
+        @transition {
+            become DYING
+        }
+        state DYING = #3
+        @states 10",
+    );
+    assert_output(
+        Err("Error at line 2; column 17
+@assert #3 != #0
+        ^^   Cell state out of range"),
+        "
+        @assert #3 != #0
+        @states 3
+        @transition {
+            become #1
+        }",
+    );
+
+    // Assertion that isn't an integer
+    assert_output(
+        Err("Error at line 2; column 17
+@assert #1
+        ^^   Type error: expected boolean but got cell state"),
+        "
+        @assert #1
+        @transition {
+            become #1
+        }",
+    );
+}
+
+#[test]
+fn test_repeat_directive() {
+    assert_output(
+        Err("Error at line 3; column 17
+@states 4
+        ^   Multiple \"states\" directives; only one is allowed"),
+        "
+        @states 3
+        @states 4
+        @transition {
+            become #1
+        }",
+    );
+    assert_output(
+        Err("Error at line 3; column 21
+@dimensions 2
+            ^   Multiple \"dimensions\" directives; only one is allowed"),
+        "
+        @dimensions 2
+        @dimensions 2
+        @transition {
+            become #1
+        }",
+    );
+}
+
+#[test]
+fn test_conflicting_directives() {
+    // Overflow modes that contradict each other
+    assert_output(
+        Err("Error at line 3; column 19
+@overflow checked
+          ^^^^^^^   This \"overflow\" directive conflicts with an earlier one"),
+        "
+        @overflow wrapping
+        @overflow checked
+        @transition {
+            become #1
+        }",
+    );
+    // The same overflow mode twice
+    assert_output(
+        Err("Error at line 3; column 19
+@overflow checked
+          ^^^^^^^   Multiple \"overflow\" directives; only one is allowed"),
+        "
+        @overflow checked
+        @overflow checked
+        @transition {
+            become #1
+        }",
+    );
+
+    // Cell state names for states beyond the `@states` directive
+    assert_output(
+        Err("Error at line 3; column 21
+state FOO = 5
+            ^   Cell state out of range; the \"states\" directive only has states #0 to #1"),
+        "
+        @states 2
+        state FOO = 5
+        @transition {
+            become FOO
+        }",
+    );
+    assert_output(
+        Err("Error at line 2; column 21
+state FOO = #3
+            ^^   Cell state out of range; the \"states\" directive only has states #0 to #2"),
+        "
+        state FOO = #3
+        @states 3
+        @transition {
+            become FOO
+        }",
+    );
+
+    // Neighbor offsets that don't match the number of dimensions
+    assert_output(
+        Err("Error at line 4; column 29
+become neighbor([1, 0])
+                ^^^^^^   Neighbor offset has 2 components, but the \"dimensions\" directive specifies 3 dimensions"),
+        "
+        @dimensions 3
+        @transition {
+            become neighbor([1, 0])
+        }",
+    );
+    assert_output(
+        Err("Error at line 3; column 29
+become neighbor([1, 0, 0])
+                ^^^^^^^^^   Neighbor offset has 3 components, but without a \"dimensions\" directive, the rule has 2 dimensions"),
+        "
+        @transition {
+            become neighbor([1, 0, 0])
+        }",
+    );
+}
+
+#[test]
+fn test_conflicting_directive_spans() {
+    // Each error also points to the directive that it conflicts with.
+    let source_code = "
+        @overflow wrapping
+        @overflow checked";
+    let err = ast::make_rule(Rc::new(source_code.to_owned())).expect_err("No error was reported");
+    assert_eq!(
+        "error: This \"overflow\" directive conflicts with an earlier one
+ --> line 3, column 19
+  |
+3 |         @overflow checked
+  |                   ^^^^^^^
+note: first defined here
+ --> line 2, column 19
+  |
+2 |         @overflow wrapping
+  |                   ^^^^^^^^",
+        format_with_source(&err, source_code),
+    );
+
+    let source_code = "
+        @states 3
+        state FOO = 4";
+    let err = ast::make_rule(Rc::new(source_code.to_owned())).expect_err("No error was reported");
+    assert_eq!(
+        "error: Cell state out of range; the \"states\" directive only has states #0 to #2
+ --> line 3, column 21
+  |
+3 |         state FOO = 4
+  |                     ^
+note: number of states declared here
+ --> line 2, column 17
+  |
+2 |         @states 3
+  |                 ^",
+        format_with_source(&err, source_code),
+    );
+
+    let source_code = "
+        @dimensions 3
+        @transition { become neighbor([1, 0]) }";
+    let err = ast::make_rule(Rc::new(source_code.to_owned())).expect_err("No error was reported");
+    assert_eq!(
+        "error: Neighbor offset has 2 components, but the \"dimensions\" directive specifies 3 dimensions
+ --> line 3, column 39
+  |
+3 |         @transition { become neighbor([1, 0]) }
+  |                                       ^^^^^^
+note: number of dimensions declared here
+ --> line 2, column 21
+  |
+2 |         @dimensions 3
+  |                     ^",
+        format_with_source(&err, source_code),
+    );
+}
+
+#[test]
+fn test_degenerate_states() {
+    // A rule with one state can only become #0.
+    assert_output(
+        Ok(ConstValue::CellState(0)),
+        "
+        @states 1
+        @transition {
+            become #0
+        }",
+    );
+    assert_output(
+        Err("Error at line 4; column 20
+become #1
+       ^^   Cell state out of range"),
+        "
+        @states 1
+        @transition {
+            become #1
+        }",
+    );
+
+    // A rule must have at least one state.
+    assert_output(
+        Err("Error at line 2; column 17
+@states 0
+        ^   Number of states must range from 1 to 256"),
+        "
+        @states 0
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_state_names() {
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @states 3
+        state DEAD = #0
+        state ALIVE = #1
+        state DYING = 2
+        @transition {
+            set s = DYING
+            if s == DYING {
+                become ALIVE
+            }
+            become #(DEAD)
+        }",
+    );
+
+    // Misspelled cell state name
+    assert_output(
+        Err("Error at line 4; column 20
+become ALIV
+       ^^^^   There is no variable or cell state named \"ALIV\"; did you mean \"ALIVE\"?"),
+        "
+        state ALIVE = #1
+        @transition {
+            become ALIV
+        }",
+    );
+
+    // Cell state that doesn't exist
+    assert_output(
+        Err("Error at line 2; column 23
+state ALIVE = #2
+              ^^   Cell state out of range; without a \"states\" directive, the rule only has states #0 to #1"),
+        "
+        state ALIVE = #2
+        @transition {
+            become ALIVE
+        }",
+    );
+
+    // Naming a cell state twice
+    assert_output(
+        Err("Error at line 3; column 15
+state ALIVE = #1
+      ^^^^^   This cell state is already named \"ON\""),
+        "
+        state ON = #1
+        state ALIVE = #1
+        @transition {
+            become ALIVE
+        }",
+    );
+}
@@ -0,0 +1,120 @@
+use std::rc::Rc;
+
+use super::ast::{self, ExternFunctions, FnSignature};
+use super::{Compiler, ConstValue, LangInt, Type};
+
+extern "C" fn noise(x: LangInt, y: LangInt) -> LangInt {
+    x * 31 + y
+}
+
+extern "C" fn is_solid(state: LangInt) -> LangInt {
+    (state == 2) as LangInt
+}
+
+/// Returns a registry of the extern functions used by these tests.
+fn make_externs() -> ExternFunctions {
+    let mut externs = ExternFunctions::new();
+    unsafe {
+        externs.register(
+            "noise",
+            FnSignature::new(vec![Type::Int, Type::Int], Type::Int),
+            noise as usize,
+        );
+        externs.register(
+            "is_solid",
+            FnSignature::new(vec![Type::CellState], Type::Bool),
+            is_solid as usize,
+        );
+        // Vectors cannot be passed to extern functions.
+        externs.register(
+            "vec_noise",
+            FnSignature::new(vec![Type::Vector(2)], Type::Int),
+            noise as usize,
+        );
+    }
+    externs
+}
+
+/// Compiles and runs the given helper function of the given source code with
+/// the extern functions from make_externs(), and returns its result (or the
+/// error, including its location).
+fn eval_with_externs(args: &[ConstValue], source_code: &str) -> Result<ConstValue, String> {
+    let rule = ast::make_rule_with_externs(Rc::new(source_code.to_owned()), make_externs())
+        .map_err(|e| e.with_source(source_code).to_string())?;
+    let mut compiler =
+        Compiler::with_int_bits(rule.meta().int_bits).expect("Failed to create compiler");
+    rule.helper_functions()["test"]
+        .compile(&mut compiler, rule.helper_functions())
+        .and_then(|mut compiled_function| compiled_function.execute(args))
+        .map_err(|e| e.with_source(source_code).to_string())
+}
+
+#[test]
+fn test_extern_calls() {
+    assert_eq!(
+        Ok(ConstValue::Int(67)),
+        eval_with_externs(
+            &[ConstValue::Int(2)],
+            "@function int test(int x) { return extern::noise(x, 3) + extern::noise(0, 2) }",
+        ),
+    );
+    // Booleans and cell states are converted to and from integers.
+    assert_eq!(
+        Ok(ConstValue::Bool(true)),
+        eval_with_externs(
+            &[],
+            "@states 3
+            @function bool test() {
+                return extern::is_solid(#2) and not extern::is_solid(#1)
+            }",
+        ),
+    );
+
+    // Unknown extern function
+    assert_eq!(
+        Err("Error at line 1; column 39
+@function int test() { return extern::perlin(1) }
+                                      ^^^^^^   There is no extern function named \"perlin\""
+            .to_owned()),
+        eval_with_externs(&[], "@function int test() { return extern::perlin(1) }"),
+    );
+    // Extern functions are only available through `extern::`.
+    assert_eq!(
+        Err("Error at line 1; column 31
+@function int test() { return noise(1, 2) }
+                              ^^^^^   There is no function named \"noise\""
+            .to_owned()),
+        eval_with_externs(&[], "@function int test() { return noise(1, 2) }"),
+    );
+
+    // Wrong number of arguments
+    assert_eq!(
+        Err("Error at line 1; column 31
+@function int test() { return extern::noise(1) }
+                              ^^^^^^^^^^^^^^^^   Expected 2 arguments; got 1 argument"
+            .to_owned()),
+        eval_with_externs(&[], "@function int test() { return extern::noise(1) }"),
+    );
+    // Wrong argument type
+    assert_eq!(
+        Err("Error at line 1; column 48
+@function int test() { return extern::noise(1, #1) }
+                                               ^^   Expected integer; got cell state"
+            .to_owned()),
+        eval_with_externs(&[], "@function int test() { return extern::noise(1, #1) }"),
+    );
+    // Unsupported type in signature
+    assert_eq!(
+        Err("Error at line 1; column 31
+@function int test() { return extern::vec_noise([1, 2]) }
+                              ^^^^^^^^^^^^^^^^^^^^^^^^^   Extern functions can only take and return integers, booleans, and cell states, not vector of length 2"
+            .to_owned()),
+        eval_with_externs(&[], "@function int test() { return extern::vec_noise([1, 2]) }"),
+    );
+
+    // Ordinary rules cannot call extern functions.
+    assert!(ast::make_rule(Rc::new(
+        "@function int test() { return extern::noise(1, 2) }".to_owned()
+    ))
+    .is_err());
+}
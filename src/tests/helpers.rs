@@ -0,0 +1,150 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_helper_calls() {
+    // Booleans are converted to integers, as if assigned to the argument.
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @function int double(int x) {
+            return x * 2
+        }
+        @transition {
+            become #(double(2) + double(true))
+        }
+        @states 7",
+    );
+
+    // Helper functions can call each other.
+    let source_code = "
+        @function int square(int x) {
+            return x * x
+        }
+        @function int f(int a, int b) {
+            return square(a) + square(b)
+        }";
+    assert_func_output(
+        &[ConstValue::Int(3), ConstValue::Int(4)],
+        Ok(ConstValue::Int(25)),
+        source_code,
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(3), ConstValue::Int(4_000_000_000)],
+        Err("Error at line 3; column 20
+return x * x
+       ^^^^^   Integer overflow"),
+        source_code,
+        Some("f"),
+    );
+
+    // Arrays are passed by value, and tuples can be returned.
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(705)),
+        "
+        @function (int, int) divmod(int a, int b) {
+            return (a / b, a % b)
+        }
+        @function int f(array2 a) {
+            set a[0] = 99
+            return a[1]
+        }
+        @function int g() {
+            set arr = array(2)
+            set arr[1] = 7
+            set (q, r) = divmod(17, 5)
+            return f(arr) * 100 + arr[0] * 10 + q + r
+        }",
+        Some("g"),
+    );
+
+    // Runtime errors in a helper function are reported there.
+    assert_output(
+        Err("Error at line 3; column 20
+return 10 / x
+       ^^^^^^   Divide by zero"),
+        "
+        @function int f(int x) {
+            return 10 / x
+        }
+        @transition {
+            become #(f(0) - 1)
+        }",
+    );
+}
+
+#[test]
+fn test_helper_call_errors() {
+    // Too many arguments
+    assert_output(
+        Err("Error at line 6; column 27
+become #(f(1, 2, 3))
+              ^^^^   Expected 1 argument; got 3 arguments"),
+        "
+        @function int f(int x) {
+            return x
+        }
+        @transition {
+            become #(f(1, 2, 3))
+        }",
+    );
+
+    // Too few arguments
+    assert_output(
+        Err("Error at line 6; column 22
+become #(f())
+         ^^^   Expected 1 argument; got 0 arguments"),
+        "
+        @function int f(int x) {
+            return x
+        }
+        @transition {
+            become #(f())
+        }",
+    );
+
+    // Wrong argument type
+    assert_output(
+        Err("Error at line 6; column 24
+become #(f(#1))
+           ^^   Expected integer; got cell state"),
+        "
+        @function int f(int x) {
+            return x
+        }
+        @transition {
+            become #(f(#1))
+        }",
+    );
+}
+
+#[test]
+fn test_recursion_errors() {
+    assert_output(
+        Err("Error at line 3; column 20
+return f(x)
+       ^^^^   Recursive function call (f -> f); helper functions cannot call themselves, even indirectly"),
+        "
+        @function int f(int x) {
+            return f(x)
+        }",
+    );
+
+    // Mutual recursion
+    assert_output(
+        Err("Error at line 6; column 20
+return f(x - 1)
+       ^^^^^^^^   Recursive function call (f -> g -> f); helper functions cannot call themselves, even indirectly"),
+        "
+        @function int f(int x) {
+            return g(x) + 1
+        }
+        @function int g(int x) {
+            return f(x - 1)
+        }
+        @transition {
+            become #(f(0))
+        }",
+    );
+}
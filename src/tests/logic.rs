@@ -0,0 +1,246 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_logical_ops() {
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            if 1 < 2 and not 3 < 2 and (0 or 2 > 1) {
+                if 1 > 2 or 0 {
+                } else if 1 xor 0 xor 1 {
+                } else {
+                    become #1
+                }
+            }
+            become #0
+        }",
+    );
+
+    // Logical operators short-circuit.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Int(0)),
+        "@function int test(int x) { if x != 0 and 10 / x > 2 { return 1 } return 0 }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Int(1)),
+        "@function int test(int x) { if x == 0 or 10 / x > 2 { return 1 } return 0 }",
+        Some("test"),
+    );
+    // ... but the right-hand side is still evaluated when necessary.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 43
+@function int test(int x) { if x == 0 and 10 / x > 2 { return 1 } return 0 }
+                                          ^^^^^^   Divide by zero"),
+        "@function int test(int x) { if x == 0 and 10 / x > 2 { return 1 } return 0 }",
+        Some("test"),
+    );
+
+    // `not` produces a boolean.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Bool(true)),
+        "@function bool test(int x) { return not x }",
+        Some("test"),
+    );
+
+    // Operand that isn't a condition
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return #1 and 1 }
+                              ^^   Type error: expected boolean but got cell state"),
+        "@function int test() { return #1 and 1 }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_conditional_expr() {
+    assert_func_output(
+        &[ConstValue::Int(-5)],
+        Ok(ConstValue::Int(5)),
+        "@function int test(int x) { return if x > 0 then x else -x }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Vector(vec![3, 4])),
+        "@function vec2 test(int x) { return if x then [1, 2] else [3, 4] }",
+        Some("test"),
+    );
+
+    // Only the selected value is evaluated.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Int(0)),
+        "@function int test(int x) { return if x == 0 then 0 else 10 / x }",
+        Some("test"),
+    );
+
+    // Values with different types
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 48
+@function int test() { return if 1 then 2 else #3 }
+                                               ^^   Type error: expected integer but got cell state"),
+        "@function int test() { return if 1 then 2 else #3 }",
+        Some("test"),
+    );
+
+    // Missing `then`
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 36
+@function int test() { return if 1 2 else 3 }
+                                   ^   Expected 'then'"),
+        "@function int test() { return if 1 2 else 3 }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_select() {
+    let source_code = "@function int test(int x) { return select(x > 0, x, 0) }";
+    for &(x, expected) in &[(-5, 0), (7, 7)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            source_code,
+            Some("test"),
+        );
+    }
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Vector(vec![3, 4])),
+        "@function vec2 test(int x) { return select(x, [1, 2], [3, 4]) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Ok(ConstValue::Int(3)),
+        "
+        @function int test(int x) {
+            set sum = 0
+            for i in select(x > 2, 1..2, 0..5) {
+                set sum += i
+            }
+            return sum
+        }",
+        Some("test"),
+    );
+
+    // Values with different types
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 49
+@function int test(int x) { return select(x, 1, #1) }
+                                                ^^   Type error: expected integer but got cell state"),
+        "@function int test(int x) { return select(x, 1, #1) }",
+        Some("test"),
+    );
+
+    // Both values are evaluated, so they cannot produce errors.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 50
+@function int test(int x) { return select(x > 0, 10 / x, 0) }
+                                                 ^^^^^^   Operands of 'select' are always evaluated, so they cannot produce runtime errors; use 'if ... then ... else' instead"),
+        "@function int test(int x) { return select(x > 0, 10 / x, 0) }",
+        Some("test"),
+    );
+    assert_output(
+        Err("Error at line 6; column 33
+return select(true, f(x), 0)
+                    ^^^^   Operands of 'select' are always evaluated, so they cannot produce runtime errors; use 'if ... then ... else' instead"),
+        "
+        @function int f(int x) {
+            return x
+        }
+        @function int test(int x) {
+            return select(true, f(x), 0)
+        }",
+    );
+}
+
+#[test]
+fn test_match() {
+    let source = "
+        @function int test(cellstate s) {
+            match s {
+                #0 => { return 10 },
+                #1 => { return 11 },
+                else => { return 20 },
+            }
+        }";
+    for &(s, expected) in &[(0, 10), (1, 11), (2, 20), (255, 20)] {
+        assert_func_output(
+            &[ConstValue::CellState(s)],
+            Ok(ConstValue::Int(expected)),
+            source,
+            Some("test"),
+        );
+    }
+
+    // Without an `else` case, nothing happens if no case matches.
+    let source = "
+        @function int test(int x) {
+            set y = 0
+            match x {
+                1 => { set y = 5 }
+                -2 => { set y = 7 }
+            }
+            return y
+        }";
+    for &(x, expected) in &[(1, 5), (-2, 7), (0, 0), (3, 0)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            source,
+            Some("test"),
+        );
+    }
+
+    // Duplicate case
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err(
+            "Error at line 1; column 47
+@function int test(int x) { match x { 1 => {} 1 => {} } return 0 }
+                                              ^   This value is already matched by an earlier case",
+        ),
+        "@function int test(int x) { match x { 1 => {} 1 => {} } return 0 }",
+        Some("test"),
+    );
+    // Case with the wrong type
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 39
+@function int test(int x) { match x { #1 => {} } return 0 }
+                                      ^^   Type error: expected integer but got cell state"),
+        "@function int test(int x) { match x { #1 => {} } return 0 }",
+        Some("test"),
+    );
+    // Case that isn't a constant
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 39
+@function int test(int x) { match x { x => {} } return 0 }
+                                      ^   Cannot evaluate this expression as a constant"),
+        "@function int test(int x) { match x { x => {} } return 0 }",
+        Some("test"),
+    );
+    // Matching on a boolean
+    assert_func_output(
+        &[ConstValue::Bool(false)],
+        Err("Error at line 1; column 36
+@function int test(bool b) { match b { else => {} } return 0 }
+                                   ^   Type error: expected cell state but got boolean"),
+        "@function int test(bool b) { match b { else => {} } return 0 }",
+        Some("test"),
+    );
+}
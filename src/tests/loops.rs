@@ -0,0 +1,309 @@
+use std::rc::Rc;
+
+use inkwell::OptimizationLevel;
+
+use super::ast;
+use super::compiler::Compiler;
+use super::{assert_output, ConstValue};
+
+#[test]
+fn test_for_loop() {
+    // Ranges are inclusive on both ends.
+    assert_output(
+        Ok(ConstValue::CellState(10)),
+        "
+        @transition {
+            set total = 0
+            for i in 1..4 {
+                set total += i
+            }
+            become #(total)
+        }
+        @states 11",
+    );
+
+    // Empty range
+    assert_output(
+        Ok(ConstValue::CellState(0)),
+        "
+        @transition {
+            for i in 3..1 {
+                become #1
+            }
+            become #0
+        }",
+    );
+
+    // Returning from inside a loop
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @transition {
+            for i in -5..10 {
+                if i == 3 {
+                    become #(i)
+                }
+            }
+        }
+        @states 4",
+    );
+
+    // Range ending at the maximum integer value
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @transition {
+            set n = 0
+            for i in 9223372036854775806..9223372036854775807 {
+                set n += 1
+            }
+            become #(n)
+        }
+        @states 3",
+    );
+
+    // Loop variable that already has a different type
+    assert_output(
+        Err("Error at line 4; column 17
+for i in 1..2 {
+    ^   Type error: expected integer but got cell state"),
+        "
+        @transition {
+            set i = #1
+            for i in 1..2 {
+            }
+        }",
+    );
+
+    // Iterating over something that isn't a range
+    assert_output(
+        Err("Error at line 3; column 22
+for i in 5 {
+         ^   Type error: expected range but got integer"),
+        "
+        @transition {
+            for i in 5 {
+            }
+        }",
+    );
+}
+
+#[test]
+fn test_while_loop() {
+    assert_output(
+        Ok(ConstValue::CellState(5)),
+        "
+        @transition {
+            set n = 0
+            while n < 5 {
+                set n += 1
+            }
+            become #(n)
+        }
+        @states 6",
+    );
+
+    // Condition that is initially false
+    assert_output(
+        Ok(ConstValue::CellState(0)),
+        "
+        @transition {
+            while 0 {
+                become #1
+            }
+            become #0
+        }",
+    );
+
+    // Returning from inside a loop
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @transition {
+            set n = 0
+            while 1 {
+                set n += 1
+                if n == 3 {
+                    become #(n)
+                }
+            }
+        }
+        @states 4",
+    );
+
+    // Condition that isn't an integer
+    assert_output(
+        Err("Error at line 3; column 19
+while #1 {
+      ^^   Type error: expected boolean but got cell state"),
+        "
+        @transition {
+            while #1 {
+            }
+        }",
+    );
+}
+
+#[test]
+fn test_break_continue() {
+    // Break out of a while loop
+    assert_output(
+        Ok(ConstValue::CellState(4)),
+        "
+        @transition {
+            set n = 0
+            while 1 {
+                if n == 4 {
+                    break
+                }
+                set n += 1
+            }
+            become #(n)
+        }
+        @states 5",
+    );
+
+    // Skip some iterations of a for loop
+    assert_output(
+        Ok(ConstValue::CellState(9)),
+        "
+        @transition {
+            set total = 0
+            for i in 1..5 {
+                if i == 2 {
+                    continue
+                }
+                if i == 4 {
+                    continue
+                }
+                set total += i
+            }
+            become #(total)
+        }
+        @states 10",
+    );
+
+    // Break out of only the innermost loop
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @transition {
+            set count = 0
+            for i in 1..3 {
+                for j in 1..3 {
+                    if j > i {
+                        break
+                    }
+                    set count += 1
+                }
+            }
+            become #(count)
+        }
+        @states 7",
+    );
+
+    // Break outside of a loop
+    assert_output(
+        Err("Error at line 3; column 13
+break
+^^^^^   This 'break' is not inside a loop"),
+        "
+        @transition {
+            break
+        }",
+    );
+
+    // Continue outside of a loop
+    assert_output(
+        Err("Error at line 4; column 17
+continue
+^^^^^^^^   This 'continue' is not inside a loop"),
+        "
+        @transition {
+            if 1 {
+                continue
+            }
+        }",
+    );
+
+    // Break outside of a loop in a branch that is never compiled
+    assert_output(
+        Err("Error at line 4; column 17
+break
+^^^^^   This 'break' is not inside a loop"),
+        "
+        @transition {
+            if false {
+                break
+            }
+        }",
+    );
+}
+
+#[test]
+fn test_for_loop_unrolling() {
+    // Compiles the transition function using the given unroll threshold
+    // (without LLVM optimization) and returns its result, along with whether
+    // the LLVM IR contains a real loop.
+    let run = |source_code: &str, unroll_threshold: usize| {
+        let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build rule");
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_unroll_threshold(unroll_threshold);
+        compiler.set_opt_level(OptimizationLevel::None);
+        let result = rule
+            .transition_function()
+            .compile(&mut compiler, rule.helper_functions())
+            .and_then(|mut compiled_function| compiled_function.execute(&[]))
+            .map_err(|e| e.msg);
+        (result, compiler.print_to_string().contains("forLatch"))
+    };
+
+    // Constant ranges within the threshold are unrolled, and give the same
+    // result as a real loop (including `break`, `continue`, and the value of
+    // the loop variable afterward).
+    let source_code = "
+        @transition {
+            set total = 0
+            for i in -1..6 {
+                if i == 0 {
+                    continue
+                }
+                if i == 5 {
+                    break
+                }
+                for j in 1..2 {
+                    set total += i * j
+                }
+            }
+            become #(total + i)
+        }
+        @states 33";
+    assert_eq!((Ok(ConstValue::CellState(32)), false), run(source_code, 16));
+    assert_eq!((Ok(ConstValue::CellState(32)), true), run(source_code, 0));
+
+    // Ranges longer than the threshold are not unrolled.
+    let source_code = "
+        @transition {
+            set n = 0
+            for i in 1..20 {
+                set n += 1
+            }
+            become #(n)
+        }
+        @states 21";
+    assert_eq!((Ok(ConstValue::CellState(20)), true), run(source_code, 16));
+    assert_eq!((Ok(ConstValue::CellState(20)), false), run(source_code, 20));
+
+    // Loops that assign to the loop variable are never unrolled.
+    let source_code = "
+        @transition {
+            set n = 0
+            for i in 1..10 {
+                set i += 2
+                set n += 1
+            }
+            become #(n)
+        }
+        @states 11";
+    assert_eq!((Ok(ConstValue::CellState(4)), true), run(source_code, 16));
+}
@@ -1,6 +1,6 @@
 use proptest::prelude::*;
 
-use super::{assert_func_output, assert_output, ConstValue, LangInt};
+use super::{assert_func_output, assert_output, eval_func, ConstValue, LangInt};
 
 proptest! {
     #[test]
@@ -19,6 +19,60 @@ proptest! {
             Some("test"),
         );
     }
+
+    #[test]
+    fn proptest_const_eval_matches_jit(
+        x: LangInt,
+        y: LangInt,
+        bits in prop::sample::select(vec![16_u32, 32, 64]),
+    ) {
+        // Constant folding must produce the same result (or error) as
+        // computing the same expression at runtime.
+        let min = LangInt::MIN >> (64 - bits);
+        let (x, y) = (x >> (64 - bits), y >> (64 - bits));
+        prop_assume!(x != min && y != min);
+        let exprs: Vec<(&str, fn(&str, &str) -> String)> = vec![
+            ("int", |x, y| format!("{} + {}", x, y)),
+            ("int", |x, y| format!("{} * {}", x, y)),
+            ("int", |x, y| format!("{} * {} + {}", x, y, y)),
+            ("int", |x, y| format!("{} + {} * 3", x, y)),
+            ("int", |x, y| format!("min({}, {})", x, y)),
+            ("int", |x, y| format!("max({}, {}, 0)", x, y)),
+            ("int", |x, _| format!("abs({})", x)),
+            ("int", |_, y| format!("{}.abs()", y)),
+            ("int", |x, y| format!("sat_add({}, {})", x, y)),
+            ("int", |x, y| format!("sat_sub({}, {})", x, y)),
+            ("int", |x, y| format!("wrap_add({}, {})", x, y)),
+            ("int", |x, y| format!("wrap_sub({}, {})", x, y)),
+            ("int", |x, y| format!("wrap_mul({}, {})", x, y)),
+            ("int", |x, _| format!("popcount({})", x)),
+            ("int", |x, _| format!("ctlz({})", x)),
+            ("int", |_, y| format!("cttz({})", y)),
+            ("vec2", |x, y| format!("[{}, {}] + {}", x, y, y)),
+            ("vec2", |x, y| format!("{} * [{}, 2]", x, y)),
+            ("vec2", |x, y| format!("max([{}, 0], [{}, 1])", x, y)),
+        ];
+        for (ret_type, make_expr) in exprs {
+            // The same expression, once using arguments and once using
+            // literals that can be folded.
+            let jit_expr = make_expr("x", "y");
+            let const_expr = make_expr(&format!("({})", x), &format!("({})", y));
+            let jit_source_code = format!(
+                "@bits {}\n@function {} test(int x, int y) {{ return {} }}",
+                bits, ret_type, jit_expr,
+            );
+            let const_source_code = format!(
+                "@bits {}\n@function {} test() {{ return {} }}",
+                bits, ret_type, const_expr,
+            );
+            prop_assert_eq!(
+                eval_func(&[ConstValue::Int(x), ConstValue::Int(y)], &jit_source_code, "test"),
+                eval_func(&[], &const_source_code, "test"),
+                "Mismatch for {:?}",
+                const_expr,
+            );
+        }
+    }
 }
 
 #[test]
@@ -172,3 +226,1240 @@ become #(12 % 0)
         }",
     );
 }
+
+#[test]
+fn test_bitshift() {
+    assert_output(
+        Ok(ConstValue::CellState(12)),
+        "
+        @transition {
+            set x = 3
+            become #(x << 2)
+        }
+        @states 13",
+    );
+
+    // Arithmetic right shift preserves the sign, but logical right shift
+    // fills with zeros.
+    for &(x, y) in &[(-8, 1), (8, 3), (-1, 63), (LangInt::MIN, 0), (12345, 7)] {
+        for &(op, expected) in &[
+            ("<<", x << y),
+            (">>", x >> y),
+            (">>>", (x as u64 >> y) as LangInt),
+        ] {
+            assert_func_output(
+                &[ConstValue::Int(x), ConstValue::Int(y)],
+                Ok(ConstValue::Int(expected)),
+                &format!("@function int test(int x, int y) {{ return x {} y }}", op),
+                Some("test"),
+            );
+        }
+    }
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @states 2
+        @assert -8 >> 1 == -4
+        @assert -8 >>> 1 == 9223372036854775804
+        @assert 1 << 63 == -9223372036854775808
+        @transition {
+            become #(-1 >>> 63)
+        }",
+    );
+
+    // Shift by a non-constant amount that is out of range
+    for &shift_amount in &[64, -1, LangInt::MIN, LangInt::MAX] {
+        assert_func_output(
+            &[ConstValue::Int(1), ConstValue::Int(shift_amount)],
+            Err("Error at line 1; column 49
+@function int test(int x, int y) { return x >>> y }
+                                                ^   Shift amount out of range; must be from 0 to 63"),
+            "@function int test(int x, int y) { return x >>> y }",
+            Some("test"),
+        );
+    }
+
+    // Shift by a constant that is too large
+    assert_output(
+        Err("Error at line 4; column 27
+become #(x << 64)
+              ^^   Shift amount out of range; must be from 0 to 63"),
+        "
+        @transition {
+            set x = 1
+            become #(x << 64)
+        }",
+    );
+
+    // Shift by a constant that is negative
+    assert_output(
+        Err("Error at line 4; column 27
+become #(x >> -1)
+              ^^   Shift amount out of range; must be from 0 to 63"),
+        "
+        @transition {
+            set x = 1
+            become #(x >> -1)
+        }",
+    );
+}
+
+#[test]
+fn test_div_floor() {
+    // Truncating division rounds toward zero ...
+    assert_output(
+        Ok(ConstValue::CellState(7)),
+        "
+        @transition {
+            become #(-7 / 2 + 10)
+        }
+        @states 11",
+    );
+    // ... but floored division rounds toward negative infinity.
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @transition {
+            become #(div_floor(-7, 2) + 10)
+        }
+        @states 11",
+    );
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @transition {
+            set x = 7
+            set y = -2
+            become #(div_floor(x, y) + 10)
+        }
+        @states 11",
+    );
+    // They're the same when there is no remainder or when the signs match.
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @transition {
+            set x = -8
+            become #(div_floor(x, 2) + 10)
+        }
+        @states 11",
+    );
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @transition {
+            set x = -7
+            become #(div_floor(x, -2))
+        }
+        @states 4",
+    );
+
+    // Divide by zero
+    assert_output(
+        Err("Error at line 3; column 22
+become #(div_floor(12, 0))
+         ^^^^^^^^^^^^^^^^   Divide by zero"),
+        "
+        @transition {
+            become #(div_floor(12, 0))
+        }",
+    );
+
+    // Overflow
+    assert_output(
+        Err("Error at line 3; column 22
+become #(div_floor(-9223372036854775808, -1))
+         ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "
+        @transition {
+            become #(div_floor(-9223372036854775808, -1))
+        }",
+    );
+}
+
+#[test]
+fn test_int_literal_bounds() {
+    // Minimum and maximum integer literals
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            become #(-9223372036854775808 + 9223372036854775807 + 2)
+        }",
+    );
+
+    // Negation without spaces
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            set x = 2
+            become #(x-1)
+        }",
+    );
+
+    // Positive literal that is too large
+    assert_output(
+        Err("Error at line 3; column 22
+become #(9223372036854775808 - 1)
+         ^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "
+        @transition {
+            become #(9223372036854775808 - 1)
+        }",
+    );
+
+    // Parenthesized literal that is too large
+    assert_output(
+        Err("Error at line 3; column 24
+become #(-(9223372036854775808))
+           ^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "
+        @transition {
+            become #(-(9223372036854775808))
+        }",
+    );
+
+    // Negated literal that is too small
+    assert_output(
+        Err("Error at line 3; column 23
+become #(-9223372036854775809)
+          ^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "
+        @transition {
+            become #(-9223372036854775809)
+        }",
+    );
+
+    // Negating the minimum integer value stored in a variable
+    assert_output(
+        Err("Error at line 4; column 22
+become #(-x)
+         ^^   Integer overflow"),
+        "
+        @transition {
+            set x = -9223372036854775808
+            become #(-x)
+        }",
+    );
+}
+
+#[test]
+fn test_unary_plus_minus() {
+    for &(expr, expected) in &[
+        ("+5", 5),
+        ("+-5", -5),
+        ("-+5", -5),
+        ("- -5", 5),
+        ("-007", -7),
+        ("+true", 1),
+        ("-(-9223372036854775807)", 9223372036854775807),
+    ] {
+        assert_func_output(
+            &[],
+            Ok(ConstValue::Int(expected)),
+            &format!("@function int test() {{ return {} }}", expr),
+            Some("test"),
+        );
+    }
+
+    // Negated and unary-plus literals are constants, so they can be used as
+    // `match` cases.
+    let source_code = "
+        @function int test(int x) {
+            match x {
+                -1 => { return 10 }
+                +1 => { return 20 }
+                -(-2) => { return 30 }
+            }
+            return 0
+        }";
+    for &(x, expected) in &[(-1, 10), (1, 20), (2, 30), (0, 0)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            source_code,
+            Some("test"),
+        );
+    }
+
+    // Negating the minimum integer value, even at compile time
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return -(-9223372036854775808) }
+                              ^^^^^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "@function int test() { return -(-9223372036854775808) }",
+        Some("test"),
+    );
+
+    // Unary plus only applies to integers
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 32
+@function int test() { return +#1 }
+                               ^^   Type error: expected integer but got cell state"),
+        "@function int test() { return +#1 }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_int_literal_bases() {
+    for &(expr, expected) in &[
+        ("0xFF", 255),
+        ("0xff", 255),
+        ("0b1010", 10),
+        ("0o17", 15),
+        ("0x7FFFFFFFFFFFFFFF", LangInt::MAX),
+        ("-0x8000000000000000", LangInt::MIN),
+        ("0xF0 & 0b111100", 0x30),
+        // Digit separators
+        ("1_000_000", 1_000_000),
+        ("0xDEAD_BEEF", 0xDEAD_BEEF),
+        ("0b1111_0000 | 0o7_7", 0b1111_1111),
+    ] {
+        assert_func_output(
+            &[],
+            Ok(ConstValue::Int(expected)),
+            &format!("@function int test() {{ return {} }}", expr),
+            Some("test"),
+        );
+    }
+
+    // Literal that fits in 64 bits, but is too large for an integer
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return 0x8000000000000000 }
+                              ^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "@function int test() { return 0x8000000000000000 }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[],
+        Err("Error at line 3; column 39
+@function int test() { return 0x8000 }
+                              ^^^^^^   Integer overflow"),
+        "
+        @bits 16
+        @function int test() { return 0x8000 }",
+        Some("test"),
+    );
+
+    // Literal that doesn't even fit in 64 bits
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return 0b11111111111111111111111111111111111111111111111111111111111111111 }
+                              ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "@function int test() { return 0b11111111111111111111111111111111111111111111111111111111111111111 }",
+        Some("test"),
+    );
+
+    // Invalid digits
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return 0b102 }
+                              ^^^^^   Invalid binary integer literal"),
+        "@function int test() { return 0b102 }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return 0x }
+                              ^^   Invalid hexadecimal integer literal"),
+        "@function int test() { return 0x }",
+        Some("test"),
+    );
+
+    // Misplaced digit separators
+    for &literal in &["1000_", "1__000", "0x_FF", "0b1010_"] {
+        let source_code = format!("@function int test() {{ return {} }}", literal);
+        let expected = format!(
+            "Error at line 1; column 31
+{}
+                              {}   Underscores in numbers must be between two digits",
+            source_code,
+            "^".repeat(literal.len()),
+        );
+        assert_func_output(&[], Err(expected.as_str()), &source_code, Some("test"));
+    }
+}
+
+#[test]
+fn test_int_bits() {
+    let source_code = "
+        @bits 16
+        @function int test(int x) {
+            return x * 2
+        }";
+    assert_func_output(
+        &[ConstValue::Int(16383)],
+        Ok(ConstValue::Int(32766)),
+        source_code,
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(-5)],
+        Ok(ConstValue::Int(-10)),
+        source_code,
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(16384)],
+        Err("Error at line 4; column 20
+return x * 2
+       ^^^^^   Integer overflow"),
+        source_code,
+        Some("test"),
+    );
+
+    // Literal that is too large
+    assert_output(
+        Err("Error at line 4; column 22
+become #(32768 - 1)
+         ^^^^^   Integer overflow"),
+        "
+        @bits 16
+        @transition {
+            become #(32768 - 1)
+        }",
+    );
+
+    // Shift amount that is too large
+    assert_output(
+        Err("Error at line 4; column 27
+become #(1 << 16)
+              ^^   Shift amount out of range; must be from 0 to 15"),
+        "
+        @bits 16
+        @transition {
+            become #(1 << 16)
+        }",
+    );
+
+    // Bits shifted past the width of an integer are discarded
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @bits 32
+        @transition {
+            become #((1 << 31) >>> 30)
+        }
+        @states 3",
+    );
+
+    // Invalid number of bits
+    assert_output(
+        Err("Error at line 2; column 15
+@bits 8
+      ^   Number of bits in an integer must range from 16 to 64"),
+        "
+        @bits 8
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_mod() {
+    for &(x, y, rem, modulo) in &[
+        (7, 3, 1, 1),
+        (-1, 3, -1, 2),
+        (-7, 3, -1, 2),
+        (7, -3, 1, -2),
+        (-7, -3, -1, -1),
+        (-6, 3, 0, 0),
+        (LangInt::MAX, LangInt::MIN, LangInt::MAX, -1),
+    ] {
+        // Truncated remainder has the same sign as the dividend ...
+        assert_func_output(
+            &[ConstValue::Int(x), ConstValue::Int(y)],
+            Ok(ConstValue::Int(rem)),
+            "@function int test(int x, int y) { return x % y }",
+            Some("test"),
+        );
+        // ... but floored modulo has the same sign as the divisor.
+        assert_func_output(
+            &[ConstValue::Int(x), ConstValue::Int(y)],
+            Ok(ConstValue::Int(modulo)),
+            "@function int test(int x, int y) { return mod(x, y) }",
+            Some("test"),
+        );
+    }
+    // Constant arguments
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @states 3
+        @assert mod(-1, 3) == 2
+        @assert -1 % 3 == -1
+        @transition {
+            become #(mod(-4, 3))
+        }",
+    );
+
+    // Divide by zero
+    assert_func_output(
+        &[ConstValue::Int(5), ConstValue::Int(0)],
+        Err("Error at line 1; column 43
+@function int test(int x, int y) { return mod(x, y) }
+                                          ^^^^^^^^^   Divide by zero"),
+        "@function int test(int x, int y) { return mod(x, y) }",
+        Some("test"),
+    );
+    // Overflow
+    assert_func_output(
+        &[ConstValue::Int(LangInt::MIN), ConstValue::Int(-1)],
+        Err("Error at line 1; column 43
+@function int test(int x, int y) { return mod(x, y) }
+                                          ^^^^^^^^^   Integer overflow"),
+        "@function int test(int x, int y) { return mod(x, y) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_wrap() {
+    for &(index, size, expected) in &[
+        (3, 5, 3),
+        (5, 5, 0),
+        (-1, 5, 4),
+        (-6, 5, 4),
+        (7, -3, -2),
+        (LangInt::MIN, LangInt::MAX, LangInt::MAX - 1),
+    ] {
+        assert_func_output(
+            &[ConstValue::Int(index), ConstValue::Int(size)],
+            Ok(ConstValue::Int(expected)),
+            "@function int test(int x, int y) { return wrap(x, y) }",
+            Some("test"),
+        );
+    }
+
+    // Vectors are wrapped component-wise.
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![5, -1]),
+            ConstValue::Vector(vec![5, 3]),
+        ],
+        Ok(ConstValue::Vector(vec![0, 2])),
+        "@function vec2 test(vec2 p, vec2 size) { return wrap(p, size) }",
+        Some("test"),
+    );
+
+    // Constant arguments
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @states 3
+        @assert wrap(-1, 3) == 2
+        @assert wrap([-1, 4], [3, 3]) == [2, 1]
+        @transition {
+            become #(wrap(-4, 3))
+        }",
+    );
+
+    // Divide by zero
+    assert_func_output(
+        &[ConstValue::Int(5), ConstValue::Int(0)],
+        Err("Error at line 1; column 43
+@function int test(int x, int y) { return wrap(x, y) }
+                                          ^^^^^^^^^^   Divide by zero"),
+        "@function int test(int x, int y) { return wrap(x, y) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 1]),
+            ConstValue::Vector(vec![2, 0]),
+        ],
+        Err("Error at line 1; column 49
+@function vec2 test(vec2 p, vec2 size) { return wrap(p, size) }
+                                                ^^^^^^^^^^^^^   Divide by zero"),
+        "@function vec2 test(vec2 p, vec2 size) { return wrap(p, size) }",
+        Some("test"),
+    );
+
+    // Mismatched types
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 46
+@function vec2 test(vec2 p) { return wrap(p, 3) }
+                                             ^   Type error: expected vector of length 2 but got integer"),
+        "@function vec2 test(vec2 p) { return wrap(p, 3) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_min_max() {
+    assert_func_output(
+        &[ConstValue::Int(4), ConstValue::Int(-2)],
+        Ok(ConstValue::Int(2)),
+        "@function int test(int x, int y) { return min(x, y, 9) + max(x, 3, y) - 0 * max(y, y) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 8]),
+            ConstValue::Vector(vec![5, -3]),
+        ],
+        Ok(ConstValue::Vector(vec![1, -3])),
+        "@function vec2 test(vec2 a, vec2 b) { return min(a, b) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 8]),
+            ConstValue::Vector(vec![5, -3]),
+        ],
+        Ok(ConstValue::Vector(vec![5, 8])),
+        "@function vec2 test(vec2 a, vec2 b) { return max(a, b) }",
+        Some("test"),
+    );
+    // Constant arguments
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @states 4
+        @assert max(1, 3, 2) == 3
+        @assert min([1, 5], [4, 2])[1] == 2
+        @transition {
+            become #(max(-10, min(3, 7)))
+        }",
+    );
+
+    // Mismatched vector lengths
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 44
+@function vec2 test() { return max([1, 2], [1, 2, 3]) }
+                                           ^^^^^^^^^   Type error: expected vector of length 2 but got vector of length 3"),
+        "@function vec2 test() { return max([1, 2], [1, 2, 3]) }",
+        Some("test"),
+    );
+    // Too few arguments
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return min(3) }
+                              ^^^^^^   Invalid arguments [Int] for min; expected [Int, Int]"),
+        "@function int test() { return min(3) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_abs() {
+    for &(x, expected) in &[(-7, 7), (0, 0), (12, 12), (LangInt::MAX, LangInt::MAX)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            "@function int test(int x) { return abs(x) }",
+            Some("test"),
+        );
+    }
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @states 4
+        @assert abs(-3) == 3
+        @transition {
+            become #(abs(-3))
+        }",
+    );
+
+    // Overflow
+    assert_func_output(
+        &[ConstValue::Int(LangInt::MIN)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return abs(x) }
+                                   ^^^^^^   Integer overflow"),
+        "@function int test(int x) { return abs(x) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return abs(-9223372036854775808) }
+                              ^^^^^^^^^^^^^^^^^^^^^^^^^   Integer overflow"),
+        "@function int test() { return abs(-9223372036854775808) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_clamp() {
+    for &(x, expected) in &[(-7, 0), (0, 0), (3, 3), (5, 5), (12, 5)] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(expected)),
+            "@function int test(int x) { return clamp(x, 0, 5) }",
+            Some("test"),
+        );
+    }
+    // Component-wise clamping
+    assert_func_output(
+        &[ConstValue::Vector(vec![-4, 9])],
+        Ok(ConstValue::Vector(vec![-1, 1])),
+        "@function vec2 test(vec2 v) { return clamp(v, -1, 1) }",
+        Some("test"),
+    );
+    // Constant arguments
+    assert_output(
+        Ok(ConstValue::CellState(2)),
+        "
+        @states 3
+        @assert clamp(10, 1, 2) == 2
+        @transition {
+            become #(clamp(-3, 2, 4))
+        }",
+    );
+
+    // Lower bound greater than upper bound
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return clamp(x, 3, 2) }
+                                   ^^^^^^^^^^^^^^   Lower bound of clamp is greater than upper bound"),
+        "@function int test(int x) { return clamp(x, 3, 2) }",
+        Some("test"),
+    );
+    // Bound that isn't an integer
+    assert_func_output(
+        &[ConstValue::Vector(vec![0, 0])],
+        Err("Error at line 1; column 47
+@function vec2 test(vec2 v) { return clamp(v, v, 2) }
+                                              ^   Type error: expected integer but got vector of length 2"),
+        "@function vec2 test(vec2 v) { return clamp(v, v, 2) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_gcd_lcm() {
+    for &(a, b, gcd, lcm) in &[
+        (12, 18, 6, 36),
+        (-12, 18, 6, 36),
+        (4, -6, 2, 12),
+        (0, 5, 5, 0),
+        (7, 0, 7, 0),
+        (0, 0, 0, 0),
+    ] {
+        assert_func_output(
+            &[ConstValue::Int(a), ConstValue::Int(b)],
+            Ok(ConstValue::Int(gcd)),
+            "@function int test(int a, int b) { return gcd(a, b) }",
+            Some("test"),
+        );
+        assert_func_output(
+            &[ConstValue::Int(a), ConstValue::Int(b)],
+            Ok(ConstValue::Int(lcm)),
+            "@function int test(int a, int b) { return lcm(a, b) }",
+            Some("test"),
+        );
+    }
+    // Constant arguments
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @states 4
+        @assert gcd(-12, 18) == 6
+        @assert lcm(4, 6) == 12
+        @transition {
+            become #(gcd(9, 6))
+        }",
+    );
+
+    // Absolute value overflow
+    assert_func_output(
+        &[ConstValue::Int(LangInt::MIN), ConstValue::Int(3)],
+        Err("Error at line 1; column 43
+@function int test(int a, int b) { return gcd(a, b) }
+                                          ^^^^^^^^^   Integer overflow"),
+        "@function int test(int a, int b) { return gcd(a, b) }",
+        Some("test"),
+    );
+    // Multiplication overflow
+    assert_func_output(
+        &[ConstValue::Int(LangInt::MAX), ConstValue::Int(2)],
+        Err("Error at line 1; column 43
+@function int test(int a, int b) { return lcm(a, b) }
+                                          ^^^^^^^^^   Integer overflow"),
+        "@function int test(int a, int b) { return lcm(a, b) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_pow() {
+    for &(x, y, expected) in &[
+        (2, 10, 1024),
+        (3, 0, 1),
+        (0, 0, 1),
+        (-2, 3, -8),
+        (-2, 63, LangInt::MIN),
+        (1, LangInt::MAX, 1),
+        (-1, LangInt::MAX, -1),
+    ] {
+        assert_func_output(
+            &[ConstValue::Int(x), ConstValue::Int(y)],
+            Ok(ConstValue::Int(expected)),
+            "@function int test(int x, int y) { return x ** y }",
+            Some("test"),
+        );
+    }
+    // Precedence and associativity
+    assert_output(
+        Ok(ConstValue::CellState(4)),
+        "
+        @states 5
+        @assert 2 ** 10 == 1024
+        @assert -2 ** 2 == -4
+        @assert 2 ** 3 ** 2 == 512
+        @assert 3 * 2 ** 2 == 12
+        @transition {
+            become #(2 ** 2)
+        }",
+    );
+
+    // Negative exponent
+    assert_func_output(
+        &[ConstValue::Int(2), ConstValue::Int(-1)],
+        Err("Error at line 1; column 43
+@function int test(int x, int y) { return x ** y }
+                                          ^^^^^^   Negative exponent"),
+        "@function int test(int x, int y) { return x ** y }",
+        Some("test"),
+    );
+    assert_output(
+        Err("Error at line 2; column 17
+@assert 2 ** -1 == 0
+        ^^^^^^^   Negative exponent"),
+        "
+        @assert 2 ** -1 == 0
+        @transition {
+            become #0
+        }",
+    );
+    // Overflow
+    assert_func_output(
+        &[ConstValue::Int(2), ConstValue::Int(63)],
+        Err("Error at line 1; column 43
+@function int test(int x, int y) { return x ** y }
+                                          ^^^^^^   Integer overflow"),
+        "@function int test(int x, int y) { return x ** y }",
+        Some("test"),
+    );
+    assert_output(
+        Err("Error at line 2; column 17
+@assert 2 ** 63 == 0
+        ^^^^^^^   Integer overflow"),
+        "
+        @assert 2 ** 63 == 0
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_bitwise_ops() {
+    for &(x, y) in &[(0b1100, 0b1010), (-1, 6), (0, LangInt::MIN), (-37, 91)] {
+        for &(op, expected) in &[("&", x & y), ("|", x | y), ("^", x ^ y)] {
+            assert_func_output(
+                &[ConstValue::Int(x), ConstValue::Int(y)],
+                Ok(ConstValue::Int(expected)),
+                &format!("@function int test(int x, int y) {{ return x {} y }}", op),
+                Some("test"),
+            );
+        }
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Int(!x)),
+            "@function int test(int x) { return ~x }",
+            Some("test"),
+        );
+    }
+    // Component-wise on vectors
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![0b1100, 5]),
+            ConstValue::Vector(vec![0b1010, 1]),
+        ],
+        Ok(ConstValue::Vector(vec![0b0110, 4])),
+        "@function vec2 test(vec2 u, vec2 v) { return u ^ v }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![0, -1, 7])],
+        Ok(ConstValue::Vector(vec![-1, 0, -8])),
+        "@function vec3 test(vec3 v) { return ~v }",
+        Some("test"),
+    );
+    // An integer operand is applied to every component.
+    assert_func_output(
+        &[ConstValue::Vector(vec![3, 4])],
+        Ok(ConstValue::Vector(vec![1, 0])),
+        "@function vec2 test(vec2 v) { return v & 1 }",
+        Some("test"),
+    );
+    // Precedence and constant evaluation
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @states 4
+        @assert 1 | 2 ^ 3 & 6 == 1
+        @assert ~0 == -1
+        @assert -~0 == 1
+        @assert ([1, 2] ^ 3)[1] == 1
+        @transition {
+            set x = 5
+            set x ^= 6
+            become #(x)
+        }",
+    );
+
+    // Vectors of different lengths
+    assert_func_output(
+        &[ConstValue::Vector(vec![0, 0]), ConstValue::Vector(vec![0, 0, 0])],
+        Err("Error at line 1; column 50
+@function vec2 test(vec2 u, vec3 v) { return u | v }
+                                                 ^   Type error: expected vector of length 2 but got vector of length 3"),
+        "@function vec2 test(vec2 u, vec3 v) { return u | v }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_cell_state_arithmetic() {
+    // Cell states are converted to integers in arithmetic.
+    assert_output(
+        Ok(ConstValue::CellState(5)),
+        "
+        @transition {
+            set s = #2
+            become #(s * 2 + #1)
+        }
+        @states 6",
+    );
+    assert_func_output(
+        &[ConstValue::CellState(3)],
+        Ok(ConstValue::Vector(vec![4, 6])),
+        "@function vec2 test(cellstate s) { return [1, 3] + s }",
+        Some("test"),
+    );
+
+    // Cell states can be converted to integers explicitly, and converting
+    // back gives the same cell state.
+    for s in 0..4 {
+        assert_func_output(
+            &[ConstValue::CellState(s)],
+            Ok(ConstValue::Int(s as LangInt * 10)),
+            "@states 4\n@function int test(cellstate s) { return id(s) * 10 }",
+            Some("test"),
+        );
+        assert_func_output(
+            &[ConstValue::CellState(s)],
+            Ok(ConstValue::CellState(s)),
+            "@states 4\n@function cellstate test(cellstate s) { return #(id(s)) }",
+            Some("test"),
+        );
+    }
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        @states 4
+        @assert id(#2) == 2
+        @transition {
+            become #(id(#1) + id(#2))
+        }",
+    );
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 31
+@function int test() { return id(3) }
+                              ^^^^^   Invalid arguments [Int] for id; expected [CellState]"),
+        "@function int test() { return id(3) }",
+        Some("test"),
+    );
+
+    // Integers are never implicitly converted to cell states.
+    assert_output(
+        Err("Error at line 4; column 21
+set s = s + 1
+        ^^^^^   Type error: expected cell state but got integer"),
+        "
+        @transition {
+            set s = #1
+            set s = s + 1
+        }",
+    );
+
+    // Operand that cannot be used as a number
+    assert_func_output(
+        &[],
+        Err("Error at line 4; column 20
+return a + 1
+       ^^^^^   Expected integers or vectors; got array of length 2 and integer"),
+        "
+        @function int test() {
+            set a = array(2)
+            return a + 1
+        }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_saturating_arithmetic() {
+    for &(a, b, sum, difference) in &[
+        (2, 3, 5, -1),
+        (32000, 1000, 32767, 31000),
+        (-32000, 1000, -31000, -32768),
+        (-32768, -1, -32768, -32767),
+    ] {
+        assert_func_output(
+            &[ConstValue::Int(a), ConstValue::Int(b)],
+            Ok(ConstValue::Int(sum)),
+            "@bits 16\n@function int test(int a, int b) { return sat_add(a, b) }",
+            Some("test"),
+        );
+        assert_func_output(
+            &[ConstValue::Int(a), ConstValue::Int(b)],
+            Ok(ConstValue::Int(difference)),
+            "@bits 16\n@function int test(int a, int b) { return sat_sub(a, b) }",
+            Some("test"),
+        );
+    }
+    // Constant evaluation
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @assert sat_add(9223372036854775807, 1) == 9223372036854775807
+        @assert sat_sub(-9223372036854775807, 2) == -9223372036854775807 - 1
+        @assert sat_sub(5, 7) == -2
+        @transition {
+            become #(sat_sub(3, 2))
+        }",
+    );
+}
+
+#[test]
+fn test_wrapping_arithmetic() {
+    let source_code = "
+        @bits 16
+        @function int test(int a, int b) {
+            return wrap_add(a, b) ^ wrap_sub(a, b) ^ wrap_mul(a, b)
+        }";
+    for &(a, b) in &[
+        (2, 3),
+        (32000, 1000),
+        (-32768, -1),
+        (-32768, 300),
+        (181, 181),
+    ] {
+        let wrap = |x: i32| x as i16 as LangInt;
+        let expected = wrap(a + b) ^ wrap(a - b) ^ wrap(a * b);
+        assert_func_output(
+            &[ConstValue::Int(a as LangInt), ConstValue::Int(b as LangInt)],
+            Ok(ConstValue::Int(expected)),
+            source_code,
+            Some("test"),
+        );
+    }
+    // Constant evaluation
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @bits 16
+        @assert wrap_add(32767, 1) == -32768
+        @assert wrap_sub(-32768, 1) == 32767
+        @assert wrap_mul(256, 256) == 0
+        @transition {
+            become #(wrap_mul(3, 3) - 8)
+        }",
+    );
+}
+
+#[test]
+fn test_bit_count() {
+    let source_code = "
+        @bits 16
+        @function vec3 test(int x) {
+            return [popcount(x), ctlz(x), cttz(x)]
+        }";
+    for &(x, popcount, ctlz, cttz) in &[
+        (0, 0, 16, 16),
+        (1, 1, 15, 0),
+        (0b1011000, 3, 9, 3),
+        (-1, 16, 0, 0),
+        (-32768, 1, 0, 15),
+    ] {
+        assert_func_output(
+            &[ConstValue::Int(x)],
+            Ok(ConstValue::Vector(vec![popcount, ctlz, cttz])),
+            source_code,
+            Some("test"),
+        );
+    }
+    // Constant evaluation
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @bits 16
+        @assert popcount(-2) == 15
+        @assert ctlz(0) == 16
+        @assert ctlz(255) == 8
+        @assert cttz(0) == 16
+        @assert cttz(-32768) == 15
+        @transition {
+            become #(popcount(8))
+        }",
+    );
+}
+
+#[test]
+fn test_mul_add() {
+    let source_code = "
+        @bits 16
+        @function int test(int x, int y) {
+            return x * y + 7 + (3 + x * 2)
+        }";
+    assert_func_output(
+        &[ConstValue::Int(5), ConstValue::Int(-4)],
+        Ok(ConstValue::Int(0)),
+        source_code,
+        Some("test"),
+    );
+    // Overflow in either the multiplication or the addition is reported at
+    // the whole expression.
+    for (x, y) in [(200, 200), (181, 181)].iter() {
+        assert_func_output(
+            &[ConstValue::Int(*x), ConstValue::Int(*y)],
+            Err("Error at line 4; column 20
+return x * y + 7 + (3 + x * 2)
+       ^^^^^^^^^   Integer overflow"),
+            source_code,
+            Some("test"),
+        );
+    }
+    assert_func_output(
+        &[ConstValue::Int(16383), ConstValue::Int(0)],
+        Err("Error at line 4; column 33
+return x * y + 7 + (3 + x * 2)
+                    ^^^^^^^^^   Integer overflow"),
+        source_code,
+        Some("test"),
+    );
+
+    // Overflow wraps in both operations.
+    assert_func_output(
+        &[ConstValue::Int(256), ConstValue::Int(256)],
+        Ok(ConstValue::Int(1)),
+        "
+        @bits 16
+        @overflow wrapping
+        @function int test(int x, int y) {
+            return 1 + x * y
+        }",
+        Some("test"),
+    );
+
+    // Vectors are not fused, but still work.
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Ok(ConstValue::Vector(vec![7, 10])),
+        "
+        @function vec2 test(int x) {
+            return [1, 2] * x + 4
+        }",
+        Some("test"),
+    );
+
+    // An error in the addend is still reported after an overflow in the
+    // multiplication.
+    assert_func_output(
+        &[ConstValue::Int(200), ConstValue::Int(0)],
+        Err("Error at line 4; column 20
+return x * x + 1 / y
+       ^^^^^   Integer overflow"),
+        "
+        @bits 16
+        @function int test(int x, int y) {
+            return x * x + 1 / y
+        }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_overflow_mode() {
+    let source_code = "
+        @bits 16
+        @overflow wrapping
+        @function int test(int x) {
+            set y = x * 2 + 1
+            set y -= 10
+            return y + 100 / x
+        }";
+    assert_func_output(
+        &[ConstValue::Int(20000)],
+        // 40000 wraps around to -25536.
+        Ok(ConstValue::Int(-25545)),
+        source_code,
+        Some("test"),
+    );
+    // Division by zero is still an error.
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Err("Error at line 7; column 24
+return y + 100 / x
+           ^^^^^^^   Divide by zero"),
+        source_code,
+        Some("test"),
+    );
+    // Constants wrap too.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @bits 16
+        @overflow wrapping
+        const BIG = 32767 + 1
+        @assert BIG == -32767 - 1
+        @assert BIG - 1 == 32767
+        @assert 256 * 256 == 0
+        @transition {
+            become #(BIG + 32767 + 2)
+        }",
+    );
+
+    // Overflow is checked by default.
+    assert_output(
+        Err("Error at line 4; column 22
+become #(32767 + 1)
+         ^^^^^^^^^   Integer overflow"),
+        "
+        @bits 16
+        @transition {
+            become #(32767 + 1)
+        }",
+    );
+
+    // Invalid mode
+    assert_output(
+        Err("Error at line 2; column 19
+@overflow saturating
+          ^^^^^^^^^^   Overflow mode must be 'checked' or 'wrapping'"),
+        "
+        @overflow saturating
+        @transition {
+            become #0
+        }",
+    );
+}
@@ -0,0 +1,43 @@
+use super::{assert_func_output, ConstValue, LangInt};
+
+#[test]
+fn test_methods() {
+    assert_func_output(
+        &[ConstValue::Vector(vec![10, 20])],
+        Ok(ConstValue::Int(2)),
+        "@function int test(vec2 v) { return v.len() }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(-5)],
+        Ok(ConstValue::Int(8)),
+        "@function int test(int x) { return [1, 2, 3].len() + x.abs().abs() }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(1)],
+        Ok(ConstValue::Int(2)),
+        "@function int test(int x) { return (x - 3).abs() }",
+        Some("test"),
+    );
+
+    // Overflow
+    assert_func_output(
+        &[ConstValue::Int(LangInt::MIN)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return x.abs() }
+                                   ^^^^^^^   Integer overflow"),
+        "@function int test(int x) { return x.abs() }",
+        Some("test"),
+    );
+
+    // Unknown method
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return x.len() }
+                                   ^^^^^^^   There is no method named \"len\" for integer"),
+        "@function int test(int x) { return x.len() }",
+        Some("test"),
+    );
+}
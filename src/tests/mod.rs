@@ -3,15 +3,37 @@
 use std::borrow::Cow;
 use std::rc::Rc;
 
+use inkwell::OptimizationLevel;
+
+mod arena;
+mod arrays;
+mod assertions;
+mod blocks;
+mod cache;
 mod cmp;
+mod constvalue;
+mod debug;
+mod diagnostics;
+mod directives;
+mod externs;
+mod helpers;
+mod logic;
+mod loops;
 mod math;
+mod methods;
+mod nbhd;
+mod prelude;
+mod ranges;
+mod reload;
+mod tuples;
 mod vars;
 mod vecs;
 
 use super::ast;
-use super::compiler::Compiler;
+use super::compiler::{Compiler, Value};
+use super::errors::LangErrorMsg;
 use super::types::LangInt;
-use super::ConstValue;
+use super::{ConstValue, Type};
 
 #[test]
 fn test_become() {
@@ -44,6 +66,318 @@ become #10
     );
 }
 
+#[test]
+fn test_cell_state_out_of_range() {
+    // Constant cell state IDs are checked at compile time, even if the
+    // conversion is never executed.
+    assert_output(
+        Err("Error at line 3; column 20
+return #(-1)
+       ^^^^^   Cell state out of range"),
+        "
+        @function cellstate f() {
+            return #(-1)
+        }
+        @transition {
+            become #0
+        }",
+    );
+
+    // Other cell state IDs are checked at runtime.
+    let source_code = "
+        @function cellstate f(int x) {
+            return #x
+        }";
+    assert_func_output(
+        &[ConstValue::Int(1)],
+        Ok(ConstValue::CellState(1)),
+        source_code,
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(2)],
+        Err("Error at line 3; column 20
+return #x
+       ^^   Cell state out of range"),
+        source_code,
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(-1)],
+        Err("Error at line 3; column 20
+return #x
+       ^^   Cell state out of range"),
+        source_code,
+        Some("f"),
+    );
+}
+
+#[test]
+fn test_unreachable_statements() {
+    assert_output(
+        Err("Error at line 4; column 13
+set x = 3
+^^^^^^^^^   This statement is unreachable"),
+        "
+        @transition {
+            become #1
+            set x = 3
+        }",
+    );
+    assert_output(
+        Err("Error at line 5; column 17
+become #1
+^^^^^^^^^   This statement is unreachable"),
+        "
+        @transition {
+            while 1 {
+                break
+                become #1
+            }
+        }",
+    );
+
+    // An `if` statement terminates if both of its branches do.
+    assert_output(
+        Err("Error at line 8; column 13
+become #1
+^^^^^^^^^   This statement is unreachable"),
+        "
+        @transition {
+            if 1 {
+                become #1
+            } else {
+                become #0
+            }
+            become #1
+        }",
+    );
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            if 0 {
+                become #0
+            }
+            become #1
+        }",
+    );
+}
+
+#[test]
+fn test_missing_return() {
+    assert_func_output(
+        &[ConstValue::Int(1)],
+        Err("Error at line 2; column 23
+@function int f(int x) {
+              ^   This function may reach the end without returning a value"),
+        "
+        @function int f(int x) {
+            if x {
+                return 1
+            }
+        }",
+        Some("f"),
+    );
+    // A `match` statement only terminates if it has an `else` case.
+    assert_func_output(
+        &[ConstValue::Int(1)],
+        Err("Error at line 2; column 23
+@function int f(int x) {
+              ^   This function may reach the end without returning a value"),
+        "
+        @function int f(int x) {
+            match x {
+                1 => { return 1 }
+            }
+        }",
+        Some("f"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(0)],
+        Ok(ConstValue::Int(2)),
+        "
+        @function int f(int x) {
+            if x {
+                return 1
+            } else {
+                return 2
+            }
+        }",
+        Some("f"),
+    );
+}
+
+#[test]
+fn test_execute() {
+    let source_code = Rc::new("@function int test(int x, vec2 v) { return x + v[1] }".to_owned());
+    let rule = ast::make_rule(source_code).expect("Failed to build rule");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    let mut compiled_function = rule.helper_functions()["test"]
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile function");
+    assert_eq!(
+        Ok(ConstValue::Int(5)),
+        compiled_function
+            .execute(&[ConstValue::Int(3), ConstValue::Vector(vec![1, 2])])
+            .map_err(|_| ()),
+    );
+    // The same compiled function can be executed again with new arguments.
+    assert_eq!(
+        Ok(ConstValue::Int(-1)),
+        compiled_function
+            .execute(&[ConstValue::Int(-3), ConstValue::Vector(vec![1, 2])])
+            .map_err(|_| ()),
+    );
+    // Wrong number of arguments
+    assert!(compiled_function.execute(&[ConstValue::Int(3)]).is_err());
+    // Wrong type of argument
+    assert!(compiled_function
+        .execute(&[ConstValue::Int(3), ConstValue::Int(2)])
+        .is_err());
+}
+
+#[test]
+fn test_narrow_inout_values() {
+    // Values assigned to arguments can be read by Rust code, even when they
+    // are stored using a wider type than the rule uses.
+    let source_code = Rc::new(
+        "
+        @bits 16
+        @function int test(int x, vec2 v) {
+            set x = x * 2
+            set v[1] = -v[1]
+            return x + v[0] + 100 / (x - 6)
+        }"
+        .to_owned(),
+    );
+    let rule = ast::make_rule(source_code).expect("Failed to build rule");
+    let mut compiler =
+        Compiler::with_int_bits(rule.meta().int_bits).expect("Failed to create compiler");
+    let mut compiled_function = rule.helper_functions()["test"]
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile function");
+    let args = [ConstValue::Int(2), ConstValue::Vector(vec![1, -5])];
+    assert_eq!(
+        Ok(ConstValue::Int(-45)),
+        compiled_function.execute(&args).map_err(|_| ()),
+    );
+    assert_eq!(ConstValue::Int(4), compiled_function.value_mut(0).get());
+    assert_eq!(
+        ConstValue::Vector(vec![1, 5]),
+        compiled_function.value_mut(1).get(),
+    );
+    // The values are also copied back when returning an error.
+    let args = [ConstValue::Int(3), ConstValue::Vector(vec![1, -5])];
+    assert!(compiled_function.execute(&args).is_err());
+    assert_eq!(ConstValue::Int(6), compiled_function.value_mut(0).get());
+    assert_eq!(
+        ConstValue::Vector(vec![1, 5]),
+        compiled_function.value_mut(1).get(),
+    );
+}
+
+#[test]
+fn test_compile_stats() {
+    let source_code = Rc::new(
+        "
+        @function int square(int x) {
+            return x * x
+        }
+        @function int test(int x) {
+            return square(x) + 1
+        }"
+        .to_owned(),
+    );
+    let rule = ast::make_rule(source_code).expect("Failed to build rule");
+    let compile = |collect_stats| {
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_collect_stats(collect_stats);
+        rule.helper_functions()["test"]
+            .compile(&mut compiler, rule.helper_functions())
+            .expect("Failed to compile function")
+    };
+
+    // Statistics are not collected by default.
+    assert_eq!(None, compile(false).stats());
+
+    let compiled_function = compile(true);
+    let stats = compiled_function.stats().expect("Missing compile stats");
+    // Both functions are counted, and each has at least one basic block and
+    // one possible overflow.
+    assert!(stats.basic_blocks >= 2);
+    assert!(stats.instructions > stats.basic_blocks);
+    assert!(stats.error_points >= 2);
+}
+
+#[test]
+fn test_opt_levels() {
+    let source_code = Rc::new(
+        "
+        @function int square(int x) {
+            return x * x
+        }
+        @function int test(int x) {
+            set total = 0
+            for i in 1..x {
+                set total += square(i)
+            }
+            return 100 / total
+        }"
+        .to_owned(),
+    );
+    let rule = ast::make_rule(source_code).expect("Failed to build rule");
+    for &opt_level in &[
+        OptimizationLevel::None,
+        OptimizationLevel::Less,
+        OptimizationLevel::Default,
+        OptimizationLevel::Aggressive,
+    ] {
+        let mut compiler = Compiler::new().expect("Failed to create compiler");
+        compiler.set_opt_level(opt_level);
+        let mut compiled_function = rule.helper_functions()["test"]
+            .compile(&mut compiler, rule.helper_functions())
+            .expect("Failed to compile function");
+        assert_eq!(
+            Ok(ConstValue::Int(20)),
+            compiled_function
+                .execute(&[ConstValue::Int(2)])
+                .map_err(|e| e.msg),
+        );
+        // Runtime errors are still reported after optimization.
+        assert_eq!(
+            Err(LangErrorMsg::DivideByZero),
+            compiled_function
+                .execute(&[ConstValue::Int(0)])
+                .map_err(|e| e.msg),
+        );
+        assert_eq!(
+            Err(LangErrorMsg::IntegerOverflow),
+            compiled_function
+                .execute(&[ConstValue::Int(4_000_000_000)])
+                .map_err(|e| e.msg),
+        );
+    }
+}
+
+#[test]
+fn test_poison_value() {
+    let compiler = Compiler::new().expect("Failed to create compiler");
+    for &ty in &[Type::Int, Type::CellState, Type::Vector(3)] {
+        let poison = Value::Poison(ty);
+        assert!(poison.is_poison());
+        // A poisoned value has a type ...
+        assert_eq!(ty, poison.ty());
+        // ... but no LLVM representation.
+        assert!(poison.clone().into_basic_value().is_err());
+        // It can be replaced with the default value of its type.
+        let unpoisoned = compiler.unpoison(poison).expect("Failed to unpoison");
+        assert!(!unpoisoned.is_poison());
+        assert_eq!(compiler.get_default_var_value(ty), Some(unpoisoned));
+    }
+    assert!(Value::Poison(Type::Int).as_int().is_err());
+}
+
 /// Compiles and runs the transition function of the given source code.
 fn assert_output<'a>(expected: Result<ConstValue, &'a str>, source_code: &str) {
     assert_func_output(&[], expected, source_code, None)
@@ -62,18 +396,16 @@ fn assert_func_output<'a>(
     let actual_result: Result<ConstValue, Cow<'a, str>> = match rule {
         Ok(rule) => {
             // Compile the rule.
-            let mut compiler = Compiler::new().expect("Failed to create compiler");
+            let mut compiler =
+                Compiler::with_int_bits(rule.meta().int_bits).expect("Failed to create compiler");
             let user_fn = if let Some(name) = fn_name {
                 &rule.helper_functions()[name]
             } else {
                 rule.transition_function()
             };
             user_fn
-                .compile(&mut compiler)
-                .and_then(|mut compiled_function| {
-                    compiled_function.set_args(args);
-                    compiled_function.call()
-                })
+                .compile(&mut compiler, rule.helper_functions())
+                .and_then(|mut compiled_function| compiled_function.execute(args))
                 .map_err(|e| e.with_source(source_code).to_string().into())
         }
         Err(e) => Err(e.with_source(source_code).to_string().into()),
@@ -88,6 +420,23 @@ fn assert_func_output<'a>(
     );
 }
 
+/// Compiles and runs the specified function of the given source code and
+/// returns its result. The location of any error is discarded, so that results
+/// from different source code can be compared.
+fn eval_func(
+    args: &[ConstValue],
+    source_code: &str,
+    fn_name: &str,
+) -> Result<ConstValue, LangErrorMsg> {
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).map_err(|e| e.msg)?;
+    let mut compiler =
+        Compiler::with_int_bits(rule.meta().int_bits).expect("Failed to create compiler");
+    rule.helper_functions()[fn_name]
+        .compile(&mut compiler, rule.helper_functions())
+        .and_then(|mut compiled_function| compiled_function.execute(args))
+        .map_err(|e| e.msg)
+}
+
 fn display_result<T: std::fmt::Debug, E: std::fmt::Display>(result: &Result<T, E>) -> String {
     match result {
         Ok(t) => format!("{:?}", t),
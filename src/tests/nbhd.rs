@@ -0,0 +1,705 @@
+use std::rc::Rc;
+
+use super::super::compiler::CompiledRule;
+use super::super::types::{LangCellState, LangInt};
+use super::{assert_output, ast, Compiler, ConstValue};
+
+/// Compiles the transition function of the given source code and runs it on
+/// each of the given neighborhoods, returning the results.
+fn run_on_nbhds(source_code: &str, nbhds: &[&[LangCellState]]) -> Vec<ConstValue> {
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build rule");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    let mut transition_function = rule
+        .transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    nbhds
+        .iter()
+        .map(|nbhd| {
+            transition_function.set_nbhd(nbhd);
+            transition_function
+                .execute(&[])
+                .expect("Failed to execute transition function")
+        })
+        .collect()
+}
+
+/// Compiles the transition function of the given source code and runs it at
+/// each of the given positions during the given step, returning the results.
+fn run_at_positions(source_code: &str, positions: &[&[LangInt]], step: u64) -> Vec<ConstValue> {
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build rule");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    let mut transition_function = rule
+        .transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    positions
+        .iter()
+        .map(|pos| {
+            transition_function.set_pos(pos);
+            transition_function.set_step(step);
+            transition_function
+                .execute(&[])
+                .expect("Failed to execute transition function")
+        })
+        .collect()
+}
+
+#[test]
+fn test_neighbor() {
+    // Conway's Game of Life
+    let life = "
+        @transition {
+            set count = 0
+            for x in -1..1 {
+                for y in -1..1 {
+                    if neighbor([x, y]) == #1 {
+                        set count += 1
+                    }
+                }
+            }
+            if neighbor([0, 0]) == #1 {
+                set count -= 1
+                if count == 2 or count == 3 {
+                    become #1
+                }
+            } else if count == 3 {
+                become #1
+            }
+            become #0
+        }";
+    assert_eq!(
+        vec![
+            ConstValue::CellState(1),
+            ConstValue::CellState(1),
+            ConstValue::CellState(0),
+        ],
+        run_on_nbhds(
+            life,
+            &[
+                &[0, 1, 0, 0, 1, 0, 0, 1, 0],
+                &[1, 1, 1, 0, 0, 0, 0, 0, 0],
+                &[1, 0, 0, 0, 1, 0, 0, 0, 0],
+            ],
+        ),
+    );
+
+    // The first axis varies fastest.
+    let mut nbhd = [0; 25];
+    nbhd[4] = 1;
+    nbhd[20] = 2;
+    assert_eq!(
+        vec![ConstValue::CellState(1)],
+        run_on_nbhds(
+            "
+            @radius 2
+            @states 3
+            @transition {
+                become neighbor([2, -2])
+            }",
+            &[&nbhd],
+        ),
+    );
+    assert_eq!(
+        vec![ConstValue::CellState(2)],
+        run_on_nbhds(
+            "
+            @radius 2
+            @states 3
+            @transition {
+                become neighbor([-2, 2])
+            }",
+            &[&nbhd],
+        ),
+    );
+
+    // Constant offset outside the neighborhood
+    assert_output(
+        Err("Error at line 3; column 29
+become neighbor([2, 0])
+                ^^^^^^   Neighbor offset out of range; each component must be from -1 to 1"),
+        "
+        @transition {
+            become neighbor([2, 0])
+        }",
+    );
+
+    // Offset outside the neighborhood at runtime
+    assert_output(
+        Err("Error at line 4; column 20
+become neighbor([x, 0])
+       ^^^^^^^^^^^^^^^^   Neighbor offset out of range; each component must be from -1 to 1"),
+        "
+        @transition {
+            set x = -2
+            become neighbor([x, 0])
+        }",
+    );
+
+    // Neighbors in a helper function
+    assert_output(
+        Err("Error at line 3; column 20
+return neighbor([0, 0])
+       ^^^^^^^^^^^^^^^^   Neighbors can only be accessed in the transition function"),
+        "
+        @function cellstate f() {
+            return neighbor([0, 0])
+        }",
+    );
+
+    // Invalid radius
+    assert_output(
+        Err("Error at line 2; column 17
+@radius 0
+        ^   Neighborhood radius must range from 1 to 8"),
+        "
+        @radius 0
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_dynamic_neighbor_offsets() {
+    // Sum a 3x3 window using offsets computed from loop variables.
+    let window_sum = "
+        @states 20
+        @transition {
+            set total = 0
+            for y in -1..1 {
+                for x in -1..1 {
+                    set total += id(neighbor([x, y]))
+                }
+            }
+            become #(total)
+        }";
+    assert_eq!(
+        vec![ConstValue::CellState(9), ConstValue::CellState(12)],
+        run_on_nbhds(window_sum, &[&[1; 9], &[0, 1, 2, 3, 0, 1, 2, 3, 0]]),
+    );
+
+    // The window does not have to be centered, as long as it stays inside the
+    // neighborhood.
+    let nbhd: Vec<LangCellState> = (0..25).map(|i| i % 4).collect();
+    assert_eq!(
+        vec![ConstValue::CellState(14)],
+        run_on_nbhds(
+            "
+            @radius 2
+            @states 20
+            @transition {
+                set total = 0
+                for y in 0..2 {
+                    for x in 0..2 {
+                        set total += id(neighbor([x, y]))
+                    }
+                }
+                become #(total)
+            }",
+            &[&nbhd],
+        ),
+    );
+
+    // Offsets are checked against the declared radius at runtime, and do not
+    // wrap around.
+    assert_output(
+        Err("Error at line 5; column 33
+set total += id(neighbor([x, 0]))
+                ^^^^^^^^^^^^^^^^   Neighbor offset out of range; each component must be from -1 to 1"),
+        "
+        @transition {
+            set total = 0
+            for x in -1..2 {
+                set total += id(neighbor([x, 0]))
+            }
+            become #(total)
+        }",
+    );
+}
+
+#[test]
+fn test_count() {
+    // The center cell is not counted.
+    assert_eq!(
+        vec![
+            ConstValue::CellState(8),
+            ConstValue::CellState(0),
+            ConstValue::CellState(3),
+        ],
+        run_on_nbhds(
+            "
+            @states 9
+            @transition {
+                become #(count(#1))
+            }",
+            &[
+                &[1; 9],
+                &[0, 0, 0, 0, 1, 0, 0, 0, 0],
+                &[1, 0, 0, 1, 0, 0, 1, 0, 0],
+            ],
+        ),
+    );
+
+    // Larger neighborhood in 1D
+    assert_eq!(
+        vec![ConstValue::CellState(2)],
+        run_on_nbhds(
+            "
+            @dimensions 1
+            @radius 2
+            @states 3
+            @transition {
+                become #(count(#2))
+            }",
+            &[&[2, 0, 2, 1, 0]],
+        ),
+    );
+
+    // Counting in a helper function
+    assert_output(
+        Err("Error at line 3; column 20
+return count(#1)
+       ^^^^^^^^^   Neighbors can only be accessed in the transition function"),
+        "
+        @function int f() {
+            return count(#1)
+        }",
+    );
+}
+
+#[test]
+fn test_single_state_nbhd() {
+    // In a rule with only one state, every neighbor is #0.
+    assert_eq!(
+        vec![ConstValue::CellState(0)],
+        run_on_nbhds(
+            "
+            @states 1
+            @transition {
+                if neighbor([1, 0]) == #0 and count(#0) == neighbor_count() {
+                    become neighbor([-1, 1])
+                }
+                become #0
+            }",
+            &[&[0; 9]],
+        ),
+    );
+    assert_output(
+        Err("Error at line 4; column 28
+become #(count(#1))
+               ^^   Cell state out of range"),
+        "
+        @states 1
+        @transition {
+            become #(count(#1))
+        }",
+    );
+}
+
+#[test]
+fn test_neighbor_count() {
+    // The neighbor count can be used as a loop bound and as a divisor.
+    assert_eq!(
+        vec![ConstValue::CellState(9), ConstValue::CellState(1)],
+        run_on_nbhds(
+            "
+            @states 10
+            @transition {
+                set n = 0
+                for i in 1..neighbor_count() {
+                    set n += 1
+                }
+                become #(count(#1) * n / neighbor_count() + 1)
+            }",
+            &[&[1; 9], &[0, 0, 0, 0, 1, 0, 0, 0, 0]],
+        ),
+    );
+
+    // It is a constant, so it can be used as the length of an array.
+    assert_output(
+        Ok(ConstValue::CellState(12)),
+        "
+        @dimensions 1
+        @radius 2
+        @states 13
+        @transition {
+            set a = array(neighbor_count())
+            for i in 0..3 {
+                set a[i] = i
+            }
+            become #(a[3] * neighbor_count())
+        }",
+    );
+
+    // It only counts cells that are part of the neighborhood shape, and never
+    // the center cell.
+    for &(directives, expected) in &[
+        ("", 8),
+        ("@neighborhood vonneumann", 4),
+        ("@radius 2 @neighborhood vonneumann", 12),
+        ("@dimensions 3", 26),
+        ("@dimensions 1 @radius 3", 6),
+    ] {
+        assert_output(
+            Ok(ConstValue::CellState(expected)),
+            &format!(
+                "{} @states 30 @transition {{ become #(neighbor_count()) }}",
+                directives,
+            ),
+        );
+    }
+
+    // Using it in a helper function, where there is no neighborhood
+    assert_output(
+        Err("Error at line 3; column 20
+return neighbor_count()
+       ^^^^^^^^^^^^^^^^   Neighbors can only be accessed in the transition function"),
+        "
+        @function int f() {
+            return neighbor_count()
+        }",
+    );
+}
+
+/// Compiles the rule from the given source code and returns its halo radius.
+fn compile_halo_radius(source_code: &str) -> Vec<usize> {
+    CompiledRule::try_new(Rc::new(source_code.to_owned()))
+        .expect("Failed to compile rule")
+        .halo_radius()
+        .to_vec()
+}
+
+#[test]
+fn test_halo_radius() {
+    // Constant offsets are tracked separately along each axis.
+    assert_eq!(
+        vec![2, 1],
+        compile_halo_radius(
+            "
+            @radius 3
+            @transition {
+                if neighbor([-2, 0]) == #1 {
+                    become #1
+                }
+                become neighbor([0, 1])
+            }",
+        ),
+    );
+    assert_eq!(
+        vec![0, 0, 0],
+        compile_halo_radius("@dimensions 3 @transition { become #1 }"),
+    );
+
+    // Offsets that are not constant may be anywhere in the neighborhood, and
+    // so may the cells counted by `count()`.
+    assert_eq!(
+        vec![3, 3],
+        compile_halo_radius(
+            "
+            @radius 3
+            @transition {
+                set x = 1
+                while x < 3 {
+                    set x += 1
+                }
+                become neighbor([x, 0])
+            }",
+        ),
+    );
+    assert_eq!(
+        vec![2],
+        compile_halo_radius("@dimensions 1 @radius 2 @transition { become #(count(#1)) }"),
+    );
+
+    // Neighbors read in a block that is never compiled do not count.
+    assert_eq!(
+        vec![0, 1],
+        compile_halo_radius(
+            "
+            @radius 3
+            @transition {
+                if false {
+                    become neighbor([3, 3])
+                }
+                become neighbor([0, -1])
+            }",
+        ),
+    );
+
+    // Helper functions cannot read neighbors.
+    let rule = ast::make_rule(Rc::new(
+        "@function int f() { return 1 }  @transition { become #(f()) }".to_owned(),
+    ))
+    .expect("Failed to build rule");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    let helper = rule.helper_functions()["f"]
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile helper function");
+    assert!(helper.halo_radius().is_empty());
+}
+
+#[test]
+fn test_von_neumann_nbhd() {
+    assert_eq!(
+        vec![ConstValue::CellState(4), ConstValue::CellState(1)],
+        run_on_nbhds(
+            "
+            @neighborhood vonneumann
+            @states 5
+            @transition {
+                become #(count(#1))
+            }",
+            &[&[1; 9], &[1, 0, 1, 0, 0, 1, 1, 0, 1]],
+        ),
+    );
+
+    // Constant offset outside the neighborhood
+    assert_output(
+        Err("Error at line 4; column 29
+become neighbor([1, 1])
+                ^^^^^^   Neighbor offset out of range; the absolute values of the components must add up to at most 1"),
+        "
+        @neighborhood vonneumann
+        @transition {
+            become neighbor([1, 1])
+        }",
+    );
+
+    // Offset outside the neighborhood at runtime
+    assert_output(
+        Err("Error at line 5; column 20
+become neighbor([x, x])
+       ^^^^^^^^^^^^^^^^   Neighbor offset out of range; the absolute values of the components must add up to at most 1"),
+        "
+        @neighborhood vonneumann
+        @transition {
+            set x = 1
+            become neighbor([x, x])
+        }",
+    );
+
+    // Invalid shape
+    assert_output(
+        Err("Error at line 2; column 23
+@neighborhood hex
+              ^^^   Neighborhood shape must be 'moore' or 'vonneumann'"),
+        "
+        @neighborhood hex
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_remain() {
+    assert_eq!(
+        vec![
+            ConstValue::CellState(2),
+            ConstValue::CellState(0),
+            ConstValue::CellState(1),
+        ],
+        run_on_nbhds(
+            "
+            @states 3
+            @transition {
+                if count(#1) == 3 {
+                    become #1
+                }
+                remain
+            }",
+            &[
+                &[0, 0, 0, 0, 2, 0, 0, 0, 0],
+                &[1, 1, 0, 0, 0, 0, 0, 0, 0],
+                &[1, 1, 1, 0, 0, 0, 0, 0, 0],
+            ],
+        ),
+    );
+
+    // The transition function implicitly remains at the end.
+    assert_eq!(
+        vec![ConstValue::CellState(2)],
+        run_on_nbhds(
+            "
+            @states 3
+            @transition {
+                if neighbor([0, 0]) == #0 {
+                    become #1
+                }
+            }",
+            &[&[0, 0, 0, 0, 2, 0, 0, 0, 0]],
+        ),
+    );
+
+    // Remaining in a helper function
+    assert_output(
+        Err("Error at line 3; column 13
+remain
+^^^^^^   'remain' can only be used in the transition function"),
+        "
+        @function cellstate f() {
+            remain
+        }",
+    );
+}
+
+#[test]
+fn test_pos() {
+    // Checkerboard
+    assert_eq!(
+        vec![
+            ConstValue::CellState(0),
+            ConstValue::CellState(1),
+            ConstValue::CellState(1),
+            ConstValue::CellState(0),
+        ],
+        run_at_positions(
+            "
+            @transition {
+                become #(mod(pos().x + pos().y, 2))
+            }",
+            &[&[0, 0], &[1, 0], &[-3, 0], &[-5, 7]],
+            0,
+        ),
+    );
+
+    // The position has one component per dimension.
+    assert_eq!(
+        vec![ConstValue::CellState(2), ConstValue::CellState(0)],
+        run_at_positions(
+            "
+            @dimensions 3
+            @states 3
+            @transition {
+                become #(mod(sum(pos()), 3))
+            }",
+            &[&[1, 0, 1], &[4, -2, 1]],
+            0,
+        ),
+    );
+
+    // Position in a helper function
+    assert_output(
+        Err("Error at line 3; column 20
+return pos()
+       ^^^^^   The position of the cell can only be accessed in the transition function"),
+        "
+        @function vec2 f() {
+            return pos()
+        }",
+    );
+}
+
+#[test]
+fn test_rand() {
+    let positions: Vec<[LangInt; 2]> = (-5..5).flat_map(|x| (-5..5).map(move |y| [x, y])).collect();
+    let positions: Vec<&[LangInt]> = positions.iter().map(|pos| &pos[..]).collect();
+    let dice = "
+        @states 6
+        @transition {
+            become #(rand(6))
+        }";
+    let results = run_at_positions(dice, &positions, 0);
+    // Every result is in range, and is not always the same.
+    for result in &results {
+        assert!(matches!(result, ConstValue::CellState(0..=5)));
+    }
+    for i in 0..6 {
+        assert!(results.contains(&ConstValue::CellState(i)));
+    }
+    // The results are reproducible ...
+    assert_eq!(results, run_at_positions(dice, &positions, 0));
+    // ... but depend on the step number ...
+    assert_ne!(results, run_at_positions(dice, &positions, 1));
+    // ... and the seed.
+    let seeded_dice = format!("@seed 12345\n{}", dice);
+    assert_ne!(results, run_at_positions(&seeded_dice, &positions, 0));
+
+    // Each call gives a different number, even in a loop.
+    let results = run_at_positions(
+        "
+        @transition {
+            set total = 0
+            for i in 1..10 {
+                set total += rand(2)
+            }
+            if total != 0 and total != 10 {
+                become #1
+            }
+            become #0
+        }",
+        &positions,
+        0,
+    );
+    assert!(results.contains(&ConstValue::CellState(1)));
+
+    // Upper bound that is not positive
+    assert_output(
+        Err("Error at line 3; column 27
+become #(rand(0))
+              ^   Upper bound for a random number must be positive"),
+        "
+        @transition {
+            become #(rand(0))
+        }",
+    );
+    assert_output(
+        Err("Error at line 4; column 22
+become #(rand(n))
+         ^^^^^^^   Upper bound for a random number must be positive"),
+        "
+        @transition {
+            set n = -1
+            become #(rand(n))
+        }",
+    );
+
+    // Random number in a helper function
+    assert_output(
+        Err("Error at line 3; column 20
+return rand(2)
+       ^^^^^^^   Random numbers can only be generated in the transition function"),
+        "
+        @function int f() {
+            return rand(2)
+        }",
+    );
+}
+
+#[test]
+fn test_step() {
+    let source_code = "
+        @function int double(int s) {
+            return s * 2
+        }
+        @transition {
+            become #(mod(double(step()) + step(), 5))
+        }
+        @states 5";
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build rule");
+    let mut compiler = Compiler::new().expect("Failed to create compiler");
+    let mut transition_function = rule
+        .transition_function()
+        .compile(&mut compiler, rule.helper_functions())
+        .expect("Failed to compile transition function");
+    for &(step, expected) in &[(0, 0), (1, 3), (2, 1), (7, 1)] {
+        assert_eq!(
+            Ok(ConstValue::CellState(expected)),
+            transition_function
+                .execute_at_step(&[], step)
+                .map_err(|e| e.msg),
+        );
+    }
+
+    // Step number in a helper function
+    assert_output(
+        Err("Error at line 3; column 20
+return step()
+       ^^^^^^   The step number can only be accessed in the transition function; pass it to helper functions as an argument"),
+        "
+        @function int f() {
+            return step()
+        }",
+    );
+}
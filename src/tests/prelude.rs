@@ -0,0 +1,107 @@
+use std::rc::Rc;
+
+use super::{assert_output, ast, ConstValue};
+
+#[test]
+fn test_prelude() {
+    for &(x, expected) in &[(-7, 0), (0, 1), (3, 2)] {
+        assert_output(
+            Ok(ConstValue::CellState(expected)),
+            &format!("@states 3 @transition {{ become #(sign({}) + 1) }}", x),
+        );
+    }
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            if is_even(-4) and is_odd(-3) and not is_odd(10) and not is_even(7) {
+                become #1
+            }
+            become #0
+        }",
+    );
+
+    // Prelude functions can be called from helper functions too.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @function int magnitude(int x) {
+            return x * sign(x)
+        }
+        @transition {
+            become #(magnitude(-1))
+        }",
+    );
+
+    // The rule's constants and cell state names do not conflict with the
+    // arguments of prelude functions.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @states 3
+        const x = -5
+        state y = #1
+        @transition {
+            become #(sign(x) + id(y) + 1)
+        }",
+    );
+}
+
+#[test]
+fn test_no_prelude() {
+    assert_output(
+        Err("Error at line 3; column 32
+@transition { become #(sign(1)) }
+                       ^^^^   There is no function named \"sign\""),
+        "
+        @no_prelude
+        @transition { become #(sign(1)) }",
+    );
+
+    // A rule without the prelude can define its own functions with the same
+    // names, without any warnings.
+    let rule = ast::make_rule(Rc::new(
+        "
+        @no_prelude
+        @function int sign(int x) { return 1 }
+        @transition { become #(sign(-1)) }"
+            .to_owned(),
+    ))
+    .expect("Failed to build rule");
+    assert!(rule.warnings().is_empty());
+    assert!(!rule.helper_functions().contains_key("is_even"));
+
+    assert_output(
+        Err("Error at line 3; column 9
+@no_prelude
+^^^^^^^^^^^   Multiple \"no_prelude\" directives; only one is allowed"),
+        "
+        @no_prelude
+        @no_prelude
+        @transition { become #0 }",
+    );
+}
+
+#[test]
+fn test_prelude_shadowing() {
+    // A helper function with the same name as a prelude function replaces it,
+    // with a warning.
+    let source_code = "
+        @function int sign(int x) { return 1 }
+        @transition { become #(sign(-1)) }";
+    assert_output(Ok(ConstValue::CellState(1)), source_code);
+    let rule = ast::make_rule(Rc::new(source_code.to_owned())).expect("Failed to build rule");
+    let warnings: Vec<String> = rule
+        .warnings()
+        .iter()
+        .map(|w| w.clone().with_source(source_code).to_string())
+        .collect();
+    assert_eq!(
+        vec![
+            "Error at line 2; column 23
+@function int sign(int x) { return 1 }
+              ^^^^   This function replaces \"sign\" from the prelude"
+        ],
+        warnings,
+    );
+}
@@ -0,0 +1,87 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_range_type() {
+    // Ranges can be constructed from integers.
+    assert_output(
+        Err("Error at line 2; column 17
+@assert 1..3
+        ^^^^   Type error: expected boolean but got range"),
+        "
+        @assert 1..3
+        @transition {
+            become #1
+        }",
+    );
+
+    // Ranges cannot be stored in variables.
+    assert_output(
+        Err("Error at line 3; column 21
+set r = -1..1
+        ^^^^^   Cannot assign range to variable"),
+        "
+        @transition {
+            set r = -1..1
+        }",
+    );
+
+    // Range endpoints must be integers.
+    assert_output(
+        Err(
+            "Error at line 2; column 17
+@assert #1..2
+        ^^^^^   Invalid arguments [CellState, Int] for binary \"..\" operator; expected [Int, Int]",
+        ),
+        "
+        @assert #1..2
+        @transition {
+            become #1
+        }",
+    );
+}
+
+#[test]
+fn test_range_contains() {
+    // Ranges are inclusive on both ends.
+    for x in -3..=4 {
+        let expected = Ok(ConstValue::Bool(-1 <= x && x <= 2));
+        for source_code in &[
+            "@function bool test(int x) { return x in -1..2 }",
+            "@function bool test(int x) { return (-1..2).contains(x) }",
+            "@function bool test(int x) { return x.is_in_range(-1..2) }",
+            // The range does not have to be constant.
+            "@function bool test(int x) { return 2 in x..x + 3 }",
+        ] {
+            assert_func_output(
+                &[ConstValue::Int(x)],
+                expected.clone(),
+                source_code,
+                Some("test"),
+            );
+        }
+    }
+
+    // Constant ranges are checked at compile time.
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @assert 3 in 1..5
+        @assert not 0 in 1..5
+        @assert (1..5).contains(5)
+        @assert not (6).is_in_range(1..5)
+        @assert not 0 in 1..-1
+        @transition {
+            become #(1 in 1..1)
+        }",
+    );
+
+    // The right-hand side of `in` must be a range.
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Err("Error at line 1; column 37
+@function bool test(int x) { return x in 5 }
+                                    ^^^^^^   Invalid arguments [Int, Int] for binary \"in\" operator; expected [Int, Range]"),
+        "@function bool test(int x) { return x in 5 }",
+        Some("test"),
+    );
+}
@@ -0,0 +1,51 @@
+use std::rc::Rc;
+
+use super::super::compiler::CompiledRule;
+use super::super::errors::LangErrorMsg;
+use super::ConstValue;
+
+#[test]
+fn test_reload() {
+    let mut rule = CompiledRule::try_new(Rc::new("@transition { become #1 }".to_owned())).unwrap();
+    assert_eq!(
+        ConstValue::CellState(1),
+        rule.transition_function_mut().execute(&[]).unwrap(),
+    );
+    let mut old_rule = rule.clone();
+
+    // Reloading replaces the rule's metadata along with its transition
+    // function.
+    rule.reload(Rc::new("@states 3 @transition { become #2 }".to_owned()))
+        .unwrap();
+    assert_eq!(3, rule.meta().state_count());
+    assert_eq!(
+        ConstValue::CellState(2),
+        rule.transition_function_mut().execute(&[]).unwrap(),
+    );
+
+    // Clones from before the reload keep running the old rule.
+    assert_eq!(2, old_rule.meta().state_count());
+    assert_eq!(
+        ConstValue::CellState(1),
+        old_rule.transition_function_mut().execute(&[]).unwrap(),
+    );
+    drop(old_rule);
+
+    // A rule with errors leaves the old rule in place.
+    let error = rule
+        .reload(Rc::new("@transition { become #5 }".to_owned()))
+        .unwrap_err();
+    assert_eq!(LangErrorMsg::CellStateOutOfRange, error.msg);
+    assert_eq!(3, rule.meta().state_count());
+    assert_eq!(
+        ConstValue::CellState(2),
+        rule.transition_function_mut().execute(&[]).unwrap(),
+    );
+
+    // The neighborhood can change too.
+    rule.reload(Rc::new(
+        "@radius 2 @transition { become neighbor([-2, 1]) }".to_owned(),
+    ))
+    .unwrap();
+    assert_eq!(vec![2, 1], rule.halo_radius().to_vec());
+}
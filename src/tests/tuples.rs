@@ -0,0 +1,111 @@
+use super::{assert_func_output, assert_output, ConstValue};
+
+#[test]
+fn test_tuple_return() {
+    let source_code = "
+        @function (int, cellstate) f(int x) {
+            return (x * 2, #1)
+        }";
+    assert_func_output(
+        &[ConstValue::Int(-3)],
+        Ok(ConstValue::Tuple(vec![
+            ConstValue::Int(-6),
+            ConstValue::CellState(1),
+        ])),
+        source_code,
+        Some("f"),
+    );
+
+    // Integers in tuples are sign-extended when they are returned.
+    let source_code = "
+        @bits 16
+        @function (bool, int) f(int x) {
+            return (x < 0, x)
+        }";
+    assert_func_output(
+        &[ConstValue::Int(-5)],
+        Ok(ConstValue::Tuple(vec![
+            ConstValue::Bool(true),
+            ConstValue::Int(-5),
+        ])),
+        source_code,
+        Some("f"),
+    );
+
+    // Tuples cannot be returned from the transition function.
+    assert_output(
+        Err("Error at line 3; column 13
+become (#1, #0)
+^^^^^^^^^^^^^^^   Type error: expected cell state but got tuple (cell state, cell state)"),
+        "
+        @transition {
+            become (#1, #0)
+        }",
+    );
+
+    // Invalid return type
+    assert_output(
+        Err("Error at line 2; column 25
+@function (int, vec2) f() {
+                ^^^^   Tuples can only contain integers, booleans, and cell states"),
+        "
+        @function (int, vec2) f() {
+            return (1, [2, 3])
+        }",
+    );
+}
+
+#[test]
+fn test_tuple_access() {
+    let source_code = "
+        @function int f(int x) {
+            set t = (x, true, #1)
+            set (a, b, c) = t
+            if b and c == #1 {
+                return a * 10 + t.0
+            }
+            return 0
+        }";
+    assert_func_output(
+        &[ConstValue::Int(4)],
+        Ok(ConstValue::Int(44)),
+        source_code,
+        Some("f"),
+    );
+
+    // Element out of range
+    assert_output(
+        Err("Error at line 4; column 22
+return t.2
+         ^   Index out of bounds"),
+        "
+        @function int f() {
+            set t = (1, 2)
+            return t.2
+        }",
+    );
+
+    // Wrong number of variables
+    assert_output(
+        Err("Error at line 3; column 29
+set (a, b, c) = (1, 2)
+                ^^^^^^   Type error: cannot unpack tuple (integer, integer) into 3 variables"),
+        "
+        @function int f() {
+            set (a, b, c) = (1, 2)
+            return a
+        }",
+    );
+
+    // Invalid element
+    assert_output(
+        Err("Error at line 3; column 25
+set t = (1, [1, 2])
+            ^^^^^^   Tuples can only contain integers, booleans, and cell states"),
+        "
+        @function int f() {
+            set t = (1, [1, 2])
+            return 0
+        }",
+    );
+}
@@ -1,9 +1,12 @@
-use super::{assert_output, ConstValue};
+use super::{assert_func_output, assert_output, ConstValue, LangInt};
 
 #[test]
 fn test_variable_init() {
+    // Assigned on only one path
     assert_output(
-        Ok(ConstValue::CellState(0)),
+        Err("Error at line 7; column 22
+become #(x)
+         ^   This variable must be initialized before it is used"),
         "
         @transition {
             if 0 {
@@ -13,6 +16,54 @@ fn test_variable_init() {
             become #(x)
         }",
     );
+    assert_output(
+        Err("Error at line 6; column 22
+become #(x)
+         ^   This variable must be initialized before it is used"),
+        "
+        @transition {
+            while 0 {
+                set x = 5
+            }
+            become #(x)
+        }",
+    );
+    assert_output(
+        Err("Error at line 3; column 17
+set x += 1
+    ^   This variable must be initialized before it is used"),
+        "
+        @transition {
+            set x += 1
+        }",
+    );
+
+    // Assigned on every path
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            if 0 {
+                set x = 5
+            } else {
+                set x = 1
+            }
+            become #(x)
+        }",
+    );
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @transition {
+            match 3 {
+                1 => { set x = 0 }
+                2 => { become #0 }
+                else => { set x = 1 }
+            }
+            for i in 1..0 {}
+            become #(x * i)
+        }",
+    );
 }
 
 #[test]
@@ -82,3 +133,165 @@ become 0
         }",
     )
 }
+
+#[test]
+fn test_constants() {
+    // Constants can refer to earlier constants and be used in directives.
+    assert_output(
+        Ok(ConstValue::CellState(5)),
+        "
+        const WIDTH = 32
+        const HALF = WIDTH / 2
+        const STATE_COUNT = 6
+        @states STATE_COUNT
+        @transition {
+            set x = HALF - 11
+            become #(x)
+        }",
+    );
+    assert_output(
+        Ok(ConstValue::CellState(3)),
+        "
+        const V = [1, 2, 3]
+        @transition {
+            become #(V[2])
+        }
+        @function int f() {
+            return V.x
+        }",
+    );
+
+    // Constants can refer to cell states and their names, which are checked
+    // against the `@states` directive even if it comes later.
+    assert_output(
+        Ok(ConstValue::CellState(7)),
+        "
+        const S = #5
+        state ALIVE = #2
+        const T = #(id(S) + id(ALIVE))
+        @transition {
+            become T
+        }
+        @states 10",
+    );
+    assert_output(
+        Err("Error at line 2; column 19
+const S = #5
+          ^^   Cell state out of range"),
+        "
+        const S = #5
+        @states 3
+        @transition {
+            become S
+        }",
+    );
+
+    // Reassigning a constant
+    assert_output(
+        Err("Error at line 4; column 17
+set WIDTH = 3
+    ^^^^^   Cannot assign to a constant"),
+        "
+        const WIDTH = 32
+        @transition {
+            set WIDTH = 3
+        }",
+    );
+    assert_output(
+        Err("Error at line 4; column 17
+for WIDTH in 1..3 {}
+    ^^^^^   Cannot assign to a constant"),
+        "
+        const WIDTH = 32
+        @transition {
+            for WIDTH in 1..3 {}
+        }",
+    );
+
+    // Referencing a constant before it is defined
+    assert_output(
+        Err("Error at line 2; column 19
+const A = B
+          ^   This variable must be initialized before it is used"),
+        "
+        const A = B
+        const B = 1
+        @transition {
+            become #0
+        }",
+    );
+
+    // Defining a constant twice
+    assert_output(
+        Err("Error at line 3; column 15
+const A = 2
+      ^   There is already a constant with this name"),
+        "
+        const A = 1
+        const A = 2
+        @transition {
+            become #0
+        }",
+    );
+}
+
+#[test]
+fn test_builtin_constants() {
+    let source_code = "@function int test() { return INT_MAX }";
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(LangInt::MAX)),
+        source_code,
+        Some("test"),
+    );
+    let source_code = "@function int test() { return INT_MIN }";
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(LangInt::MIN)),
+        source_code,
+        Some("test"),
+    );
+
+    // The values depend on the number of bits in an integer.
+    let source_code = "
+        @bits 16
+        const HI = INT_MAX - 1
+        @function int test(int x) {
+            return clamp(x, INT_MIN, HI)
+        }";
+    assert_func_output(
+        &[ConstValue::Int(32767)],
+        Ok(ConstValue::Int(32766)),
+        source_code,
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(-32768)],
+        Ok(ConstValue::Int(-32768)),
+        source_code,
+        Some("test"),
+    );
+
+    // Reassigning a builtin constant
+    assert_output(
+        Err("Error at line 3; column 17
+set INT_MAX = 3
+    ^^^^^^^   Cannot assign to a constant"),
+        "
+        @transition {
+            set INT_MAX = 3
+        }",
+    );
+
+    // Redefining a builtin constant
+    assert_output(
+        Err("Error at line 2; column 15
+const INT_MIN = 0
+      ^^^^^^^   There is already a constant with this name"),
+        "
+        const INT_MIN = 0
+        @transition {
+            become #0
+        }",
+    );
+}
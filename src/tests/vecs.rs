@@ -1,4 +1,4 @@
-use super::{assert_output, ConstValue};
+use super::{assert_func_output, assert_output, ConstValue, LangInt};
 
 #[test]
 fn test_vector_types() {
@@ -41,22 +41,65 @@ fn test_vector_access() {
         }
         @states 10",
     );
-    assert_output(
-        Ok(ConstValue::CellState(0)),
-        "
-        @transition {
-            set v = [1, 10, 100]
-            become #(v.w)
-        }",
+    // Multiple components
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2, 3, 4])],
+        Ok(ConstValue::Vector(vec![4, 1, 1])),
+        "@function vec3 test(vec4 v) { return v.wxx }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![5, 6, 7])],
+        Ok(ConstValue::Vector(vec![6, 5])),
+        "@function vec2 test(vec3 v) { return v.yx }",
+        Some("test"),
     );
+    // Constant vectors
     assert_output(
-        Ok(ConstValue::CellState(0)),
+        Ok(ConstValue::CellState(3)),
         "
+        @states 4
+        @assert [1, 2, 3].z == 3
+        @assert [1, 2, 3].zy.x == 3
         @transition {
-            set v = [1, 10, 100]
-            become #(v.w)
+            become #([1, 2, 3].zy.x)
         }",
     );
+
+    // Component beyond the length of the vector
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 10, 100])],
+        Err("Error at line 1; column 39
+@function int test(vec3 v) { return v.w }
+                                      ^   Index out of bounds"),
+        "@function int test(vec3 v) { return v.w }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 10])],
+        Err("Error at line 1; column 40
+@function vec2 test(vec2 v) { return v.xz }
+                                       ^^   Index out of bounds"),
+        "@function vec2 test(vec2 v) { return v.xz }",
+        Some("test"),
+    );
+    // Unknown property
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 10])],
+        Err("Error at line 1; column 39
+@function int test(vec2 v) { return v.q }
+                                      ^   There is no property named \"q\" for vector of length 2"),
+        "@function int test(vec2 v) { return v.q }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(1)],
+        Err("Error at line 1; column 38
+@function int test(int x) { return x.x }
+                                     ^   There is no property named \"x\" for integer"),
+        "@function int test(int x) { return x.x }",
+        Some("test"),
+    );
 }
 
 #[test]
@@ -67,40 +110,103 @@ fn test_vector_ops() {
         "
         @transition {
             set v = [1, 10, 100]
-            v += [2, 2, 2]
+            set v += [1, 10, 100]
             // v = [2, 20, 200]
-            // v.product = 2 * 20 * 200 = 8000
-            // v.sum = 2 + 20 + 200 = 222
-            // v.product - v.sum = 8000 - 222 = 7778
-            become #(v.product - v.sum - 7700)
+            // product(v) = 2 * 20 * 200 = 8000
+            // sum(v) = 2 + 20 + 200 = 222
+            // product(v) - sum(v) = 8000 - 222 = 7778
+            become #(product(v) - sum(v) - 7700)
         }
         @states 79",
     );
-    // Test ops on vectors of different lengths.
-    assert_output(
-        Ok(ConstValue::CellState(100)),
-        "
-        @transition {
-            set v = [1, 10, 100] - [1, 10]
-            become #(v.sum)
-        }
-        @states 101",
+    // Test component-wise operations.
+    let source_code = "@function vec3 test(vec3 u, vec3 v) { return u * v - u / v }";
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![6, -7, 0]),
+            ConstValue::Vector(vec![2, 3, -5]),
+        ],
+        Ok(ConstValue::Vector(vec![9, -19, 0])),
+        source_code,
+        Some("test"),
+    );
+    // Test that an integer is applied to every component.
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, -2, 30])],
+        Ok(ConstValue::Vector(vec![3, 0, 32])),
+        "@function vec3 test(vec3 v) { return v + 2 }",
+        Some("test"),
     );
-    // Test that multiplication and bitwise AND between vectors of different
-    // lengths shrink vectors instead of extending them.
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, -2, 30])],
+        Ok(ConstValue::Vector(vec![9, 12, -20])),
+        "@function vec3 test(vec3 v) { return 10 - v }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2])],
+        Ok(ConstValue::Vector(vec![2, 4])),
+        "@function vec2 test(vec2 v) { return 2 ** v }",
+        Some("test"),
+    );
+    // Constant vectors
     assert_output(
-        Ok(ConstValue::CellState(122)),
+        Ok(ConstValue::CellState(4)),
         "
+        @states 5
+        @assert ([1, 2] * 3)[1] == 6
+        @assert sum([1, 2, 3] << 1) == 12
         @transition {
-            set a = [1, 10, 100] & [3, 3] // [1, 2]
-            set b = [1, -10, 100] * [3, 4] // [3, -40]
-            // a.product = 1 * 2 = 2
-            // b.product = 3 * -40 = -120
+            become #(([1, 10] % [3, 3])[0] * 4)
+        }",
+    );
 
-            // If the vectors were extended, then the product would be zero.
-            become #(a.product + -b.product)
-        }
-        @sstates 123",
+    // Overflow in a component
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, LangInt::MAX, 0]),
+            ConstValue::Vector(vec![1, 1, 0]),
+        ],
+        Err("Error at line 1; column 46
+@function vec3 test(vec3 u, vec3 v) { return u + v }
+                                             ^^^^^   Integer overflow"),
+        "@function vec3 test(vec3 u, vec3 v) { return u + v }",
+        Some("test"),
+    );
+    // The first error is reported.
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, LangInt::MIN, 1]),
+            ConstValue::Vector(vec![1, -1, 0]),
+        ],
+        Err("Error at line 1; column 46
+@function vec3 test(vec3 u, vec3 v) { return u / v }
+                                             ^^^^^   Integer overflow"),
+        "@function vec3 test(vec3 u, vec3 v) { return u / v }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 1, LangInt::MIN]),
+            ConstValue::Vector(vec![1, 0, -1]),
+        ],
+        Err("Error at line 1; column 46
+@function vec3 test(vec3 u, vec3 v) { return u / v }
+                                             ^^^^^   Divide by zero"),
+        "@function vec3 test(vec3 u, vec3 v) { return u / v }",
+        Some("test"),
+    );
+    // Test ops on vectors of different lengths.
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 10, 100]),
+            ConstValue::Vector(vec![1, 10]),
+        ],
+        Err("Error at line 1; column 50
+@function vec3 test(vec3 u, vec2 v) { return u - v }
+                                                 ^   Type error: expected vector of length 3 but got vector of length 2"),
+        "@function vec3 test(vec3 u, vec2 v) { return u - v }",
+        Some("test"),
     );
 }
 
@@ -123,3 +229,369 @@ fn test_vector_constructor() {
         @states 3",
     );
 }
+
+#[test]
+fn test_vector_broadcast() {
+    // Assigning an integer to a vector variable
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2])],
+        Ok(ConstValue::Vector(vec![5, 5])),
+        "@function vec2 test(vec2 v) { set v = 5 return v }",
+        Some("test"),
+    );
+    // Returning an integer from a function that returns a vector
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Vector(vec![3, 3])),
+        "@function vec2 test() { return 3 }",
+        Some("test"),
+    );
+    // Vectors are not converted to integers
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2])],
+        Err("Error at line 1; column 30
+@function int test(vec2 v) { return v }
+                             ^^^^^^^^   Type error: expected integer but got vector of length 2"),
+        "@function int test(vec2 v) { return v }",
+        Some("test"),
+    );
+    // Integers are not converted to vectors in comparisons
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2])],
+        Err("Error at line 1; column 37
+@function int test(vec2 v) { return v == 1 }
+                                    ^^^^^^   Type error: cannot compare vector of length 2 to integer using '=='"),
+        "@function int test(vec2 v) { return v == 1 }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_literal() {
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Vector(vec![1, -2])),
+        "@function vec2 test() { return [1, -2] }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Int(4)],
+        Ok(ConstValue::Vector(vec![4, 12])),
+        "@function vec2 test(int x) { return [x, x * 3] }",
+        Some("test"),
+    );
+    // Empty vector
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 32
+@function vec2 test() { return [] }
+                               ^^   Vector length must range from 1 to 256"),
+        "@function vec2 test() { return [] }",
+        Some("test"),
+    );
+    // Non-integer component
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 36
+@function vec2 test() { return [1, #1] }
+                                   ^^   Type error: expected integer but got cell state"),
+        "@function vec2 test() { return [1, #1] }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_index() {
+    // Indexing a vector literal
+    assert_func_output(
+        &[],
+        Ok(ConstValue::Int(2)),
+        "@function int test() { return [1, 2, 3][1] }",
+        Some("test"),
+    );
+
+    // Runtime index
+    let source_code = "@function int test(vec2 v, int i) { return v[i] }";
+    assert_func_output(
+        &[ConstValue::Vector(vec![5, 6]), ConstValue::Int(1)],
+        Ok(ConstValue::Int(6)),
+        source_code,
+        Some("test"),
+    );
+    for &i in &[2, -1] {
+        assert_func_output(
+            &[ConstValue::Vector(vec![5, 6]), ConstValue::Int(i)],
+            Err("Error at line 1; column 44
+@function int test(vec2 v, int i) { return v[i] }
+                                           ^^^^   Index out of bounds"),
+            source_code,
+            Some("test"),
+        );
+    }
+
+    // Constant index out of bounds
+    assert_func_output(
+        &[ConstValue::Vector(vec![5, 6])],
+        Err("Error at line 1; column 39
+@function int test(vec2 v) { return v[2] }
+                                      ^   Index out of bounds"),
+        "@function int test(vec2 v) { return v[2] }",
+        Some("test"),
+    );
+
+    // Indexing something that isn't a vector
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return x[0] }
+                                   ^   Type error: cannot index integer"),
+        "@function int test(int x) { return x[0] }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_index_assignment() {
+    let source_code = "
+        @function vec3 test(vec3 v, int i) {
+            set v[i] = 10
+            set v[0] += 1
+            return v
+        }";
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2, 3]), ConstValue::Int(2)],
+        Ok(ConstValue::Vector(vec![2, 2, 10])),
+        source_code,
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2, 3]), ConstValue::Int(0)],
+        Ok(ConstValue::Vector(vec![11, 2, 3])),
+        source_code,
+        Some("test"),
+    );
+
+    // Index out of bounds at runtime
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2, 3]), ConstValue::Int(3)],
+        Err("Error at line 3; column 17
+set v[i] = 10
+    ^^^^   Index out of bounds"),
+        source_code,
+        Some("test"),
+    );
+
+    // Constant index out of bounds
+    assert_func_output(
+        &[ConstValue::Vector(vec![1, 2, 3])],
+        Err("Error at line 3; column 19
+set v[3] = 10
+      ^   Index out of bounds"),
+        "
+        @function vec3 test(vec3 v) {
+            set v[3] = 10
+            return v
+        }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_dot() {
+    let source_code = "@function int test(vec3 u, vec3 v) { return dot(u, v) }";
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1, 2, 3]),
+            ConstValue::Vector(vec![4, -5, 6]),
+        ],
+        Ok(ConstValue::Int(12)),
+        source_code,
+        Some("test"),
+    );
+    // Constant vectors
+    assert_output(
+        Ok(ConstValue::CellState(11)),
+        "
+        @states 12
+        @assert dot([1, 0], [0, 1]) == 0
+        @transition {
+            become #(dot([1, 2], [3, 4]))
+        }",
+    );
+
+    // Overflow in a product
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![0, 1 << 62, 0]),
+            ConstValue::Vector(vec![0, 2, 0]),
+        ],
+        Err("Error at line 1; column 45
+@function int test(vec3 u, vec3 v) { return dot(u, v) }
+                                            ^^^^^^^^^   Integer overflow"),
+        source_code,
+        Some("test"),
+    );
+    // Overflow in the sum
+    assert_func_output(
+        &[
+            ConstValue::Vector(vec![1 << 62, 1 << 62, 0]),
+            ConstValue::Vector(vec![1, 1, 0]),
+        ],
+        Err("Error at line 1; column 45
+@function int test(vec3 u, vec3 v) { return dot(u, v) }
+                                            ^^^^^^^^^   Integer overflow"),
+        source_code,
+        Some("test"),
+    );
+    // Vectors of different lengths
+    assert_func_output(
+        &[ConstValue::Vector(vec![0, 0]), ConstValue::Vector(vec![0, 0, 0])],
+        Err("Error at line 1; column 45
+@function int test(vec2 u, vec3 v) { return dot(u, v) }
+                                            ^^^^^^^^^   Invalid arguments [Vector(2), Vector(3)] for dot; expected [Vector(2), Vector(2)]"),
+        "@function int test(vec2 u, vec3 v) { return dot(u, v) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_reduce() {
+    for (v, sum, product, min, max) in vec![
+        (vec![2, 20, 200], 222, 8000, 2, 200),
+        (vec![-3, 7, 0, 4], 8, 0, -3, 7),
+        (vec![-5], -5, -5, -5, -5),
+    ] {
+        let len = v.len();
+        for &(func, expected) in &[
+            ("sum", sum),
+            ("product", product),
+            ("min", min),
+            ("max", max),
+        ] {
+            assert_func_output(
+                &[ConstValue::Vector(v.clone())],
+                Ok(ConstValue::Int(expected)),
+                &format!("@function int test(vec{} v) {{ return {}(v) }}", len, func),
+                Some("test"),
+            );
+        }
+    }
+    // Constant vectors
+    assert_output(
+        Ok(ConstValue::CellState(6)),
+        "
+        @states 7
+        @assert sum([1, 2, 3]) == 6
+        @assert product([1, 2, 3]) == 6
+        @assert min([4, -1, 9]) == -1
+        @assert max([4, -1, 9]) == 9
+        @transition {
+            become #(sum([1, 2, 3]))
+        }",
+    );
+
+    // Overflow
+    assert_func_output(
+        &[ConstValue::Vector(vec![1 << 62, 1 << 62])],
+        Err("Error at line 1; column 37
+@function int test(vec2 v) { return sum(v) }
+                                    ^^^^^^   Integer overflow"),
+        "@function int test(vec2 v) { return sum(v) }",
+        Some("test"),
+    );
+    assert_func_output(
+        &[ConstValue::Vector(vec![1 << 32, 1 << 31])],
+        Err("Error at line 1; column 37
+@function int test(vec2 v) { return product(v) }
+                                    ^^^^^^^^^^   Integer overflow"),
+        "@function int test(vec2 v) { return product(v) }",
+        Some("test"),
+    );
+    // Reducing something that isn't a vector
+    assert_func_output(
+        &[ConstValue::Int(3)],
+        Err("Error at line 1; column 36
+@function int test(int x) { return sum(x) }
+                                   ^^^^^^   Invalid arguments [Int] for sum"),
+        "@function int test(int x) { return sum(x) }",
+        Some("test"),
+    );
+}
+
+#[test]
+fn test_vector_cmp() {
+    let source_code = "
+        @function bool eq(vec3 u, vec3 v) { return u == v }
+        @function bool neq(vec3 u, vec3 v) { return u != v }";
+    for &(u, v) in &[
+        ([1, 2, 3], [1, 2, 3]),
+        ([1, 2, 3], [1, 2, 4]),
+        ([0, 2, 3], [1, 2, 3]),
+    ] {
+        let args = &[
+            ConstValue::Vector(u.to_vec()),
+            ConstValue::Vector(v.to_vec()),
+        ];
+        assert_func_output(args, Ok(ConstValue::Bool(u == v)), source_code, Some("eq"));
+        assert_func_output(args, Ok(ConstValue::Bool(u != v)), source_code, Some("neq"));
+    }
+
+    // Constant vectors are compared at compile time, with the same result.
+    for &(u, v) in &[
+        ([1, 2, 3], [1, 2, 3]),
+        ([1, 2, 3], [3, 2, 1]),
+        ([-5, 0, 7], [-5, 0, 7]),
+        ([-5, 0, 7], [-5, 0, 8]),
+    ] {
+        let args = &[
+            ConstValue::Vector(u.to_vec()),
+            ConstValue::Vector(v.to_vec()),
+        ];
+        for &(op, name, expected) in &[("==", "eq", u == v), ("!=", "neq", u != v)] {
+            let const_source_code =
+                format!("@function bool test() {{ return {:?} {} {:?} }}", u, op, v);
+            let expected = Ok(ConstValue::Bool(expected));
+            assert_func_output(&[], expected.clone(), &const_source_code, Some("test"));
+            assert_func_output(args, expected, source_code, Some(name));
+        }
+    }
+    assert_output(
+        Ok(ConstValue::CellState(1)),
+        "
+        @assert [1, 2] == [1, 2]
+        @assert [1, 2] != [2, 1]
+        @transition {
+            become #([3, 4] == [3, 4])
+        }",
+    );
+
+    // Vectors of different lengths
+    assert_output(
+        Err("Error at line 3; column 22
+become #([1, 2] == [1, 2, 3])
+         ^^^^^^^^^^^^^^^^^^^   Type error: cannot compare vector of length 2 to vector of length 3 using '=='"),
+        "
+        @transition {
+            become #([1, 2] == [1, 2, 3])
+        }",
+    );
+    // The same error is reported for constant vectors in a helper function.
+    assert_func_output(
+        &[],
+        Err("Error at line 1; column 32
+@function bool test() { return [1] != [1, 2] }
+                               ^^^^^^^^^^^^^   Type error: cannot compare vector of length 1 to vector of length 2 using '!='"),
+        "@function bool test() { return [1] != [1, 2] }",
+        Some("test"),
+    );
+    // Vectors have no natural order.
+    assert_output(
+        Err("Error at line 3; column 22
+become #([1, 2] < [3, 4])
+         ^^^^^^^^^^^^^^^   Type error: cannot compare vector of length 2 to vector of length 2 using '<'; vectors have no natural order, so compare using 'sum()' or 'dot()'"),
+        "
+        @transition {
+            become #([1, 2] < [3, 4])
+        }",
+    );
+}
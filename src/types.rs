@@ -1,11 +1,46 @@
 //! Types used by NDCA.
 
+use lazy_static::lazy_static;
 use std::fmt;
+use std::sync::Mutex;
 
 /// Rust type used for NDCA integers.
 pub type LangInt = i64;
-/// Number of bits in an NDCA integer.
+/// Number of bits used to store an NDCA integer outside of compiled code. This
+/// is also the default and maximum integer width for a rule.
 pub const INT_BITS: u32 = 64;
+/// Minimum number of bits in an NDCA integer. This must be greater than
+/// CELL_STATE_BITS so that every cell state ID fits in a signed integer.
+pub const MIN_INT_BITS: u32 = 16;
+
+/// Returns true if the given integer can be represented using a signed integer
+/// with the given number of bits.
+pub fn int_fits_in_bits(i: LangInt, bits: u32) -> bool {
+    wrap_int_to_bits(i, bits) == i
+}
+/// Truncates an integer to the given number of bits and sign-extends it back,
+/// matching the wrapping behavior of a signed integer with that many bits.
+pub fn wrap_int_to_bits(i: LangInt, bits: u32) -> LangInt {
+    let unused_bits = INT_BITS - bits;
+    (i << unused_bits) >> unused_bits
+}
+/// Clamps an integer to the range of a signed integer with the given number of
+/// bits, matching the saturating behavior of a signed integer with that many
+/// bits.
+pub fn saturate_int_to_bits(i: LangInt, bits: u32) -> LangInt {
+    std::cmp::max(
+        min_int_for_bits(bits),
+        std::cmp::min(max_int_for_bits(bits), i),
+    )
+}
+/// Returns the minimum value of a signed integer with the given number of bits.
+pub fn min_int_for_bits(bits: u32) -> LangInt {
+    LangInt::MIN >> (INT_BITS - bits)
+}
+/// Returns the maximum value of a signed integer with the given number of bits.
+pub fn max_int_for_bits(bits: u32) -> LangInt {
+    LangInt::MAX >> (INT_BITS - bits)
+}
 
 /// Rust type used for an NDCA cell state.
 pub type LangCellState = u8;
@@ -14,6 +49,9 @@ pub const CELL_STATE_BITS: u32 = 8;
 
 /// Maximum length for a vector.
 pub const MAX_VECTOR_LEN: usize = 256;
+/// Maximum length for an array. Arrays are allocated on the stack, so this is
+/// limited to keep compiled functions from using too much stack space.
+pub const MAX_ARRAY_LEN: usize = 4096;
 
 /// Any data type.
 ///
@@ -24,10 +62,23 @@ pub const MAX_VECTOR_LEN: usize = 256;
 pub enum Type {
     /// Integer.
     Int,
+    /// Boolean (true or false), such as the result of a comparison.
+    Bool,
     /// Cell state.
     CellState,
     /// Vector of a specific length (from 1 to 256).
     Vector(usize),
+    /// Array of integers of a specific length (from 1 to 4096). Unlike a
+    /// vector, an array is stored in memory and can be modified one element
+    /// at a time.
+    Array(usize),
+    /// Tuple of two or more integers, booleans, and/or cell states, such as
+    /// the result of a helper function that returns several values. Use
+    /// Type::tuple() to construct this.
+    Tuple(&'static [Type]),
+    /// Range of integers, inclusive on both ends (e.g. `-1..1` contains -1, 0,
+    /// and 1).
+    Range,
 }
 impl Default for Type {
     fn default() -> Self {
@@ -38,17 +89,68 @@ impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Int => write!(f, "integer"),
+            Self::Bool => write!(f, "boolean"),
             Self::CellState => write!(f, "cell state"),
             Self::Vector(len) => write!(f, "vector of length {}", len),
+            Self::Array(len) => write!(f, "array of length {}", len),
+            Self::Tuple(elements) => {
+                write!(f, "tuple (")?;
+                for (i, ty) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                write!(f, ")")
+            }
+            Self::Range => write!(f, "range"),
         }
     }
 }
+lazy_static! {
+    /// Element types of every tuple type constructed so far.
+    static ref TUPLE_TYPES: Mutex<Vec<&'static [Type]>> = Mutex::new(vec![]);
+}
 impl Type {
+    /// Returns the tuple type with the given element types.
+    ///
+    /// The element types are interned (so that Type can still be Copy), so
+    /// each distinct tuple type only allocates once.
+    pub fn tuple(elements: &[Type]) -> Self {
+        let mut tuple_types = TUPLE_TYPES.lock().unwrap();
+        if let Some(&existing) = tuple_types.iter().find(|&&t| t == elements) {
+            return Self::Tuple(existing);
+        }
+        let new: &'static [Type] = Box::leak(elements.to_vec().into_boxed_slice());
+        tuple_types.push(new);
+        Self::Tuple(new)
+    }
+    /// Returns the types of the elements if this is a tuple type, or None
+    /// otherwise.
+    pub fn tuple_elements(self) -> Option<&'static [Type]> {
+        match self {
+            Self::Tuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+    /// Returns true if a tuple may contain values of this type.
+    pub fn can_be_tuple_element(self) -> bool {
+        match self {
+            Self::Int | Self::Bool | Self::CellState => true,
+            _ => false,
+        }
+    }
     /// Returns true if this type has a representation in compiled code or false
     /// otherwise; i.e. whether a variable can contain a value of this type.
     pub fn has_runtime_representation(self) -> bool {
         match self {
-            Self::Int | Self::CellState | Self::Vector(_) => true,
+            Self::Int
+            | Self::Bool
+            | Self::CellState
+            | Self::Vector(_)
+            | Self::Array(_)
+            | Self::Tuple(_) => true,
+            Self::Range => false,
         }
     }
     /// Returns the number of bytes used to represent this type in compiled
@@ -57,8 +159,14 @@ impl Type {
         // TODO: test this method along with Value::from_bytes() and to_bytes()
         match self {
             Self::Int => Some(std::mem::size_of::<LangInt>()),
+            Self::Bool => Some(std::mem::size_of::<bool>()),
             Self::CellState => Some(std::mem::size_of::<LangCellState>()),
-            Self::Vector(len) => Some(len as usize * Self::Int.size_of().unwrap()),
+            Self::Vector(len) | Self::Array(len) => {
+                Some(len as usize * Self::Int.size_of().unwrap())
+            }
+            // Tuples are stored without padding between elements.
+            Self::Tuple(elements) => elements.iter().map(|ty| ty.size_of()).sum(),
+            Self::Range => None,
         }
     }
 }